@@ -20,6 +20,8 @@ pub mod global {
     /// avoid having to write it out manually, while keeping a nice API ([`NaiveDateTime`]).
     #[derive(ComponentPiece)]
     struct TimeComponentImpl {
+        /// Milliseconds since the unix epoch, in UTC.
+        #[component(unit = "ms")]
         unix_timestamp_millis: i64,
     }
 
@@ -45,51 +47,62 @@ pub mod global {
     #[derive(ComponentPiece, Component)]
     #[component(name = "global_temperature", ty = "global")]
     pub struct TemperatureComponent {
-        // Current temperature in degrees (celsius)
+        /// Current temperature in degrees celsius
+        #[component(unit = "°C", min = -273.15)]
         pub current_temp: f64,
-        // Scalar for the temperature
+        /// Scalar for the temperature
+        #[component(default = 1.0)]
         pub scalar: f64,
     }
     #[derive(ComponentPiece, Component)]
     #[component(name = "global_precipitation", ty = "global")]
     pub struct PrecipitationComponent {
-        // Precipitation (millimeters per hour)
+        /// Precipitation in millimeters per hour
+        #[component(unit = "mm/h", min = 0)]
         pub precipitation: f64,
-        // Scalar for the precipitation
+        /// Scalar for the precipitation
+        #[component(default = 1.0)]
         pub scalar: f64,
     }
 
     #[derive(ComponentPiece, Component)]
     #[component(name = "global_wind_speed", ty = "global")]
     pub struct WindSpeedComponent {
-        // Wind speed (meters per second)
+        /// Wind speed in meters per second
+        #[component(unit = "m/s", min = 0)]
         pub wind_speed: f64,
-        // Scalar for the wind speed
+        /// Scalar for the wind speed
+        #[component(default = 1.0)]
         pub scalar: f64,
     }
 
     #[derive(ComponentPiece, Component)]
     #[component(name = "global_wind_direction", ty = "global")]
     pub struct WindDirectionComponent {
-        // Wind direction (degrees)
+        /// Wind direction in degrees
+        #[component(unit = "°", min = 0, max = 360)]
         pub wind_direction: f64,
     }
 
     #[derive(ComponentPiece, Component)]
     #[component(name = "global_irradiance", ty = "global")]
     pub struct IrradianceComponent {
-        // Irradiance (watts per square metre)
+        /// Irradiance in watts per square metre
+        #[component(unit = "W/m²", min = 0)]
         pub irradiance: f64,
-        // Scalar for the irradiance
+        /// Scalar for the irradiance
+        #[component(default = 1.0)]
         pub scalar: f64,
     }
 
     #[derive(ComponentPiece, Component)]
     #[component(name = "global_illuminance", ty = "global")]
     pub struct IlluminanceComponent {
-        // This is the current illuminance in lux
+        /// This is the current illuminance in lux
+        #[component(unit = "lx", min = 0)]
         pub current_illuminance: f64,
-        // Scalar for the illuminance
+        /// Scalar for the illuminance
+        #[component(default = 1.0)]
         pub scalar: f64,
     }
 
@@ -114,10 +127,6 @@ pub mod global {
 }
 
 pub mod energy {
-    use simulator_communication::component::ComponentPiece;
-    use simulator_communication::component_structure::ComponentStructure;
-    use simulator_communication::prost_types::{value::Kind, Value};
-    use simulator_communication::proto::ComponentPrimitive;
     use simulator_communication_macros::Component;
     use simulator_communication_macros::ComponentPiece;
     #[derive(ComponentPiece, Component)]
@@ -144,8 +153,10 @@ pub mod energy {
         /// returns true if solver converged successfully
         pub solver_converged: bool,
         /// input: maximum number of iterations
+        #[component(min = 1)]
         pub max_iterations_input: i32,
         /// input: maximum tolerance for error
+        #[component(min = 0)]
         pub tolerance_input: f64,
         /// output: error message if solver did not converge
         pub error_message: String,
@@ -171,12 +182,16 @@ pub mod energy {
     #[component(name = "energy_load_node", ty = "node")]
     pub struct LoadNode {
         /// Voltage amplitude in p.u
+        #[component(unit = "p.u.")]
         pub voltage_amplitude: f64,
         /// Voltage angle in radians
+        #[component(unit = "rad")]
         pub voltage_angle: f64,
         /// Active power in p.u
+        #[component(unit = "p.u.")]
         pub active_power: f64,
         /// Reactive power in p.u
+        #[component(unit = "p.u.")]
         pub reactive_power: f64,
     }
     /// The slack is a mathematical concept used for load-flow analysis. Each network has one slack node. This node serves as a reference point with known voltage magnitude and angle, facilitating power flow analysis and ensuring the balance of power generation and consumption within the system.
@@ -184,12 +199,16 @@ pub mod energy {
     #[component(name = "energy_slack_node", ty = "node")]
     pub struct SlackNode {
         /// Voltage amplitude in p.u
+        #[component(unit = "p.u.")]
         pub voltage_amplitude: f64,
         /// Voltage angle in radians
+        #[component(unit = "rad")]
         pub voltage_angle: f64,
         /// Active power in p.u
+        #[component(unit = "p.u.")]
         pub active_power: f64,
         /// Reactive power in p.u
+        #[component(unit = "p.u.")]
         pub reactive_power: f64,
     }
     /// Represents a node in the system that's generating power, with properties like voltage amplitude, voltage angle, active power, reactive power, and power type.
@@ -197,36 +216,49 @@ pub mod energy {
     #[component(name = "energy_generator_node", ty = "node")]
     pub struct GeneratorNode {
         /// Voltage amplitude in p.u
+        #[component(unit = "p.u.")]
         pub voltage_amplitude: f64,
         /// Voltage angle in radians
+        #[component(unit = "rad")]
         pub voltage_angle: f64,
         /// Active power in p.u
+        #[component(unit = "p.u.")]
         pub active_power: f64,
         /// Type of power produced
         pub power_type: PowerType,
         /// Max active power in MVAR, set by manufacturer
+        #[component(unit = "MVAR")]
         pub max_active_power: f64,
         /// Minimum active power in MVAR, set by manufacturer
+        #[component(unit = "MVAR")]
         pub min_active_power: f64,
     }
 
     #[derive(ComponentPiece, Component)]
     #[component(name = "energy_transmission_edge", ty = "edge")]
     pub struct TransmissionEdge {
+        /// Ohms per meter
+        #[component(unit = "Ω/m", min = 0)]
         pub resistance_per_meter: f64,
         /// Ohms per meter for AC lines
+        #[component(unit = "Ω/m")]
         pub reactance_per_meter: f64,
         /// Length of the transmission line in meters (m)
+        #[component(unit = "m", min = 0)]
         pub length: f64,
         /// Type of the transmission line
         pub line_type: CableType,
         /// Current flowing through the transmission line in amperes
+        #[component(unit = "A")]
         pub current: f64,
         /// Minimum required voltage in per unit (pu)
+        #[component(unit = "p.u.")]
         pub min_voltage_magnitude: f64,
         /// Maximum voltage in per unit (pu)
+        #[component(unit = "p.u.")]
         pub max_voltage_magnitude: f64,
         /// Maximum allowable current on transmission lines in amperes.
+        #[component(unit = "A", min = 0)]
         pub max_current: f64,
     }
 
@@ -236,79 +268,28 @@ pub mod energy {
         pub power_type: PowerType,
         pub percentage: f64,
     }
-    #[derive(Clone, Debug, Copy, PartialEq)]
+    #[derive(ComponentPiece, Clone, Debug, Copy, PartialEq)]
     pub enum LoadFlowSolvers {
+        #[component(alias = "GausSeidel")]
         GaussSeidel,
         NewtonRaphson,
     }
-    impl ComponentPiece for LoadFlowSolvers {
-        fn get_structure() -> ComponentStructure {
-            ComponentStructure::Primitive(ComponentPrimitive::String.into())
-        }
 
-        fn from_value(value: Value) -> Option<Self> {
-            match value.kind? {
-                Kind::StringValue(s) => match s.as_str() {
-                    "GausSeidel" => Some(Self::GaussSeidel),
-                    "NewtonRaphson" => Some(Self::NewtonRaphson),
-                    _ => None,
-                },
-                _ => None,
-            }
-        }
-
-        fn to_value(&self) -> Value {
-            let s = match self {
-                LoadFlowSolvers::GaussSeidel => "GausSeidel",
-                LoadFlowSolvers::NewtonRaphson => "NewtonRaphson",
-            };
-            Value {
-                kind: Some(Kind::StringValue(s.to_owned())),
-            }
-        }
-    }
-
-    #[derive(Clone, Debug, Copy, PartialEq)]
+    #[derive(ComponentPiece, Clone, Debug, Copy, PartialEq)]
     pub enum CableType {
+        #[component(rename = "ACSR_Conductor")]
         ACSRConductor,
+        #[component(rename = "AAC_Conductor")]
         AACConductor,
+        #[component(rename = "AAAC_Conductor")]
         AAACConductor,
+        #[component(rename = "XLPE_Cable", alias = "XLPE_Conductor")]
         XLPECable,
+        #[component(rename = "PILC_Cable", alias = "PILC_Conductor")]
         PILCCable,
     }
-    impl ComponentPiece for CableType {
-        fn get_structure() -> ComponentStructure {
-            ComponentStructure::Primitive(ComponentPrimitive::String.into())
-        }
-
-        fn from_value(value: Value) -> Option<Self> {
-            match value.kind? {
-                Kind::StringValue(s) => match s.as_str() {
-                    "ACSR_Conductor" => Some(Self::ACSRConductor),
-                    "AAC_Conductor" => Some(Self::AACConductor),
-                    "AAAC_Conductor" => Some(Self::AAACConductor),
-                    "XLPE_Conductor" => Some(Self::XLPECable),
-                    "PILC_Conductor" => Some(Self::PILCCable),
-                    _ => None,
-                },
-                _ => None,
-            }
-        }
 
-        fn to_value(&self) -> Value {
-            let s = match self {
-                CableType::ACSRConductor => "ACSR_Conductor",
-                CableType::AACConductor => "AAC_Conductor",
-                CableType::AAACConductor => "AAAC_Conductor",
-                CableType::XLPECable => "XLPE_Conductor",
-                CableType::PILCCable => "PILC_Conductor",
-            };
-            Value {
-                kind: Some(Kind::StringValue(s.to_owned())),
-            }
-        }
-    }
-    #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+    #[derive(ComponentPiece, Clone, Debug, Copy, PartialEq, Eq, Hash)]
     pub enum PowerType {
         Fossil,
         Renewable,
@@ -319,45 +300,6 @@ pub mod energy {
         Battery,
         Storage,
     }
-    impl ComponentPiece for PowerType {
-        fn get_structure() -> ComponentStructure {
-            ComponentStructure::Primitive(ComponentPrimitive::String.into())
-        }
-
-        fn from_value(value: Value) -> Option<Self> {
-            let res = match value.kind? {
-                Kind::StringValue(s) => match s.to_lowercase().as_str() {
-                    "fossil" => Some(Self::Fossil),
-                    "renewable" => Some(Self::Renewable),
-                    "nuclear" => Some(Self::Nuclear),
-                    "hydro" => Some(Self::Hydro),
-                    "solar" => Some(Self::Solar),
-                    "wind" => Some(Self::Wind),
-                    "battery" => Some(Self::Battery),
-                    "storage" => Some(Self::Storage),
-                    _ => None,
-                },
-                _ => None,
-            };
-            res
-        }
-
-        fn to_value(&self) -> Value {
-            let s = match self {
-                PowerType::Fossil => "Fossil",
-                PowerType::Renewable => "Renewable",
-                PowerType::Nuclear => "Nuclear",
-                PowerType::Hydro => "Hydro",
-                PowerType::Solar => "Solar",
-                PowerType::Wind => "Wind",
-                PowerType::Battery => "Battery",
-                PowerType::Storage => "Storage",
-            };
-            Value {
-                kind: Some(Kind::StringValue(s.to_owned())),
-            }
-        }
-    }
 }
 #[derive(ComponentPiece, Component)]
 #[component(name = "building", ty = "node")]
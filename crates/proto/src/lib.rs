@@ -77,6 +77,7 @@ pub mod simulation {
 
     pub mod simulation_manager {
         use crate::simulation;
+        use crate::simulation::simulator;
         pub use proto_simulation_manager::{
             simulation_manager_client::*, simulation_manager_server::*, *,
         };
@@ -1,6 +1,8 @@
 use darling::{FromAttributes, FromMeta};
 use quote::quote;
-use syn::{parse_macro_input, parse_quote, DeriveInput, Path, TypeParamBound};
+use syn::{parse_macro_input, parse_quote, DeriveInput, Path, Type, TypeParamBound};
+
+use crate::metadata::MetadataAttr;
 
 #[derive(FromMeta)]
 enum ComponentType {
@@ -14,6 +16,15 @@ enum ComponentType {
 struct ComponentAttr {
     name: String,
     ty: ComponentType,
+    /// The version of the structure, 0 when not given.
+    #[darling(default)]
+    version: u32,
+    /// A function `fn(u32, Value) -> Option<Value>` that upgrades a value of the given version to
+    /// the next version.
+    upgrade: Option<Path>,
+    /// The metadata of the component as a whole.
+    #[darling(flatten)]
+    metadata: MetadataAttr,
 }
 
 pub fn derive_component(
@@ -38,6 +49,22 @@ pub fn derive_component(
     };
 
     let component_name = attribute.name;
+    let self_ty: Type = parse_quote!(Self);
+    let metadata = attribute.metadata.to_tokens(
+        &item.attrs,
+        Some(&self_ty),
+        quote!(#crate_name::component::ComponentPiece),
+        &crate_name,
+    );
+
+    let version = attribute.version;
+    let upgrade = attribute.upgrade.map(|upgrade| {
+        quote! {
+            fn upgrade(version: u32, value: #crate_name::Value) -> Option<#crate_name::Value> {
+                #upgrade(version, value)
+            }
+        }
+    });
 
     let component_type = match attribute.ty {
         ComponentType::Edge => quote!(Edge),
@@ -56,9 +83,13 @@ pub fn derive_component(
                     r#type: #crate_name::proto::ComponentType::#component_type.into(),
                     structure: Some(#crate_name::ComponentStructure {
                         component_structure: Some(<Self as #crate_name::component::ComponentPiece>::get_structure()),
+                        metadata: #metadata,
                     }),
+                    version: #version,
                 }
             }
+
+            #upgrade
         }
     };
 
@@ -1,7 +1,13 @@
+use darling::FromAttributes;
 use proc_macro2::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
-use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Path, TypeParamBound};
+use syn::{
+    parse_macro_input, parse_quote, Data, DataEnum, DeriveInput, Error, Fields, FieldsNamed, Ident,
+    Path, TypeParamBound,
+};
+
+use crate::metadata::MetadataAttr;
 
 struct DerivedFunctions {
     get_structure: TokenStream,
@@ -9,6 +15,26 @@ struct DerivedFunctions {
     to_value: TokenStream,
 }
 
+#[derive(FromAttributes)]
+#[darling(attributes(component))]
+struct VariantAttr {
+    /// The name of the variant in a component, the name of the variant itself when not given.
+    rename: Option<String>,
+    /// Other names that are accepted when reading the variant, like names it had before.
+    #[darling(multiple)]
+    alias: Vec<String>,
+    /// The metadata of the data of the variant.
+    #[darling(flatten)]
+    metadata: MetadataAttr,
+}
+
+#[derive(FromAttributes)]
+#[darling(attributes(component))]
+struct FieldAttr {
+    #[darling(flatten)]
+    metadata: MetadataAttr,
+}
+
 pub fn derive_component_piece(
     item: proc_macro::TokenStream,
     crate_name: Path,
@@ -60,72 +86,13 @@ fn get_structure(
 ) -> Result<DerivedFunctions, Error> {
     match data {
         Data::Struct(data) => match &data.fields {
-            Fields::Named(fields) => {
-                let mut get_structure_parts = Vec::new();
-                let mut from_value_parts = Vec::new();
-                let mut to_value_parts = Vec::new();
-
-                for f in &fields.named {
-                    let name = f.ident.as_ref().unwrap();
-                    let name_str = name.to_string();
-                    let ty = &f.ty;
-
-                    let get_structure = quote_spanned! {ty.span()=>
-                        (
-                            #name_str.to_owned(),
-                            #crate_name::proto::ComponentStructure {
-                                component_structure: Some(
-                                    <#ty as #trait_name>::get_structure()
-                                ),
-                            },
-                        )
-                    };
-                    let from_value = quote_spanned! {ty.span()=>
-                        #name: #trait_name::from_value(fields.remove(#name_str)?)?
-                    };
-                    let to_value = quote_spanned! {ty.span()=>
-                        (
-                            #name_str.to_owned(),
-                            #trait_name::to_value(&self.#name),
-                        )
-                    };
-
-                    get_structure_parts.push(get_structure);
-                    from_value_parts.push(from_value);
-                    to_value_parts.push(to_value);
-                }
-
-                let get_structure = quote! {
-                    #crate_name::component_structure::ComponentStructure::Struct(#crate_name::proto::ComponentStruct {
-                        data: std::collections::HashMap::from([
-                            #(#get_structure_parts),*
-                        ]),
-                    })
-                };
-                let from_value = quote! {
-                    match value.kind? {
-                        #crate_name::prost_types::value::Kind::StructValue(#crate_name::prost_types::Struct { mut fields }) => Some(Self {
-                            #(#from_value_parts),*
-                        }),
-                        _ => None,
-                    }
-                };
-                let to_value = quote! {
-                    #crate_name::Value {
-                        kind: Some(#crate_name::prost_types::value::Kind::StructValue(#crate_name::prost_types::Struct {
-                            fields: std::collections::BTreeMap::from([
-                                #(#to_value_parts),*
-                            ]),
-                        })),
-                    }
-                };
-
-                Ok(DerivedFunctions {
-                    get_structure,
-                    from_value,
-                    to_value,
-                })
-            }
+            Fields::Named(fields) => named_fields(
+                fields,
+                quote!(Self),
+                |name| quote!(&self.#name),
+                trait_name,
+                crate_name,
+            ),
             Fields::Unnamed(ref fields) => {
                 if fields.unnamed.len() != 1 {
                     return Err(Error::new_spanned(
@@ -135,6 +102,7 @@ fn get_structure(
                 }
                 let field = fields.unnamed.first().unwrap();
                 let ty = &field.ty;
+                no_metadata(&field.attrs, field)?;
 
                 let get_structure = quote_spanned! {ty.span()=>
                     <#ty as #trait_name>::get_structure()
@@ -157,13 +125,312 @@ fn get_structure(
                 "Can't impl ComponentPiece on unit structs",
             )),
         },
-        Data::Enum(ref e) => Err(Error::new_spanned(
-            e.enum_token,
-            "Can't impl ComponentPiece on enums",
-        )),
+        Data::Enum(ref data) => enum_variants(data, trait_name, crate_name),
         Data::Union(ref u) => Err(Error::new_spanned(
             u.union_token,
             "Can't impl ComponentPiece on unions",
         )),
     }
 }
+
+/// The structure of named fields: a struct with a field for every field.
+///
+/// `constructor` creates the value from the fields and `field` gives a reference to a field of
+/// the value. The metadata of a field is read from its attributes and doc comments.
+fn named_fields(
+    fields: &FieldsNamed,
+    constructor: TokenStream,
+    field: impl Fn(&Ident) -> TokenStream,
+    trait_name: TypeParamBound,
+    crate_name: Path,
+) -> Result<DerivedFunctions, Error> {
+    let mut get_structure_parts = Vec::new();
+    let mut from_value_parts = Vec::new();
+    let mut to_value_parts = Vec::new();
+
+    for f in &fields.named {
+        let name = f.ident.as_ref().unwrap();
+        let name_str = name.to_string();
+        let ty = &f.ty;
+        let field = field(name);
+        let metadata = FieldAttr::from_attributes(&f.attrs)?.metadata.to_tokens(
+            &f.attrs,
+            Some(ty),
+            &trait_name,
+            &crate_name,
+        );
+
+        let get_structure = quote_spanned! {ty.span()=>
+            (
+                #name_str.to_owned(),
+                #crate_name::proto::ComponentStructure {
+                    component_structure: Some(
+                        <#ty as #trait_name>::get_structure()
+                    ),
+                    metadata: #metadata,
+                },
+            )
+        };
+        // a missing field is read as null, so a missing optional field is `None`
+        let from_value = quote_spanned! {ty.span()=>
+            #name: #trait_name::from_value(fields.remove(#name_str).unwrap_or(#crate_name::Value {
+                kind: Some(#crate_name::prost_types::value::Kind::NullValue(0)),
+            }))?
+        };
+        let to_value = quote_spanned! {ty.span()=>
+            (
+                #name_str.to_owned(),
+                #trait_name::to_value(#field),
+            )
+        };
+
+        get_structure_parts.push(get_structure);
+        from_value_parts.push(from_value);
+        to_value_parts.push(to_value);
+    }
+
+    let get_structure = quote! {
+        #crate_name::component_structure::ComponentStructure::Struct(#crate_name::proto::ComponentStruct {
+            data: std::collections::HashMap::from([
+                #(#get_structure_parts),*
+            ]),
+        })
+    };
+    let from_value = quote! {
+        match value.kind? {
+            #crate_name::prost_types::value::Kind::StructValue(#crate_name::prost_types::Struct { mut fields }) => Some(#constructor {
+                #(#from_value_parts),*
+            }),
+            _ => None,
+        }
+    };
+    let to_value = quote! {
+        #crate_name::Value {
+            kind: Some(#crate_name::prost_types::value::Kind::StructValue(#crate_name::prost_types::Struct {
+                fields: std::collections::BTreeMap::from([
+                    #(#to_value_parts),*
+                ]),
+            })),
+        }
+    };
+
+    Ok(DerivedFunctions {
+        get_structure,
+        from_value,
+        to_value,
+    })
+}
+
+/// The structure of an enum. A unit variant is a string with the name of the variant, which is
+/// parsed case-insensitively. A variant with data is a struct with a single field, named after
+/// the variant, that holds the data. The aliases of a variant are only used when parsing.
+fn enum_variants(
+    data: &DataEnum,
+    trait_name: TypeParamBound,
+    crate_name: Path,
+) -> Result<DerivedFunctions, Error> {
+    if data.variants.is_empty() {
+        return Err(Error::new_spanned(
+            data.enum_token,
+            "Can't impl ComponentPiece on enums without variants",
+        ));
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    let mut unit_names = Vec::new();
+    let mut unit_aliases = Vec::new();
+    let mut unit_idents = Vec::new();
+    let mut data_names = Vec::new();
+    let mut data_aliases = Vec::new();
+    let mut data_structures = Vec::new();
+    let mut data_metadata = Vec::new();
+    let mut data_from_values = Vec::new();
+    let mut data_patterns = Vec::new();
+    let mut data_to_values = Vec::new();
+
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let attr = VariantAttr::from_attributes(&variant.attrs)?;
+        let name = attr.rename.unwrap_or_else(|| ident.to_string());
+        for name in std::iter::once(&name).chain(&attr.alias) {
+            if names.iter().any(|other| other.eq_ignore_ascii_case(name)) {
+                return Err(Error::new_spanned(
+                    variant,
+                    format!(
+                        "Can't impl ComponentPiece on enums with multiple variants named `{name}`"
+                    ),
+                ));
+            }
+            names.push(name.clone());
+        }
+        let aliases = attr.alias;
+
+        let (pattern, data, metadata) = match &variant.fields {
+            Fields::Unit => {
+                if !attr.metadata.is_empty() {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "Can't give metadata to variants without data",
+                    ));
+                }
+                unit_names.push(name);
+                unit_aliases.push(aliases);
+                unit_idents.push(ident);
+                continue;
+            }
+            Fields::Unnamed(fields) => {
+                if fields.unnamed.len() != 1 {
+                    return Err(Error::new_spanned(
+                        &variant.fields,
+                        "Can't impl ComponentPiece on variants with more then one unnamed field",
+                    ));
+                }
+                let field = fields.unnamed.first().unwrap();
+                let ty = &field.ty;
+                no_metadata(&field.attrs, field)?;
+                let metadata =
+                    attr.metadata
+                        .to_tokens(&variant.attrs, Some(ty), &trait_name, &crate_name);
+                let data = DerivedFunctions {
+                    get_structure: quote_spanned! {ty.span()=>
+                        <#ty as #trait_name>::get_structure()
+                    },
+                    from_value: quote_spanned! {ty.span()=>
+                        Some(Self::#ident(<#ty as #trait_name>::from_value(value)?))
+                    },
+                    to_value: quote_spanned! {ty.span()=>
+                        #trait_name::to_value(data)
+                    },
+                };
+                (quote!(Self::#ident(data)), data, metadata)
+            }
+            Fields::Named(fields) => {
+                let bindings = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
+                let data = named_fields(
+                    fields,
+                    quote!(Self::#ident),
+                    |name| quote!(#name),
+                    trait_name.clone(),
+                    crate_name.clone(),
+                )?;
+                if attr.metadata.has_default() {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "Can't give a default to variants with named fields",
+                    ));
+                }
+                let metadata =
+                    attr.metadata
+                        .to_tokens(&variant.attrs, None, &trait_name, &crate_name);
+                (quote!(Self::#ident { #(#bindings),* }), data, metadata)
+            }
+        };
+
+        data_names.push(name);
+        data_aliases.push(aliases);
+        data_structures.push(data.get_structure);
+        data_metadata.push(metadata);
+        data_from_values.push(data.from_value);
+        data_patterns.push(pattern);
+        data_to_values.push(data.to_value);
+    }
+
+    let (alias_names, alias_variants): (Vec<_>, Vec<_>) = unit_names
+        .iter()
+        .zip(&unit_aliases)
+        .chain(data_names.iter().zip(&data_aliases))
+        .flat_map(|(name, aliases)| aliases.iter().map(move |alias| (alias, name)))
+        .unzip();
+
+    let get_structure = quote! {
+        #crate_name::component_structure::ComponentStructure::Enum(#crate_name::proto::ComponentEnum {
+            variants: vec![#(#unit_names.to_owned()),*],
+            data_variants: std::collections::HashMap::from([
+                #((
+                    #data_names.to_owned(),
+                    #crate_name::proto::ComponentStructure {
+                        component_structure: Some(#data_structures),
+                        metadata: #data_metadata,
+                    },
+                )),*
+            ]),
+            aliases: std::collections::HashMap::from([
+                #((#alias_names.to_owned(), #alias_variants.to_owned())),*
+            ]),
+        })
+    };
+
+    let from_string = (!unit_names.is_empty()).then(|| {
+        quote! {
+            #crate_name::prost_types::value::Kind::StringValue(name) => {
+                #(
+                    if name.eq_ignore_ascii_case(#unit_names)
+                        #(|| name.eq_ignore_ascii_case(#unit_aliases))*
+                    {
+                        return Some(Self::#unit_idents);
+                    }
+                )*
+                None
+            }
+        }
+    });
+    let from_struct = (!data_names.is_empty()).then(|| {
+        quote! {
+            #crate_name::prost_types::value::Kind::StructValue(#crate_name::prost_types::Struct { fields }) => {
+                if fields.len() != 1 {
+                    return None;
+                }
+                let (name, value) = fields.into_iter().next()?;
+                match name.as_str() {
+                    #(#data_names #(| #data_aliases)* => #data_from_values,)*
+                    _ => None,
+                }
+            }
+        }
+    });
+    let from_value = quote! {
+        match value.kind? {
+            #from_string
+            #from_struct
+            _ => None,
+        }
+    };
+
+    let to_value = quote! {
+        match self {
+            #(
+                Self::#unit_idents => #crate_name::Value {
+                    kind: Some(#crate_name::prost_types::value::Kind::StringValue(#unit_names.to_owned())),
+                },
+            )*
+            #(
+                #data_patterns => #crate_name::Value {
+                    kind: Some(#crate_name::prost_types::value::Kind::StructValue(#crate_name::prost_types::Struct {
+                        fields: std::collections::BTreeMap::from([
+                            (#data_names.to_owned(), #data_to_values),
+                        ]),
+                    })),
+                },
+            )*
+        }
+    };
+
+    Ok(DerivedFunctions {
+        get_structure,
+        from_value,
+        to_value,
+    })
+}
+
+/// Metadata has to be given where a type is used, as the structure of a single unnamed field is
+/// the structure of the field itself.
+fn no_metadata(attrs: &[syn::Attribute], field: &syn::Field) -> Result<(), Error> {
+    if FieldAttr::from_attributes(attrs)?.metadata.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            field,
+            "Can't give metadata to a single unnamed field, give it where the type is used",
+        ))
+    }
+}
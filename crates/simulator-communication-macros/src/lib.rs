@@ -1,5 +1,6 @@
 mod component;
 mod component_piece;
+mod metadata;
 
 use syn::{parse_quote, GenericParam, Generics, TypeParamBound};
 
@@ -17,7 +18,7 @@ pub fn derive_component(item: proc_macro::TokenStream) -> proc_macro::TokenStrea
     component::derive_component(item, parse_quote!(::simulator_communication))
 }
 
-#[proc_macro_derive(ComponentPiece)]
+#[proc_macro_derive(ComponentPiece, attributes(component))]
 pub fn derive_component_piece(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     component_piece::derive_component_piece(item, parse_quote!(::simulator_communication))
 }
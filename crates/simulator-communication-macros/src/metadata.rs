@@ -0,0 +1,134 @@
+use darling::{FromMeta, Result};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{Attribute, Expr, ExprLit, ExprUnary, Lit, Meta, Path, Type, UnOp};
+
+/// Information about a part of a component structure, given in `#[component(...)]`.
+///
+/// The doc comments of the item are used as `doc` when it is not given.
+#[derive(FromMeta, Default)]
+pub struct MetadataAttr {
+    doc: Option<String>,
+    unit: Option<String>,
+    min: Option<Number>,
+    max: Option<Number>,
+    /// An expression that can be converted into the type of the value with `Into`.
+    default: Option<DefaultExpr>,
+}
+
+/// A number literal, which may be negative and does not need a decimal point.
+pub struct Number(f64);
+
+impl FromMeta for Number {
+    fn from_expr(expr: &Expr) -> Result<Self> {
+        match expr {
+            Expr::Unary(ExprUnary {
+                op: UnOp::Neg(_),
+                expr,
+                ..
+            }) => Ok(Number(-Self::from_expr(expr)?.0)),
+            Expr::Group(group) => Self::from_expr(&group.expr),
+            Expr::Lit(ExprLit { lit, .. }) => Self::from_value(lit),
+            _ => Err(darling::Error::unexpected_expr_type(expr)),
+        }
+    }
+
+    fn from_value(value: &Lit) -> Result<Self> {
+        match value {
+            Lit::Float(f) => f.base10_parse().map(Number).map_err(Into::into),
+            Lit::Int(i) => i.base10_parse().map(Number).map_err(Into::into),
+            _ => Err(darling::Error::unexpected_lit_type(value)),
+        }
+    }
+}
+
+/// Any expression, a string literal is not parsed as an expression like `syn::Expr` does.
+pub struct DefaultExpr(Expr);
+
+impl FromMeta for DefaultExpr {
+    fn from_expr(expr: &Expr) -> Result<Self> {
+        Ok(DefaultExpr(expr.clone()))
+    }
+}
+
+impl MetadataAttr {
+    /// Whether any metadata is given in the attribute, doc comments are not included.
+    pub fn is_empty(&self) -> bool {
+        self.doc.is_none()
+            && self.unit.is_none()
+            && self.min.is_none()
+            && self.max.is_none()
+            && self.default.is_none()
+    }
+
+    pub fn has_default(&self) -> bool {
+        self.default.is_some()
+    }
+
+    /// The `metadata` of a `ComponentStructure` for a value of type `ty`. `None` when there is no
+    /// attribute and no doc comment.
+    ///
+    /// The default is left out when the type is not known.
+    pub fn to_tokens(
+        &self,
+        attrs: &[Attribute],
+        ty: Option<&Type>,
+        trait_name: impl ToTokens,
+        crate_name: &Path,
+    ) -> TokenStream {
+        let doc = self.doc.clone().or_else(|| doc_comment(attrs));
+        if self.is_empty() && doc.is_none() {
+            return quote!(None);
+        }
+
+        let doc = doc.unwrap_or_default();
+        let unit = self.unit.clone().unwrap_or_default();
+        let bound = |bound: &Option<Number>| match bound {
+            Some(Number(n)) => quote!(Some(#n)),
+            None => quote!(None),
+        };
+        let min = bound(&self.min);
+        let max = bound(&self.max);
+        let default = match (&self.default, ty) {
+            (Some(DefaultExpr(expr)), Some(ty)) => quote! {
+                Some(<#ty as #trait_name>::to_value(&::core::convert::Into::<#ty>::into(#expr)))
+            },
+            _ => quote!(None),
+        };
+
+        quote! {
+            Some(#crate_name::proto::ComponentMetadata {
+                doc: #doc.to_owned(),
+                unit: #unit.to_owned(),
+                min: #min,
+                max: #max,
+                default_value: #default,
+            })
+        }
+    }
+}
+
+/// The doc comments in `attrs`, as a single string without the leading space of every line.
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|line| {
+            line.strip_prefix(' ')
+                .unwrap_or(&line)
+                .trim_end()
+                .to_owned()
+        })
+        .collect();
+    let doc = lines.join("\n").trim().to_owned();
+    (!doc.is_empty()).then_some(doc)
+}
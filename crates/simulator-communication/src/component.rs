@@ -28,8 +28,18 @@ pub trait Component: ComponentPiece {
     fn get_name() -> String;
 
     /// The "shape" of this component. i.e. what fields and types it has.
-    /// Together with the type of the component.
+    /// Together with the type of the component and the version of its structure.
     fn get_spec() -> ComponentSpecification;
+
+    /// Upgrade a value stored with version `version` of the structure to version `version + 1`.
+    ///
+    /// Values of older versions are upgraded one version at a time, up to the version in
+    /// [`get_spec`](Component::get_spec). Returns `None` when the value can not be upgraded,
+    /// which is the default.
+    fn upgrade(version: u32, value: Value) -> Option<Value> {
+        let _ = (version, value);
+        None
+    }
 }
 
 /// Trait for a part of component.
@@ -150,6 +160,7 @@ impl<T: ComponentPiece> ComponentPiece for Option<T> {
     fn get_structure() -> ComponentStructure {
         ComponentStructure::Option(Box::new(crate::proto::ComponentStructure {
             component_structure: Some(T::get_structure()),
+            metadata: None,
         }))
     }
 
@@ -174,6 +185,7 @@ impl<T: ComponentPiece> ComponentPiece for Vec<T> {
     fn get_structure() -> ComponentStructure {
         ComponentStructure::List(Box::new(crate::proto::ComponentStructure {
             component_structure: Some(T::get_structure()),
+            metadata: None,
         }))
     }
 
@@ -263,7 +275,8 @@ mod tests {
                         proto::ComponentStructure {
                             component_structure: Some(ComponentStructure::Primitive(
                                 proto::ComponentPrimitive::U32.into()
-                            ))
+                            )),
+                            metadata: None,
                         }
                     ),
                     (
@@ -271,7 +284,8 @@ mod tests {
                         proto::ComponentStructure {
                             component_structure: Some(ComponentStructure::Primitive(
                                 proto::ComponentPrimitive::F64.into()
-                            ))
+                            )),
+                            metadata: None,
                         }
                     )
                 ])
@@ -329,4 +343,209 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn missing_optional_field() {
+        #[derive(ComponentPiece, PartialEq, Debug)]
+        struct Cable {
+            length: f64,
+            depth: Option<f64>,
+        }
+
+        let cable = |fields: &[(&str, f64)]| Value {
+            kind: Some(Kind::StructValue(prost_types::Struct {
+                fields: fields
+                    .iter()
+                    .map(|(name, n)| {
+                        (
+                            name.to_string(),
+                            Value {
+                                kind: Some(Kind::NumberValue(*n)),
+                            },
+                        )
+                    })
+                    .collect(),
+            })),
+        };
+        assert_eq!(
+            Cable::from_value(cable(&[("length", 2.0)])),
+            Some(Cable {
+                length: 2.0,
+                depth: None
+            })
+        );
+        assert_eq!(
+            Cable::from_value(cable(&[("length", 2.0), ("depth", 1.0)])),
+            Some(Cable {
+                length: 2.0,
+                depth: Some(1.0)
+            })
+        );
+        // fields that are not optional are still required
+        assert_eq!(Cable::from_value(cable(&[("depth", 1.0)])), None);
+    }
+
+    #[derive(ComponentPiece, PartialEq, Debug)]
+    enum Line {
+        Overhead,
+        #[component(rename = "XLPE_Cable", alias = "XLPE_Conductor")]
+        XlpeCable,
+        /// A cable with a custom conductor.
+        #[component(alias = "Conductor")]
+        Custom(ElecInfo),
+        Spec {
+            length: f64,
+        },
+    }
+
+    #[test]
+    fn component_piece_derive_enum() {
+        let string = |s: &str| Value {
+            kind: Some(Kind::StringValue(s.to_owned())),
+        };
+        let tagged = |tag: &str, value: Value| Value {
+            kind: Some(Kind::StructValue(prost_types::Struct {
+                fields: BTreeMap::from([(tag.to_owned(), value)]),
+            })),
+        };
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+
+        let ComponentStructure::Enum(structure) = Line::get_structure() else {
+            panic!("not an enum");
+        };
+        assert_eq!(structure.variants, vec!["Overhead", "XLPE_Cable"]);
+        assert_eq!(
+            structure.aliases,
+            HashMap::from([
+                ("XLPE_Conductor".to_owned(), "XLPE_Cable".to_owned()),
+                ("Conductor".to_owned(), "Custom".to_owned()),
+            ])
+        );
+        assert_eq!(
+            structure.data_variants["Custom"].component_structure,
+            Some(ComponentStructure::Primitive(
+                proto::ComponentPrimitive::U32.into()
+            ))
+        );
+        assert!(matches!(
+            structure.data_variants["Spec"].component_structure,
+            Some(ComponentStructure::Struct(_))
+        ));
+
+        assert_eq!(Line::from_value(string("overhead")), Some(Line::Overhead));
+        assert_eq!(
+            Line::from_value(string("xlpe_cable")),
+            Some(Line::XlpeCable)
+        );
+        assert_eq!(Line::from_value(string("XlpeCable")), None);
+        // aliases are accepted, but not written
+        assert_eq!(
+            Line::from_value(string("xlpe_conductor")),
+            Some(Line::XlpeCable)
+        );
+        assert_eq!(
+            Line::from_value(tagged("Conductor", number(3.0))),
+            Some(Line::Custom(ElecInfo(3)))
+        );
+        assert_eq!(
+            Line::from_value(tagged("Custom", number(3.0))),
+            Some(Line::Custom(ElecInfo(3)))
+        );
+        assert_eq!(
+            Line::from_value(tagged("Spec", tagged("length", number(1.5)))),
+            Some(Line::Spec { length: 1.5 })
+        );
+        assert_eq!(Line::from_value(tagged("Overhead", number(0.0))), None);
+
+        assert_eq!(Line::XlpeCable.to_value(), string("XLPE_Cable"));
+        assert_eq!(
+            Line::Custom(ElecInfo(3)).to_value(),
+            tagged("Custom", number(3.0))
+        );
+        assert_eq!(
+            Line::Spec { length: 1.5 }.to_value(),
+            tagged("Spec", tagged("length", number(1.5)))
+        );
+    }
+
+    /// A cable in the ground.
+    ///
+    /// Buried at least a meter deep.
+    #[derive(ComponentPiece, Component)]
+    #[component(name = "cable", ty = "edge", unit = "m")]
+    struct Cable {
+        /// The current through the cable.
+        #[component(unit = "A", min = 0, max = 400.5)]
+        current: f64,
+        /// Overwritten by the attribute.
+        #[component(doc = "The angle of the current.", min = -3.2, default = 0.5)]
+        angle: Option<f64>,
+        #[component(default = Line::XlpeCable)]
+        line: Line,
+        length: f64,
+    }
+
+    #[test]
+    fn component_metadata() {
+        let metadata = |doc: &str, unit: &str, min, max, default_value| {
+            Some(proto::ComponentMetadata {
+                doc: doc.to_owned(),
+                unit: unit.to_owned(),
+                min,
+                max,
+                default_value,
+            })
+        };
+
+        let spec = Cable::get_spec().structure.expect("no structure");
+        assert_eq!(
+            spec.metadata,
+            metadata(
+                "A cable in the ground.\n\nBuried at least a meter deep.",
+                "m",
+                None,
+                None,
+                None
+            )
+        );
+        let Some(ComponentStructure::Struct(structure)) = spec.component_structure else {
+            panic!("not a struct");
+        };
+        assert_eq!(
+            structure.data["current"].metadata,
+            metadata(
+                "The current through the cable.",
+                "A",
+                Some(0.0),
+                Some(400.5),
+                None
+            )
+        );
+        assert_eq!(
+            structure.data["angle"].metadata,
+            metadata(
+                "The angle of the current.",
+                "",
+                Some(-3.2),
+                None,
+                Some(Some(0.5).to_value())
+            )
+        );
+        assert_eq!(
+            structure.data["line"].metadata,
+            metadata("", "", None, None, Some(Line::XlpeCable.to_value()))
+        );
+        assert_eq!(structure.data["length"].metadata, None);
+
+        let ComponentStructure::Enum(line) = Line::get_structure() else {
+            panic!("not an enum");
+        };
+        assert_eq!(
+            line.data_variants["Custom"].metadata,
+            metadata("A cable with a custom conductor.", "", None, None, None)
+        );
+        assert_eq!(line.data_variants["Spec"].metadata, None);
+    }
 }
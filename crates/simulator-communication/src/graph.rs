@@ -2,7 +2,7 @@
 
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     hash::Hash,
     iter,
 };
@@ -11,11 +11,11 @@ use crate::{component::Component, proto, simulator::ComponentsInfo, Value};
 
 /// A pointer to a specific Node in the [`Graph`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct NodeId(usize);
+pub struct NodeId(pub(crate) usize);
 
 /// A pointer to a specific Edge in the [`Graph`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct EdgeId(usize);
+pub struct EdgeId(pub(crate) usize);
 
 /// A single node in the world with a location.
 #[derive(Debug, PartialEq)]
@@ -48,6 +48,18 @@ struct ComponentStorageMap {
 }
 
 impl ComponentStorageMap {
+    /// Get the storage of `C`, creating it when it does not exist yet.
+    fn get_or_insert<C: Component>(&mut self) -> &mut ComponentStorage<C> {
+        let component_storage = self
+            .components
+            .entry(TypeId::of::<C>())
+            .or_insert_with(|| Box::new(ComponentStorage::<C> { components: vec![] }));
+        match component_storage.downcast_mut::<ComponentStorage<C>>() {
+            Some(c) => c,
+            None => unreachable!("ComponentStorage had ComponentStorage with wrong type, This is a bug in the simulator communication lib"),
+        }
+    }
+
     fn downcast<C: Component>(&self) -> Option<&ComponentStorage<C>> {
         let component_storage = self.components.get(&TypeId::of::<C>())?;
         match component_storage.downcast_ref::<ComponentStorage<C>>() {
@@ -71,10 +83,18 @@ impl ComponentStorageMap {
 /// Nodes can be connected with edges, all containing exactly one [`Component`].
 ///
 /// There can also be global [`Component`]s.
+///
+/// Nodes and edges can be added and removed, see [`insert_node`](Graph::insert_node) and
+/// [`remove_node`](Graph::remove_node). The manager allocates the ids of new nodes and edges once
+/// the timestep is done.
 #[derive(Debug)]
 pub struct Graph {
-    nodes: Vec<Node>,
-    edges: Vec<Edge>,
+    /// Removed nodes are [`None`], so the index of a node never changes.
+    nodes: Vec<Option<Node>>,
+    /// Removed edges are [`None`], so the index of an edge never changes.
+    edges: Vec<Option<Edge>>,
+    /// The edges added by the simulator, with a function that gets the name of their component.
+    added_edges: HashMap<EdgeId, fn() -> String>,
 
     node_components: ComponentStorageMap,
     edge_components: ComponentStorageMap,
@@ -98,7 +118,8 @@ impl Graph {
             .iter()
             .filter_map(|(index, component)| {
                 self.nodes
-                    .get(*index)
+                    .get(*index)?
+                    .as_ref()
                     .map(|node| (NodeId(*index), node, component))
             });
 
@@ -116,7 +137,8 @@ impl Graph {
             .iter()
             .filter_map(|(index, component)| {
                 self.edges
-                    .get(*index)
+                    .get(*index)?
+                    .as_ref()
                     .map(|edge| (EdgeId(*index), edge, component))
             });
 
@@ -136,7 +158,8 @@ impl Graph {
             .iter_mut()
             .filter_map(|(index, ref mut component)| {
                 self.nodes
-                    .get(*index)
+                    .get(*index)?
+                    .as_ref()
                     .map(|node: &Node| (NodeId(*index), node, component))
             });
 
@@ -156,7 +179,8 @@ impl Graph {
             .iter_mut()
             .filter_map(|(index, ref mut component)| {
                 self.edges
-                    .get(*index)
+                    .get(*index)?
+                    .as_ref()
                     .map(|edge: &Edge| (EdgeId(*index), edge, component))
             });
 
@@ -165,8 +189,10 @@ impl Graph {
 
     /// Get the [`Component`] `C` for a single node.
     ///
-    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`].
+    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`], or if the node
+    /// was removed.
     pub fn get_node_component<C: Component>(&self, id: NodeId) -> Option<&C> {
+        self.get_node(id)?;
         let components = &self.node_components.downcast()?.components;
         if let Ok(i) = components.binary_search_by(|(correct_index, _)| correct_index.cmp(&id.0)) {
             return Some(&components[i].1);
@@ -177,8 +203,10 @@ impl Graph {
 
     /// Get the [`Component`] `C` for a single edge.
     ///
-    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`].
+    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`], or if the edge
+    /// was removed.
     pub fn get_edge_component<C: Component>(&self, id: EdgeId) -> Option<&C> {
+        self.get_edge(id)?;
         let components = &self.edge_components.downcast()?.components;
         if let Ok(i) = components.binary_search_by(|(correct_index, _)| correct_index.cmp(&id.0)) {
             return Some(&components[i].1);
@@ -189,8 +217,10 @@ impl Graph {
 
     /// Get the [`Component`] `C` for a single node.
     ///
-    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`].
+    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`], or if the node
+    /// was removed.
     pub fn get_node_component_mut<C: Component>(&mut self, id: NodeId) -> Option<&mut C> {
+        self.get_node(id)?;
         let components = &mut self.node_components.downcast_mut()?.components;
         if let Ok(i) = components.binary_search_by(|(correct_index, _)| correct_index.cmp(&id.0)) {
             return Some(&mut components[i].1);
@@ -201,8 +231,10 @@ impl Graph {
 
     /// Get the [`Component`] `C` for a single edge.
     ///
-    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`].
+    /// Returns [`None`] if the [`Component`] `C` does not exist in this [`Graph`], or if the edge
+    /// was removed.
     pub fn get_edge_component_mut<C: Component>(&mut self, id: EdgeId) -> Option<&mut C> {
+        self.get_edge(id)?;
         let components = &mut self.edge_components.downcast_mut()?.components;
         if let Ok(i) = components.binary_search_by(|(correct_index, _)| correct_index.cmp(&id.0)) {
             return Some(&mut components[i].1);
@@ -251,9 +283,9 @@ impl Graph {
         let components = &self.edge_components.downcast::<C>()?.components;
 
         Some(components.iter().filter_map(move |(edge_id, comp)| {
-            let edge = &self.edges[*edge_id];
+            let edge = self.edges[*edge_id].as_ref()?;
             if edge.from == from {
-                Some((edge.to, &self.nodes[edge.to.0], comp))
+                Some((edge.to, self.nodes[edge.to.0].as_ref()?, comp))
             } else {
                 None
             }
@@ -272,17 +304,99 @@ impl Graph {
         let components = &self.edge_components.downcast::<C>()?.components;
 
         Some(components.iter().filter_map(move |(edge_id, comp)| {
-            let edge = &self.edges[*edge_id];
+            let edge = self.edges[*edge_id].as_ref()?;
             if edge.from == from {
-                Some((edge.to, &self.nodes[edge.to.0], comp))
+                Some((edge.to, self.nodes[edge.to.0].as_ref()?, comp))
             } else if edge.to == from {
-                Some((edge.from, &self.nodes[edge.from.0], comp))
+                Some((edge.from, self.nodes[edge.from.0].as_ref()?, comp))
             } else {
                 None
             }
         }))
     }
 
+    /// Get a single node.
+    ///
+    /// Returns [`None`] if the node was removed.
+    pub fn get_node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    /// Get a single edge.
+    ///
+    /// Returns [`None`] if the edge was removed.
+    pub fn get_edge(&self, id: EdgeId) -> Option<&Edge> {
+        self.edges.get(id.0)?.as_ref()
+    }
+
+    /// Add a new node without components, see
+    /// [`insert_node_component`](Graph::insert_node_component).
+    ///
+    /// The node is sent to the manager with its output components, and the manager allocates its
+    /// id once the timestep is done.
+    pub fn insert_node(&mut self, node: Node) -> NodeId {
+        self.nodes.push(Some(node));
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Give a node the [`Component`] `C`, replacing the component it had.
+    ///
+    /// Returns `false` if the node was removed.
+    pub fn insert_node_component<C: Component>(&mut self, id: NodeId, component: C) -> bool {
+        if self.get_node(id).is_none() {
+            return false;
+        }
+        let components = &mut self.node_components.get_or_insert::<C>().components;
+        match components.binary_search_by(|(correct_index, _)| correct_index.cmp(&id.0)) {
+            Ok(i) => components[i].1 = component,
+            Err(i) => components.insert(i, (id.0, component)),
+        }
+        true
+    }
+
+    /// Remove a node, together with the edges from and to it.
+    ///
+    /// Returns [`None`] if the node was already removed.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<Node> {
+        let node = self.nodes.get_mut(id.0)?.take()?;
+        for edge in &mut self.edges {
+            if edge.as_ref().is_some_and(|e| e.from == id || e.to == id) {
+                *edge = None;
+            }
+        }
+        Some(node)
+    }
+
+    /// Add a new edge with the [`Component`] `C`.
+    ///
+    /// `C` has to be an output component, the manager allocates the id of the edge once the
+    /// timestep is done. Returns [`None`] if one of the nodes was removed.
+    pub fn insert_edge<C: Component>(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        component: C,
+    ) -> Option<EdgeId> {
+        self.get_node(from)?;
+        self.get_node(to)?;
+        self.edges.push(Some(Edge { from, to }));
+        let id = EdgeId(self.edges.len() - 1);
+        // the new edge has the largest index, so the storage stays sorted
+        self.edge_components
+            .get_or_insert::<C>()
+            .components
+            .push((id.0, component));
+        self.added_edges.insert(id, C::get_name);
+        Some(id)
+    }
+
+    /// Remove an edge.
+    ///
+    /// Returns [`None`] if the edge was already removed.
+    pub fn remove_edge(&mut self, id: EdgeId) -> Option<Edge> {
+        self.edges.get_mut(id.0)?.take()
+    }
+
     /// Create a new graph containing only the components marked as output components in
     /// the given [`ComponentsInfo`].
     ///
@@ -470,8 +584,9 @@ impl Graph {
         )?;
 
         Some(Graph {
-            nodes,
-            edges,
+            nodes: nodes.into_iter().map(Some).collect(),
+            edges: edges.into_iter().map(Some).collect(),
+            added_edges: HashMap::new(),
             node_components,
             edge_components,
             global_components,
@@ -483,8 +598,18 @@ impl Graph {
         })
     }
 
-    /// Create [`proto::State`] from a [`Graph`].
-    pub(crate) fn into_state(self, components_info: &ComponentsInfo) -> Option<proto::State> {
+    /// Create [`proto::State`] from a [`Graph`], together with the nodes and edges that were
+    /// added and removed.
+    ///
+    /// New nodes and edges get provisional ids that are larger than the id of every existing node
+    /// or edge.
+    ///
+    /// Returns an error when a new edge has a component that is not an output component, as the
+    /// edge can not be sent to the manager without it.
+    pub(crate) fn into_state(
+        self,
+        components_info: &ComponentsInfo,
+    ) -> Result<(proto::State, proto::simulator::TopologyChanges), String> {
         let mut global_components = HashMap::new();
         for (type_id, component_storage) in self.global_components.components {
             let Some(info) = components_info.output_components.get(&type_id) else {
@@ -497,15 +622,28 @@ impl Graph {
             }
         }
 
+        let first_new_node = next_id(self.node_index_to_manager_id.values());
+        let node_id = |index: usize| match self.node_index_to_manager_id.get(&NodeId(index)) {
+            Some(id) => *id,
+            None => first_new_node + index as u64,
+        };
+        let first_new_edge = next_id(self.edge_index_to_manager_id.values());
+        let edge_id = |index: usize| match self.edge_index_to_manager_id.get(&EdgeId(index)) {
+            Some(id) => *id,
+            None => first_new_edge + index as u64,
+        };
+
         let mut nodes: Vec<_> = self
             .nodes
-            .into_iter()
+            .iter()
             .enumerate()
-            .map(|(index, n)| proto::Node {
-                longitude: n.longitude,
-                latitude: n.latitude,
-                id: self.node_index_to_manager_id[&NodeId(index)],
-                components: HashMap::new(),
+            .map(|(index, n)| {
+                n.as_ref().map(|n| proto::Node {
+                    longitude: n.longitude,
+                    latitude: n.latitude,
+                    id: node_id(index),
+                    components: HashMap::new(),
+                })
             })
             .collect();
 
@@ -516,10 +654,27 @@ impl Graph {
             };
 
             for (i, component) in (info.components_to_values)(component_storage) {
-                nodes
-                    .get_mut(i)?
-                    .components
-                    .insert(info.name.clone(), component);
+                let node = nodes.get_mut(i).ok_or_else(|| {
+                    format!(
+                        "component `{}` belongs to node {i}, which does not exist",
+                        info.name
+                    )
+                })?;
+                // Skip components of removed nodes
+                if let Some(node) = node {
+                    node.components.insert(info.name.clone(), component);
+                }
+            }
+        }
+
+        let mut topology = proto::simulator::TopologyChanges::default();
+        let mut state_nodes = Vec::new();
+        for (index, node) in nodes.into_iter().enumerate() {
+            match (node, self.node_index_to_manager_id.get(&NodeId(index))) {
+                (Some(node), Some(_)) => state_nodes.push(node),
+                (Some(node), None) => topology.added_nodes.push(node),
+                (None, Some(id)) => topology.removed_nodes.push(*id),
+                (None, None) => {}
             }
         }
 
@@ -532,14 +687,39 @@ impl Graph {
             };
 
             for (i, component) in (info.components_to_values)(component_storage) {
-                let edge = &self.edges[i];
-                edges.push(proto::Edge {
-                    from: self.node_index_to_manager_id[&edge.from],
-                    to: self.node_index_to_manager_id[&edge.to],
-                    id: self.edge_index_to_manager_id[&EdgeId(i)],
+                let Some(edge) = &self.edges[i] else {
+                    // Skip removed edges
+                    continue;
+                };
+                let edge = proto::Edge {
+                    from: node_id(edge.from.0),
+                    to: node_id(edge.to.0),
+                    id: edge_id(i),
                     component_type: info.name.clone(),
                     component_data: Some(component),
-                });
+                };
+                if self.edge_index_to_manager_id.contains_key(&EdgeId(i)) {
+                    edges.push(edge);
+                } else {
+                    topology.added_edges.push(edge);
+                }
+            }
+        }
+
+        for (index, edge) in self.edges.iter().enumerate() {
+            match (edge, self.edge_index_to_manager_id.get(&EdgeId(index))) {
+                (None, Some(id)) => topology.removed_edges.push(*id),
+                // A new edge is only sent if its component is an output component
+                (Some(_), None) if !topology.added_edges.iter().any(|e| e.id == edge_id(index)) => {
+                    let name = self
+                        .added_edges
+                        .get(&EdgeId(index))
+                        .map_or_else(|| "unknown".to_string(), |name| name());
+                    return Err(format!(
+                        "the new edge {index} has component `{name}`, which is not an output component"
+                    ));
+                }
+                _ => {}
             }
         }
 
@@ -547,13 +727,61 @@ impl Graph {
             debug_assert!(edge.component_data.is_some());
         }
 
-        Some(proto::State {
-            graph: Some(proto::Graph { nodes, edge: edges }),
-            global_components,
-        })
+        Ok((
+            proto::State {
+                graph: Some(proto::Graph {
+                    nodes: state_nodes,
+                    edge: edges,
+                }),
+                global_components,
+            },
+            topology,
+        ))
     }
 }
 
+impl Graph {
+    /// Take the input components that are not output components in `components_info` out of the
+    /// graph, as the values of the nodes, edges and global components by name. These components
+    /// are not sent to the manager.
+    pub(crate) fn take_non_output(
+        &mut self,
+        components_info: &ComponentsInfo,
+    ) -> BTreeMap<String, Vec<(usize, Value)>> {
+        let mut values = BTreeMap::new();
+        for storage in [
+            &mut self.node_components,
+            &mut self.edge_components,
+            &mut self.global_components,
+        ] {
+            let non_output: Vec<TypeId> = storage
+                .components
+                .keys()
+                .filter(|type_id| !components_info.output_components.contains_key(type_id))
+                .copied()
+                .collect();
+            for type_id in non_output {
+                // Components the simulator did not ask for can not be converted
+                let Some(info) = components_info.components.get(&type_id) else {
+                    continue;
+                };
+                if let Some(component_storage) = storage.components.remove(&type_id) {
+                    values
+                        .entry(info.name.clone())
+                        .or_insert_with(Vec::new)
+                        .extend((info.components_to_values)(component_storage));
+                }
+            }
+        }
+        values
+    }
+}
+
+/// The id after the largest id in `ids`.
+fn next_id<'a>(ids: impl Iterator<Item = &'a u64>) -> u64 {
+    ids.max().map_or(0, |id| id + 1)
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
@@ -988,4 +1216,95 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn insert_and_remove() {
+        let (mut graph, _state, components_info) = create_test_graph();
+        let components_info = components_info
+            .add_output_component::<TestNodeComp>()
+            .add_output_component::<TestEdgeComp>();
+
+        // removes the edges 0 (0 -> 2), 1 (2 -> 4) and 100 (0 -> 2)
+        assert!(graph.remove_node(NodeId(1)).is_some());
+        assert!(graph.remove_node(NodeId(1)).is_none());
+        assert_eq!(graph.get_node_component::<TestNodeComp>(NodeId(1)), None);
+        assert!(graph.remove_edge(EdgeId(3)).is_some());
+        assert_eq!(
+            graph
+                .neighbors_undirected::<TestEdgeComp>(NodeId(2))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        let new = graph.insert_node(Node {
+            latitude: 5.0,
+            longitude: 5.0,
+        });
+        assert!(graph.insert_node_component(new, TestNodeComp(5)));
+        assert!(!graph.insert_node_component(NodeId(1), TestNodeComp(5)));
+        assert!(graph.insert_edge(NodeId(1), new, TestEdgeComp(6)).is_none());
+        let new_edge = graph.insert_edge(new, NodeId(3), TestEdgeComp(6)).unwrap();
+        assert_eq!(
+            graph.get_edge_component::<TestEdgeComp>(new_edge),
+            Some(&TestEdgeComp(6))
+        );
+
+        let (state, topology) = graph.into_state(&components_info).unwrap();
+        let node_ids: Vec<_> = state
+            .graph
+            .as_ref()
+            .unwrap()
+            .nodes
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(node_ids, vec![0, 4, 6]);
+        let edge_ids: Vec<_> = state
+            .graph
+            .as_ref()
+            .unwrap()
+            .edge
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(edge_ids, vec![2]);
+
+        assert_eq!(topology.removed_nodes, vec![2]);
+        assert_eq!(topology.removed_edges, vec![0, 1, 3, 100]);
+        // provisional ids are larger than the ids of the existing nodes and edges
+        assert_eq!(
+            topology.added_nodes,
+            vec![::proto::simulation::Node {
+                longitude: 5.0,
+                latitude: 5.0,
+                id: 11,
+                components: HashMap::from([(TestNodeComp::get_name(), TestNodeComp(5).to_value())]),
+            }]
+        );
+        assert_eq!(
+            topology.added_edges,
+            vec![::proto::simulation::Edge {
+                from: 11,
+                to: 6,
+                id: 108,
+                component_type: TestEdgeComp::get_name(),
+                component_data: Some(TestEdgeComp(6).to_value()),
+            }]
+        );
+    }
+
+    #[test]
+    fn insert_edge_without_output_component() {
+        let (mut graph, _state, components_info) = create_test_graph();
+        graph.insert_edge(NodeId(0), NodeId(1), TestEdgeComp(7));
+        let new_edge = graph.edges.len() - 1;
+        assert_eq!(
+            graph.into_state(&components_info).err(),
+            Some(format!(
+                "the new edge {new_edge} has component `{}`, which is not an output component",
+                TestEdgeComp::get_name()
+            ))
+        );
+    }
 }
@@ -9,8 +9,15 @@
 pub mod component;
 pub mod graph;
 pub mod simulator;
-
-use std::{error::Error, net::SocketAddr, time::Duration};
+pub mod testing;
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use tokio::sync::Mutex;
 use tonic::{transport, Request, Response, Status};
@@ -18,10 +25,10 @@ use tonic::{transport, Request, Response, Status};
 use proto::{
     simulator::{
         simulator_server::SimulatorServer, InitialState, IoConfigRequest, SetupResponse,
-        SimulatorIoConfig, TimestepResult,
+        SimulatorIoConfig, TeardownRequest, TeardownResponse, TimestepRequest, TimestepResult,
+        UpgradeRequest, UpgradeResult,
     },
-    simulator_connection::{SimulatorConnectionClient, SimulatorInfo},
-    State,
+    simulator_connection::{DisconnectRequest, SimulatorConnectionClient, SimulatorInfo},
 };
 
 pub use graph::Graph;
@@ -33,10 +40,17 @@ pub use simulator::{ComponentsInfo, Simulator};
 ///
 /// Use the `component` attribute to specify the component name and type.
 /// The posible types are: `"node"`, `"edge"` and `"global"`.
+/// The attribute can also hold the metadata of the whole component, see
+/// [`ComponentPiece`](derive@ComponentPiece). The doc comment of the struct is used as its `doc`.
+///
+/// When the structure of a component changes, increase its `version` (0 by default) and give an
+/// `upgrade` function with the signature of [`Component::upgrade`], which upgrades a value of the
+/// given version to the next one.
 /// # Example:
 /// ```
 /// # use simulator_communication::{Component, ComponentPiece};
 ///
+/// /// A line that transports electricity.
 /// #[derive(ComponentPiece, Component)]
 /// #[component(name = "transmision-line", ty = "edge")]
 /// struct TransmisionLine {
@@ -45,9 +59,20 @@ pub use simulator::{ComponentsInfo, Simulator};
 /// }
 /// ```
 pub use simulator_communication_macros::Component;
-/// Derive [`ComponentPiece`] on a struct
+/// Derive [`ComponentPiece`] on a struct or enum
 ///
-/// This macro can only be used on structs.
+/// A unit variant of an enum is a string with the name of the variant, which is parsed
+/// case-insensitively. A variant with data is a struct with a single field, named after the
+/// variant, that holds the data. Use `#[component(rename = "...")]` on a variant to give it
+/// another name, and `#[component(alias = "...")]` to also accept an old name when reading it.
+///
+/// A missing field of a struct is read as null, so a missing `Option` field is [`None`].
+///
+/// Fields and variants with data can describe their value with
+/// `#[component(unit = "...", min = 0.0, max = 1.0, doc = "...", default = ...)]`, all of which are
+/// optional. Doc comments are used when `doc` is not given. The `default` is an expression that is
+/// converted into the type of the field with [`Into`]. The manager rejects simulations with numbers
+/// outside of `min` and `max`, this includes the numbers in an `Option` or `Vec`.
 ///
 /// # Example:
 /// ```
@@ -66,7 +91,19 @@ pub use simulator_communication_macros::Component;
 /// #[component(name = "transmision-line", ty = "edge")]
 /// struct TransmisionLine {
 ///     shared_elec_data: SharedElecData,
+///     /// The length of the line.
+///     #[component(unit = "m", min = 0)]
 ///     length: f64,
+///     #[component(default = LineKind::Overhead)]
+///     kind: LineKind,
+/// }
+///
+/// #[derive(ComponentPiece)]
+/// enum LineKind {
+///     Overhead,
+///     #[component(rename = "XLPE_Cable", alias = "XLPE_Conductor")]
+///     XlpeCable,
+///     Custom { max_current: f64 },
 /// }
 /// ```
 pub use simulator_communication_macros::ComponentPiece;
@@ -97,55 +134,127 @@ impl<S: Simulator> proto::simulator::simulator_server::Simulator for Server<S> {
         request: Request<InitialState>,
     ) -> Result<Response<SetupResponse>, Status> {
         let initial_state = request.into_inner();
+        let session_id = initial_state.session_id;
 
         let delta_time = Duration::from_millis(initial_state.timestep_delta);
         let initial_state = initial_state
             .initial_state
             .ok_or_else(|| Status::invalid_argument("sould provide initial state"))?;
 
-        let graph = Graph::from_state(initial_state, &self.components_info)
-            .ok_or_else(|| Status::invalid_argument("Could not create graph"))?;
+        let graph = graph_from_state(initial_state, &self.components_info)?;
 
-        *self.simulator.lock().await = Some(S::new(delta_time, graph).await?);
+        let session = Session {
+            simulator: S::new(delta_time, graph).await?,
+            last_used: Instant::now(),
+        };
+        self.sessions
+            .lock()
+            .await
+            .insert(session_id, Arc::new(Mutex::new(session)));
 
         Ok(Response::new(SetupResponse {}))
     }
 
-    /// Takes a simulation state as input, executes a simulation step, and returns the resulting state.
+    /// Takes a simulation state as input, executes a simulation step for the given session, and returns the resulting state.
     async fn do_timestep(
         &self,
-        request: Request<State>,
+        request: Request<TimestepRequest>,
     ) -> Result<Response<TimestepResult>, Status> {
-        let state = request.into_inner();
+        let TimestepRequest { session_id, state } = request.into_inner();
+        let state = state.ok_or_else(|| Status::invalid_argument("should provide state"))?;
 
-        let graph = Graph::from_state(state, &self.components_info)
-            .ok_or_else(|| Status::invalid_argument("Could not create graph"))?;
+        let graph = graph_from_state(state, &self.components_info)?;
 
-        let result_graph = self
-            .simulator
+        // Only hold the lock on the map for as long as it takes to find the session, so other
+        // sessions can do their timestep at the same time.
+        let session = self
+            .sessions
             .lock()
             .await
-            .as_mut()
+            .get(&session_id)
+            .cloned()
             .ok_or_else(|| {
-                Status::failed_precondition("should `setup` before calling `do_timestep`")
-            })?
-            .do_timestep(graph)
-            .await?;
-
-        Ok(Response::new(TimestepResult {
-            output_state: Some(
-                result_graph
-                    .into_state(&self.components_info)
-                    .ok_or_else(|| Status::internal("could not create state from graph"))?,
-            ),
+                Status::failed_precondition(format!(
+                    "should `setup` session `{session_id}` before calling `do_timestep`"
+                ))
+            })?;
+        let mut session = session.lock().await;
+
+        let result_graph = session.simulator.do_timestep(graph).await?;
+        session.last_used = Instant::now();
+
+        Ok(Response::new(timestep_result(
+            result_graph,
+            &self.components_info,
+        )?))
+    }
+
+    /// Ends a session. Tearing down a session that does not exist (anymore) is not an error.
+    async fn teardown(
+        &self,
+        request: Request<TeardownRequest>,
+    ) -> Result<Response<TeardownResponse>, Status> {
+        let session_id = request.into_inner().session_id;
+        if self.sessions.lock().await.remove(&session_id).is_some() {
+            info!("Session `{session_id}` was torn down");
+        }
+        Ok(Response::new(TeardownResponse {}))
+    }
+
+    /// Upgrades values of an older version of a component with [`Component::upgrade`](component::Component::upgrade).
+    async fn upgrade_components(
+        &self,
+        request: Request<UpgradeRequest>,
+    ) -> Result<Response<UpgradeResult>, Status> {
+        let UpgradeRequest {
+            component,
+            version,
+            values,
+        } = request.into_inner();
+        let info = self
+            .components_info
+            .get_by_name(&component)
+            .ok_or_else(|| Status::not_found(format!("unknown component `{component}`")))?;
+        Ok(Response::new(UpgradeResult {
+            values: info.upgrade(version, values)?,
         }))
     }
 }
 
+/// Create the graph given to a simulator from a state sent by the manager. Only the components the
+/// simulator asked for are included.
+pub(crate) fn graph_from_state(
+    state: proto::State,
+    components_info: &ComponentsInfo,
+) -> Result<Graph, Status> {
+    Graph::from_state(state, components_info)
+        .ok_or_else(|| Status::invalid_argument("Could not create graph"))
+}
+
+/// Create the result sent to the manager from the graph returned by a simulator. Only the output
+/// components are included.
+pub(crate) fn timestep_result(
+    graph: Graph,
+    components_info: &ComponentsInfo,
+) -> Result<TimestepResult, Status> {
+    let (output_state, topology) = graph
+        .into_state(components_info)
+        .map_err(|err| Status::internal(format!("could not create state from graph: {err}")))?;
+    Ok(TimestepResult {
+        output_state: Some(output_state),
+        topology: Some(topology),
+    })
+}
+
 /// Server struct that holds the state and configuration of the simulation.
 ///
 /// This is the main struct you will want to use the start a new simulation server.
 ///
+/// The server can run multiple simulations at the same time. Every simulation is a separate
+/// session with its own instance of the [`Simulator`]. Sessions are removed when the manager
+/// tears them down, or when they have not been used for some time (see
+/// [`with_session_timeout`](Server::with_session_timeout)).
+///
 /// # Example:
 
 /// ```
@@ -177,9 +286,21 @@ impl<S: Simulator> proto::simulator::simulator_server::Simulator for Server<S> {
 pub struct Server<S: Simulator> {
     io_config: SimulatorIoConfig,
     components_info: ComponentsInfo,
-    simulator: Mutex<Option<S>>,
+    sessions: Mutex<HashMap<String, Arc<Mutex<Session<S>>>>>,
+    session_timeout: Option<Duration>,
+    timestep_timeout: Option<Duration>,
+}
+
+/// A single simulation running in the server.
+struct Session<S: Simulator> {
+    simulator: S,
+    /// Last time the session was set up or did a timestep.
+    last_used: Instant,
 }
 
+/// The default time after which an idle session is removed.
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
 /// Possible errors the server could return.
 pub enum ServerError {
     /// A transport error from main server.
@@ -259,13 +380,50 @@ impl<S: Simulator> Server<S> {
                 components,
             },
             components_info,
-            simulator: Mutex::new(None),
+            sessions: Mutex::new(HashMap::new()),
+            session_timeout: Some(DEFAULT_SESSION_TIMEOUT),
+            timestep_timeout: None,
         }
     }
 
+    /// Set the time after which a session that has not been used is removed.
+    ///
+    /// Use `None` to keep sessions around until they are torn down explicitly.
+    /// Defaults to [`DEFAULT_SESSION_TIMEOUT`].
+    pub fn with_session_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    /// Set how long the manager should wait for a single timestep of this simulator before it
+    /// considers it failed.
+    ///
+    /// Use `None` to use the default of the manager, which is also the default here.
+    pub fn with_timestep_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timestep_timeout = timeout;
+        self
+    }
+
+    /// Remove all sessions that have been idle for longer than `timeout`.
+    ///
+    /// Sessions that are currently doing a timestep are never removed.
+    async fn remove_idle_sessions(&self, timeout: Duration) {
+        self.sessions.lock().await.retain(|session_id, session| {
+            let Ok(session) = session.try_lock() else {
+                return true;
+            };
+            let keep = session.last_used.elapsed() < timeout;
+            if !keep {
+                info!("Session `{session_id}` timed out");
+            }
+            keep
+        });
+    }
+
     /// Start a new server listening on the given `simulator_addr` and attaches itself to the manager with given `manager_addr`.
     /// `manager_addr` needs to be a valid endpoint, meaning "http://" is included.
-    /// This function does not return unless there is some error.
+    /// This function does not return unless there is some error, or the process receives a
+    /// ctrl-c signal. In the latter case the simulator disconnects from the manager first.
     pub async fn start(
         self,
         simulator_addr: impl Into<SocketAddr>,
@@ -274,9 +432,22 @@ impl<S: Simulator> Server<S> {
     ) -> Result<(), ServerError> {
         let addr = simulator_addr.into();
         let port = addr.port() as u32;
+        let timestep_timeout_ms = self.timestep_timeout.map(|t| t.as_millis() as u64);
+
+        let this = Arc::new(self);
+        if let Some(timeout) = this.session_timeout {
+            let this = this.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(timeout.min(Duration::from_secs(60)));
+                loop {
+                    interval.tick().await;
+                    this.remove_idle_sessions(timeout).await;
+                }
+            });
+        }
 
         let server = transport::Server::builder()
-            .add_service(SimulatorServer::new(self))
+            .add_service(SimulatorServer::from_arc(this))
             .serve(addr);
 
         // Keep simulator running in a different thread
@@ -293,13 +464,23 @@ impl<S: Simulator> Server<S> {
                 err?.map_err(ServerError::Transport)?
             },
             connection = connection => {
+                let mut connection = connection.map_err(ServerError::ConnectionTransport)?;
                 connection
-                    .map_err(ServerError::ConnectionTransport)?
-                    .connect_simulator(SimulatorInfo { port, name: name.to_string()  }).await
+                    .connect_simulator(SimulatorInfo { port, name: name.to_string(), timestep_timeout_ms }).await
                     .map_err(|e| ServerError::ConnectionReturn(e.message().to_owned()))?;
 
-                // Keep simulator running
-                task.await?.map_err(ServerError::Transport)?
+                // Keep simulator running until it is stopped
+                tokio::select! {
+                    err = task => err?.map_err(ServerError::Transport)?,
+                    signal = tokio::signal::ctrl_c() => {
+                        signal?;
+                        info!("Disconnecting from manager");
+                        connection
+                            .disconnect_simulator(DisconnectRequest { name: name.to_string() })
+                            .await
+                            .map_err(|e| ServerError::ConnectionReturn(e.message().to_owned()))?;
+                    },
+                }
             },
         }
 
@@ -312,3 +493,180 @@ impl<S: Simulator> Default for Server<S> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        component::ComponentPiece as _,
+        proto::{simulator::simulator_server::Simulator as _, State},
+        simulator::SimulationError,
+    };
+
+    #[derive(ComponentPiece, Component)]
+    #[component(name = "test-counter", ty = "global", version = 2, upgrade = upgrade_counter)]
+    struct Counter(u32);
+
+    /// Version 0 of the counter was a struct with a `count` field, version 1 had the same
+    /// structure as version 2.
+    fn upgrade_counter(version: u32, value: Value) -> Option<Value> {
+        match (version, value.kind?) {
+            (0, prost_types::value::Kind::StructValue(mut value)) => value.fields.remove("count"),
+            (1, kind) => Some(Value { kind: Some(kind) }),
+            _ => None,
+        }
+    }
+
+    /// Simulator counting the amount of timesteps it did.
+    struct CountingSimulator {
+        steps: u32,
+    }
+
+    impl Simulator for CountingSimulator {
+        fn get_component_info() -> ComponentsInfo {
+            ComponentsInfo::new()
+                .add_required_component::<Counter>()
+                .add_output_component::<Counter>()
+        }
+
+        async fn new(_delta_time: Duration, _graph: Graph) -> Result<Self, SimulationError> {
+            Ok(Self { steps: 0 })
+        }
+
+        async fn do_timestep(&mut self, mut graph: Graph) -> Result<Graph, SimulationError> {
+            self.steps += 1;
+            graph.get_global_component_mut::<Counter>().unwrap().0 = self.steps;
+            Ok(graph)
+        }
+    }
+
+    fn state() -> State {
+        State {
+            graph: Some(proto::Graph {
+                nodes: Vec::new(),
+                edge: Vec::new(),
+            }),
+            global_components: HashMap::from([("test-counter".to_owned(), Counter(0).to_value())]),
+        }
+    }
+
+    async fn setup(server: &Server<CountingSimulator>, session_id: &str) {
+        server
+            .setup(Request::new(InitialState {
+                timestep_delta: 1000,
+                initial_state: Some(state()),
+                session_id: session_id.to_owned(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    async fn do_timestep(
+        server: &Server<CountingSimulator>,
+        session_id: &str,
+    ) -> Result<u32, Status> {
+        let result = server
+            .do_timestep(Request::new(TimestepRequest {
+                session_id: session_id.to_owned(),
+                state: Some(state()),
+            }))
+            .await?
+            .into_inner()
+            .output_state
+            .unwrap();
+        let counter = result
+            .global_components
+            .get("test-counter")
+            .unwrap()
+            .clone();
+        Ok(Counter::from_value(counter).unwrap().0)
+    }
+
+    #[tokio::test]
+    async fn sessions_are_independent() {
+        let server = Server::<CountingSimulator>::new();
+        setup(&server, "a").await;
+        setup(&server, "b").await;
+
+        assert_eq!(do_timestep(&server, "a").await.unwrap(), 1);
+        assert_eq!(do_timestep(&server, "a").await.unwrap(), 2);
+        assert_eq!(do_timestep(&server, "b").await.unwrap(), 1);
+
+        // Setting up an existing session starts it over.
+        setup(&server, "a").await;
+        assert_eq!(do_timestep(&server, "a").await.unwrap(), 1);
+        assert_eq!(do_timestep(&server, "b").await.unwrap(), 2);
+
+        let err = do_timestep(&server, "c").await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn sessions_are_removed() {
+        let server = Server::<CountingSimulator>::new();
+        setup(&server, "a").await;
+        setup(&server, "b").await;
+
+        server
+            .teardown(Request::new(TeardownRequest {
+                session_id: "a".to_owned(),
+            }))
+            .await
+            .unwrap();
+        assert!(do_timestep(&server, "a").await.is_err());
+        assert!(do_timestep(&server, "b").await.is_ok());
+
+        server.remove_idle_sessions(Duration::ZERO).await;
+        assert!(do_timestep(&server, "b").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn upgrade_components() {
+        let server = Server::<CountingSimulator>::new();
+        let upgrade = |component: &str, version, values| {
+            server.upgrade_components(Request::new(UpgradeRequest {
+                component: component.to_owned(),
+                version,
+                values,
+            }))
+        };
+        let old = |count: u32| Value {
+            kind: Some(prost_types::value::Kind::StructValue(prost_types::Struct {
+                fields: [("count".to_owned(), Counter(count).to_value())].into(),
+            })),
+        };
+
+        let upgraded = upgrade("test-counter", 0, vec![old(3), old(5)])
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            upgraded.values,
+            vec![Counter(3).to_value(), Counter(5).to_value()]
+        );
+        let current = upgrade("test-counter", 2, vec![Counter(3).to_value()])
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(current.values, vec![Counter(3).to_value()]);
+
+        let invalid = upgrade("test-counter", 0, vec![Counter(3).to_value()])
+            .await
+            .unwrap_err();
+        assert_eq!(invalid.code(), tonic::Code::FailedPrecondition);
+        assert_eq!(
+            invalid.message(),
+            "component `test-counter` can not be upgraded from version 0"
+        );
+        let newer = upgrade("test-counter", 3, vec![]).await.unwrap_err();
+        assert_eq!(
+            newer.message(),
+            "component `test-counter` can not be downgraded from version 3 to version 2"
+        );
+        let unknown = upgrade("unknown", 0, vec![]).await.unwrap_err();
+        assert_eq!(unknown.code(), tonic::Code::NotFound);
+    }
+}
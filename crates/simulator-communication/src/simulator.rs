@@ -21,6 +21,7 @@ use crate::{
 
 type ValueToComponentsFn = fn(Vec<(usize, Value)>) -> Option<Box<dyn Any + Send + Sync>>;
 type ComponentsToValueFn = fn(Box<dyn Any + Send + Sync>) -> Vec<(usize, Value)>;
+type UpgradeFn = fn(u32, Value) -> Option<Value>;
 
 /// Contains all the data needed to easily work with different components at runtime.
 #[derive(Debug, Clone)]
@@ -31,6 +32,7 @@ pub(crate) struct ComponentInfo {
     pub(crate) proto_spec: crate::ComponentSpecification,
     pub(crate) values_to_components: ValueToComponentsFn,
     pub(crate) components_to_values: ComponentsToValueFn,
+    pub(crate) upgrade: UpgradeFn,
 }
 
 /// Generic function to go from a Vec of Values to a ComponentStorage.
@@ -75,8 +77,33 @@ impl ComponentInfo {
             proto_spec: C::get_spec(),
             values_to_components: values_to_components::<C>,
             components_to_values: components_to_values::<C>,
+            upgrade: C::upgrade,
         }
     }
+
+    /// Upgrade values of version `version` to the version of the component.
+    pub(crate) fn upgrade(&self, version: u32, values: Vec<Value>) -> Result<Vec<Value>, Status> {
+        if version > self.proto_spec.version {
+            return Err(Status::failed_precondition(format!(
+                "component `{}` can not be downgraded from version {version} to version {}",
+                self.name, self.proto_spec.version
+            )));
+        }
+        values
+            .into_iter()
+            .map(|mut value| {
+                for from in version..self.proto_spec.version {
+                    value = (self.upgrade)(from, value).ok_or_else(|| {
+                        Status::failed_precondition(format!(
+                            "component `{}` can not be upgraded from version {from}",
+                            self.name
+                        ))
+                    })?;
+                }
+                Ok(value)
+            })
+            .collect()
+    }
 }
 
 /// Contains information about the [`Component`]s a [`Simulator`] wants to use.
@@ -147,6 +174,14 @@ impl ComponentsInfo {
         }
     }
 
+    /// Get the component with the name `name`.
+    pub(crate) fn get_by_name(&self, name: &str) -> Option<&ComponentInfo> {
+        let type_id = self.string_to_typeid.get(name)?;
+        self.components
+            .get(type_id)
+            .or_else(|| self.output_components.get(type_id))
+    }
+
     /// Add the [`Component`] `C` as a component we promise to send back to the
     /// manager.
     pub fn add_output_component<C: Component>(mut self) -> Self {
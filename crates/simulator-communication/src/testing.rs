@@ -0,0 +1,728 @@
+//! Helpers to test a [`Simulator`] without a manager.
+//!
+//! Build the initial state of a simulation with a [`GraphBuilder`] and run the simulator with a
+//! [`TestSimulation`]. The simulator gets the same graphs, and its results are converted in the
+//! same way, as when it runs in a [`Server`](crate::Server). After every timestep the outputs are
+//! applied to the state like the manager does, so the next timestep gets them as input.
+//!
+//! The outputs can be compared with a file using [`assert_snapshot`], for golden-file tests.
+//!
+//! # Example:
+//! ```
+//! # use std::time::Duration;
+//! # use simulator_communication::{
+//! #     simulator::SimulationError, Component, ComponentPiece, ComponentsInfo, Graph, Simulator,
+//! # };
+//! use simulator_communication::testing::{GraphBuilder, TestSimulation};
+//!
+//! #[derive(ComponentPiece, Component)]
+//! #[component(name = "temperature", ty = "node")]
+//! struct Temperature(f64);
+//!
+//! struct HeatingSimulator;
+//!
+//! impl Simulator for HeatingSimulator {
+//!     fn get_component_info() -> ComponentsInfo {
+//!         ComponentsInfo::new()
+//!             .add_required_component::<Temperature>()
+//!             .add_output_component::<Temperature>()
+//!     }
+//!
+//!     async fn new(_delta_time: Duration, _graph: Graph) -> Result<Self, SimulationError> {
+//!         Ok(Self)
+//!     }
+//!
+//!     async fn do_timestep(&mut self, mut graph: Graph) -> Result<Graph, SimulationError> {
+//!         for (_, _, temperature) in graph.get_all_nodes_mut::<Temperature>().into_iter().flatten() {
+//!             temperature.0 += 1.0;
+//!         }
+//!         Ok(graph)
+//!     }
+//! }
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let mut builder = GraphBuilder::new();
+//! let node = builder.add_node(52.0, 4.0);
+//! builder.add_node_component(node, Temperature(20.0));
+//!
+//! let mut simulation =
+//!     TestSimulation::<HeatingSimulator>::new(Duration::from_secs(60), builder.to_state())
+//!         .await
+//!         .expect("the simulator can be set up");
+//! simulation.run(3).await.expect("the timesteps succeed");
+//!
+//! let graph = simulation.graph();
+//! assert_eq!(graph.get_node_component::<Temperature>(node).map(|t| t.0), Some(23.0));
+//! # }
+//! ```
+
+use std::{collections::HashMap, env, fs, path::Path, time::Duration};
+
+use tonic::Status;
+
+use crate::{
+    component::Component,
+    graph::{EdgeId, Graph, NodeId},
+    graph_from_state,
+    proto::{self, simulator::TimestepResult, ComponentType},
+    timestep_result, ComponentsInfo, Simulator, Value,
+};
+
+/// The environment variable that makes [`assert_snapshot`] overwrite snapshots instead of
+/// comparing them.
+pub const UPDATE_SNAPSHOTS: &str = "UPDATE_SNAPSHOTS";
+
+/// Builds the state of a simulation from typed components.
+///
+/// Nodes and edges get the ids the manager would give them, starting at 0. The [`NodeId`]s and
+/// [`EdgeId`]s returned by the builder can be used with the [`Graph`] built from it, and with the
+/// graph of a [`TestSimulation`] as long as no nodes or edges were removed.
+#[derive(Debug, Clone, Default)]
+pub struct GraphBuilder {
+    nodes: Vec<proto::Node>,
+    edges: Vec<proto::Edge>,
+    global_components: HashMap<String, Value>,
+}
+
+impl GraphBuilder {
+    /// Create a new empty [`GraphBuilder`].
+    pub fn new() -> Self {
+        GraphBuilder::default()
+    }
+
+    /// Add a node without components.
+    pub fn add_node(&mut self, latitude: f64, longitude: f64) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(proto::Node {
+            id: id.0 as u64,
+            longitude,
+            latitude,
+            components: HashMap::new(),
+        });
+        id
+    }
+
+    /// Give a node the node [`Component`] `C`, replacing the component it had.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the node does not exist or `C` is not a node component.
+    pub fn add_node_component<C: Component>(&mut self, node: NodeId, component: C) -> &mut Self {
+        assert_type::<C>(ComponentType::Node);
+        let node = self
+            .nodes
+            .get_mut(node.0)
+            .unwrap_or_else(|| panic!("node {} does not exist", node.0));
+        node.components.insert(C::get_name(), component.to_value());
+        self
+    }
+
+    /// Add an edge with the edge [`Component`] `C`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when one of the nodes does not exist or `C` is not an edge component.
+    pub fn add_edge<C: Component>(&mut self, from: NodeId, to: NodeId, component: C) -> EdgeId {
+        assert_type::<C>(ComponentType::Edge);
+        for node in [from, to] {
+            assert!(node.0 < self.nodes.len(), "node {} does not exist", node.0);
+        }
+        let id = EdgeId(self.edges.len());
+        self.edges.push(proto::Edge {
+            id: id.0 as u64,
+            from: from.0 as u64,
+            to: to.0 as u64,
+            component_type: C::get_name(),
+            component_data: Some(component.to_value()),
+        });
+        id
+    }
+
+    /// Set the global [`Component`] `C`, replacing the component that was set.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `C` is not a global component.
+    pub fn add_global_component<C: Component>(&mut self, component: C) -> &mut Self {
+        assert_type::<C>(ComponentType::Global);
+        self.global_components
+            .insert(C::get_name(), component.to_value());
+        self
+    }
+
+    /// The state as the manager sends it to the simulators.
+    pub fn to_state(&self) -> proto::State {
+        proto::State {
+            graph: Some(proto::Graph {
+                nodes: self.nodes.clone(),
+                edge: self.edges.clone(),
+            }),
+            global_components: self.global_components.clone(),
+        }
+    }
+
+    /// The [`Graph`] a simulator with `components_info` gets for this state.
+    ///
+    /// # Panics
+    ///
+    /// Panics when a required component is missing, like [`Simulator::new`] would fail.
+    pub fn build(&self, components_info: &ComponentsInfo) -> Graph {
+        graph_from_state(self.to_state(), components_info)
+            .unwrap_or_else(|status| panic!("{}", status.message()))
+    }
+}
+
+/// Panic when `C` does not have the type `ty`.
+fn assert_type<C: Component>(ty: ComponentType) {
+    let actual = C::get_spec().r#type();
+    assert_eq!(
+        actual,
+        ty,
+        "component `{}` is a {} component",
+        C::get_name(),
+        actual.as_str_name().to_lowercase()
+    );
+}
+
+/// Runs a [`Simulator`] in-process, the way the manager and a [`Server`](crate::Server) would.
+///
+/// Every timestep the simulator gets the current state, filtered to the components it asked for.
+/// Its output components, and the nodes and edges it added and removed, are applied to the state
+/// for the next timestep. Components it changed that are not output components are not sent to
+/// the manager, so [`do_timestep`](TestSimulation::do_timestep) compares their values before and
+/// after the timestep, and panics when they differ.
+pub struct TestSimulation<S: Simulator> {
+    simulator: S,
+    components_info: ComponentsInfo,
+    state: proto::State,
+    outputs: Vec<TimestepResult>,
+    next_node: u64,
+    next_edge: u64,
+}
+
+impl<S: Simulator> TestSimulation<S> {
+    /// Set up the simulator with `initial_state`, like the manager does when a simulation starts.
+    pub async fn new(delta_time: Duration, initial_state: proto::State) -> Result<Self, Status> {
+        let components_info = S::get_component_info();
+        let graph = graph_from_state(initial_state.clone(), &components_info)?;
+        let simulator = S::new(delta_time, graph).await?;
+
+        let graph = initial_state.graph.as_ref();
+        let next_node = graph.and_then(|g| g.nodes.iter().map(|n| n.id + 1).max());
+        let next_edge = graph.and_then(|g| g.edge.iter().map(|e| e.id + 1).max());
+        Ok(Self {
+            simulator,
+            components_info,
+            state: initial_state,
+            outputs: Vec::new(),
+            next_node: next_node.unwrap_or(0),
+            next_edge: next_edge.unwrap_or(0),
+        })
+    }
+
+    /// Do a single timestep and apply its outputs to the state. Returns the outputs, as they are
+    /// sent to the manager.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the simulator changed components that are not output components.
+    pub async fn do_timestep(&mut self) -> Result<&TimestepResult, Status> {
+        let graph = graph_from_state(self.state.clone(), &self.components_info)?;
+        let before = graph_from_state(self.state.clone(), &self.components_info)?
+            .take_non_output(&self.components_info);
+        let mut graph = self.simulator.do_timestep(graph).await?;
+
+        let mut after = graph.take_non_output(&self.components_info);
+        let mut changed: Vec<String> = before
+            .into_iter()
+            .filter(|(name, values)| after.remove(name).as_ref() != Some(values))
+            .map(|(name, _)| name)
+            .collect();
+        // components that were added
+        changed.extend(after.into_keys());
+        changed.sort_unstable();
+        assert!(
+            changed.is_empty(),
+            "the simulator changed components that are not output components, these changes are not sent to the manager: {}",
+            changed.join(", ")
+        );
+
+        let result = timestep_result(graph, &self.components_info)?;
+        self.apply(&result)?;
+        self.outputs.push(result);
+        Ok(&self.outputs[self.outputs.len() - 1])
+    }
+
+    /// Do `timesteps` timesteps. Returns the outputs of every timestep that was done so far.
+    pub async fn run(&mut self, timesteps: usize) -> Result<&[TimestepResult], Status> {
+        for _ in 0..timesteps {
+            self.do_timestep().await?;
+        }
+        Ok(&self.outputs)
+    }
+
+    /// The current state of the simulation, with every component.
+    pub fn state(&self) -> &proto::State {
+        &self.state
+    }
+
+    /// The current state of the simulation, with the components the simulator asked for.
+    ///
+    /// # Panics
+    ///
+    /// Panics when a required component is missing.
+    pub fn graph(&self) -> Graph {
+        graph_from_state(self.state.clone(), &self.components_info)
+            .unwrap_or_else(|status| panic!("{}", status.message()))
+    }
+
+    /// The outputs of every timestep that was done so far.
+    pub fn outputs(&self) -> &[TimestepResult] {
+        &self.outputs
+    }
+
+    /// The outputs of every timestep as JSON, with nodes and edges ordered by id and the fields of
+    /// objects ordered by name, to compare with [`assert_snapshot`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the outputs contain a number that is not finite, as JSON has no such numbers.
+    pub fn snapshot(&self) -> String {
+        let timesteps = self
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(index, result)| {
+                let state = result.output_state.clone().unwrap_or_default();
+                let topology = result.topology.clone().unwrap_or_default();
+                let graph = state.graph.unwrap_or_default();
+                let mut fields = vec![
+                    ("timestep", number((index + 1) as f64)),
+                    ("nodes", nodes_value(graph.nodes)),
+                    ("edges", edges_value(graph.edge)),
+                    ("global_components", object(state.global_components)),
+                ];
+                if !topology.added_nodes.is_empty() {
+                    fields.push(("added_nodes", nodes_value(topology.added_nodes)));
+                }
+                if !topology.added_edges.is_empty() {
+                    fields.push(("added_edges", edges_value(topology.added_edges)));
+                }
+                if !topology.removed_nodes.is_empty() {
+                    fields.push(("removed_nodes", ids_value(topology.removed_nodes)));
+                }
+                if !topology.removed_edges.is_empty() {
+                    fields.push(("removed_edges", ids_value(topology.removed_edges)));
+                }
+                object(
+                    fields
+                        .into_iter()
+                        .map(|(name, value)| (name.to_owned(), value)),
+                )
+            })
+            .collect();
+
+        let json = prost_value::prost_to_serde_json(list(timesteps))
+            .expect("the outputs only contain finite numbers");
+        let mut json =
+            serde_json::to_string_pretty(&json).expect("a JSON value can always be serialized");
+        json.push('\n');
+        json
+    }
+
+    /// Apply the outputs of a timestep to the state, like the manager does.
+    fn apply(&mut self, result: &TimestepResult) -> Result<(), Status> {
+        let output = result.output_state.clone().unwrap_or_default();
+        let graph = self.state.graph.get_or_insert_with(Default::default);
+
+        let output_graph = output.graph.unwrap_or_default();
+        for output_node in output_graph.nodes {
+            let node = graph
+                .nodes
+                .iter_mut()
+                .find(|n| n.id == output_node.id)
+                .ok_or_else(|| {
+                    Status::internal(format!("returned unknown node {}", output_node.id))
+                })?;
+            node.components.extend(output_node.components);
+        }
+        for output_edge in output_graph.edge {
+            let edge = graph
+                .edge
+                .iter_mut()
+                .find(|e| e.id == output_edge.id)
+                .ok_or_else(|| {
+                    Status::internal(format!("returned unknown edge {}", output_edge.id))
+                })?;
+            *edge = output_edge;
+        }
+        self.state
+            .global_components
+            .extend(output.global_components);
+
+        let Some(topology) = result.topology.clone() else {
+            return Ok(());
+        };
+        for id in topology.removed_edges {
+            let index = graph
+                .edge
+                .iter()
+                .position(|e| e.id == id)
+                .ok_or_else(|| Status::internal(format!("can not remove unknown edge {id}")))?;
+            graph.edge.remove(index);
+        }
+        for id in topology.removed_nodes {
+            let index = graph
+                .nodes
+                .iter()
+                .position(|n| n.id == id)
+                .ok_or_else(|| Status::internal(format!("can not remove unknown node {id}")))?;
+            graph.nodes.remove(index);
+            graph.edge.retain(|e| e.from != id && e.to != id);
+        }
+
+        // provisional ids of the new nodes, to the ids the manager would give them
+        let mut added = HashMap::new();
+        for mut node in topology.added_nodes {
+            added.insert(node.id, self.next_node);
+            node.id = self.next_node;
+            self.next_node += 1;
+            graph.nodes.push(node);
+        }
+        for mut edge in topology.added_edges {
+            for id in [&mut edge.from, &mut edge.to] {
+                if let Some(allocated) = added.get(id) {
+                    *id = *allocated;
+                } else if !graph.nodes.iter().any(|n| n.id == *id) {
+                    return Err(Status::internal(format!(
+                        "can not add an edge to unknown node {id}"
+                    )));
+                }
+            }
+            edge.id = self.next_edge;
+            self.next_edge += 1;
+            graph.edge.push(edge);
+        }
+        Ok(())
+    }
+}
+
+/// Compare `actual` with the snapshot stored in the file at `path`.
+///
+/// The snapshot is written instead when the file does not exist, or when the environment variable
+/// [`UPDATE_SNAPSHOTS`] is set. Commit the written files, so later runs compare with them.
+///
+/// # Panics
+///
+/// Panics when `actual` does not match the snapshot, or the file can not be read or written.
+pub fn assert_snapshot(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    if env::var_os(UPDATE_SNAPSHOTS).is_some() || !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .unwrap_or_else(|err| panic!("could not create `{}`: {err}", dir.display()));
+        }
+        fs::write(path, actual)
+            .unwrap_or_else(|err| panic!("could not write `{}`: {err}", path.display()));
+        return;
+    }
+
+    let expected = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("could not read `{}`: {err}", path.display()));
+    if expected == actual {
+        return;
+    }
+    let (line, expected_line, actual_line) = expected
+        .lines()
+        .map(Some)
+        .chain(std::iter::repeat(None))
+        .zip(actual.lines().map(Some).chain(std::iter::repeat(None)))
+        .enumerate()
+        .find(|(_, (expected, actual))| expected != actual)
+        .map(|(index, (expected, actual))| (index + 1, expected, actual))
+        .unwrap_or((0, None, None));
+    panic!(
+        "the snapshot `{}` does not match, set {UPDATE_SNAPSHOTS} to update it\nfirst difference at line {line}:\nexpected: {}\nactual:   {}",
+        path.display(),
+        expected_line.unwrap_or("<end of file>"),
+        actual_line.unwrap_or("<end of file>"),
+    );
+}
+
+fn number(n: f64) -> Value {
+    Value {
+        kind: Some(prost_types::value::Kind::NumberValue(n)),
+    }
+}
+
+fn list(values: Vec<Value>) -> Value {
+    Value {
+        kind: Some(prost_types::value::Kind::ListValue(
+            prost_types::ListValue { values },
+        )),
+    }
+}
+
+fn object(fields: impl IntoIterator<Item = (String, Value)>) -> Value {
+    Value {
+        kind: Some(prost_types::value::Kind::StructValue(prost_types::Struct {
+            fields: fields.into_iter().collect(),
+        })),
+    }
+}
+
+fn ids_value(mut ids: Vec<u64>) -> Value {
+    ids.sort_unstable();
+    list(ids.into_iter().map(|id| number(id as f64)).collect())
+}
+
+fn nodes_value(mut nodes: Vec<proto::Node>) -> Value {
+    nodes.sort_unstable_by_key(|n| n.id);
+    list(
+        nodes
+            .into_iter()
+            .map(|n| {
+                object([
+                    ("id".to_owned(), number(n.id as f64)),
+                    ("latitude".to_owned(), number(n.latitude)),
+                    ("longitude".to_owned(), number(n.longitude)),
+                    ("components".to_owned(), object(n.components)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn edges_value(mut edges: Vec<proto::Edge>) -> Value {
+    edges.sort_unstable_by_key(|e| e.id);
+    list(
+        edges
+            .into_iter()
+            .map(|e| {
+                object([
+                    ("id".to_owned(), number(e.id as f64)),
+                    ("from".to_owned(), number(e.from as f64)),
+                    ("to".to_owned(), number(e.to as f64)),
+                    (
+                        "component_type".to_owned(),
+                        Value {
+                            kind: Some(prost_types::value::Kind::StringValue(e.component_type)),
+                        },
+                    ),
+                    (
+                        "component_data".to_owned(),
+                        e.component_data.unwrap_or_default(),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::{
+        component::ComponentPiece as _, simulator::SimulationError, Component, ComponentPiece,
+    };
+
+    #[derive(ComponentPiece, Component, Debug, PartialEq)]
+    #[component(name = "test-level", ty = "node")]
+    struct Level(f64);
+
+    #[derive(ComponentPiece, Component, Debug, PartialEq)]
+    #[component(name = "test-pipe", ty = "edge")]
+    struct Pipe {
+        flow: f64,
+    }
+
+    #[derive(ComponentPiece, Component, Debug, PartialEq)]
+    #[component(name = "test-rain", ty = "global")]
+    struct Rain(f64);
+
+    /// Fills every node with the rain, and adds a new node connected to the first node in the
+    /// second timestep.
+    struct RainSimulator {
+        steps: u32,
+    }
+
+    impl Simulator for RainSimulator {
+        fn get_component_info() -> ComponentsInfo {
+            ComponentsInfo::new()
+                .add_required_component::<Rain>()
+                .add_required_component::<Level>()
+                .add_optional_component::<Pipe>()
+                .add_output_component::<Level>()
+                .add_output_component::<Pipe>()
+        }
+
+        async fn new(_delta_time: Duration, _graph: Graph) -> Result<Self, SimulationError> {
+            Ok(Self { steps: 0 })
+        }
+
+        async fn do_timestep(&mut self, mut graph: Graph) -> Result<Graph, SimulationError> {
+            self.steps += 1;
+            let rain = graph.get_global_component::<Rain>().unwrap().0;
+            for (_, _, level) in graph.get_all_nodes_mut::<Level>().unwrap() {
+                level.0 += rain;
+            }
+            if self.steps == 2 {
+                let node = graph.insert_node(crate::graph::Node {
+                    latitude: 1.0,
+                    longitude: 1.0,
+                });
+                graph.insert_node_component(node, Level(0.0));
+                graph.insert_edge(NodeId(0), node, Pipe { flow: 0.5 });
+            }
+            Ok(graph)
+        }
+    }
+
+    /// Sets the rain to 0, while it is not one of its output components.
+    struct CloudSimulator;
+
+    impl Simulator for CloudSimulator {
+        fn get_component_info() -> ComponentsInfo {
+            ComponentsInfo::new().add_required_component::<Rain>()
+        }
+
+        async fn new(_delta_time: Duration, _graph: Graph) -> Result<Self, SimulationError> {
+            Ok(Self)
+        }
+
+        async fn do_timestep(&mut self, mut graph: Graph) -> Result<Graph, SimulationError> {
+            graph.get_global_component_mut::<Rain>().unwrap().0 = 0.0;
+            Ok(graph)
+        }
+    }
+
+    fn builder() -> (GraphBuilder, NodeId) {
+        let mut builder = GraphBuilder::new();
+        let first = builder.add_node(52.0, 4.0);
+        builder.add_node_component(first, Level(1.0));
+        let second = builder.add_node(52.1, 4.1);
+        builder.add_node_component(second, Level(2.0));
+        builder.add_edge(first, second, Pipe { flow: 1.0 });
+        builder.add_global_component(Rain(0.5));
+        (builder, first)
+    }
+
+    #[test]
+    fn build_graph() {
+        let (builder, first) = builder();
+        let graph = builder.build(&RainSimulator::get_component_info());
+        assert_eq!(graph.get_node_component::<Level>(first), Some(&Level(1.0)));
+        assert_eq!(
+            graph.get_edge_component::<Pipe>(EdgeId(0)),
+            Some(&Pipe { flow: 1.0 })
+        );
+        assert_eq!(graph.get_global_component::<Rain>(), Some(&Rain(0.5)));
+
+        // only the components the simulator asked for are included
+        let graph = builder.build(&CloudSimulator::get_component_info());
+        assert_eq!(graph.get_node_component::<Level>(first), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "component `test-rain` is a global component")]
+    fn wrong_component_type() {
+        let mut builder = GraphBuilder::new();
+        let node = builder.add_node(0.0, 0.0);
+        builder.add_node_component(node, Rain(1.0));
+    }
+
+    #[tokio::test]
+    async fn run_simulation() {
+        let (builder, first) = builder();
+        let mut simulation =
+            TestSimulation::<RainSimulator>::new(Duration::from_secs(1), builder.to_state())
+                .await
+                .unwrap();
+        let outputs = simulation.run(3).await.unwrap();
+        assert_eq!(outputs.len(), 3);
+        // the rain is not an output component, so it is not returned
+        assert!(outputs[0]
+            .output_state
+            .as_ref()
+            .unwrap()
+            .global_components
+            .is_empty());
+
+        let graph = simulation.graph();
+        assert_eq!(graph.get_node_component::<Level>(first), Some(&Level(2.5)));
+        // the new node got the next id and was filled in the third timestep
+        let nodes = &simulation.state().graph.as_ref().unwrap().nodes;
+        assert_eq!(nodes[2].id, 2);
+        assert_eq!(
+            Level::from_value(nodes[2].components["test-level"].clone()),
+            Some(Level(0.5))
+        );
+        let edges = &simulation.state().graph.as_ref().unwrap().edge;
+        assert_eq!((edges[1].id, edges[1].from, edges[1].to), (1, 0, 2));
+    }
+
+    #[tokio::test]
+    #[should_panic(
+        expected = "not output components, these changes are not sent to the manager: test-rain"
+    )]
+    async fn changed_input_component() {
+        let (builder, _) = builder();
+        let mut simulation =
+            TestSimulation::<CloudSimulator>::new(Duration::from_secs(1), builder.to_state())
+                .await
+                .unwrap();
+        let _ = simulation.do_timestep().await;
+    }
+
+    #[tokio::test]
+    async fn unchanged_input_component() {
+        // the rain is borrowed mutably, but keeps its value
+        let mut builder = GraphBuilder::new();
+        builder.add_global_component(Rain(0.0));
+        let mut simulation =
+            TestSimulation::<CloudSimulator>::new(Duration::from_secs(1), builder.to_state())
+                .await
+                .unwrap();
+        simulation.do_timestep().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn snapshot() {
+        let (builder, _) = builder();
+        let mut simulation =
+            TestSimulation::<RainSimulator>::new(Duration::from_secs(1), builder.to_state())
+                .await
+                .unwrap();
+        simulation.run(2).await.unwrap();
+        let snapshot = simulation.snapshot();
+        assert_snapshot("src/snapshots/rain_simulator.json", &snapshot);
+        assert_eq!(
+            snapshot.lines().take(8).collect::<Vec<_>>(),
+            [
+                "[",
+                "  {",
+                "    \"edges\": [",
+                "      {",
+                "        \"component_data\": {",
+                "          \"flow\": 1.0",
+                "        },",
+                "        \"component_type\": \"test-pipe\",",
+            ]
+        );
+    }
+
+    #[test]
+    fn compare_snapshot() {
+        let path = env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+        fs::remove_file(&path).ok();
+        // a missing snapshot is written
+        assert_snapshot(&path, "[\n  1\n]\n");
+        assert_snapshot(&path, "[\n  1\n]\n");
+        let mismatch = std::panic::catch_unwind(|| assert_snapshot(&path, "[\n  2\n]\n"));
+        fs::remove_file(&path).ok();
+        let message = *mismatch.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.ends_with("first difference at line 2:\nexpected:   1\nactual:     2"));
+    }
+}
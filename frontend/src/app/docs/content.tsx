@@ -1130,8 +1130,8 @@ const ContentSection: React.FC<ContentSectionProps> = ({ refs }) => {
                             <List.Item>ACSR_Conductor</List.Item>
                             <List.Item>AAC_Conductor</List.Item>
                             <List.Item>AAAC_Conductor</List.Item>
-                            <List.Item>XLPE_Conductor</List.Item>
-                            <List.Item>PILC_Conductor</List.Item>
+                            <List.Item>XLPE_Cable</List.Item>
+                            <List.Item>PILC_Cable</List.Item>
                         </List>
                     </List.Item>
                 </List>
@@ -15,8 +15,10 @@ interface Props {
 
 export const TypeConverter = (structure: ComponentStructure | undefined): any => {
     // convert a field type coming from the simulator to a coressponding
-    // value
-    if (structure?.primitive == 11) {
+    // value, using the default the simulator gives when there is one
+    if (structure?.metadata?.defaultValue !== undefined) {
+        return structure.metadata.defaultValue;
+    } else if (structure?.primitive == 11) {
         return '';
     } else if (structure?.primitive && structure?.primitive <= 10) {
         return 0;
@@ -31,6 +33,13 @@ export const TypeConverter = (structure: ComponentStructure | undefined): any =>
             items[key] = TypeConverter(structure?.struct?.data[key]);
         });
         return items;
+    } else if (structure?.enum) {
+        // a unit variant is its name, a variant with data is an object with a single field
+        if (structure.enum.variants.length > 0) {
+            return structure.enum.variants[0];
+        }
+        const [name] = Object.keys(structure.enum.dataVariants);
+        return { [name]: TypeConverter(structure.enum.dataVariants[name]) };
     } else {
         console.log('error');
     }
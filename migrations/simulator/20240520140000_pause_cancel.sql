@@ -0,0 +1,2 @@
+ALTER TYPE enum_status ADD VALUE 'Paused';
+ALTER TYPE enum_status ADD VALUE 'Cancelled';
@@ -0,0 +1,13 @@
+-- What the runner does when a simulator keeps failing during a timestep
+CREATE TYPE enum_error_policy AS ENUM ('FailFast', 'SkipAndCarryForward');
+ALTER TABLE simulations ADD COLUMN error_policy enum_error_policy NOT NULL DEFAULT 'FailFast';
+
+-- Simulators that were skipped while computing a timestep
+CREATE TABLE skipped_simulators
+(
+    simulation_id INT     NOT NULL,
+    time_step     INT     NOT NULL,
+    simulator     VARCHAR NOT NULL,
+    PRIMARY KEY (simulation_id, time_step, simulator),
+    FOREIGN KEY (simulation_id) REFERENCES simulations (id) ON DELETE CASCADE
+);
@@ -0,0 +1,28 @@
+-- Frames are stored as deltas: a node, edge or component is only stored in a time step if it
+-- changed since the previous time step. Every `keyframe_interval` time steps the full frame is
+-- stored, so a frame can be rebuilt from the last keyframe before it.
+ALTER TABLE simulations ADD COLUMN keyframe_interval INT NOT NULL DEFAULT 50 CHECK (keyframe_interval > 0);
+
+-- Edges can be stored in time steps in which their nodes did not change
+ALTER TABLE edges DROP CONSTRAINT edges_from_node_simulation_id_time_step_fkey;
+ALTER TABLE edges DROP CONSTRAINT edges_to_node_simulation_id_time_step_fkey;
+
+-- Frames that have been computed. A frame in which nothing changed has no other rows.
+CREATE TABLE frames
+(
+    simulation_id INT     NOT NULL,
+    time_step     INT     NOT NULL,
+    keyframe      BOOLEAN NOT NULL,
+    PRIMARY KEY (simulation_id, time_step),
+    FOREIGN KEY (simulation_id) REFERENCES simulations (id) ON DELETE CASCADE
+);
+-- Existing simulations stored every frame in full
+INSERT INTO frames (simulation_id, time_step, keyframe)
+SELECT simulation_id, time_step, TRUE FROM nodes
+UNION
+SELECT simulation_id, time_step, TRUE FROM global_components;
+
+CREATE INDEX nodes_simulation_id_time_step ON nodes (simulation_id, time_step);
+CREATE INDEX node_components_node_id ON node_components (node_id);
+CREATE INDEX edges_simulation_id_time_step ON edges (simulation_id, time_step);
+CREATE INDEX global_components_simulation_id_time_step ON global_components (simulation_id, time_step);
@@ -0,0 +1,24 @@
+-- Pending simulations are started by priority, highest first, and not before `not_before`
+ALTER TABLE simulations ADD COLUMN priority INT NOT NULL DEFAULT 0;
+ALTER TABLE simulations ADD COLUMN not_before TIMESTAMPTZ;
+CREATE INDEX simulations_queue ON simulations (priority DESC, not_before ASC NULLS FIRST, id ASC) WHERE status = 'Pending';
+
+-- How long it took to compute a frame in seconds, used to estimate when queued simulations start
+ALTER TABLE frames ADD COLUMN step_duration DOUBLE PRECISION;
+
+-- Wake up the runners of all managers when a simulation is queued. The notification is only
+-- delivered once the transaction that queued the simulation is committed.
+CREATE FUNCTION notify_simulation_queued() RETURNS TRIGGER AS
+$$
+BEGIN
+    PERFORM pg_notify('simulation_queue', NEW.id::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER simulation_queued
+    AFTER INSERT OR UPDATE OF status
+    ON simulations
+    FOR EACH ROW
+    WHEN (NEW.status = 'Pending')
+EXECUTE FUNCTION notify_simulation_queued();
@@ -0,0 +1,3 @@
+-- The simulation and frame a simulation was branched from
+ALTER TABLE simulations ADD COLUMN parent_id INT REFERENCES simulations (id) ON DELETE SET NULL;
+ALTER TABLE simulations ADD COLUMN parent_frame INT;
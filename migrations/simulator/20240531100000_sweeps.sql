@@ -0,0 +1,11 @@
+-- Simulations that were pushed together, one for every combination of a set of parameter values
+CREATE TABLE sweeps
+(
+    id   SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL UNIQUE
+);
+
+-- The sweep a simulation belongs to, and the value of every parameter of the sweep for it
+ALTER TABLE simulations ADD COLUMN sweep_id INT REFERENCES sweeps (id) ON DELETE SET NULL;
+ALTER TABLE simulations ADD COLUMN sweep_parameters JSONB;
+CREATE INDEX simulations_sweep ON simulations (sweep_id) WHERE sweep_id IS NOT NULL;
@@ -0,0 +1,10 @@
+-- The configuration every selected simulator reported when the simulation was started, stored as
+-- an encoded `SimulatorIOConfig` message. Archives of the simulation include it.
+CREATE TABLE simulator_configs
+(
+    simulation_id INT     NOT NULL,
+    simulator     VARCHAR NOT NULL,
+    io_config     BYTEA   NOT NULL,
+    PRIMARY KEY (simulation_id, simulator),
+    FOREIGN KEY (simulation_id) REFERENCES simulations (id) ON DELETE CASCADE
+);
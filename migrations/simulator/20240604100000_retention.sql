@@ -0,0 +1,18 @@
+-- The twin a simulation belongs to, used to keep a maximum amount of simulations per twin
+ALTER TABLE simulations ADD COLUMN twin VARCHAR;
+CREATE INDEX simulations_twin ON simulations (twin) WHERE twin IS NOT NULL;
+-- Pinned simulations can be kept when they expire
+ALTER TABLE simulations ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+-- Only every `downsampled`th frame is kept of a downsampled simulation, and its last frame
+ALTER TABLE simulations ADD COLUMN downsampled INT CHECK (downsampled > 1);
+
+-- What the maintenance task reclaimed since the database was created, a single row
+CREATE TABLE maintenance
+(
+    id                      BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    last_run                TIMESTAMPTZ,
+    deleted_simulations     BIGINT NOT NULL DEFAULT 0,
+    downsampled_simulations BIGINT NOT NULL DEFAULT 0,
+    reclaimed_rows          BIGINT NOT NULL DEFAULT 0
+);
+INSERT INTO maintenance DEFAULT VALUES;
@@ -0,0 +1,13 @@
+-- Conditions that finish a simulation early, encoded as protobuf `StopCondition` messages
+CREATE TABLE stop_conditions
+(
+    simulation_id INT   NOT NULL,
+    index         INT   NOT NULL,
+    condition     BYTEA NOT NULL,
+    PRIMARY KEY (simulation_id, index),
+    FOREIGN KEY (simulation_id) REFERENCES simulations (id) ON DELETE CASCADE
+);
+
+-- The condition that finished a simulation early, and the frame in which it fired
+ALTER TABLE simulations ADD COLUMN stopped_by INT;
+ALTER TABLE simulations ADD COLUMN stopped_at INT;
@@ -0,0 +1,4 @@
+-- Nodes and edges that were removed in a time step, so a frame in which simulators removed nodes
+-- or edges can be stored as a delta. Ids of removed nodes and edges are never used again.
+ALTER TABLE frames ADD COLUMN removed_nodes INT[] NOT NULL DEFAULT '{}';
+ALTER TABLE frames ADD COLUMN removed_edges INT[] NOT NULL DEFAULT '{}';
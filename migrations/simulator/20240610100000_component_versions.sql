@@ -0,0 +1,10 @@
+-- The version of the structure of every component in the frames of a simulation. Components that
+-- are not listed have version 0.
+CREATE TABLE component_versions
+(
+    simulation_id INT     NOT NULL,
+    component     VARCHAR NOT NULL,
+    version       INT     NOT NULL,
+    PRIMARY KEY (simulation_id, component),
+    FOREIGN KEY (simulation_id) REFERENCES simulations (id) ON DELETE CASCADE
+);
@@ -0,0 +1,46 @@
+-- A request to pause or cancel a simulation that is being computed. The worker computing the
+-- simulation checks it before every timestep, so any manager can stop the simulation.
+CREATE TYPE enum_control AS ENUM ('Pause', 'Cancel');
+ALTER TABLE simulations ADD COLUMN control enum_control;
+
+-- Notify all managers when a frame is stored or the status of a simulation changes, so they can
+-- follow the progress of simulations computed by other managers. The notifications are only
+-- delivered once the transaction is committed. The status info is shortened to stay within the
+-- size limit of a notification.
+CREATE FUNCTION notify_frame_stored() RETURNS TRIGGER AS
+$$
+BEGIN
+    PERFORM pg_notify('simulation_events', json_build_object(
+        'simulation_id', NEW.simulation_id,
+        'frame_nr', NEW.time_step,
+        'step_duration', NEW.step_duration
+    )::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER frame_stored
+    AFTER UPDATE OF step_duration
+    ON frames
+    FOR EACH ROW
+    WHEN (NEW.step_duration IS NOT NULL)
+EXECUTE FUNCTION notify_frame_stored();
+
+CREATE FUNCTION notify_status_changed() RETURNS TRIGGER AS
+$$
+BEGIN
+    PERFORM pg_notify('simulation_events', json_build_object(
+        'simulation_id', NEW.id,
+        'status', NEW.status,
+        'status_info', LEFT(COALESCE(NEW.status_info, ''), 1000)
+    )::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER status_changed
+    AFTER UPDATE OF status, status_info
+    ON simulations
+    FOR EACH ROW
+    WHEN (OLD.status IS DISTINCT FROM NEW.status OR OLD.status_info IS DISTINCT FROM NEW.status_info)
+EXECUTE FUNCTION notify_status_changed();
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use prost::Message;
+use proto::simulation::simulation_manager::{
+    simulation_archive_part::Part, ArchivedFrame, ErrorPolicy, SimulationArchiveHeader,
+    SimulationArchivePart, SimulationId, Stop,
+};
+use tokio::sync::Mutex;
+use tokio_stream::Stream;
+
+use crate::database::{ErrorPolicyEnum, SimulationsDB, StatusEnum};
+use crate::stop::StopConditions;
+
+/// The version of the archive format. Archives of other versions can not be imported.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// The maximum amount of frames that are read from the database at once when archiving.
+const ARCHIVE_CHUNK_SIZE: usize = 64;
+
+/// A simulation read from an archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive {
+    pub header: SimulationArchiveHeader,
+    /// Every frame of the simulation, ordered by frame number.
+    pub frames: Vec<ArchivedFrame>,
+}
+
+impl Archive {
+    /// Collect the parts of an archive: a header followed by every frame, in order.
+    pub fn from_parts(
+        parts: impl IntoIterator<Item = SimulationArchivePart>,
+    ) -> Result<Self, String> {
+        let mut parts = parts.into_iter().map(|part| part.part);
+        let header = match parts.next() {
+            Some(Some(Part::Header(header))) => header,
+            _ => return Err("the archive does not start with a header".to_string()),
+        };
+        if header.version != ARCHIVE_VERSION {
+            return Err(format!(
+                "the archive has version {}, only version {ARCHIVE_VERSION} is supported",
+                header.version
+            ));
+        }
+        if header.id.as_ref().map_or("", |id| &id.uuid).is_empty() {
+            return Err("the archive has no simulation name".to_string());
+        }
+        if ErrorPolicy::try_from(header.error_policy).is_err() {
+            return Err("the archive has an unknown error policy".to_string());
+        }
+        StopConditions::new(&header.stop_conditions)
+            .map_err(|err| format!("the archive has an invalid {err}"))?;
+        if let Some(stopped) = &header.stopped {
+            if stopped.condition as usize >= header.stop_conditions.len() {
+                return Err(format!(
+                    "the archive was stopped by stop condition {}, which it does not have",
+                    stopped.condition
+                ));
+            }
+        }
+
+        let mut frames = Vec::new();
+        for part in parts {
+            let frame = match part {
+                Some(Part::Frame(frame)) => frame,
+                Some(Part::Header(_)) => return Err("the archive has a second header".to_string()),
+                None => return Err("the archive has an empty part".to_string()),
+            };
+            if frame.frame_nr as usize != frames.len() {
+                return Err(format!(
+                    "expected frame {} in the archive, found frame {}",
+                    frames.len(),
+                    frame.frame_nr
+                ));
+            }
+            if frame.state.is_none() {
+                return Err(format!(
+                    "frame {} in the archive has no state",
+                    frame.frame_nr
+                ));
+            }
+            frames.push(frame);
+        }
+        if frames.is_empty() {
+            return Err("the archive has no frames".to_string());
+        }
+        if let Some(stopped) = &header.stopped {
+            if stopped.frame_nr as usize >= frames.len() {
+                return Err(format!(
+                    "the archive was stopped in frame {}, which it does not have",
+                    stopped.frame_nr
+                ));
+            }
+        }
+        Ok(Self { header, frames })
+    }
+
+    /// The name the simulation is imported under.
+    pub fn name(&self) -> &str {
+        self.header.id.as_ref().map_or("", |id| &id.uuid)
+    }
+
+    /// Change the name the simulation is imported under.
+    pub fn rename(&mut self, name: String) {
+        self.header.id = Some(SimulationId { uuid: name });
+    }
+
+    /// Store the simulation as a finished simulation, in a single transaction. Returns the id of
+    /// the simulation.
+    pub async fn import(&self, db: &mut SimulationsDB) -> Result<i32> {
+        db.begin_transaction().await?;
+        match self.store(db).await {
+            Ok(simulation_id) => {
+                db.commit().await?;
+                Ok(simulation_id)
+            }
+            Err(err) => {
+                db.rollback().await?;
+                Err(err)
+            }
+        }
+    }
+
+    async fn store(&self, db: &mut SimulationsDB) -> Result<i32> {
+        let header = &self.header;
+        let simulation_id = db
+            .add_simulation(
+                self.name(),
+                header.step_size_ms as i32,
+                header.timesteps as i32,
+                StatusEnum::Finished,
+                header.simulators.clone(),
+            )
+            .await?;
+        db.set_error_policy(
+            simulation_id,
+            ErrorPolicyEnum::from_error_policy(header.error_policy()),
+        )
+        .await?;
+        if let Some(info) = &header.status_info {
+            db.update_status(simulation_id, StatusEnum::Finished, Some(info))
+                .await?;
+        }
+        for (simulator, io_config) in &header.simulator_configs {
+            db.set_simulator_config(simulation_id, simulator, io_config)
+                .await?;
+        }
+        db.set_component_versions(simulation_id, &header.component_versions)
+            .await?;
+        db.set_stop_conditions(simulation_id, &header.stop_conditions)
+            .await?;
+        if let Some(stopped) = &header.stopped {
+            db.set_stopped(
+                simulation_id,
+                stopped.condition as i32,
+                stopped.frame_nr as i32,
+            )
+            .await?;
+        }
+
+        let mut previous = None;
+        for frame in &self.frames {
+            let time_step = frame.frame_nr as i32;
+            let state = frame.state.as_ref().context("missing state")?;
+            db.add_frame(simulation_id, time_step, state, previous)
+                .await?;
+            if !frame.skipped_simulators.is_empty() {
+                db.add_skipped_simulators(simulation_id, time_step, &frame.skipped_simulators)
+                    .await?;
+            }
+            previous = Some(state);
+        }
+        Ok(simulation_id)
+    }
+}
+
+/// Get the header of the archive of a simulation.
+async fn header(db: &mut SimulationsDB, simulation_id: i32) -> Result<SimulationArchiveHeader> {
+    let simulation = db.get_simulation_via_id(simulation_id).await?;
+    let error_policy = db.get_error_policy(simulation_id).await?;
+    Ok(SimulationArchiveHeader {
+        version: ARCHIVE_VERSION,
+        id: Some(SimulationId {
+            uuid: simulation.name,
+        }),
+        timesteps: simulation.max_steps as u64,
+        step_size_ms: simulation.step_size_ms as u32,
+        simulators: db
+            .get_selected_simulators(simulation_id)
+            .await?
+            .unwrap_or_default(),
+        simulator_configs: db.get_simulator_configs(simulation_id).await?,
+        error_policy: ErrorPolicyEnum::to_error_policy(error_policy).into(),
+        status_info: simulation.status_info,
+        component_versions: db.get_component_versions(simulation_id).await?,
+        stop_conditions: db.get_stop_conditions(simulation_id).await?,
+        stopped: db
+            .get_stopped(simulation_id)
+            .await?
+            .map(|(condition, frame_nr)| Stop {
+                condition: condition as u32,
+                frame_nr: frame_nr as u32,
+            }),
+    })
+}
+
+/// Archive a simulation. The header is followed by every frame, the frames are read from the
+/// database in chunks of [`ARCHIVE_CHUNK_SIZE`] frames.
+pub fn archive(
+    db: Arc<Mutex<SimulationsDB>>,
+    simulation_id: i32,
+) -> impl Stream<Item = Result<SimulationArchivePart>> {
+    async_stream::try_stream! {
+        let mut guard = db.lock().await;
+        let header = header(&mut guard, simulation_id).await?;
+        let last = guard.get_max_timestep(simulation_id).await?;
+        drop(guard);
+        yield SimulationArchivePart { part: Some(Part::Header(header)) };
+
+        let time_steps: Vec<i32> = (0..=last).collect();
+        for chunk in time_steps.chunks(ARCHIVE_CHUNK_SIZE) {
+            let mut guard = db.lock().await;
+            let frames = guard.get_frames(simulation_id, chunk, None).await?;
+            let mut skipped = guard.get_skipped_simulators_in(simulation_id, chunk).await?;
+            drop(guard);
+
+            for (frame_nr, state) in frames {
+                yield SimulationArchivePart {
+                    part: Some(Part::Frame(ArchivedFrame {
+                        frame_nr: frame_nr as u32,
+                        state: Some(state),
+                        skipped_simulators: skipped.remove(&frame_nr).unwrap_or_default(),
+                    })),
+                };
+            }
+        }
+    }
+}
+
+/// Encode a part of an archive the way it is stored in an archive file.
+pub fn encode(part: &SimulationArchivePart) -> Vec<u8> {
+    part.encode_length_delimited_to_vec()
+}
+
+/// Decode the parts of an archive file.
+pub fn decode(mut bytes: &[u8]) -> Result<Vec<SimulationArchivePart>> {
+    let mut parts = Vec::new();
+    while !bytes.is_empty() {
+        parts.push(SimulationArchivePart::decode_length_delimited(&mut bytes)?);
+    }
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::simulation::{Graph, Node, State};
+
+    fn header() -> SimulationArchivePart {
+        SimulationArchivePart {
+            part: Some(Part::Header(SimulationArchiveHeader {
+                version: ARCHIVE_VERSION,
+                id: Some(SimulationId {
+                    uuid: "archived".to_string(),
+                }),
+                timesteps: 1,
+                step_size_ms: 1000,
+                simulators: vec!["a".to_string()],
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn frame(frame_nr: u32) -> SimulationArchivePart {
+        SimulationArchivePart {
+            part: Some(Part::Frame(ArchivedFrame {
+                frame_nr,
+                state: Some(State {
+                    graph: Some(Graph {
+                        nodes: vec![Node {
+                            id: 1,
+                            ..Default::default()
+                        }],
+                        edge: vec![],
+                    }),
+                    global_components: Default::default(),
+                }),
+                skipped_simulators: vec![],
+            })),
+        }
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let parts = vec![header(), frame(0), frame(1)];
+        let bytes: Vec<u8> = parts.iter().flat_map(encode).collect();
+        assert_eq!(decode(&bytes).unwrap(), parts);
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn parts_in_order() {
+        let archive = Archive::from_parts(vec![header(), frame(0), frame(1)]).unwrap();
+        assert_eq!(archive.name(), "archived");
+        assert_eq!(archive.frames.len(), 2);
+
+        assert!(Archive::from_parts(vec![frame(0)]).is_err());
+        assert!(Archive::from_parts(vec![header()]).is_err());
+        assert!(Archive::from_parts(vec![header(), frame(1)]).is_err());
+        assert!(Archive::from_parts(vec![header(), frame(0), header()]).is_err());
+
+        let mut newer = header();
+        if let Some(Part::Header(header)) = &mut newer.part {
+            header.version = ARCHIVE_VERSION + 1;
+        }
+        assert!(Archive::from_parts(vec![newer, frame(0)]).is_err());
+    }
+
+    #[test]
+    fn stopped_by_unknown_condition() {
+        let mut stopped = header();
+        if let Some(Part::Header(header)) = &mut stopped.part {
+            header.stopped = Some(Stop {
+                condition: 0,
+                frame_nr: 0,
+            });
+        }
+        assert!(Archive::from_parts(vec![stopped, frame(0)]).is_err());
+    }
+}
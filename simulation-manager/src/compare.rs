@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use prost_types::{value::Kind, Value};
+use proto::simulation::{
+    simulation_manager::{
+        component_path::Location, ComponentPath, FieldDeviation, FrameDifference, ValueDifference,
+    },
+    Edge, Node, State,
+};
+
+/// How much the numeric values of a component field deviate between two simulations.
+#[derive(Debug, Default, Clone, Copy)]
+struct Deviation {
+    max: f64,
+    sum_of_squares: f64,
+    count: u64,
+}
+
+/// Compares the frames of two simulations one by one, while keeping track of how much the numeric
+/// values of every component field deviate over all frames.
+///
+/// Nodes and edges are matched by id. The values of a component field are the values at the same
+/// JSON path in the component with the same name, of any node, edge or in the global components.
+pub struct Comparison {
+    tolerance: f64,
+    components: Option<Vec<String>>,
+    deviations: BTreeMap<(String, String), Deviation>,
+}
+
+impl Comparison {
+    /// Numeric values are only reported as different when they differ by more than `tolerance`.
+    /// Only the components in `components` are compared, or all of them when it is `None`.
+    pub fn new(tolerance: f64, components: Option<Vec<String>>) -> Self {
+        Self {
+            tolerance,
+            components,
+            deviations: BTreeMap::new(),
+        }
+    }
+
+    /// Compare frame `frame_nr` of simulation `a` to the same frame of simulation `b`.
+    ///
+    /// Nodes and edges that are only in `b` are added, those that are only in `a` are removed.
+    /// Returns `None` when the frames do not differ.
+    pub fn compare(&mut self, frame_nr: u32, a: &State, b: &State) -> Option<FrameDifference> {
+        let mut difference = FrameDifference {
+            frame_nr,
+            ..Default::default()
+        };
+
+        let (nodes_a, nodes_b) = (nodes(a), nodes(b));
+        for (id, node) in &nodes_a {
+            match nodes_b.get(id) {
+                Some(other) => self.compare_components(
+                    Some(Location::NodeId(*id)),
+                    self.filter(&node.components),
+                    self.filter(&other.components),
+                    &mut difference.differences,
+                ),
+                None => difference.removed_nodes.push(*id),
+            }
+        }
+        difference.added_nodes = nodes_b
+            .keys()
+            .filter(|id| !nodes_a.contains_key(id))
+            .copied()
+            .collect();
+
+        let (edges_a, edges_b) = (edges(a), edges(b));
+        for (id, edge) in &edges_a {
+            match edges_b.get(id) {
+                Some(other) => self.compare_components(
+                    Some(Location::EdgeId(*id)),
+                    self.filter([(&edge.component_type, edge.component_data.as_ref())]),
+                    self.filter([(&other.component_type, other.component_data.as_ref())]),
+                    &mut difference.differences,
+                ),
+                None => difference.removed_edges.push(*id),
+            }
+        }
+        difference.added_edges = edges_b
+            .keys()
+            .filter(|id| !edges_a.contains_key(id))
+            .copied()
+            .collect();
+
+        self.compare_components(
+            None,
+            self.filter(&a.global_components),
+            self.filter(&b.global_components),
+            &mut difference.differences,
+        );
+        let unchanged = FrameDifference {
+            frame_nr,
+            ..Default::default()
+        };
+        (difference != unchanged).then_some(difference)
+    }
+
+    /// The largest and the root mean square deviation of every numeric component field that has
+    /// been compared so far.
+    pub fn summary(&self) -> Vec<FieldDeviation> {
+        self.deviations
+            .iter()
+            .map(|((component, path), deviation)| FieldDeviation {
+                component: component.clone(),
+                path: path.clone(),
+                max: deviation.max,
+                rms: (deviation.sum_of_squares / deviation.count as f64).sqrt(),
+                count: deviation.count,
+            })
+            .collect()
+    }
+
+    /// The components that are compared, by name.
+    fn filter<'s, V: Into<Option<&'s Value>>>(
+        &self,
+        components: impl IntoIterator<Item = (&'s String, V)>,
+    ) -> BTreeMap<&'s str, &'s Value> {
+        components
+            .into_iter()
+            .filter(|(name, _)| match &self.components {
+                Some(components) => components.contains(*name),
+                None => true,
+            })
+            .filter_map(|(name, value)| Some((name.as_str(), value.into()?)))
+            .collect()
+    }
+
+    fn compare_components(
+        &mut self,
+        location: Option<Location>,
+        a: BTreeMap<&str, &Value>,
+        b: BTreeMap<&str, &Value>,
+        differences: &mut Vec<ValueDifference>,
+    ) {
+        for name in a.keys().chain(b.keys()).collect::<BTreeSet<_>>() {
+            let path = ComponentPath {
+                location: location.clone(),
+                component: name.to_string(),
+                path: "$".to_string(),
+            };
+            match (a.get(name), b.get(name)) {
+                (Some(a), Some(b)) => self.compare_values(path, a, b, differences),
+                (a, b) => differences.push(difference(path, a.copied(), b.copied())),
+            }
+        }
+    }
+
+    fn compare_values(
+        &mut self,
+        path: ComponentPath,
+        a: &Value,
+        b: &Value,
+        differences: &mut Vec<ValueDifference>,
+    ) {
+        let children: Vec<(String, Option<&Value>, Option<&Value>)> = match (&a.kind, &b.kind) {
+            (Some(Kind::NumberValue(x)), Some(Kind::NumberValue(y))) => {
+                let deviation = (x - y).abs();
+                let field = self
+                    .deviations
+                    .entry((path.component.clone(), path.path.clone()))
+                    .or_default();
+                field.max = field.max.max(deviation);
+                field.sum_of_squares += deviation * deviation;
+                field.count += 1;
+                if deviation > self.tolerance {
+                    differences.push(difference(path, Some(a), Some(b)));
+                }
+                return;
+            }
+            (Some(Kind::StructValue(x)), Some(Kind::StructValue(y))) => x
+                .fields
+                .keys()
+                .chain(y.fields.keys())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|name| (format!(".{name}"), x.fields.get(name), y.fields.get(name)))
+                .collect(),
+            (Some(Kind::ListValue(x)), Some(Kind::ListValue(y))) => {
+                (0..x.values.len().max(y.values.len()))
+                    .map(|index| {
+                        (
+                            format!("[{index}]"),
+                            x.values.get(index),
+                            y.values.get(index),
+                        )
+                    })
+                    .collect()
+            }
+            _ => {
+                if a != b {
+                    differences.push(difference(path, Some(a), Some(b)));
+                }
+                return;
+            }
+        };
+        for (segment, a, b) in children {
+            let path = ComponentPath {
+                path: format!("{}{segment}", path.path),
+                ..path.clone()
+            };
+            match (a, b) {
+                (Some(a), Some(b)) => self.compare_values(path, a, b, differences),
+                (a, b) => differences.push(difference(path, a, b)),
+            }
+        }
+    }
+}
+
+/// The nodes of a state by id.
+fn nodes(state: &State) -> BTreeMap<u64, &Node> {
+    state
+        .graph
+        .iter()
+        .flat_map(|graph| &graph.nodes)
+        .map(|node| (node.id, node))
+        .collect()
+}
+
+/// The edges of a state by id.
+fn edges(state: &State) -> BTreeMap<u64, &Edge> {
+    state
+        .graph
+        .iter()
+        .flat_map(|graph| &graph.edge)
+        .map(|edge| (edge.id, edge))
+        .collect()
+}
+
+/// A value that differs between two frames. A value that is missing in one of the frames is not
+/// set.
+fn difference(path: ComponentPath, a: Option<&Value>, b: Option<&Value>) -> ValueDifference {
+    ValueDifference {
+        path: Some(path),
+        a: a.cloned(),
+        b: b.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{ListValue, Struct};
+    use proto::simulation::Graph;
+
+    fn number(n: f64) -> Value {
+        Value {
+            kind: Some(Kind::NumberValue(n)),
+        }
+    }
+
+    fn state(nodes: Vec<Node>, edges: Vec<Edge>, global: Vec<(&str, Value)>) -> State {
+        State {
+            graph: Some(Graph { nodes, edge: edges }),
+            global_components: global
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    fn node(id: u64, components: Vec<(&str, Value)>) -> Node {
+        Node {
+            id,
+            components: components
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn path(location: Option<Location>, component: &str, path: &str) -> Option<ComponentPath> {
+        Some(ComponentPath {
+            location,
+            component: component.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let edge = |id| Edge {
+            id,
+            component_type: "Cable".to_string(),
+            component_data: Some(number(1.0)),
+            ..Default::default()
+        };
+        let a = state(
+            vec![node(0, vec![]), node(1, vec![])],
+            vec![edge(0)],
+            vec![],
+        );
+        let b = state(
+            vec![node(1, vec![]), node(2, vec![])],
+            vec![edge(0), edge(1)],
+            vec![("Weather", number(1.0))],
+        );
+        let difference = Comparison::new(0.0, None).compare(4, &a, &b).unwrap();
+        assert_eq!(difference.frame_nr, 4);
+        assert_eq!(difference.removed_nodes, vec![0]);
+        assert_eq!(difference.added_nodes, vec![2]);
+        assert!(difference.removed_edges.is_empty());
+        assert_eq!(difference.added_edges, vec![1]);
+        assert_eq!(
+            difference.differences,
+            vec![ValueDifference {
+                path: path(None, "Weather", "$"),
+                a: None,
+                b: Some(number(1.0)),
+            }]
+        );
+    }
+
+    #[test]
+    fn tolerance() {
+        let temperature = |temperatures: Vec<f64>| Value {
+            kind: Some(Kind::StructValue(Struct {
+                fields: [(
+                    "temperatures".to_string(),
+                    Value {
+                        kind: Some(Kind::ListValue(ListValue {
+                            values: temperatures.into_iter().map(number).collect(),
+                        })),
+                    },
+                )]
+                .into(),
+            })),
+        };
+        let a = state(
+            vec![node(0, vec![("Temperature", temperature(vec![1.0, 2.0]))])],
+            vec![],
+            vec![],
+        );
+        let b = state(
+            vec![node(
+                0,
+                vec![("Temperature", temperature(vec![1.05, 3.0, 4.0]))],
+            )],
+            vec![],
+            vec![],
+        );
+        let mut comparison = Comparison::new(0.1, None);
+        let difference = comparison.compare(0, &a, &b).unwrap();
+        let location = Some(Location::NodeId(0));
+        assert_eq!(
+            difference.differences,
+            vec![
+                ValueDifference {
+                    path: path(location.clone(), "Temperature", "$.temperatures[1]"),
+                    a: Some(number(2.0)),
+                    b: Some(number(3.0)),
+                },
+                ValueDifference {
+                    path: path(location, "Temperature", "$.temperatures[2]"),
+                    a: None,
+                    b: Some(number(4.0)),
+                },
+            ]
+        );
+
+        let summary = comparison.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].path, "$.temperatures[0]");
+        assert!((summary[0].max - 0.05).abs() < 1e-9);
+        assert_eq!(summary[1].path, "$.temperatures[1]");
+        assert_eq!(summary[1].max, 1.0);
+    }
+
+    #[test]
+    fn statistics_over_frames() {
+        let frame = |value: f64| state(vec![], vec![], vec![("Load", number(value))]);
+        let mut comparison = Comparison::new(10.0, Some(vec!["Load".to_string()]));
+        assert_eq!(comparison.compare(0, &frame(0.0), &frame(3.0)), None);
+        assert_eq!(comparison.compare(1, &frame(0.0), &frame(4.0)), None);
+        assert_eq!(comparison.compare(2, &frame(2.0), &frame(2.0)), None);
+
+        let summary = comparison.summary();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].component, "Load");
+        assert_eq!(summary[0].max, 4.0);
+        assert_eq!(summary[0].rms, (25.0f64 / 3.0).sqrt());
+        assert_eq!(summary[0].count, 3);
+    }
+
+    #[test]
+    fn component_filter() {
+        let a = state(
+            vec![node(0, vec![("Load", number(1.0)), ("Other", number(1.0))])],
+            vec![],
+            vec![("Other", number(1.0))],
+        );
+        let b = state(
+            vec![node(0, vec![("Load", number(2.0)), ("Other", number(2.0))])],
+            vec![],
+            vec![],
+        );
+        let difference = Comparison::new(0.0, Some(vec!["Load".to_string()]))
+            .compare(0, &a, &b)
+            .unwrap();
+        assert_eq!(
+            difference.differences,
+            vec![ValueDifference {
+                path: path(Some(Location::NodeId(0)), "Load", "$"),
+                a: Some(number(1.0)),
+                b: Some(number(2.0)),
+            }]
+        );
+    }
+}
@@ -1,17 +1,27 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+use futures::future;
 use proto::simulation::{
-    simulator::SimulatorClient,
-    simulator_connection::{SimulatorConnection, SimulatorInfo},
+    simulator::{IoConfigRequest, SimulatorClient},
+    simulator_connection::{DisconnectRequest, SimulatorConnection, SimulatorInfo},
 };
 use tokio::sync::Mutex;
 use tonic::{transport::Channel, Request, Response, Status};
-use tracing::debug;
+use tracing::{debug, info, warn};
 
 /// The connector allows the Simulation Manager to keep track of available simulators.
 /// This is done by modifying the Vec which is shared by the manager and the runner.
 ///
-/// Right now, simulators can only be added, but they can not be removed.
+/// A simulator that connects with the name of a simulator that is already known replaces the old
+/// one. Simulators are removed when they disconnect, or when they stop responding to the health
+/// checks done by [`SimulatorConnector::health_check`].
 ///
 /// To add a simulator:
 /// ```
@@ -23,19 +33,78 @@ use tracing::debug;
 /// let connector = SimulatorConnectionClient::connect("127.0.0.1:8099").await.unwrap();
 /// connector.connect_simulator(SimulatorPort { port: 8101 }).await;
 /// ```
+#[derive(Clone)]
 pub struct SimulatorsInfo {
     pub(crate) simulator: SimulatorClient<Channel>,
     pub(crate) name: String,
+    /// Unique for every connection, used to tell a reconnected simulator apart from the old one.
+    pub(crate) id: u64,
+    /// How long a timestep of this simulator may take, if the simulator asked for a specific
+    /// deadline.
+    pub(crate) timestep_timeout: Option<Duration>,
 }
 
 pub struct SimulatorConnector {
     simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
+    next_id: AtomicU64,
 }
 
 impl SimulatorConnector {
     pub fn new(simulators_info: Arc<Mutex<Vec<SimulatorsInfo>>>) -> Self {
         Self {
             simulators: simulators_info,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Periodically check if all simulators are still responding.
+    ///
+    /// Every `interval` each simulator is asked for its io config. Simulators that return an
+    /// error, or do not respond within `timeout`, are removed.
+    pub async fn health_check(
+        simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
+        interval: Duration,
+        timeout: Duration,
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+
+            // Do not hold the lock while waiting for the simulators.
+            let connected = simulators
+                .lock()
+                .await
+                .iter()
+                .map(|sim| (sim.id, sim.name.clone(), sim.simulator.clone()))
+                .collect::<Vec<_>>();
+
+            let dead = future::join_all(connected.into_iter().map(
+                |(id, name, mut simulator)| async move {
+                    let request = Request::new(IoConfigRequest {});
+                    match tokio::time::timeout(timeout, simulator.get_io_config(request)).await {
+                        Ok(Ok(_)) => None,
+                        Ok(Err(err)) => {
+                            warn!("Simulator {name} failed health check: {err}");
+                            Some(id)
+                        }
+                        Err(_) => {
+                            warn!("Simulator {name} did not respond to health check");
+                            Some(id)
+                        }
+                    }
+                },
+            ))
+            .await
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+            if !dead.is_empty() {
+                simulators
+                    .lock()
+                    .await
+                    .retain(|sim| !dead.contains(&sim.id));
+            }
         }
     }
 }
@@ -50,6 +119,7 @@ impl SimulatorConnection for SimulatorConnector {
         let simulator: SimulatorInfo = request.into_inner();
         let port = simulator.port as u16;
         let name = simulator.name;
+        let timestep_timeout = simulator.timestep_timeout_ms.map(Duration::from_millis);
 
         debug!("Got request from simulator on port: {port}");
 
@@ -74,12 +144,34 @@ impl SimulatorConnection for SimulatorConnector {
             .map_err(|err| Status::from_error(Box::new(err)))?;
 
         let mut simulators = self.simulators.lock().await;
+        // A simulator that restarted replaces its old connection.
+        simulators.retain(|sim| sim.name != name);
         simulators.push(SimulatorsInfo {
             simulator: client,
             name,
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestep_timeout,
         });
         drop(simulators);
 
         Ok(Response::new(()))
     }
+
+    async fn disconnect_simulator(
+        &self,
+        request: Request<DisconnectRequest>,
+    ) -> Result<Response<()>, Status> {
+        let name = request.into_inner().name;
+
+        let mut simulators = self.simulators.lock().await;
+        let len = simulators.len();
+        simulators.retain(|sim| sim.name != name);
+        if simulators.len() == len {
+            return Err(Status::not_found(format!("no simulator named {name}")));
+        }
+        drop(simulators);
+
+        info!("Simulator {name} disconnected");
+        Ok(Response::new(()))
+    }
 }
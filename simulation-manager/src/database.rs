@@ -1,19 +1,28 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use anyhow::{anyhow, Context, Ok, Result};
 use sqlx::pool::PoolConnection;
-use sqlx::types::chrono::NaiveDate;
+use sqlx::types::chrono::{DateTime, NaiveDate, Utc};
 use sqlx::{query, PgConnection, PgPool, Postgres, Transaction};
 
 use database_config::database_url;
+use prost::Message;
 use prost_types::Value;
 use prost_value::*;
-use proto::simulation::{simulation_manager::SimulationStatus, Edge, Node};
+use proto::simulation::{
+    simulation_manager::{ErrorPolicy, SimulationStatus, StopCondition},
+    simulator::SimulatorIoConfig,
+    Edge, Graph, Node, State,
+};
 use tonic::Status;
 
 type Date = NaiveDate;
 
+/// The maximum amount of frames that are read from the database at once by
+/// [`SimulationsDB::keep_frames`].
+const KEEP_FRAMES_CHUNK_SIZE: usize = 64;
+
 #[derive(Debug, sqlx::Type, PartialEq, Clone)]
 #[sqlx(type_name = "enum_status")]
 pub enum StatusEnum {
@@ -21,6 +30,8 @@ pub enum StatusEnum {
     Computing,
     Finished,
     Failed,
+    Paused,
+    Cancelled,
 }
 impl StatusEnum {
     pub fn to_string(status: StatusEnum) -> String {
@@ -28,6 +39,8 @@ impl StatusEnum {
             StatusEnum::Pending => "Pending",
             StatusEnum::Computing => "Computing",
             StatusEnum::Finished => "Finished",
+            StatusEnum::Paused => "Paused",
+            StatusEnum::Cancelled => "Cancelled",
             _ => "Failed",
         }
         .to_string()
@@ -37,6 +50,8 @@ impl StatusEnum {
             "Pending" => StatusEnum::Pending,
             "Computing" => StatusEnum::Computing,
             "Finished" => StatusEnum::Finished,
+            "Paused" => StatusEnum::Paused,
+            "Cancelled" => StatusEnum::Cancelled,
             _ => StatusEnum::Failed,
         }
     }
@@ -46,10 +61,50 @@ impl StatusEnum {
             StatusEnum::Pending => SimulationStatus::Pending,
             StatusEnum::Computing => SimulationStatus::Computing,
             StatusEnum::Finished => SimulationStatus::Finished,
+            StatusEnum::Paused => SimulationStatus::Paused,
+            StatusEnum::Cancelled => SimulationStatus::Cancelled,
             _ => SimulationStatus::Failed,
         }
     }
 }
+
+/// What the runner does when a simulator keeps failing during a timestep.
+#[derive(Debug, sqlx::Type, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "enum_error_policy")]
+pub enum ErrorPolicyEnum {
+    /// Mark the simulation as failed.
+    FailFast,
+    /// Keep the components of the simulator from the previous timestep and continue.
+    SkipAndCarryForward,
+}
+impl ErrorPolicyEnum {
+    pub fn from_error_policy(policy: ErrorPolicy) -> ErrorPolicyEnum {
+        match policy {
+            ErrorPolicy::FailFast => ErrorPolicyEnum::FailFast,
+            ErrorPolicy::SkipAndCarryForward => ErrorPolicyEnum::SkipAndCarryForward,
+        }
+    }
+
+    pub fn to_error_policy(policy: ErrorPolicyEnum) -> ErrorPolicy {
+        match policy {
+            ErrorPolicyEnum::FailFast => ErrorPolicy::FailFast,
+            ErrorPolicyEnum::SkipAndCarryForward => ErrorPolicy::SkipAndCarryForward,
+        }
+    }
+}
+
+/// A request to stop a simulation that is being computed.
+///
+/// The worker computing the simulation checks for a request before every timestep.
+#[derive(Debug, sqlx::Type, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "enum_control")]
+pub enum ControlEnum {
+    /// Stop the simulation so it can be resumed later.
+    Pause,
+    /// Stop the simulation for good.
+    Cancel,
+}
+
 pub struct Simulation {
     pub id: i32,
     pub date: Date,
@@ -60,11 +115,62 @@ pub struct Simulation {
     pub status_info: Option<String>,
 }
 
+/// A simulation that is waiting in the queue.
+pub struct QueuedSimulation {
+    pub id: i32,
+    pub name: String,
+    pub priority: i32,
+    pub not_before: Option<DateTime<Utc>>,
+    /// The amount of time steps that still have to be computed.
+    pub remaining_steps: i32,
+}
+
+/// A simulation that is being computed.
+pub struct ComputingSimulation {
+    pub id: i32,
+    /// The amount of time steps that still have to be computed.
+    pub remaining_steps: i32,
+    /// The average time it took to compute a time step of this simulation, in seconds.
+    pub step_duration: Option<f64>,
+}
+
+/// A simulation that belongs to a sweep.
+pub struct SweepSimulation {
+    pub id: i32,
+    pub name: String,
+    pub status: StatusEnum,
+    /// The value of every parameter of the sweep for this simulation.
+    pub parameters: Vec<Value>,
+}
+
+/// What the retention policy needs to know about a simulation.
+pub struct Retention {
+    pub pinned: bool,
+    /// The stride with which the simulation was downsampled, if it was.
+    pub downsampled: Option<i32>,
+}
+
+/// How much data is stored and how much the maintenance task has reclaimed.
+pub struct StorageStats {
+    pub simulations: i64,
+    pub frames: i64,
+    /// The rows of the frames, nodes, node components, edges, global components and skipped
+    /// simulators.
+    pub rows: i64,
+    pub deleted_simulations: i64,
+    pub downsampled_simulations: i64,
+    pub reclaimed_rows: i64,
+    pub last_run: Option<DateTime<Utc>>,
+}
+
 /// An API abstraction over the simulations database.
 pub struct SimulationsDB {
     pool: PgPool,
     transaction: Option<Transaction<'static, Postgres>>,
     connection: Option<PoolConnection<Postgres>>,
+    /// The connection that holds the lock on the simulation that is being computed, see
+    /// [`SimulationsDB::lock_simulation`].
+    lock: Option<PoolConnection<Postgres>>,
 }
 impl SimulationsDB {
     /// Create a new connection to the database. Uses the credentials from the `SIMULATIONS`
@@ -76,6 +182,7 @@ impl SimulationsDB {
             pool,
             transaction: None,
             connection: None,
+            lock: None,
         })
     }
 
@@ -85,6 +192,7 @@ impl SimulationsDB {
             pool,
             transaction: None,
             connection: None,
+            lock: None,
         })
     }
 
@@ -119,6 +227,30 @@ impl SimulationsDB {
         Ok(())
     }
 
+    /// Undo all changes since the last call to begin()
+    pub async fn rollback(&mut self) -> Result<()> {
+        let t = self.transaction.take().context("missing transaction")?;
+        t.rollback().await.map_err(|e| anyhow!(e))?;
+        Ok(())
+    }
+
+    /// Commit the transaction when `result` is `Ok`, and roll it back otherwise, so the
+    /// connection can be used again after an error. Returns `result`.
+    pub async fn end_transaction<T>(&mut self, result: Result<T>) -> Result<T> {
+        match result {
+            Err(err) => {
+                if let Err(rollback) = self.rollback().await {
+                    return Err(err.context(format!("could not roll back: {rollback}")));
+                }
+                Err(err)
+            }
+            result => {
+                self.commit().await?;
+                result
+            }
+        }
+    }
+
     /// get tick step size
     pub async fn get_delta(&mut self, simulation_id: i32) -> Result<i32> {
         let delta = sqlx::query!(
@@ -186,6 +318,38 @@ impl SimulationsDB {
         Ok(true)
     }
 
+    /// Delete a simulation from all tables of the database using the id of the simulation.
+    pub async fn delete_simulation_via_id(&mut self, id: i32) -> Result<()> {
+        query!("DELETE FROM simulations WHERE id = $1", id)
+            .execute(self.connection().await?)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether a simulation with the name `name` exists.
+    pub async fn has_simulation(&mut self, name: &str) -> Result<bool> {
+        Ok(query!(
+            "SELECT EXISTS (SELECT 1 FROM simulations WHERE name = $1) AS \"exists!\"",
+            name
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .exists)
+    }
+
+    /// Get the names in `names` that are already used by a simulation.
+    pub async fn existing_simulations(&mut self, names: &[String]) -> Result<Vec<String>> {
+        Ok(query!(
+            "SELECT name FROM simulations WHERE name = ANY($1) ORDER BY name",
+            names
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|row| row.name)
+        .collect())
+    }
+
     /// Get a simulation from the simulations table using the name.
     pub async fn get_simulation_via_name(&mut self, name: &str) -> Result<Simulation> {
         let result = query!("SELECT id, date, name, step_size_ms, max_steps, status as \"enum_status: StatusEnum \", status_info FROM simulations WHERE name = $1", name)
@@ -220,261 +384,855 @@ impl SimulationsDB {
         Ok(sim)
     }
 
-    /// Get the first pending simulation from the simulation database.
+    /// Take the next simulation from the queue and set its status to `Computing`.
+    ///
+    /// Pending simulations are taken by priority, highest first, then by `not_before` and then in
+    /// the order they were queued. Simulations whose `not_before` lies in the future are skipped.
+    /// The simulation is locked, see [`SimulationsDB::lock_simulation`]. Rows that are being
+    /// taken by other managers are skipped, so multiple managers can share the queue. Simulations
+    /// that are still locked are skipped as well, which happens when a worker has not released a
+    /// simulation that was paused and resumed right away.
     pub async fn get_next_simulation(&mut self) -> Result<Option<i32>> {
-        let status: StatusEnum = StatusEnum::from_string("Pending");
-        let simulation_id = query!(
-            "SELECT id FROM simulations WHERE status = $1 ORDER BY id ASC LIMIT 1",
-            status as _
+        let connection = self.lock_connection().await?;
+        // The advisory lock is only tried on the rows in the order of the queue, up to the first
+        // one that can be locked. `OFFSET 0` keeps Postgres from trying it on every pending row.
+        Ok(query!(
+            "UPDATE simulations SET status = $1 WHERE id = (SELECT id FROM (SELECT id FROM simulations WHERE status = $2 AND (not_before IS NULL OR not_before <= now()) ORDER BY priority DESC, not_before ASC NULLS FIRST, id ASC OFFSET 0 FOR UPDATE SKIP LOCKED) AS queued WHERE pg_try_advisory_lock(id) LIMIT 1) RETURNING id",
+            StatusEnum::Computing as _,
+            StatusEnum::Pending as _
         )
-        .fetch_optional(self.connection().await?)
+        .fetch_optional(connection)
+        .await?
+        .map(|next| next.id))
+    }
+
+    /// Lock a simulation that is being computed, so no other manager will compute it.
+    ///
+    /// The lock is held by a separate connection and is released when
+    /// [`SimulationsDB::unlock_simulation`] is called or when the connection is lost, for example
+    /// because the manager stopped. Returns false if the simulation is locked by another manager
+    /// or is no longer being computed.
+    pub async fn lock_simulation(&mut self, simulation_id: i32) -> Result<bool> {
+        let connection = self.lock_connection().await?;
+        let locked = query!(
+            "SELECT pg_try_advisory_lock($1) AS \"locked!\"",
+            simulation_id as i64
+        )
+        .fetch_one(connection)
         .await?
-        .map(|s| s.id);
-        if let Some(id) = simulation_id {
-            self.update_status(id, StatusEnum::Computing, None).await?;
+        .locked;
+        if !locked {
+            return Ok(false);
+        }
+        // The simulation might have been finished by the manager that held the lock before.
+        if self.get_status(simulation_id).await? != StatusEnum::Computing {
+            self.unlock_simulation(simulation_id).await?;
+            return Ok(false);
         }
-        Ok(simulation_id)
+        Ok(true)
     }
 
-    /// Add a node to the nodes table and its components to the node_components table.
-    pub async fn add_node(
-        &mut self,
-        node: Node,
-        simulation_id: i32,
-        time_step: i32,
-    ) -> Result<i32> {
-        // add the nodes
-        let node_id = query!("INSERT INTO nodes (node_id, simulation_id, time_step, longitude, latitude) VALUES ($1, $2, $3, $4, $5) RETURNING id",
-                        node.id as i32, simulation_id, time_step, node.longitude, node.latitude)
-                    .fetch_one(self.connection().await?)
-                        .await
-                        .map_err(|e| anyhow!(e))
-                        .map(|n| n.id)?;
-        for component in node.components {
-            // add the component
-            query!(
-                "INSERT INTO node_components (name, node_id, component_data) VALUES ($1, $2, $3)",
-                component.0,
-                node_id,
-                prost_to_serde_json(component.1).context("invalid component data")?
-            )
-            .execute(self.connection().await?)
+    /// Release the lock on a simulation that was taken with [`SimulationsDB::get_next_simulation`]
+    /// or [`SimulationsDB::lock_simulation`].
+    pub async fn unlock_simulation(&mut self, simulation_id: i32) -> Result<()> {
+        query!("SELECT pg_advisory_unlock($1)", simulation_id as i64)
+            .fetch_one(self.lock_connection().await?)
             .await?;
+        Ok(())
+    }
+
+    /// Gets the connection that holds the lock on the simulation that is being computed.
+    async fn lock_connection(&mut self) -> Result<&mut PgConnection> {
+        if self.lock.is_none() {
+            self.lock = Some(self.pool.acquire().await?);
         }
-        Ok(node_id)
+        self.lock.as_deref_mut().context("missing lock connection")
     }
 
-    /// Get all nodes with their components from the nodes table. If the components field isn't
-    /// None, it only returns those components. If it is None, all components are returned. If
-    /// components is empty, no components are returned.
-    pub async fn get_nodes_filtered(
+    /// Record that a simulation was branched from frame `parent_frame` of another simulation.
+    pub async fn set_parent(
         &mut self,
         simulation_id: i32,
-        time_step: i32,
-        components: Option<Vec<String>>,
-    ) -> Result<Vec<Node>> {
-        // get the nodes
-        let mut records: Vec<_> = query!(
-            "SELECT * FROM nodes WHERE simulation_id = $1 AND time_step = $2",
-            simulation_id,
-            time_step
+        parent_id: i32,
+        parent_frame: i32,
+    ) -> Result<()> {
+        query!(
+            "UPDATE simulations SET parent_id = $1, parent_frame = $2 WHERE id = $3",
+            parent_id,
+            parent_frame,
+            simulation_id
         )
-        .fetch_all(&self.pool)
-        .await?
-        .into_iter()
-        .collect();
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
 
-        let mut nodes = Vec::new();
-        for n in &mut records {
-            // get node components
-            let node_components = match components {
-                Some(ref comps) => {
-                    query!(
-                        "SELECT * FROM node_components WHERE node_id = $1 AND name IN (SELECT unnest($2::text[]))",
-                        n.id,
-                        comps
-                    )
-                    .fetch_all(self.connection().await?)
-                    .await?
-                    .into_iter()
-                    .map(|c| Some((c.name, serde_json_to_prost(c.component_data)?)))
-                    .collect::<Option<_>>()
-                    .context("invalid component in db")?
-                }
+    /// Get the name of the simulation a simulation was branched from, and the frame it was
+    /// branched from. Returns None if the simulation is not a branch, or if its parent was
+    /// deleted.
+    pub async fn get_parent(&mut self, simulation_id: i32) -> Result<Option<(String, i32)>> {
+        Ok(query!(
+            "SELECT p.name, c.parent_frame AS \"parent_frame!\" FROM simulations c JOIN simulations p ON p.id = c.parent_id WHERE c.id = $1",
+            simulation_id
+        )
+        .fetch_optional(self.connection().await?)
+        .await?
+        .map(|parent| (parent.name, parent.parent_frame)))
+    }
 
-                None => query!("SELECT * FROM node_components WHERE node_id = $1", n.id)
-                    .fetch_all(self.connection().await?)
-                    .await?
-                    .into_iter()
-                    .map(|c| Some((c.name, serde_json_to_prost(c.component_data)?)))
-                    .collect::<Option<_>>()
-                    .context("invalid component in db")?
-            };
+    /// Add a sweep to the sweeps table and return its id.
+    pub async fn add_sweep(&mut self, name: &str) -> Result<i32> {
+        Ok(
+            query!("INSERT INTO sweeps (name) VALUES ($1) RETURNING id", name)
+                .fetch_one(self.connection().await?)
+                .await?
+                .id,
+        )
+    }
 
-            nodes.push(Node {
-                id: n.node_id as u64,
-                longitude: n.longitude,
-                latitude: n.latitude,
-                components: node_components,
-            });
-        }
-        Ok(nodes)
+    /// Get the id of a sweep via its name.
+    pub async fn get_sweep_via_name(&mut self, name: &str) -> Result<i32> {
+        Ok(query!("SELECT id FROM sweeps WHERE name = $1", name)
+            .fetch_one(self.connection().await?)
+            .await?
+            .id)
     }
 
-    /// Get all nodes and their components with a `simulation_id` and `time_step` from the nodes table.
-    pub async fn get_nodes(&mut self, simulation_id: i32, time_step: i32) -> Result<Vec<Node>> {
-        self.get_nodes_filtered(simulation_id, time_step, None)
-            .await
+    /// Check whether a sweep with the name `name` exists.
+    pub async fn has_sweep(&mut self, name: &str) -> Result<bool> {
+        Ok(query!("SELECT id FROM sweeps WHERE name = $1", name)
+            .fetch_optional(self.connection().await?)
+            .await?
+            .is_some())
     }
 
-    /// Get one specific node.
-    pub async fn get_node(
+    /// Record that a simulation belongs to a sweep, along with the value of every parameter of
+    /// the sweep for the simulation.
+    pub async fn add_to_sweep(
         &mut self,
         simulation_id: i32,
-        time_step: i32,
-        node_id: i32,
-    ) -> Result<(Node, i32)> {
-        let result1 = query!(
-            "SELECT * FROM nodes WHERE simulation_id = $1 AND time_step = $2 AND node_id = $3",
-            simulation_id,
-            time_step,
-            node_id
+        sweep_id: i32,
+        parameters: Vec<Value>,
+    ) -> Result<()> {
+        let parameters = parameters
+            .into_iter()
+            .map(prost_to_serde_json)
+            .collect::<Option<Vec<_>>>()
+            .context("invalid parameter value")?;
+        query!(
+            "UPDATE simulations SET sweep_id = $1, sweep_parameters = $2 WHERE id = $3",
+            sweep_id,
+            serde_json::Value::Array(parameters),
+            simulation_id
         )
-        .fetch_one(self.connection().await?)
+        .execute(self.connection().await?)
         .await?;
+        Ok(())
+    }
 
-        // get node components
-        let result2 = query!(
-            "SELECT * FROM node_components WHERE node_id = $1",
-            result1.node_id as i32
+    /// Get the simulations of a sweep, ordered in the way they were added.
+    pub async fn get_sweep_simulations(&mut self, sweep_id: i32) -> Result<Vec<SweepSimulation>> {
+        query!(
+            "SELECT id, name, status AS \"status!: StatusEnum\", sweep_parameters AS \"parameters!\" FROM simulations WHERE sweep_id = $1 ORDER BY id ASC",
+            sweep_id
         )
         .fetch_all(self.connection().await?)
         .await?
         .into_iter()
-        .map(|c| Some((c.name, serde_json_to_prost(c.component_data)?)))
-        .collect::<Option<_>>()
-        .context("invalid component in db")?;
+        .map(|s| {
+            let serde_json::Value::Array(parameters) = s.parameters else {
+                return Err(anyhow!("invalid sweep parameters in db"));
+            };
+            Ok(SweepSimulation {
+                id: s.id,
+                name: s.name,
+                status: s.status,
+                parameters: parameters
+                    .into_iter()
+                    .map(serde_json_to_prost)
+                    .collect::<Option<_>>()
+                    .context("invalid sweep parameters in db")?,
+            })
+        })
+        .collect()
+    }
 
-        let node: (Node, i32) = (
-            Node {
-                id: result1.node_id as u64,
-                longitude: result1.longitude,
-                latitude: result1.latitude,
-                components: result2,
-            },
-            result1.id,
-        );
-        Ok(node)
+    /// Set the priority of a simulation and the time before which it should not be started.
+    pub async fn set_schedule(
+        &mut self,
+        simulation_id: i32,
+        priority: i32,
+        not_before: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        query!(
+            "UPDATE simulations SET priority = $1, not_before = $2 WHERE id = $3",
+            priority,
+            not_before,
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
     }
 
-    /// Add an edge to the edges table.
-    pub async fn add_edge(&mut self, edge: Edge, simulation_id: i32, time_step: i32) -> Result<()> {
-        let component_data =
-            prost_to_serde_json(edge.component_data.context("missing component data")?)
-                .context("invalid component data")?;
-        let rows_affected = query!(
-            "INSERT INTO edges (edge_id, simulation_id, time_step, from_node, to_node, component_data, component_type) VALUES ($1, $2, $3, $4, $5, $6, $7)",
-            edge.id as i32, simulation_id, time_step, edge.from as i32, edge.to as i32, component_data, edge.component_type
-        ).execute(self.connection().await?).await?.rows_affected();
-        if rows_affected == 1 {
-            Ok(())
-        } else {
-            Err(anyhow!("failed to add edge"))
-        }
+    /// Get the first time at which a pending simulation that can not be started yet may be
+    /// started.
+    pub async fn get_next_not_before(&mut self) -> Result<Option<DateTime<Utc>>> {
+        Ok(query!(
+            "SELECT MIN(not_before) AS next FROM simulations WHERE status = $1 AND not_before > now()",
+            StatusEnum::Pending as _
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .next)
     }
 
-    /// Get all edges with a `simulation_id` and `time_step` from the edges table.
-    pub async fn get_edges(&mut self, simulation_id: i32, time_step: i32) -> Result<Vec<Edge>> {
-        let edges = query!(
-            "SELECT * FROM edges WHERE simulation_id = $1 AND time_step = $2",
-            simulation_id,
-            time_step
+    /// Get all pending simulations, ordered in the same way as in
+    /// [`SimulationsDB::get_next_simulation`].
+    pub async fn get_queue(&mut self) -> Result<Vec<QueuedSimulation>> {
+        Ok(query!(
+            "SELECT id, name, priority, not_before, max_steps - COALESCE((SELECT MAX(time_step) FROM frames WHERE frames.simulation_id = simulations.id), 0) AS \"remaining_steps!\" FROM simulations WHERE status = $1 ORDER BY priority DESC, not_before ASC NULLS FIRST, id ASC",
+            StatusEnum::Pending as _
         )
         .fetch_all(self.connection().await?)
         .await?
         .into_iter()
-        .map(|e| {
-            Ok(Edge {
-                from: e.from_node as u64,
-                to: e.to_node as u64,
-                component_type: e.component_type,
-                component_data: Some(
-                    serde_json_to_prost(e.component_data).context("invalid component in db")?,
-                ),
-                id: e.edge_id as u64,
-            })
+        .map(|s| QueuedSimulation {
+            id: s.id,
+            name: s.name,
+            priority: s.priority,
+            not_before: s.not_before,
+            remaining_steps: s.remaining_steps,
         })
-        .collect::<Result<_>>()?;
-        Ok(edges)
+        .collect())
     }
 
-    /// Get one specific edge.
-    pub async fn get_edge(
-        &mut self,
-        simulation_id: i32,
-        time_step: i32,
-        edge_id: i32,
-    ) -> Result<(Edge, i32)> {
-        let result = query!(
-            "SELECT * FROM edges WHERE simulation_id = $1 AND time_step = $2 AND edge_id = $3",
-            simulation_id,
-            time_step,
-            edge_id
+    /// Get all simulations that are being computed, along with how fast they are computed.
+    pub async fn get_computing_simulations(&mut self) -> Result<Vec<ComputingSimulation>> {
+        Ok(query!(
+            "SELECT id, max_steps - COALESCE(MAX(time_step), 0) AS \"remaining_steps!\", AVG(step_duration) AS step_duration FROM simulations LEFT JOIN frames ON simulation_id = id WHERE status = $1 GROUP BY id ORDER BY id ASC",
+            StatusEnum::Computing as _
         )
-        .fetch_one(self.connection().await?)
-        .await?;
-        let edge = (
-            Edge {
-                id: result.edge_id as u64,
-                from: result.from_node as u64,
-                to: result.to_node as u64,
-                component_type: result.component_type,
-                component_data: Some(
-                    serde_json_to_prost(result.component_data)
-                        .context("invalid component in db")?,
-                ),
-            },
-            result.id,
-        );
-        Ok(edge)
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|s| ComputingSimulation {
+            id: s.id,
+            remaining_steps: s.remaining_steps,
+            step_duration: s.step_duration,
+        })
+        .collect())
     }
-    pub async fn get_node_max_timestep(&mut self, simulation_id: i32) -> Result<i32> {
-        let node_timestep = sqlx::query!(
-            "SELECT time_step FROM nodes WHERE simulation_id = $1 ORDER BY time_step DESC",
-            simulation_id
+
+    /// Get the average time it took to compute one of the last `frames` time steps, of any
+    /// simulation, in seconds.
+    pub async fn get_average_step_duration(&mut self, frames: i64) -> Result<Option<f64>> {
+        Ok(query!(
+            "SELECT AVG(step_duration) AS step_duration FROM (SELECT step_duration FROM frames WHERE step_duration IS NOT NULL ORDER BY simulation_id DESC, time_step DESC LIMIT $1) recent",
+            frames
         )
-        .fetch_optional(self.connection().await?)
-        .await
-        .map_err(|err| Status::from_error(Box::new(err)))?
-        .map(|t| t.time_step)
-        .unwrap_or(0);
-        Ok(node_timestep)
+        .fetch_one(self.connection().await?)
+        .await?
+        .step_duration)
     }
 
-    /// Add a global component to the global_components table.
-    pub async fn add_global_component(
+    /// Store how long it took to compute a time step, in seconds.
+    pub async fn set_step_duration(
         &mut self,
-        name: &str,
-        value: Value,
         simulation_id: i32,
         time_step: i32,
+        step_duration: f64,
     ) -> Result<()> {
         query!(
-            "INSERT INTO global_components (time_step, name, simulation_id, component_data) VALUES ($1, $2, $3, $4)",
-            time_step, name, simulation_id, prost_to_serde_json(value).context("invalid component data")?
+            "UPDATE frames SET step_duration = $1 WHERE simulation_id = $2 AND time_step = $3",
+            step_duration,
+            simulation_id,
+            time_step
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the ids of all simulations with the given status, ordered by id.
+    pub async fn get_simulations_with_status(&mut self, status: StatusEnum) -> Result<Vec<i32>> {
+        Ok(query!(
+            "SELECT id FROM simulations WHERE status = $1 ORDER BY id ASC",
+            status as _
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|s| s.id)
+        .collect())
+    }
+
+    /// Count the simulations with every status. Statuses without simulations are left out.
+    pub async fn count_simulations_by_status(&mut self) -> Result<Vec<(StatusEnum, i64)>> {
+        Ok(query!(
+            "SELECT status AS \"status!: StatusEnum\", COUNT(*) AS \"count!\" FROM simulations WHERE status IS NOT NULL GROUP BY status"
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|s| (s.status, s.count))
+        .collect())
+    }
+
+    /// Count the pending simulations that may be started right away, so not the ones that are
+    /// scheduled for later.
+    pub async fn count_startable_simulations(&mut self) -> Result<i64> {
+        Ok(query!(
+            "SELECT COUNT(*) AS \"count!\" FROM simulations WHERE status = $1 AND (not_before IS NULL OR not_before <= now())",
+            StatusEnum::Pending as _
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .count)
+    }
+
+    /// Get the number of time steps between two keyframes of a simulation.
+    pub async fn get_keyframe_interval(&mut self, simulation_id: i32) -> Result<i32> {
+        Ok(query!(
+            "SELECT keyframe_interval FROM simulations WHERE id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .keyframe_interval)
+    }
+
+    /// Get the last keyframe at or before `time_step`, a frame is rebuilt starting from there.
+    async fn get_keyframe(&mut self, simulation_id: i32, time_step: i32) -> Result<i32> {
+        Ok(query!(
+            "SELECT MAX(time_step) FROM frames WHERE simulation_id = $1 AND time_step <= $2 AND keyframe",
+            simulation_id,
+            time_step
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .max
+        .unwrap_or(0))
+    }
+
+    /// Store the state of a simulation at a time step.
+    ///
+    /// Only the nodes, edges and components that changed since `previous` are stored. A node is
+    /// stored with only its changed components, and the ids of removed nodes and edges are stored
+    /// with the frame. The full state is stored as a keyframe when there is no previous state,
+    /// every `keyframe_interval` time steps, and when a component was removed since the previous
+    /// state.
+    pub async fn add_frame(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        state: &State,
+        previous: Option<&State>,
+    ) -> Result<()> {
+        let interval = self.get_keyframe_interval(simulation_id).await?;
+        let previous = previous
+            .filter(|previous| time_step % interval != 0 && !removes_components(previous, state));
+        let (removed_nodes, removed_edges) = previous
+            .map(|previous| removed_items(previous, state))
+            .unwrap_or_default();
+        query!(
+            "INSERT INTO frames (simulation_id, time_step, keyframe, removed_nodes, removed_edges) VALUES ($1, $2, $3, $4, $5)",
+            simulation_id,
+            time_step,
+            previous.is_none(),
+            &removed_nodes,
+            &removed_edges
+        )
+        .execute(self.connection().await?)
+        .await?;
+
+        let empty = State::default();
+        let previous = previous.unwrap_or(&empty);
+        let (previous_nodes, previous_edges): (HashMap<_, &Node>, HashMap<_, &Edge>) =
+            match &previous.graph {
+                Some(graph) => (
+                    graph.nodes.iter().map(|n| (n.id, n)).collect(),
+                    graph.edge.iter().map(|e| (e.id, e)).collect(),
+                ),
+                None => (HashMap::new(), HashMap::new()),
+            };
+
+        if let Some(graph) = &state.graph {
+            for node in &graph.nodes {
+                let node = match previous_nodes.get(&node.id) {
+                    Some(previous) => {
+                        let components: HashMap<_, _> = node
+                            .components
+                            .iter()
+                            .filter(|(name, value)| previous.components.get(*name) != Some(value))
+                            .map(|(name, value)| (name.clone(), value.clone()))
+                            .collect();
+                        if components.is_empty()
+                            && node.longitude == previous.longitude
+                            && node.latitude == previous.latitude
+                        {
+                            continue;
+                        }
+                        Node {
+                            components,
+                            ..node.clone()
+                        }
+                    }
+                    None => node.clone(),
+                };
+                self.add_node(node, simulation_id, time_step).await?;
+            }
+            for edge in &graph.edge {
+                if previous_edges.get(&edge.id) != Some(&edge) {
+                    self.add_edge(edge.clone(), simulation_id, time_step)
+                        .await?;
+                }
+            }
+        }
+        for (name, value) in &state.global_components {
+            if previous.global_components.get(name) != Some(value) {
+                self.add_global_component(name, value.clone(), simulation_id, time_step)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether frame `time_step` of a simulation is stored. Frames that were dropped when the
+    /// simulation was downsampled are not, see [`Self::keep_frames`].
+    pub async fn has_frame(&mut self, simulation_id: i32, time_step: i32) -> Result<bool> {
+        Ok(query!(
+            "SELECT EXISTS (SELECT 1 FROM frames WHERE simulation_id = $1 AND time_step = $2) AS \"exists!\"",
+            simulation_id,
+            time_step
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .exists)
+    }
+
+    /// Copy frame `time_step` of a simulation to a keyframe at time step 0 of another simulation.
+    ///
+    /// The frame is rebuilt from the last keyframe before it and copied inside the database, see
+    /// [`Self::add_frame`]. Nodes and edges that were removed after they were last stored are not
+    /// copied. Fails when the frame is not stored.
+    pub async fn copy_frame(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        to_simulation_id: i32,
+    ) -> Result<()> {
+        if !self.has_frame(simulation_id, time_step).await? {
+            return Err(anyhow!(
+                "frame {time_step} of simulation `{simulation_id}` is not stored"
+            ));
+        }
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
+        query!(
+            "INSERT INTO frames (simulation_id, time_step, keyframe) VALUES ($1, 0, TRUE)",
+            to_simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "INSERT INTO nodes (node_id, simulation_id, time_step, longitude, latitude) SELECT DISTINCT ON (node_id) node_id, $1, 0, longitude, latitude FROM nodes n WHERE simulation_id = $2 AND time_step BETWEEN $3 AND $4 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $2 AND f.time_step > n.time_step AND f.time_step <= $4 AND n.node_id = ANY(f.removed_nodes)) ORDER BY node_id, time_step DESC",
+            to_simulation_id,
+            simulation_id,
+            keyframe,
+            time_step
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "INSERT INTO node_components (name, node_id, component_data) SELECT c.name, n.id, c.component_data FROM (SELECT DISTINCT ON (n.node_id, c.name) n.node_id, c.name, c.component_data FROM node_components c JOIN nodes n ON n.id = c.node_id WHERE n.simulation_id = $2 AND n.time_step BETWEEN $3 AND $4 ORDER BY n.node_id, c.name, n.time_step DESC) c JOIN nodes n ON n.node_id = c.node_id AND n.simulation_id = $1 AND n.time_step = 0",
+            to_simulation_id,
+            simulation_id,
+            keyframe,
+            time_step
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "INSERT INTO edges (edge_id, simulation_id, time_step, from_node, to_node, component_data, component_type) SELECT DISTINCT ON (edge_id) edge_id, $1, 0, from_node, to_node, component_data, component_type FROM edges e WHERE simulation_id = $2 AND time_step BETWEEN $3 AND $4 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $2 AND f.time_step > e.time_step AND f.time_step <= $4 AND e.edge_id = ANY(f.removed_edges)) ORDER BY edge_id, time_step DESC",
+            to_simulation_id,
+            simulation_id,
+            keyframe,
+            time_step
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "INSERT INTO global_components (time_step, name, simulation_id, component_data) SELECT DISTINCT ON (name) 0, name, $1, component_data FROM global_components WHERE simulation_id = $2 AND time_step BETWEEN $3 AND $4 ORDER BY name, time_step DESC",
+            to_simulation_id,
+            simulation_id,
+            keyframe,
+            time_step
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Replace components of a node in a keyframe, or add them if the node does not have them.
+    ///
+    /// Returns false if the node is not stored in the keyframe.
+    pub async fn override_node_components(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        node_id: i32,
+        components: HashMap<String, Value>,
+    ) -> Result<bool> {
+        let Some(node) = query!(
+            "SELECT id FROM nodes WHERE simulation_id = $1 AND time_step = $2 AND node_id = $3",
+            simulation_id,
+            time_step,
+            node_id
+        )
+        .fetch_optional(self.connection().await?)
+        .await?
+        else {
+            return Ok(false);
+        };
+        for (name, value) in components {
+            query!(
+                "DELETE FROM node_components WHERE node_id = $1 AND name = $2",
+                node.id,
+                name
+            )
+            .execute(self.connection().await?)
+            .await?;
+            query!(
+                "INSERT INTO node_components (name, node_id, component_data) VALUES ($1, $2, $3)",
+                name,
+                node.id,
+                prost_to_serde_json(value).context("invalid component data")?
+            )
+            .execute(self.connection().await?)
+            .await?;
+        }
+        Ok(true)
+    }
+
+    /// Replace the component of an edge in a keyframe.
+    ///
+    /// Returns false if the edge is not stored in the keyframe.
+    pub async fn override_edge_component(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        edge_id: i32,
+        value: Value,
+    ) -> Result<bool> {
+        let rows_affected = query!(
+            "UPDATE edges SET component_data = $1 WHERE simulation_id = $2 AND time_step = $3 AND edge_id = $4",
+            prost_to_serde_json(value).context("invalid component data")?,
+            simulation_id,
+            time_step,
+            edge_id
+        )
+        .execute(self.connection().await?)
+        .await?
+        .rows_affected();
+        Ok(rows_affected == 1)
+    }
+
+    /// Replace a global component in a keyframe, or add it if the keyframe does not have it.
+    pub async fn override_global_component(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        name: &str,
+        value: Value,
+    ) -> Result<()> {
+        query!(
+            "DELETE FROM global_components WHERE simulation_id = $1 AND time_step = $2 AND name = $3",
+            simulation_id,
+            time_step,
+            name
+        )
+        .execute(self.connection().await?)
+        .await?;
+        self.add_global_component(name, value, simulation_id, time_step)
+            .await
+    }
+
+    /// Add a node to the nodes table and its components to the node_components table.
+    pub async fn add_node(
+        &mut self,
+        node: Node,
+        simulation_id: i32,
+        time_step: i32,
+    ) -> Result<i32> {
+        // add the nodes
+        let node_id = query!("INSERT INTO nodes (node_id, simulation_id, time_step, longitude, latitude) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+                        node.id as i32, simulation_id, time_step, node.longitude, node.latitude)
+                    .fetch_one(self.connection().await?)
+                        .await
+                        .map_err(|e| anyhow!(e))
+                        .map(|n| n.id)?;
+        for component in node.components {
+            // add the component
+            query!(
+                "INSERT INTO node_components (name, node_id, component_data) VALUES ($1, $2, $3)",
+                component.0,
+                node_id,
+                prost_to_serde_json(component.1).context("invalid component data")?
+            )
+            .execute(self.connection().await?)
+            .await?;
+        }
+        Ok(node_id)
+    }
+
+    /// Get all nodes with their components from the nodes table. If the components field isn't
+    /// None, it only returns those components. If it is None, all components are returned. If
+    /// components is empty, no components are returned.
+    ///
+    /// The nodes are rebuilt from the last keyframe before `time_step`, see [`Self::add_frame`].
+    pub async fn get_nodes_filtered(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        components: Option<Vec<String>>,
+    ) -> Result<Vec<Node>> {
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
+
+        // get the last stored position of every node
+        let mut nodes: Vec<_> = query!(
+            "SELECT DISTINCT ON (node_id) node_id, longitude, latitude FROM nodes n WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $1 AND f.time_step > n.time_step AND f.time_step <= $3 AND n.node_id = ANY(f.removed_nodes)) ORDER BY node_id, time_step DESC",
+            simulation_id,
+            keyframe,
+            time_step
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|n| Node {
+            id: n.node_id as u64,
+            longitude: n.longitude,
+            latitude: n.latitude,
+            components: HashMap::new(),
+        })
+        .collect();
+        let indices: HashMap<u64, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, n)| (n.id, index))
+            .collect();
+
+        // get the last stored value of every node component
+        let node_components = query!(
+            "SELECT DISTINCT ON (n.node_id, c.name) n.node_id, c.name, c.component_data FROM node_components c JOIN nodes n ON n.id = c.node_id WHERE n.simulation_id = $1 AND n.time_step BETWEEN $2 AND $3 AND ($4::text[] IS NULL OR c.name = ANY($4)) AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $1 AND f.time_step > n.time_step AND f.time_step <= $3 AND n.node_id = ANY(f.removed_nodes)) ORDER BY n.node_id, c.name, n.time_step DESC",
+            simulation_id,
+            keyframe,
+            time_step,
+            components.as_deref() as _
+        )
+        .fetch_all(self.connection().await?)
+        .await?;
+        for c in node_components {
+            let index = indices
+                .get(&(c.node_id as u64))
+                .context("component of unknown node")?;
+            nodes[*index].components.insert(
+                c.name,
+                serde_json_to_prost(c.component_data).context("invalid component in db")?,
+            );
+        }
+        Ok(nodes)
+    }
+
+    /// Get all nodes and their components with a `simulation_id` and `time_step` from the nodes table.
+    pub async fn get_nodes(&mut self, simulation_id: i32, time_step: i32) -> Result<Vec<Node>> {
+        self.get_nodes_filtered(simulation_id, time_step, None)
+            .await
+    }
+
+    /// Get one specific node, together with the id of the row in which it was last stored.
+    pub async fn get_node(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        node_id: i32,
+    ) -> Result<(Node, i32)> {
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
+        let result1 = query!(
+            "SELECT id, node_id, longitude, latitude FROM nodes n WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 AND node_id = $4 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $1 AND f.time_step > n.time_step AND f.time_step <= $3 AND n.node_id = ANY(f.removed_nodes)) ORDER BY time_step DESC LIMIT 1",
+            simulation_id,
+            keyframe,
+            time_step,
+            node_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?;
+
+        // get node components
+        let result2 = query!(
+            "SELECT DISTINCT ON (c.name) c.name, c.component_data FROM node_components c JOIN nodes n ON n.id = c.node_id WHERE n.simulation_id = $1 AND n.time_step BETWEEN $2 AND $3 AND n.node_id = $4 ORDER BY c.name, n.time_step DESC",
+            simulation_id,
+            keyframe,
+            time_step,
+            node_id
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|c| Some((c.name, serde_json_to_prost(c.component_data)?)))
+        .collect::<Option<_>>()
+        .context("invalid component in db")?;
+
+        let node: (Node, i32) = (
+            Node {
+                id: result1.node_id as u64,
+                longitude: result1.longitude,
+                latitude: result1.latitude,
+                components: result2,
+            },
+            result1.id,
+        );
+        Ok(node)
+    }
+
+    /// Add an edge to the edges table.
+    pub async fn add_edge(&mut self, edge: Edge, simulation_id: i32, time_step: i32) -> Result<()> {
+        let component_data =
+            prost_to_serde_json(edge.component_data.context("missing component data")?)
+                .context("invalid component data")?;
+        let rows_affected = query!(
+            "INSERT INTO edges (edge_id, simulation_id, time_step, from_node, to_node, component_data, component_type) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            edge.id as i32, simulation_id, time_step, edge.from as i32, edge.to as i32, component_data, edge.component_type
+        ).execute(self.connection().await?).await?.rows_affected();
+        if rows_affected == 1 {
+            Ok(())
+        } else {
+            Err(anyhow!("failed to add edge"))
+        }
+    }
+
+    /// Get all edges with a `simulation_id` and `time_step` from the edges table.
+    ///
+    /// The edges are rebuilt from the last keyframe before `time_step`, see [`Self::add_frame`].
+    pub async fn get_edges(&mut self, simulation_id: i32, time_step: i32) -> Result<Vec<Edge>> {
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
+        let edges = query!(
+            "SELECT DISTINCT ON (edge_id) edge_id, from_node, to_node, component_data, component_type FROM edges e WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $1 AND f.time_step > e.time_step AND f.time_step <= $3 AND e.edge_id = ANY(f.removed_edges)) ORDER BY edge_id, time_step DESC",
+            simulation_id,
+            keyframe,
+            time_step
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|e| {
+            Ok(Edge {
+                from: e.from_node as u64,
+                to: e.to_node as u64,
+                component_type: e.component_type,
+                component_data: Some(
+                    serde_json_to_prost(e.component_data).context("invalid component in db")?,
+                ),
+                id: e.edge_id as u64,
+            })
+        })
+        .collect::<Result<_>>()?;
+        Ok(edges)
+    }
+
+    /// Get one specific edge, together with the id of the row in which it was last stored.
+    pub async fn get_edge(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        edge_id: i32,
+    ) -> Result<(Edge, i32)> {
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
+        let result = query!(
+            "SELECT e.* FROM edges e WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 AND edge_id = $4 AND NOT EXISTS (SELECT 1 FROM frames f WHERE f.simulation_id = $1 AND f.time_step > e.time_step AND f.time_step <= $3 AND e.edge_id = ANY(f.removed_edges)) ORDER BY time_step DESC LIMIT 1",
+            simulation_id,
+            keyframe,
+            time_step,
+            edge_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?;
+        let edge = (
+            Edge {
+                id: result.edge_id as u64,
+                from: result.from_node as u64,
+                to: result.to_node as u64,
+                component_type: result.component_type,
+                component_data: Some(
+                    serde_json_to_prost(result.component_data)
+                        .context("invalid component in db")?,
+                ),
+            },
+            result.id,
+        );
+        Ok(edge)
+    }
+    /// Return the last time step that was stored for a simulation.
+    pub async fn get_max_timestep(&mut self, simulation_id: i32) -> Result<i32> {
+        let time_step = query!(
+            "SELECT MAX(time_step) FROM frames WHERE simulation_id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .max
+        .unwrap_or(0);
+        Ok(time_step)
+    }
+
+    /// Get the ids after the largest node and edge ids a simulation ever used, new nodes and
+    /// edges get ids from here.
+    pub async fn get_next_ids(&mut self, simulation_id: i32) -> Result<(u64, u64)> {
+        let node = query!(
+            "SELECT COALESCE(MAX(node_id) + 1, 0) AS \"next!\" FROM nodes WHERE simulation_id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .next;
+        let edge = query!(
+            "SELECT COALESCE(MAX(edge_id) + 1, 0) AS \"next!\" FROM edges WHERE simulation_id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .next;
+        Ok((node as u64, edge as u64))
+    }
+
+    /// Add a global component to the global_components table.
+    pub async fn add_global_component(
+        &mut self,
+        name: &str,
+        value: Value,
+        simulation_id: i32,
+        time_step: i32,
+    ) -> Result<()> {
+        query!(
+            "INSERT INTO global_components (time_step, name, simulation_id, component_data) VALUES ($1, $2, $3, $4)",
+            time_step, name, simulation_id, prost_to_serde_json(value).context("invalid component data")?
         ).execute(self.connection().await?).await?;
         Ok(())
     }
 
     /// Get all global components with a `simulation_id` and `time_step` from the global_components table.
+    ///
+    /// The components are rebuilt from the last keyframe before `time_step`, see
+    /// [`Self::add_frame`].
     pub async fn get_global_components(
         &mut self,
         simulation_id: i32,
         time_step: i32,
     ) -> Result<HashMap<String, Value>> {
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
         Ok(query!(
-            "SELECT * FROM global_components WHERE simulation_id = $1 AND time_step = $2",
+            "SELECT DISTINCT ON (name) name, component_data FROM global_components WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 ORDER BY name, time_step DESC",
             simulation_id,
+            keyframe,
             time_step
         )
         .fetch_all(self.connection().await?)
@@ -492,17 +1250,201 @@ impl SimulationsDB {
         time_step: i32,
         name: &str,
     ) -> Result<(String, Value)> {
-        let components = query!("SELECT * FROM global_components WHERE simulation_id = $1 AND time_step = $2 AND name = $3",
+        let keyframe = self.get_keyframe(simulation_id, time_step).await?;
+        let components = query!("SELECT * FROM global_components WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 AND name = $4 ORDER BY time_step DESC LIMIT 1",
             simulation_id,
+            keyframe,
             time_step,
             name
         )
-            .fetch_one(self.connection().await?)
-            .await?;
-        Ok((
-            components.name,
-            serde_json_to_prost(components.component_data).context("invalid component in db")?,
-        ))
+            .fetch_one(self.connection().await?)
+            .await?;
+        Ok((
+            components.name,
+            serde_json_to_prost(components.component_data).context("invalid component in db")?,
+        ))
+    }
+
+    /// Get the state of multiple frames of a simulation at once.
+    ///
+    /// The frames are read with one query per table instead of per frame, and rebuilt by applying
+    /// the changes of every time step since the last keyframe before the first requested frame.
+    /// Only node and global components in `components` are returned, or all of them when it is
+    /// `None`. Every time step in `time_steps` is present in the result, frames that do not exist
+    /// are empty.
+    pub async fn get_frames(
+        &mut self,
+        simulation_id: i32,
+        time_steps: &[i32],
+        components: Option<&[String]>,
+    ) -> Result<BTreeMap<i32, State>> {
+        let mut frames: BTreeMap<i32, State> = time_steps
+            .iter()
+            .map(|t| {
+                (
+                    *t,
+                    State {
+                        graph: Some(Graph {
+                            nodes: vec![],
+                            edge: vec![],
+                        }),
+                        global_components: HashMap::new(),
+                    },
+                )
+            })
+            .collect();
+        let (Some(first), Some(last)) = (time_steps.iter().min(), time_steps.iter().max()) else {
+            return Ok(frames);
+        };
+        let first = self.get_keyframe(simulation_id, *first).await?;
+        let last = *last;
+
+        let stored = query!(
+            "SELECT time_step, keyframe, removed_nodes, removed_edges FROM frames WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 ORDER BY time_step",
+            simulation_id,
+            first,
+            last
+        )
+        .fetch_all(self.connection().await?)
+        .await?;
+        let mut nodes = query!(
+            "SELECT node_id, time_step, longitude, latitude FROM nodes WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 ORDER BY time_step, id",
+            simulation_id,
+            first,
+            last
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .peekable();
+        let mut node_components = query!(
+            "SELECT n.node_id, n.time_step, c.name, c.component_data FROM node_components c JOIN nodes n ON n.id = c.node_id WHERE n.simulation_id = $1 AND n.time_step BETWEEN $2 AND $3 AND ($4::text[] IS NULL OR c.name = ANY($4)) ORDER BY n.time_step",
+            simulation_id,
+            first,
+            last,
+            components as _
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .peekable();
+        let mut edges = query!(
+            "SELECT edge_id, time_step, from_node, to_node, component_data, component_type FROM edges WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 ORDER BY time_step, id",
+            simulation_id,
+            first,
+            last
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .peekable();
+        let mut globals = query!(
+            "SELECT time_step, name, component_data FROM global_components WHERE simulation_id = $1 AND time_step BETWEEN $2 AND $3 AND ($4::text[] IS NULL OR name = ANY($4)) ORDER BY time_step",
+            simulation_id,
+            first,
+            last,
+            components as _
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .peekable();
+
+        // the state at the current time step, by id
+        let mut current_nodes: BTreeMap<u64, Node> = BTreeMap::new();
+        let mut current_edges: BTreeMap<u64, Edge> = BTreeMap::new();
+        let mut current_globals: HashMap<String, Value> = HashMap::new();
+        for frame in stored {
+            let time_step = frame.time_step;
+            if frame.keyframe {
+                current_nodes.clear();
+                current_edges.clear();
+                current_globals.clear();
+            }
+            for id in frame.removed_nodes {
+                current_nodes.remove(&(id as u64));
+            }
+            for id in frame.removed_edges {
+                current_edges.remove(&(id as u64));
+            }
+            while let Some(n) = nodes.next_if(|n| n.time_step == time_step) {
+                current_nodes
+                    .entry(n.node_id as u64)
+                    .and_modify(|node| {
+                        node.longitude = n.longitude;
+                        node.latitude = n.latitude;
+                    })
+                    .or_insert(Node {
+                        id: n.node_id as u64,
+                        longitude: n.longitude,
+                        latitude: n.latitude,
+                        components: HashMap::new(),
+                    });
+            }
+            while let Some(c) = node_components.next_if(|c| c.time_step == time_step) {
+                current_nodes
+                    .get_mut(&(c.node_id as u64))
+                    .context("component of unknown node")?
+                    .components
+                    .insert(
+                        c.name,
+                        serde_json_to_prost(c.component_data).context("invalid component in db")?,
+                    );
+            }
+            while let Some(e) = edges.next_if(|e| e.time_step == time_step) {
+                current_edges.insert(
+                    e.edge_id as u64,
+                    Edge {
+                        from: e.from_node as u64,
+                        to: e.to_node as u64,
+                        component_type: e.component_type,
+                        component_data: Some(
+                            serde_json_to_prost(e.component_data)
+                                .context("invalid component in db")?,
+                        ),
+                        id: e.edge_id as u64,
+                    },
+                );
+            }
+            while let Some(c) = globals.next_if(|c| c.time_step == time_step) {
+                current_globals.insert(
+                    c.name,
+                    serde_json_to_prost(c.component_data).context("invalid component in db")?,
+                );
+            }
+
+            if let Some(frame) = frames.get_mut(&time_step) {
+                *frame = State {
+                    graph: Some(Graph {
+                        nodes: current_nodes.values().cloned().collect(),
+                        edge: current_edges.values().cloned().collect(),
+                    }),
+                    global_components: current_globals.clone(),
+                };
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /// Get the simulators that were skipped in multiple time steps, by time step.
+    pub async fn get_skipped_simulators_in(
+        &mut self,
+        simulation_id: i32,
+        time_steps: &[i32],
+    ) -> Result<HashMap<i32, Vec<String>>> {
+        let mut skipped: HashMap<i32, Vec<String>> = HashMap::new();
+        let records = query!(
+            "SELECT time_step, simulator FROM skipped_simulators WHERE simulation_id = $1 AND time_step = ANY($2) ORDER BY simulator",
+            simulation_id,
+            time_steps
+        )
+        .fetch_all(self.connection().await?)
+        .await?;
+        for r in records {
+            skipped.entry(r.time_step).or_default().push(r.simulator);
+        }
+        Ok(skipped)
     }
 
     /// Get all global components of a simulation regardless of the time_step.
@@ -522,20 +1464,6 @@ impl SimulationsDB {
         .context("invalid component in db")?)
     }
 
-    /// Return the highest time_step of all global components of a simulation.
-    pub async fn get_global_components_max_timestep(&mut self, simulation_id: i32) -> Result<i32> {
-        let component_timestep = sqlx::query!(
-            "SELECT time_step FROM global_components WHERE simulation_id = $1 ORDER BY time_step DESC",
-            simulation_id
-        )
-            .fetch_optional(self.connection().await?)
-            .await
-            .map_err(|err| Status::from_error(Box::new(err)))?
-            .map(|t| t.time_step)
-            .unwrap_or(0);
-        Ok(component_timestep)
-    }
-
     /// Get a status of the simulation.
     pub async fn get_status(&mut self, simulation_id: i32) -> Result<StatusEnum> {
         let status = query!(
@@ -578,6 +1506,564 @@ impl SimulationsDB {
         }
         Ok(())
     }
+
+    /// Get the error policy of a simulation.
+    pub async fn get_error_policy(&mut self, simulation_id: i32) -> Result<ErrorPolicyEnum> {
+        Ok(query!(
+            "SELECT error_policy as \"error_policy: ErrorPolicyEnum\" FROM simulations WHERE id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .error_policy)
+    }
+
+    /// Set the error policy of a simulation.
+    pub async fn set_error_policy(
+        &mut self,
+        simulation_id: i32,
+        policy: ErrorPolicyEnum,
+    ) -> Result<()> {
+        query!(
+            "UPDATE simulations SET error_policy = $1 WHERE id = $2",
+            policy as _,
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Store the simulators that were skipped while computing a timestep.
+    pub async fn add_skipped_simulators(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+        simulators: &[String],
+    ) -> Result<()> {
+        query!(
+            "INSERT INTO skipped_simulators (simulation_id, time_step, simulator) SELECT $1, $2, * FROM UNNEST($3::VARCHAR[])",
+            simulation_id,
+            time_step,
+            simulators
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the simulators that were skipped while computing a timestep.
+    pub async fn get_skipped_simulators(
+        &mut self,
+        simulation_id: i32,
+        time_step: i32,
+    ) -> Result<Vec<String>> {
+        Ok(query!(
+            "SELECT simulator FROM skipped_simulators WHERE simulation_id = $1 AND time_step = $2 ORDER BY simulator",
+            simulation_id,
+            time_step
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|s| s.simulator)
+        .collect())
+    }
+
+    /// Store the configuration a simulator reported when a simulation was started. Replaces the
+    /// configuration stored when the simulation was started before.
+    pub async fn set_simulator_config(
+        &mut self,
+        simulation_id: i32,
+        simulator: &str,
+        io_config: &SimulatorIoConfig,
+    ) -> Result<()> {
+        query!(
+            "INSERT INTO simulator_configs (simulation_id, simulator, io_config) VALUES ($1, $2, $3) ON CONFLICT (simulation_id, simulator) DO UPDATE SET io_config = EXCLUDED.io_config",
+            simulation_id,
+            simulator,
+            io_config.encode_to_vec()
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the configuration every simulator reported when a simulation was started, by
+    /// simulator.
+    pub async fn get_simulator_configs(
+        &mut self,
+        simulation_id: i32,
+    ) -> Result<HashMap<String, SimulatorIoConfig>> {
+        query!(
+            "SELECT simulator, io_config FROM simulator_configs WHERE simulation_id = $1",
+            simulation_id
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|r| {
+            let io_config = SimulatorIoConfig::decode(r.io_config.as_slice())
+                .context("invalid simulator config in db")?;
+            Ok((r.simulator, io_config))
+        })
+        .collect()
+    }
+
+    /// Store the versions of the structures of the components of a simulation, replacing the
+    /// versions that were stored for the same components.
+    pub async fn set_component_versions(
+        &mut self,
+        simulation_id: i32,
+        versions: &HashMap<String, u32>,
+    ) -> Result<()> {
+        for (component, version) in versions {
+            query!(
+                "INSERT INTO component_versions (simulation_id, component, version) VALUES ($1, $2, $3) ON CONFLICT (simulation_id, component) DO UPDATE SET version = EXCLUDED.version",
+                simulation_id,
+                component,
+                *version as i32
+            )
+            .execute(self.connection().await?)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Get the versions of the structures of the components of a simulation, by component.
+    /// Components that are not included have version 0.
+    pub async fn get_component_versions(
+        &mut self,
+        simulation_id: i32,
+    ) -> Result<HashMap<String, u32>> {
+        Ok(query!(
+            "SELECT component, version FROM component_versions WHERE simulation_id = $1",
+            simulation_id
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|r| (r.component, r.version as u32))
+        .collect())
+    }
+
+    /// Store the conditions that finish a simulation early, in order.
+    pub async fn set_stop_conditions(
+        &mut self,
+        simulation_id: i32,
+        conditions: &[StopCondition],
+    ) -> Result<()> {
+        for (index, condition) in conditions.iter().enumerate() {
+            query!(
+                "INSERT INTO stop_conditions (simulation_id, index, condition) VALUES ($1, $2, $3)",
+                simulation_id,
+                index as i32,
+                condition.encode_to_vec()
+            )
+            .execute(self.connection().await?)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Get the conditions that finish a simulation early, in order.
+    pub async fn get_stop_conditions(&mut self, simulation_id: i32) -> Result<Vec<StopCondition>> {
+        query!(
+            "SELECT condition FROM stop_conditions WHERE simulation_id = $1 ORDER BY index",
+            simulation_id
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|r| {
+            StopCondition::decode(r.condition.as_slice()).context("invalid stop condition in db")
+        })
+        .collect()
+    }
+
+    /// Record that stop condition `condition` finished a simulation in frame `time_step`.
+    pub async fn set_stopped(
+        &mut self,
+        simulation_id: i32,
+        condition: i32,
+        time_step: i32,
+    ) -> Result<()> {
+        query!(
+            "UPDATE simulations SET stopped_by = $1, stopped_at = $2 WHERE id = $3",
+            condition,
+            time_step,
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the stop condition that finished a simulation early and the frame in which it fired.
+    pub async fn get_stopped(&mut self, simulation_id: i32) -> Result<Option<(i32, i32)>> {
+        let stopped = query!(
+            "SELECT stopped_by, stopped_at FROM simulations WHERE id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?;
+        Ok(stopped.stopped_by.zip(stopped.stopped_at))
+    }
+
+    /// Update the status of the simulation, but only if it currently has status `from`.
+    ///
+    /// Returns whether the status was updated.
+    pub async fn update_status_from(
+        &mut self,
+        simulation_id: i32,
+        from: StatusEnum,
+        to: StatusEnum,
+    ) -> Result<bool> {
+        let rows_affected = query!(
+            "UPDATE simulations SET status = $1 WHERE id = $2 AND status = $3",
+            to as _,
+            simulation_id,
+            from as _
+        )
+        .execute(self.connection().await?)
+        .await?
+        .rows_affected();
+        Ok(rows_affected == 1)
+    }
+
+    /// Request the worker computing a simulation to stop, but only if the simulation is being
+    /// computed.
+    ///
+    /// Returns whether the request was stored.
+    pub async fn set_control(&mut self, simulation_id: i32, control: ControlEnum) -> Result<bool> {
+        let rows_affected = query!(
+            "UPDATE simulations SET control = $1 WHERE id = $2 AND status = $3",
+            control as _,
+            simulation_id,
+            StatusEnum::Computing as _
+        )
+        .execute(self.connection().await?)
+        .await?
+        .rows_affected();
+        Ok(rows_affected == 1)
+    }
+
+    /// Withdraw the request to stop a simulation, but only if it is `control`.
+    ///
+    /// Returns whether the request was withdrawn.
+    pub async fn clear_control(
+        &mut self,
+        simulation_id: i32,
+        control: ControlEnum,
+    ) -> Result<bool> {
+        let rows_affected = query!(
+            "UPDATE simulations SET control = NULL WHERE id = $1 AND control = $2",
+            simulation_id,
+            control as _
+        )
+        .execute(self.connection().await?)
+        .await?
+        .rows_affected();
+        Ok(rows_affected == 1)
+    }
+
+    /// Take the request to stop a simulation, so it is only handled once. The simulation is only
+    /// written to when there is a request.
+    pub async fn take_control(&mut self, simulation_id: i32) -> Result<Option<ControlEnum>> {
+        Ok(query!(
+            "UPDATE simulations SET control = NULL FROM (SELECT id, control FROM simulations WHERE id = $1 AND control IS NOT NULL FOR UPDATE) previous WHERE simulations.id = previous.id RETURNING previous.control AS \"control: ControlEnum\"",
+            simulation_id
+        )
+        .fetch_optional(self.connection().await?)
+        .await?
+        .and_then(|row| row.control))
+    }
+
+    /// Record the twin a simulation belongs to.
+    pub async fn set_twin(&mut self, simulation_id: i32, twin: &str) -> Result<()> {
+        query!(
+            "UPDATE simulations SET twin = $1 WHERE id = $2",
+            twin,
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Pin or unpin a simulation.
+    pub async fn set_pinned(&mut self, simulation_id: i32, pinned: bool) -> Result<()> {
+        query!(
+            "UPDATE simulations SET pinned = $1 WHERE id = $2",
+            pinned,
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Get whether a simulation is pinned and whether it was downsampled.
+    pub async fn get_retention(&mut self, simulation_id: i32) -> Result<Retention> {
+        let retention = query!(
+            "SELECT pinned, downsampled FROM simulations WHERE id = $1",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?;
+        Ok(Retention {
+            pinned: retention.pinned,
+            downsampled: retention.downsampled,
+        })
+    }
+
+    /// Lock the row of a simulation until the end of the transaction, and get whether it is
+    /// pinned and whether it was downsampled.
+    ///
+    /// Returns `None` when the simulation no longer exists, or when its row is locked by another
+    /// manager.
+    pub async fn lock_retention(&mut self, simulation_id: i32) -> Result<Option<Retention>> {
+        Ok(query!(
+            "SELECT pinned, downsampled FROM simulations WHERE id = $1 FOR UPDATE SKIP LOCKED",
+            simulation_id
+        )
+        .fetch_optional(self.connection().await?)
+        .await?
+        .map(|retention| Retention {
+            pinned: retention.pinned,
+            downsampled: retention.downsampled,
+        }))
+    }
+
+    /// Get the simulations that have expired, oldest first.
+    ///
+    /// Only simulations that finished, failed or were cancelled expire. A simulation expires when
+    /// it was created more than `max_age_days` days ago, or when its twin has more than
+    /// `max_per_twin` newer simulations. Pinned simulations are left out, and do not count
+    /// towards the maximum of their twin, when `keep_pinned` is set. Simulations that were
+    /// already downsampled are left out when `skip_downsampled` is set.
+    pub async fn get_expired_simulations(
+        &mut self,
+        max_age_days: Option<i32>,
+        max_per_twin: Option<i64>,
+        keep_pinned: bool,
+        skip_downsampled: bool,
+    ) -> Result<Vec<i32>> {
+        Ok(query!(
+            "SELECT id AS \"id!\" FROM (SELECT id, date, twin, status, downsampled, ROW_NUMBER() OVER (PARTITION BY twin ORDER BY id DESC) AS newest FROM simulations WHERE NOT (pinned AND $3)) s WHERE status IN ('Finished', 'Failed', 'Cancelled') AND (date < CURRENT_DATE - $1::INT OR (twin IS NOT NULL AND newest > $2::BIGINT)) AND NOT (downsampled IS NOT NULL AND $4) ORDER BY id",
+            max_age_days,
+            max_per_twin,
+            keep_pinned,
+            skip_downsampled
+        )
+        .fetch_all(self.connection().await?)
+        .await?
+        .into_iter()
+        .map(|s| s.id)
+        .collect())
+    }
+
+    /// Count the rows the frames of a simulation are stored in, see [`StorageStats::rows`].
+    pub async fn count_rows(&mut self, simulation_id: i32) -> Result<i64> {
+        Ok(query!(
+            "SELECT (SELECT COUNT(*) FROM frames WHERE simulation_id = $1) + (SELECT COUNT(*) FROM nodes WHERE simulation_id = $1) + (SELECT COUNT(*) FROM node_components c JOIN nodes n ON n.id = c.node_id WHERE n.simulation_id = $1) + (SELECT COUNT(*) FROM edges WHERE simulation_id = $1) + (SELECT COUNT(*) FROM global_components WHERE simulation_id = $1) + (SELECT COUNT(*) FROM skipped_simulators WHERE simulation_id = $1) AS \"rows!\"",
+            simulation_id
+        )
+        .fetch_one(self.connection().await?)
+        .await?
+        .rows)
+    }
+
+    /// Delete every frame of a simulation except the frames at `time_steps`.
+    ///
+    /// The kept frames are rebuilt and stored again as if they directly followed each other, see
+    /// [`Self::add_frame`]. They are first stored under a new simulation, because they are
+    /// rebuilt from the frames that are deleted, so this should be done in a transaction.
+    pub async fn keep_frames(&mut self, simulation_id: i32, time_steps: &[i32]) -> Result<()> {
+        let simulation = self.get_simulation_via_id(simulation_id).await?;
+        let mut name = format!("{} (downsampling)", simulation.name);
+        let mut attempt = 1;
+        while self.has_simulation(&name).await? {
+            attempt += 1;
+            name = format!("{} (downsampling {attempt})", simulation.name);
+        }
+        let kept = self
+            .add_simulation(
+                &name,
+                simulation.step_size_ms,
+                simulation.max_steps,
+                StatusEnum::from_string(&simulation.status),
+                vec![],
+            )
+            .await?;
+        query!(
+            "UPDATE simulations SET keyframe_interval = (SELECT keyframe_interval FROM simulations WHERE id = $1) WHERE id = $2",
+            simulation_id,
+            kept
+        )
+        .execute(self.connection().await?)
+        .await?;
+
+        let mut previous: Option<State> = None;
+        for chunk in time_steps.chunks(KEEP_FRAMES_CHUNK_SIZE) {
+            for (time_step, state) in self.get_frames(simulation_id, chunk, None).await? {
+                self.add_frame(kept, time_step, &state, previous.as_ref())
+                    .await?;
+                previous = Some(state);
+            }
+        }
+
+        // node components are deleted along with their nodes
+        query!("DELETE FROM nodes WHERE simulation_id = $1", simulation_id)
+            .execute(self.connection().await?)
+            .await?;
+        query!("DELETE FROM edges WHERE simulation_id = $1", simulation_id)
+            .execute(self.connection().await?)
+            .await?;
+        query!(
+            "DELETE FROM global_components WHERE simulation_id = $1",
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!("DELETE FROM frames WHERE simulation_id = $1", simulation_id)
+            .execute(self.connection().await?)
+            .await?;
+        query!(
+            "DELETE FROM skipped_simulators WHERE simulation_id = $1 AND time_step <> ALL($2)",
+            simulation_id,
+            time_steps
+        )
+        .execute(self.connection().await?)
+        .await?;
+
+        query!(
+            "UPDATE nodes SET simulation_id = $1 WHERE simulation_id = $2",
+            simulation_id,
+            kept
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "UPDATE edges SET simulation_id = $1 WHERE simulation_id = $2",
+            simulation_id,
+            kept
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "UPDATE global_components SET simulation_id = $1 WHERE simulation_id = $2",
+            simulation_id,
+            kept
+        )
+        .execute(self.connection().await?)
+        .await?;
+        query!(
+            "UPDATE frames SET simulation_id = $1 WHERE simulation_id = $2",
+            simulation_id,
+            kept
+        )
+        .execute(self.connection().await?)
+        .await?;
+        self.delete_simulation_via_id(kept).await
+    }
+
+    /// Record that only every `stride`th frame of a simulation was kept.
+    pub async fn set_downsampled(&mut self, simulation_id: i32, stride: i32) -> Result<()> {
+        query!(
+            "UPDATE simulations SET downsampled = $1 WHERE id = $2",
+            stride,
+            simulation_id
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Add what a run of the maintenance task reclaimed to the totals.
+    pub async fn add_maintenance_run(
+        &mut self,
+        deleted_simulations: i64,
+        downsampled_simulations: i64,
+        reclaimed_rows: i64,
+    ) -> Result<()> {
+        query!(
+            "UPDATE maintenance SET last_run = now(), deleted_simulations = deleted_simulations + $1, downsampled_simulations = downsampled_simulations + $2, reclaimed_rows = reclaimed_rows + $3",
+            deleted_simulations,
+            downsampled_simulations,
+            reclaimed_rows
+        )
+        .execute(self.connection().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Get how much data is stored and how much the maintenance task has reclaimed.
+    pub async fn get_storage_stats(&mut self) -> Result<StorageStats> {
+        let stats = query!(
+            "SELECT (SELECT COUNT(*) FROM simulations) AS \"simulations!\", (SELECT COUNT(*) FROM frames) AS \"frames!\", (SELECT COUNT(*) FROM frames) + (SELECT COUNT(*) FROM nodes) + (SELECT COUNT(*) FROM node_components) + (SELECT COUNT(*) FROM edges) + (SELECT COUNT(*) FROM global_components) + (SELECT COUNT(*) FROM skipped_simulators) AS \"rows!\", deleted_simulations, downsampled_simulations, reclaimed_rows, last_run FROM maintenance"
+        )
+        .fetch_one(self.connection().await?)
+        .await?;
+        Ok(StorageStats {
+            simulations: stats.simulations,
+            frames: stats.frames,
+            rows: stats.rows,
+            deleted_simulations: stats.deleted_simulations,
+            downsampled_simulations: stats.downsampled_simulations,
+            reclaimed_rows: stats.reclaimed_rows,
+            last_run: stats.last_run,
+        })
+    }
+}
+
+/// Whether a component of `previous` is missing in `state`, on a node that was not removed or in
+/// the global components.
+///
+/// Removed components can not be stored as a change, so such a state is stored as a keyframe.
+fn removes_components(previous: &State, state: &State) -> bool {
+    let removed_global = previous
+        .global_components
+        .keys()
+        .any(|name| !state.global_components.contains_key(name));
+    let (graph, previous_graph) = match (&state.graph, &previous.graph) {
+        (Some(graph), Some(previous_graph)) => (graph, previous_graph),
+        (None, Some(_)) => return true,
+        (_, None) => return removed_global,
+    };
+    let nodes: HashMap<_, _> = graph.nodes.iter().map(|n| (n.id, n)).collect();
+    let removed_component = previous_graph.nodes.iter().any(|previous| {
+        nodes.get(&previous.id).is_some_and(|node| {
+            previous
+                .components
+                .keys()
+                .any(|name| !node.components.contains_key(name))
+        })
+    });
+    removed_component || removed_global
+}
+
+/// The ids of the nodes and edges of `previous` that are missing in `state`.
+fn removed_items(previous: &State, state: &State) -> (Vec<i32>, Vec<i32>) {
+    let (Some(graph), Some(previous_graph)) = (&state.graph, &previous.graph) else {
+        return (vec![], vec![]);
+    };
+    let nodes: HashSet<_> = graph.nodes.iter().map(|n| n.id).collect();
+    let edges: HashSet<_> = graph.edge.iter().map(|e| e.id).collect();
+    (
+        previous_graph
+            .nodes
+            .iter()
+            .filter(|n| !nodes.contains(&n.id))
+            .map(|n| n.id as i32)
+            .collect(),
+        previous_graph
+            .edge
+            .iter()
+            .filter(|e| !edges.contains(&e.id))
+            .map(|e| e.id as i32)
+            .collect(),
+    )
 }
 
 #[cfg(feature = "db_test")]
@@ -713,4 +2199,821 @@ mod database_test {
         assert!(!filtered[0].components.contains_key("first"));
         assert!(unfiltered[0].components.contains_key("first"));
     }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_simulations_with_status(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let first = db
+            .add_simulation("first", 1000, 10, StatusEnum::Computing, vec![])
+            .await
+            .unwrap();
+        db.add_simulation("second", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        let third = db
+            .add_simulation("third", 1000, 10, StatusEnum::Computing, vec![])
+            .await
+            .unwrap();
+        let scheduled = db
+            .add_simulation("scheduled", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        db.set_schedule(
+            scheduled,
+            0,
+            Some(Utc::now() + std::time::Duration::from_secs(3600)),
+        )
+        .await
+        .unwrap();
+
+        let computing = db
+            .get_simulations_with_status(StatusEnum::Computing)
+            .await
+            .unwrap();
+        assert_eq!(computing, vec![first, third]);
+
+        let mut counts = db.count_simulations_by_status().await.unwrap();
+        counts.sort_by_key(|(status, _)| StatusEnum::to_string(status.clone()));
+        assert_eq!(
+            counts,
+            vec![(StatusEnum::Computing, 2), (StatusEnum::Pending, 2)]
+        );
+        assert_eq!(db.count_startable_simulations().await.unwrap(), 1);
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_error_policy_and_skipped_simulators(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let id = db
+            .add_simulation("sim", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_error_policy(id).await.unwrap(),
+            ErrorPolicyEnum::FailFast
+        );
+        db.set_error_policy(id, ErrorPolicyEnum::SkipAndCarryForward)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_error_policy(id).await.unwrap(),
+            ErrorPolicyEnum::SkipAndCarryForward
+        );
+
+        db.add_skipped_simulators(id, 2, &["b".to_string(), "a".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_skipped_simulators(id, 2).await.unwrap(),
+            vec!["a", "b"]
+        );
+        assert!(db.get_skipped_simulators(id, 1).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_branch(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let parent = db
+            .add_simulation("parent", 1000, 10, StatusEnum::Finished, vec![])
+            .await
+            .unwrap();
+        let child = db
+            .add_simulation("child", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+        let states: Vec<State> = (0..4)
+            .map(|time_step| State {
+                graph: Some(Graph {
+                    nodes: (0..2)
+                        .map(|id| Node {
+                            id,
+                            latitude: 0.0,
+                            longitude: 0.0,
+                            components: [
+                                ("changing".to_string(), number((time_step * id) as f64)),
+                                ("constant".to_string(), number(id as f64)),
+                            ]
+                            .into(),
+                        })
+                        .collect(),
+                    edge: vec![Edge {
+                        from: 0,
+                        to: 1,
+                        component_type: "Sometype".to_string(),
+                        component_data: Some(number(time_step as f64)),
+                        id: 0,
+                    }],
+                }),
+                global_components: [("temperature".to_string(), number(time_step as f64))].into(),
+            })
+            .collect();
+        for (time_step, state) in states.iter().enumerate() {
+            let previous = time_step.checked_sub(1).map(|t| &states[t]);
+            db.add_frame(parent, time_step as i32, state, previous)
+                .await
+                .unwrap();
+        }
+
+        // frame 2 is stored as a delta, but copied in full
+        db.set_parent(child, parent, 2).await.unwrap();
+        db.copy_frame(parent, 2, child).await.unwrap();
+        assert_eq!(
+            db.get_frames(child, &[0], None).await.unwrap()[&0],
+            states[2]
+        );
+        assert_eq!(
+            db.get_parent(child).await.unwrap(),
+            Some(("parent".to_string(), 2))
+        );
+        assert_eq!(db.get_parent(parent).await.unwrap(), None);
+
+        assert!(db
+            .override_node_components(
+                child,
+                0,
+                1,
+                [
+                    ("changing".to_string(), number(100.0)),
+                    ("new".to_string(), number(1.0)),
+                ]
+                .into()
+            )
+            .await
+            .unwrap());
+        assert!(db
+            .override_edge_component(child, 0, 0, number(7.0))
+            .await
+            .unwrap());
+        db.override_global_component(child, 0, "temperature", number(9.0))
+            .await
+            .unwrap();
+        db.override_global_component(child, 0, "pressure", number(3.0))
+            .await
+            .unwrap();
+        assert!(!db
+            .override_node_components(child, 0, 9, [("new".to_string(), number(1.0))].into())
+            .await
+            .unwrap());
+        assert!(!db
+            .override_edge_component(child, 0, 9, number(1.0))
+            .await
+            .unwrap());
+
+        let mut expected = states[2].clone();
+        let graph = expected.graph.as_mut().unwrap();
+        graph.nodes[1]
+            .components
+            .insert("changing".to_string(), number(100.0));
+        graph.nodes[1]
+            .components
+            .insert("new".to_string(), number(1.0));
+        graph.edge[0].component_data = Some(number(7.0));
+        expected.global_components = [
+            ("temperature".to_string(), number(9.0)),
+            ("pressure".to_string(), number(3.0)),
+        ]
+        .into();
+        assert_eq!(
+            db.get_frames(child, &[0], None).await.unwrap()[&0],
+            expected
+        );
+        // the parent is not changed
+        assert_eq!(
+            db.get_frames(parent, &[2], None).await.unwrap()[&2],
+            states[2]
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_sweep(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+
+        let sweep = db.add_sweep("sweep").await.unwrap();
+        assert_eq!(db.get_sweep_via_name("sweep").await.unwrap(), sweep);
+        assert!(db.get_sweep_via_name("other").await.is_err());
+        assert!(db.add_sweep("sweep").await.is_err());
+
+        let other = db.add_sweep("other").await.unwrap();
+        let first = db
+            .add_simulation("sweep-0", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        let second = db
+            .add_simulation("sweep-1", 1000, 10, StatusEnum::Failed, vec![])
+            .await
+            .unwrap();
+        let unrelated = db
+            .add_simulation("other-0", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        db.add_to_sweep(first, sweep, vec![number(0.1), number(1.0)])
+            .await
+            .unwrap();
+        db.add_to_sweep(second, sweep, vec![number(0.2), number(1.0)])
+            .await
+            .unwrap();
+        db.add_to_sweep(unrelated, other, vec![]).await.unwrap();
+
+        let simulations = db.get_sweep_simulations(sweep).await.unwrap();
+        assert_eq!(
+            simulations
+                .iter()
+                .map(|s| (s.id, s.name.as_str(), s.status.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (first, "sweep-0", StatusEnum::Pending),
+                (second, "sweep-1", StatusEnum::Failed)
+            ]
+        );
+        assert_eq!(simulations[1].parameters, vec![number(0.2), number(1.0)]);
+        assert!(db
+            .get_sweep_simulations(other)
+            .await
+            .unwrap()
+            .iter()
+            .all(|s| s.parameters.is_empty()));
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_queue(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        let mut ids = HashMap::new();
+        for (name, priority, delay) in [
+            ("low", 0, None),
+            ("high", 5, None),
+            ("later", 10, Some(3600)),
+            ("last", 0, None),
+        ] {
+            let id = db
+                .add_simulation(name, 1000, 10, StatusEnum::Pending, vec![])
+                .await
+                .unwrap();
+            let not_before = delay.map(|secs| Utc::now() + std::time::Duration::from_secs(secs));
+            db.set_schedule(id, priority, not_before).await.unwrap();
+            ids.insert(name, id);
+        }
+
+        let queue = db.get_queue().await.unwrap();
+        let names: Vec<_> = queue.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["later", "high", "low", "last"]);
+        assert_eq!(db.get_next_not_before().await.unwrap(), queue[0].not_before);
+
+        // simulations that are scheduled for later are skipped
+        let mut first = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        let mut second = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        assert_eq!(
+            first.get_next_simulation().await.unwrap(),
+            Some(ids["high"])
+        );
+        assert_eq!(
+            second.get_next_simulation().await.unwrap(),
+            Some(ids["low"])
+        );
+        second.unlock_simulation(ids["low"]).await.unwrap();
+        drop(second);
+        assert_eq!(
+            db.get_status(ids["high"]).await.unwrap(),
+            StatusEnum::Computing
+        );
+
+        // a computing simulation is locked until it is released
+        let mut other = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        assert!(!other.lock_simulation(ids["high"]).await.unwrap());
+        first.unlock_simulation(ids["high"]).await.unwrap();
+        assert!(other.lock_simulation(ids["high"]).await.unwrap());
+        drop(other);
+        // only computing simulations can be locked
+        assert!(!first.lock_simulation(ids["last"]).await.unwrap());
+        // pending simulations that are still locked are skipped
+        let mut lock = pool.acquire().await.unwrap();
+        sqlx::query("SELECT pg_advisory_lock($1)")
+            .bind(ids["last"] as i64)
+            .execute(&mut *lock)
+            .await
+            .unwrap();
+        assert_eq!(first.get_next_simulation().await.unwrap(), None);
+        assert_eq!(
+            db.get_status(ids["last"]).await.unwrap(),
+            StatusEnum::Pending
+        );
+        sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(ids["last"] as i64)
+            .execute(&mut *lock)
+            .await
+            .unwrap();
+
+        // computing simulations with the durations of their time steps
+        for time_step in 0..3 {
+            db.add_frame(ids["high"], time_step, &State::default(), None)
+                .await
+                .unwrap();
+        }
+        db.set_step_duration(ids["high"], 1, 2.0).await.unwrap();
+        db.set_step_duration(ids["high"], 2, 4.0).await.unwrap();
+        let computing = db.get_computing_simulations().await.unwrap();
+        let computing: Vec<_> = computing
+            .iter()
+            .map(|s| (s.id, s.remaining_steps, s.step_duration))
+            .collect();
+        assert_eq!(
+            computing,
+            vec![(ids["low"], 10, None), (ids["high"], 8, Some(3.0))]
+        );
+        assert_eq!(db.get_average_step_duration(1).await.unwrap(), Some(4.0));
+        assert_eq!(db.get_average_step_duration(10).await.unwrap(), Some(3.0));
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_get_frames(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let simulation_id = db
+            .add_simulation("sim", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+        let mut previous = None;
+        for time_step in 0..4 {
+            let state = State {
+                graph: Some(Graph {
+                    nodes: (0..3)
+                        .map(|id| Node {
+                            id,
+                            latitude: id as f64,
+                            longitude: time_step as f64,
+                            components: [
+                                ("first".to_string(), number(time_step as f64)),
+                                ("second".to_string(), number(id as f64)),
+                            ]
+                            .into(),
+                        })
+                        .collect(),
+                    edge: vec![Edge {
+                        from: 0,
+                        to: 1,
+                        component_type: "Sometype".to_string(),
+                        component_data: Some(number(time_step as f64)),
+                        id: 7,
+                    }],
+                }),
+                global_components: [("global".to_string(), number(1.0))].into(),
+            };
+            db.add_frame(simulation_id, time_step, &state, previous.as_ref())
+                .await
+                .unwrap();
+            previous = Some(state);
+        }
+
+        let frames = db.get_frames(simulation_id, &[1, 3], None).await.unwrap();
+        assert_eq!(frames.keys().copied().collect::<Vec<_>>(), vec![1, 3]);
+        for (time_step, state) in frames {
+            let graph = state.graph.unwrap();
+            let mut nodes = db.get_nodes(simulation_id, time_step).await.unwrap();
+            nodes.sort_by_key(|n| n.id);
+            assert_eq!(graph.nodes, nodes);
+            assert_eq!(
+                graph.edge,
+                db.get_edges(simulation_id, time_step).await.unwrap()
+            );
+            assert_eq!(
+                state.global_components,
+                db.get_global_components(simulation_id, time_step)
+                    .await
+                    .unwrap()
+            );
+        }
+
+        // only the requested components are returned, and missing frames are empty
+        let frames = db
+            .get_frames(simulation_id, &[2, 9], Some(&["second".to_string()]))
+            .await
+            .unwrap();
+        let graph = frames[&2].graph.as_ref().unwrap();
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(graph
+            .nodes
+            .iter()
+            .all(|n| n.components.keys().eq(["second"].iter())));
+        assert_eq!(graph.edge.len(), 1);
+        assert!(frames[&2].global_components.is_empty());
+        assert!(frames[&9].graph.as_ref().unwrap().nodes.is_empty());
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_delta_frames(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        let delta = db
+            .add_simulation("delta", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        // a keyframe interval of 1 stores every frame in full
+        let full = db
+            .add_simulation("full", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        for (id, interval) in [(delta, 4), (full, 1)] {
+            sqlx::query("UPDATE simulations SET keyframe_interval = $1 WHERE id = $2")
+                .bind(interval)
+                .bind(id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+        let states: Vec<State> = (0..10)
+            .map(|time_step| {
+                // node 2 and its edge are removed at time step 7, node 5 is added at time step 8
+                let ids = match time_step {
+                    0..=6 => vec![0, 1, 2],
+                    7 => vec![0, 1],
+                    _ => vec![0, 1, 5],
+                };
+                let nodes = ids
+                    .iter()
+                    .map(|&id| Node {
+                        id,
+                        latitude: id as f64,
+                        longitude: if id == 1 && time_step >= 4 { 1.0 } else { 0.0 },
+                        components: [
+                            ("changing".to_string(), number((time_step * id) as f64)),
+                            ("constant".to_string(), number(id as f64)),
+                        ]
+                        .into(),
+                    })
+                    .collect();
+                let mut edge = vec![Edge {
+                    from: 0,
+                    to: 1,
+                    component_type: "Sometype".to_string(),
+                    component_data: Some(number((time_step / 6) as f64)),
+                    id: 0,
+                }];
+                if time_step < 7 {
+                    edge.push(Edge {
+                        from: 1,
+                        to: 2,
+                        component_type: "Sometype".to_string(),
+                        component_data: Some(number(2.0)),
+                        id: 1,
+                    });
+                }
+                let mut global_components: HashMap<_, _> =
+                    [("temperature".to_string(), number((time_step / 2) as f64))].into();
+                if time_step < 7 {
+                    global_components.insert("constant".to_string(), number(1.0));
+                }
+                State {
+                    graph: Some(Graph { nodes, edge }),
+                    global_components,
+                }
+            })
+            .collect();
+
+        for simulation_id in [delta, full] {
+            for (time_step, state) in states.iter().enumerate() {
+                let previous = time_step.checked_sub(1).map(|t| &states[t]);
+                db.add_frame(simulation_id, time_step as i32, state, previous)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let time_steps: Vec<i32> = (0..10).collect();
+        let delta_frames = db.get_frames(delta, &time_steps, None).await.unwrap();
+        let full_frames = db.get_frames(full, &time_steps, None).await.unwrap();
+        for (time_step, state) in states.iter().enumerate() {
+            let time_step = time_step as i32;
+            let graph = state.graph.as_ref().unwrap();
+            for simulation_id in [delta, full] {
+                assert_eq!(
+                    db.get_nodes(simulation_id, time_step).await.unwrap(),
+                    graph.nodes
+                );
+                assert_eq!(
+                    db.get_edges(simulation_id, time_step).await.unwrap(),
+                    graph.edge
+                );
+                assert_eq!(
+                    db.get_global_components(simulation_id, time_step)
+                        .await
+                        .unwrap(),
+                    state.global_components
+                );
+            }
+            assert_eq!(&delta_frames[&time_step], state);
+            assert_eq!(&full_frames[&time_step], state);
+        }
+        assert_eq!(db.get_max_timestep(delta).await.unwrap(), 9);
+
+        // only the changes are stored
+        let count = |simulation_id: i32| {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM nodes WHERE simulation_id = $1")
+                .bind(simulation_id)
+                .fetch_one(&pool)
+        };
+        assert_eq!(count(full).await.unwrap(), 29);
+        assert!(count(delta).await.unwrap() < 29);
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_removed_items(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        let id = db
+            .add_simulation("topology", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        assert_eq!(db.get_next_ids(id).await.unwrap(), (0, 0));
+
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+        let node = |id: u64| Node {
+            id,
+            longitude: 0.0,
+            latitude: 0.0,
+            components: [("load".to_string(), number(id as f64))].into(),
+        };
+        let edge = |id: u64, from: u64, to: u64| Edge {
+            id,
+            from,
+            to,
+            component_type: "cable".to_string(),
+            component_data: Some(number(1.0)),
+        };
+        // node 1 and its edge are removed at time step 2, node 3 is added at time step 3
+        let states = [
+            (
+                vec![node(0), node(1), node(2)],
+                vec![edge(0, 0, 1), edge(1, 0, 2)],
+            ),
+            (
+                vec![node(0), node(1), node(2)],
+                vec![edge(0, 0, 1), edge(1, 0, 2)],
+            ),
+            (vec![node(0), node(2)], vec![edge(1, 0, 2)]),
+            (
+                vec![node(0), node(2), node(3)],
+                vec![edge(1, 0, 2), edge(2, 2, 3)],
+            ),
+        ]
+        .map(|(nodes, edge)| State {
+            graph: Some(Graph { nodes, edge }),
+            global_components: HashMap::new(),
+        });
+        for (time_step, state) in states.iter().enumerate() {
+            let previous = time_step.checked_sub(1).map(|t| &states[t]);
+            db.add_frame(id, time_step as i32, state, previous)
+                .await
+                .unwrap();
+        }
+
+        // the removals are stored as a delta
+        let keyframes: Vec<bool> = sqlx::query_scalar(
+            "SELECT keyframe FROM frames WHERE simulation_id = $1 ORDER BY time_step",
+        )
+        .bind(id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(keyframes, vec![true, false, false, false]);
+
+        let time_steps: Vec<i32> = (0..4).collect();
+        let frames = db.get_frames(id, &time_steps, None).await.unwrap();
+        for (time_step, state) in states.iter().enumerate() {
+            let time_step = time_step as i32;
+            let graph = state.graph.as_ref().unwrap();
+            assert_eq!(&frames[&time_step], state);
+            assert_eq!(db.get_nodes(id, time_step).await.unwrap(), graph.nodes);
+            assert_eq!(db.get_edges(id, time_step).await.unwrap(), graph.edge);
+        }
+        assert!(db.get_node(id, 1, 1).await.is_ok());
+        assert!(db.get_node(id, 2, 1).await.is_err());
+        assert!(db.get_edge(id, 3, 0).await.is_err());
+        assert_eq!(db.get_next_ids(id).await.unwrap(), (4, 3));
+
+        // removed nodes and edges are not copied to a branch
+        let branch = db
+            .add_simulation("branch", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        db.copy_frame(id, 3, branch).await.unwrap();
+        assert_eq!(
+            db.get_frames(branch, &[0], None).await.unwrap()[&0],
+            states[3]
+        );
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_retention(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        let mut ids = HashMap::new();
+        for (name, status, twin, age) in [
+            ("old", StatusEnum::Finished, None, 40),
+            ("new", StatusEnum::Finished, None, 0),
+            ("running", StatusEnum::Computing, None, 40),
+            ("pinned", StatusEnum::Cancelled, None, 40),
+            ("first", StatusEnum::Failed, Some("twin"), 0),
+            ("second", StatusEnum::Finished, Some("twin"), 0),
+            ("third", StatusEnum::Finished, Some("twin"), 0),
+        ] {
+            let id = db
+                .add_simulation(name, 1000, 10, status, vec![])
+                .await
+                .unwrap();
+            if let Some(twin) = twin {
+                db.set_twin(id, twin).await.unwrap();
+            }
+            sqlx::query("UPDATE simulations SET date = CURRENT_DATE - $1::INT WHERE id = $2")
+                .bind(age)
+                .bind(id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            ids.insert(name, id);
+        }
+        db.set_pinned(ids["pinned"], true).await.unwrap();
+
+        let expired = db
+            .get_expired_simulations(Some(30), Some(2), true, false)
+            .await
+            .unwrap();
+        assert_eq!(expired, vec![ids["old"], ids["first"]]);
+        let expired = db
+            .get_expired_simulations(Some(30), None, false, false)
+            .await
+            .unwrap();
+        assert_eq!(expired, vec![ids["old"], ids["pinned"]]);
+        assert!(db
+            .get_expired_simulations(None, None, true, false)
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.set_downsampled(ids["old"], 5).await.unwrap();
+        let expired = db
+            .get_expired_simulations(Some(30), None, true, true)
+            .await
+            .unwrap();
+        assert!(expired.is_empty());
+        let retention = db.get_retention(ids["old"]).await.unwrap();
+        assert_eq!(retention.downsampled, Some(5));
+        assert!(!retention.pinned);
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_keep_frames(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let simulation_id = db
+            .add_simulation("sim", 1000, 12, StatusEnum::Finished, vec![])
+            .await
+            .unwrap();
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+        let states: Vec<State> = (0..12)
+            .map(|time_step| State {
+                graph: Some(Graph {
+                    nodes: vec![Node {
+                        id: 0,
+                        latitude: 0.0,
+                        longitude: 0.0,
+                        components: [("changing".to_string(), number(time_step as f64))].into(),
+                    }],
+                    edge: vec![],
+                }),
+                global_components: [("temperature".to_string(), number((time_step / 3) as f64))]
+                    .into(),
+            })
+            .collect();
+        for (time_step, state) in states.iter().enumerate() {
+            let previous = time_step.checked_sub(1).map(|t| &states[t]);
+            db.add_frame(simulation_id, time_step as i32, state, previous)
+                .await
+                .unwrap();
+        }
+        db.add_skipped_simulators(simulation_id, 3, &["a".to_string()])
+            .await
+            .unwrap();
+        db.add_skipped_simulators(simulation_id, 5, &["a".to_string()])
+            .await
+            .unwrap();
+        let before = db.count_rows(simulation_id).await.unwrap();
+        // the name of the simulation the frames are kept in while downsampling is taken
+        db.add_simulation("sim (downsampling)", 1000, 12, StatusEnum::Finished, vec![])
+            .await
+            .unwrap();
+
+        db.begin_transaction().await.unwrap();
+        db.keep_frames(simulation_id, &[0, 5, 10, 11])
+            .await
+            .unwrap();
+        db.commit().await.unwrap();
+
+        // frames that were dropped can not be branched from
+        assert!(db.has_frame(simulation_id, 5).await.unwrap());
+        assert!(!db.has_frame(simulation_id, 4).await.unwrap());
+        let branch = db
+            .add_simulation("branch", 1000, 12, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        assert!(db.copy_frame(simulation_id, 4, branch).await.is_err());
+        db.copy_frame(simulation_id, 5, branch).await.unwrap();
+        db.delete_simulation_via_id(branch).await.unwrap();
+
+        let frames = db
+            .get_frames(simulation_id, &(0..12).collect::<Vec<_>>(), None)
+            .await
+            .unwrap();
+        for (time_step, frame) in frames {
+            if [0, 5, 10, 11].contains(&time_step) {
+                assert_eq!(frame, states[time_step as usize]);
+            } else {
+                assert!(frame.graph.unwrap().nodes.is_empty());
+            }
+        }
+        assert_eq!(db.get_max_timestep(simulation_id).await.unwrap(), 11);
+        assert_eq!(
+            db.get_skipped_simulators(simulation_id, 5).await.unwrap(),
+            vec!["a"]
+        );
+        assert!(db
+            .get_skipped_simulators(simulation_id, 3)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db.count_rows(simulation_id).await.unwrap() < before);
+        assert_eq!(db.get_storage_stats().await.unwrap().simulations, 2);
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_stop_conditions(pool: sqlx::PgPool) {
+        use proto::simulation::simulation_manager::{stop_condition::Operand, ComparisonOperator};
+
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let simulation_id = db
+            .add_simulation("sim", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        let conditions: Vec<StopCondition> = (0..3)
+            .map(|n| StopCondition {
+                component: "global_time".to_string(),
+                path: "$".to_string(),
+                comparison: ComparisonOperator::GreaterOrEqual.into(),
+                operand: Some(Operand::Value(Value {
+                    kind: Some(Kind::NumberValue(n as f64)),
+                })),
+                consecutive: n,
+            })
+            .collect();
+        db.set_stop_conditions(simulation_id, &conditions)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_stop_conditions(simulation_id).await.unwrap(),
+            conditions
+        );
+
+        assert_eq!(db.get_stopped(simulation_id).await.unwrap(), None);
+        db.set_stopped(simulation_id, 2, 7).await.unwrap();
+        assert_eq!(db.get_stopped(simulation_id).await.unwrap(), Some((2, 7)));
+    }
+
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_component_versions(pool: sqlx::PgPool) {
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let simulation_id = db
+            .add_simulation("sim", 1000, 10, StatusEnum::Pending, vec![])
+            .await
+            .unwrap();
+        assert!(db
+            .get_component_versions(simulation_id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.set_component_versions(
+            simulation_id,
+            &HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]),
+        )
+        .await
+        .unwrap();
+        db.set_component_versions(simulation_id, &HashMap::from([("a".to_string(), 3)]))
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_component_versions(simulation_id).await.unwrap(),
+            HashMap::from([("a".to_string(), 3), ("b".to_string(), 2)])
+        );
+    }
 }
@@ -1,22 +1,57 @@
 use crate::database::{SimulationsDB, StatusEnum};
+use crate::telemetry;
 use anyhow::Context;
+use metrics::{counter, gauge};
 use proto::simulation::State;
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tracing::{debug_span, Instrument};
 
 /// The database buffer struct holds a postgres connection pool and an async channel. The postgres
 /// connection is used to write every timeframe to the database and update simulation status.
 /// The async channel is used by the simulation runner to pass finished timesteps and status to the database buffer
 /// which then writes them to the database.
+/// Every write is published by the database as an [`Event`](crate::events::Event), so other parts
+/// of the manager can follow the progress of simulations without polling the database.
 pub struct DatabaseBuffer {
     state_receiver: mpsc::UnboundedReceiver<Transport>,
     connection: SimulationsDB,
+    /// The last stored state of every running simulation. Only the changes since this state are
+    /// written for the next timestep.
+    previous: HashMap<i32, State>,
+}
+
+/// The sending half of the channel to the database buffer.
+///
+/// Every message is counted in the [`telemetry::DATABASE_BACKLOG`] gauge from when it is sent
+/// until the database buffer receives it.
+#[derive(Clone)]
+pub struct BufferSender(mpsc::UnboundedSender<Transport>);
+
+impl BufferSender {
+    /// Send a message to the database buffer.
+    pub fn send(&self, transport: Transport) -> Result<(), mpsc::error::SendError<Transport>> {
+        gauge!(telemetry::DATABASE_BACKLOG).increment(1.0);
+        self.0.send(transport).map_err(|err| {
+            gauge!(telemetry::DATABASE_BACKLOG).decrement(1.0);
+            err
+        })
+    }
+}
+
+/// Create the channel to the database buffer.
+pub fn channel() -> (BufferSender, mpsc::UnboundedReceiver<Transport>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (BufferSender(sender), receiver)
 }
 
 /// Specifies either simulation state or simulation status
 pub enum Transport {
     State(StateTransport),
     Status(StatusTransport),
+    Stopped(StoppedTransport),
 }
 
 /// Struct to transport simulation data to database buffer
@@ -25,6 +60,10 @@ pub struct StateTransport {
     pub simulation_id: i32,
     pub iteration: i32,
     pub state: State,
+    pub step_duration: Duration,
+    /// Simulators that were skipped because they failed, their components were copied from the
+    /// previous timestep.
+    pub skipped_simulators: Vec<String>,
 }
 
 /// Struct to transport status information to database buffer
@@ -35,86 +74,144 @@ pub struct StatusTransport {
     pub status_info: String,
 }
 
+/// Struct to transport the stop condition that finished a simulation early to database buffer
+#[derive(Clone)]
+pub struct StoppedTransport {
+    pub simulation_id: i32,
+    /// The index of the stop condition that fired.
+    pub condition: i32,
+    /// The frame in which the condition fired.
+    pub iteration: i32,
+    pub status_info: String,
+}
+
 impl DatabaseBuffer {
     /// Create new database buffer
     pub async fn new(pool: PgPool, state_receiver: mpsc::UnboundedReceiver<Transport>) -> Self {
         Self {
             connection: SimulationsDB::from_pg_pool(pool).await.unwrap(),
             state_receiver,
+            previous: HashMap::new(),
         }
     }
 
     /// Start the infinite loop in which the database buffer will check if new messages have arrived
     /// at the async channel.
-    /// If there is a new message it will process the timeframe by writing all nodes, node components,
-    /// edges and global components to the database. While writing all the different parts of the state
-    /// to the database it keeps track of which nodes, edges and components were returned. If this does
-    /// not match the state that was sent, the parts that were not returned will be duplicated to the
-    /// next timestep.
+    /// If there is a new message it will process the timeframe by writing the nodes, node components,
+    /// edges and global components that changed since the previous timestep to the database. The
+    /// previous state is kept in memory, or read from the database when the simulation was
+    /// resumed.
     pub async fn start(mut self) -> anyhow::Result<()> {
         loop {
             if let Some(transport) = self.state_receiver.recv().await {
-                self.connection
-                    .begin_transaction()
-                    .await
-                    .context("while trying to begin transaction")?;
-
-                match transport {
-                    // write state to database
-                    Transport::State(transport) => {
-                        // unpack transport
-                        let i = transport.iteration;
-                        let simulation_id = transport.simulation_id;
-                        let state = transport.state;
-
-                        // multiple checks were done to make sure a graph is present before sending it here
-                        let graph = state.graph.context("while trying to extract graph")?;
-                        let global = state.global_components;
-                        let nodes = graph.nodes;
-                        let edges = graph.edge;
-
-                        for node in nodes {
-                            // write node to db
-                            self.connection
-                                .add_node(node, simulation_id, i)
-                                .await
-                                .context("while trying to add node to database")?;
-                        }
-
-                        for edge in edges {
-                            // write edge to tb
-                            self.connection
-                                .add_edge(edge, simulation_id, i)
-                                .await
-                                .context("while trying to add edge to database")?;
-                        }
-
-                        for (key, value) in global {
-                            // write global component to db
-                            self.connection
-                                .add_global_component(&key, value, simulation_id, i)
-                                .await
-                                .context("while trying to add global component to database")?;
-                        }
-                    }
-                    // update status in database
+                gauge!(telemetry::DATABASE_BACKLOG).decrement(1.0);
+                let span = match &transport {
+                    Transport::State(transport) => debug_span!(
+                        "write_frame",
+                        simulation_id = transport.simulation_id,
+                        frame = transport.iteration
+                    ),
                     Transport::Status(transport) => {
-                        let simulation_id = transport.simulation_id;
-                        let status = transport.status;
-                        let info = transport.status_info;
-                        // write status to db
-                        self.connection
-                            .update_status(simulation_id, status, Some(&info))
-                            .await
-                            .context("while trying to update status")?;
+                        debug_span!("write_status", simulation_id = transport.simulation_id)
+                    }
+                    Transport::Stopped(transport) => {
+                        debug_span!("write_status", simulation_id = transport.simulation_id)
                     }
+                };
+                let is_frame = matches!(transport, Transport::State(_));
+                self.write(transport).instrument(span).await?;
+                if is_frame {
+                    counter!(telemetry::FRAMES_WRITTEN).increment(1);
                 }
+            }
+        }
+    }
+
+    /// Write a frame or status update in a single transaction.
+    async fn write(&mut self, transport: Transport) -> anyhow::Result<()> {
+        self.connection
+            .begin_transaction()
+            .await
+            .context("while trying to begin transaction")?;
+
+        match transport {
+            // write state to database
+            Transport::State(transport) => {
+                // unpack transport
+                let i = transport.iteration;
+                let simulation_id = transport.simulation_id;
+                let state = transport.state;
+
+                // multiple checks were done to make sure a graph is present before sending it here
+                state
+                    .graph
+                    .as_ref()
+                    .context("while trying to extract graph")?;
+
+                let previous = match self.previous.remove(&simulation_id) {
+                    Some(previous) => Some(previous),
+                    None if i > 0 => self
+                        .connection
+                        .get_frames(simulation_id, &[i - 1], None)
+                        .await
+                        .context("while trying to get previous frame from database")?
+                        .remove(&(i - 1)),
+                    None => None,
+                };
+                self.connection
+                    .add_frame(simulation_id, i, &state, previous.as_ref())
+                    .await
+                    .context("while trying to add frame to database")?;
+                self.connection
+                    .set_step_duration(simulation_id, i, transport.step_duration.as_secs_f64())
+                    .await
+                    .context("while trying to add step duration to database")?;
+                self.previous.insert(simulation_id, state);
 
+                if !transport.skipped_simulators.is_empty() {
+                    self.connection
+                        .add_skipped_simulators(simulation_id, i, &transport.skipped_simulators)
+                        .await
+                        .context("while trying to add skipped simulators to database")?;
+                }
+            }
+            // update status in database
+            Transport::Status(transport) => {
+                let simulation_id = transport.simulation_id;
+                let status = transport.status;
+                let info = transport.status_info;
+                if status != StatusEnum::Computing {
+                    self.previous.remove(&simulation_id);
+                }
+                // write status to db
                 self.connection
-                    .commit()
+                    .update_status(simulation_id, status, Some(&info))
                     .await
-                    .context("while trying to commit transaction")?;
+                    .context("while trying to update status")?;
+            }
+            // finish the simulation and record the stop condition
+            Transport::Stopped(transport) => {
+                let simulation_id = transport.simulation_id;
+                self.previous.remove(&simulation_id);
+                self.connection
+                    .set_stopped(simulation_id, transport.condition, transport.iteration)
+                    .await
+                    .context("while trying to record stop condition")?;
+                self.connection
+                    .update_status(
+                        simulation_id,
+                        StatusEnum::Finished,
+                        Some(&transport.status_info),
+                    )
+                    .await
+                    .context("while trying to update status")?;
             }
         }
+
+        self.connection
+            .commit()
+            .await
+            .context("while trying to commit transaction")?;
+        Ok(())
     }
 }
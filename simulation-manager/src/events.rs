@@ -0,0 +1,154 @@
+use crate::database::StatusEnum;
+use anyhow::Context;
+use serde_json::Value;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// The channel on which the database notifies that a frame was stored or that the status of a
+/// simulation changed.
+const EVENT_CHANNEL: &str = "simulation_events";
+
+/// A change to a simulation that has been stored in the database.
+///
+/// Events are published by the database once the change is committed, so the changes made by
+/// other managers are received as well.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// A new frame was stored.
+    Frame {
+        simulation_id: i32,
+        frame_nr: i32,
+        /// How long it took the runner to compute the frame.
+        step_duration: Duration,
+    },
+    /// The status of a simulation changed. An empty `status_info` means the simulation has no
+    /// status info.
+    Status {
+        simulation_id: i32,
+        status: StatusEnum,
+        status_info: String,
+    },
+}
+
+impl Event {
+    /// The simulation this event is about.
+    pub fn simulation_id(&self) -> i32 {
+        match self {
+            Event::Frame { simulation_id, .. } | Event::Status { simulation_id, .. } => {
+                *simulation_id
+            }
+        }
+    }
+
+    /// Read an event from the payload of a notification of the database.
+    fn from_payload(payload: &str) -> anyhow::Result<Event> {
+        let payload: Value = serde_json::from_str(payload).context("invalid json")?;
+        let field = |name: &str| payload.get(name).context(format!("missing `{name}`"));
+        let simulation_id = field("simulation_id")?
+            .as_i64()
+            .context("invalid simulation id")? as i32;
+        if let Some(status) = payload.get("status") {
+            let status = status.as_str().context("invalid status")?;
+            let status_info = field("status_info")?
+                .as_str()
+                .context("invalid status info")?;
+            return Ok(Event::Status {
+                simulation_id,
+                status: StatusEnum::from_string(status),
+                status_info: status_info.to_string(),
+            });
+        }
+        let frame_nr = field("frame_nr")?.as_i64().context("invalid frame")? as i32;
+        let step_duration = field("step_duration")?
+            .as_f64()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .context("invalid step duration")?;
+        Ok(Event::Frame {
+            simulation_id,
+            frame_nr,
+            step_duration,
+        })
+    }
+}
+
+/// The event listener forwards the events published by the database to a broadcast channel, so
+/// other parts of the manager can follow the progress of simulations without polling the
+/// database.
+pub struct EventListener {
+    listener: PgListener,
+    events: broadcast::Sender<Event>,
+}
+
+impl EventListener {
+    /// Create a new event listener. It listens from here on, so the events published before it is
+    /// started are not missed.
+    pub async fn new(pool: &PgPool, events: broadcast::Sender<Event>) -> anyhow::Result<Self> {
+        let mut listener = PgListener::connect_with(pool)
+            .await
+            .context("Failed to set up a listener for events")?;
+        listener
+            .listen(EVENT_CHANNEL)
+            .await
+            .context("Failed to listen to events")?;
+        Ok(Self { listener, events })
+    }
+
+    /// Start the infinite loop in which the event listener forwards events. When the connection
+    /// to the database is lost, it reconnects and the events that were published in the meantime
+    /// are missed.
+    pub async fn start(mut self) -> anyhow::Result<()> {
+        loop {
+            let notification = self
+                .listener
+                .recv()
+                .await
+                .context("could not listen to events")?;
+            match Event::from_payload(notification.payload()) {
+                // An error only means that nobody is listening.
+                Ok(event) => {
+                    self.events.send(event).ok();
+                }
+                Err(err) => warn!("Invalid event `{}`: {err:#}", notification.payload()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_payload() {
+        let frame =
+            Event::from_payload(r#"{"simulation_id" : 3, "frame_nr" : 7, "step_duration" : 0.5}"#)
+                .unwrap();
+        assert!(matches!(
+            frame,
+            Event::Frame {
+                simulation_id: 3,
+                frame_nr: 7,
+                step_duration,
+            } if step_duration == Duration::from_millis(500)
+        ));
+
+        let status = Event::from_payload(
+            r#"{"simulation_id" : 3, "status" : "Paused", "status_info" : ""}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            status,
+            Event::Status {
+                simulation_id: 3,
+                status: StatusEnum::Paused,
+                status_info,
+            } if status_info.is_empty()
+        ));
+
+        assert!(Event::from_payload(r#"{"simulation_id" : 3, "frame_nr" : 7}"#).is_err());
+        assert!(Event::from_payload("3").is_err());
+    }
+}
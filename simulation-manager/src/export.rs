@@ -0,0 +1,504 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use anyhow::Result;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use prost_types::{value::Kind, Value};
+use proto::simulation::State;
+use tokio::sync::Mutex;
+use tokio_stream::Stream;
+
+use crate::database::SimulationsDB;
+
+/// The maximum amount of frames that are read from the database at once when exporting. Every
+/// chunk is written as a row group in Parquet.
+const EXPORT_CHUNK_SIZE: usize = 64;
+
+/// The columns of an export, see [`Row`].
+const COLUMNS: [&str; 7] = [
+    "time_step",
+    "entity",
+    "entity_id",
+    "component",
+    "field",
+    "number",
+    "text",
+];
+
+const PARQUET_SCHEMA: &str = "
+message frame {
+    REQUIRED INT32 time_step;
+    REQUIRED BYTE_ARRAY entity (UTF8);
+    OPTIONAL INT64 entity_id;
+    REQUIRED BYTE_ARRAY component (UTF8);
+    REQUIRED BYTE_ARRAY field (UTF8);
+    OPTIONAL DOUBLE number;
+    OPTIONAL BYTE_ARRAY text (UTF8);
+}
+";
+
+/// The file format of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+/// A single value in a frame: the value at one path in a component of a node, an edge or the
+/// global components, in one time step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub time_step: i32,
+    /// `node`, `edge` or `global`.
+    pub entity: &'static str,
+    /// The id of the node or edge, not set for global components.
+    pub entity_id: Option<u64>,
+    pub component: String,
+    /// The JSON path of the value within the component, in the same format as the paths of
+    /// structure violations.
+    pub field: String,
+    /// Set when the value is a number.
+    pub number: Option<f64>,
+    /// Set when the value is a string or a boolean.
+    pub text: Option<String>,
+}
+
+/// The time steps from `start` up to (but not including) `end`, without the time steps after
+/// `last`. All time steps from `start` up to `last` when `end` is not set.
+pub fn time_steps(start: u32, end: Option<u32>, last: i32) -> Vec<i32> {
+    let end = end.unwrap_or(u32::MAX).min(last.max(-1) as u32 + 1);
+    (start..end).map(|time_step| time_step as i32).collect()
+}
+
+/// Flatten a frame into one row per value in its components.
+///
+/// Only the components in `components` are exported, or all of them when it is `None`. Components
+/// of a node and global components are exported ordered by name.
+pub fn flatten(time_step: i32, state: &State, components: Option<&[String]>) -> Vec<Row> {
+    let exported = |name: &String| match components {
+        Some(components) => components.contains(name),
+        None => true,
+    };
+    let mut rows = Vec::new();
+    let mut add = |entity, entity_id, component: &str, value: &Value| {
+        flatten_value(value, "$".to_string(), &mut |field, value| {
+            let (number, text) = match &value.kind {
+                Some(Kind::NumberValue(number)) => (Some(*number), None),
+                Some(Kind::StringValue(text)) => (None, Some(text.clone())),
+                Some(Kind::BoolValue(value)) => (None, Some(value.to_string())),
+                _ => (None, None),
+            };
+            rows.push(Row {
+                time_step,
+                entity,
+                entity_id,
+                component: component.to_string(),
+                field,
+                number,
+                text,
+            });
+        });
+    };
+
+    if let Some(graph) = &state.graph {
+        for node in &graph.nodes {
+            let components: BTreeMap<_, _> = node.components.iter().collect();
+            for (name, value) in components.into_iter().filter(|(name, _)| exported(name)) {
+                add("node", Some(node.id), name, value);
+            }
+        }
+        for edge in graph
+            .edge
+            .iter()
+            .filter(|edge| exported(&edge.component_type))
+        {
+            if let Some(value) = &edge.component_data {
+                add("edge", Some(edge.id), &edge.component_type, value);
+            }
+        }
+    }
+    let globals: BTreeMap<_, _> = state.global_components.iter().collect();
+    for (name, value) in globals.into_iter().filter(|(name, _)| exported(name)) {
+        add("global", None, name, value);
+    }
+    rows
+}
+
+/// Call `row` for every value in `value` that is not a struct or a list.
+fn flatten_value(value: &Value, path: String, row: &mut impl FnMut(String, &Value)) {
+    match &value.kind {
+        Some(Kind::StructValue(fields)) => {
+            for (name, value) in &fields.fields {
+                flatten_value(value, format!("{path}.{name}"), row);
+            }
+        }
+        Some(Kind::ListValue(list)) => {
+            for (index, value) in list.values.iter().enumerate() {
+                flatten_value(value, format!("{path}[{index}]"), row);
+            }
+        }
+        _ => row(path, value),
+    }
+}
+
+/// A buffer that is shared with a writer, so the bytes that have been written can be taken while
+/// the writer is still in use.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().expect("buffer lock poisoned"))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .expect("buffer lock poisoned")
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Inner {
+    Csv(Box<csv::Writer<SharedBuffer>>),
+    JsonLines,
+    Parquet(Box<SerializedFileWriter<SharedBuffer>>),
+}
+
+/// Writes rows to a file in one of the export formats, piece by piece.
+pub struct Writer {
+    buffer: SharedBuffer,
+    inner: Inner,
+}
+
+impl Writer {
+    pub fn new(format: Format) -> Result<Self> {
+        let buffer = SharedBuffer::default();
+        let inner = match format {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(buffer.clone());
+                writer.write_record(COLUMNS)?;
+                Inner::Csv(Box::new(writer))
+            }
+            Format::JsonLines => Inner::JsonLines,
+            Format::Parquet => Inner::Parquet(Box::new(SerializedFileWriter::new(
+                buffer.clone(),
+                Arc::new(parse_message_type(PARQUET_SCHEMA)?),
+                Arc::new(WriterProperties::builder().build()),
+            )?)),
+        };
+        Ok(Self { buffer, inner })
+    }
+
+    /// Write rows and return the bytes of the file that are ready.
+    pub fn write(&mut self, rows: &[Row]) -> Result<Vec<u8>> {
+        match &mut self.inner {
+            Inner::Csv(writer) => {
+                for row in rows {
+                    writer.write_record([
+                        row.time_step.to_string(),
+                        row.entity.to_string(),
+                        row.entity_id.map(|id| id.to_string()).unwrap_or_default(),
+                        row.component.clone(),
+                        row.field.clone(),
+                        row.number
+                            .map(|number| number.to_string())
+                            .unwrap_or_default(),
+                        row.text.clone().unwrap_or_default(),
+                    ])?;
+                }
+                writer.flush()?;
+            }
+            Inner::JsonLines => {
+                for row in rows {
+                    let line = serde_json::json!({
+                        "time_step": row.time_step,
+                        "entity": row.entity,
+                        "entity_id": row.entity_id,
+                        "component": row.component,
+                        "field": row.field,
+                        "number": row.number,
+                        "text": row.text,
+                    });
+                    serde_json::to_writer(&mut self.buffer, &line)?;
+                    self.buffer.write_all(b"\n")?;
+                }
+            }
+            Inner::Parquet(writer) if !rows.is_empty() => write_row_group(writer, rows)?,
+            Inner::Parquet(_) => {}
+        }
+        Ok(self.buffer.take())
+    }
+
+    /// Finish the file and return its last bytes.
+    pub fn finish(self) -> Result<Vec<u8>> {
+        match self.inner {
+            Inner::Csv(mut writer) => writer.flush()?,
+            Inner::JsonLines => {}
+            Inner::Parquet(writer) => {
+                writer.close()?;
+            }
+        }
+        Ok(self.buffer.take())
+    }
+}
+
+/// Write rows to a Parquet file as a single row group.
+fn write_row_group(writer: &mut SerializedFileWriter<SharedBuffer>, rows: &[Row]) -> Result<()> {
+    let strings = |column: fn(&Row) -> &str| -> Vec<ByteArray> {
+        rows.iter()
+            .map(|row| ByteArray::from(column(row)))
+            .collect()
+    };
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match COLUMNS[index] {
+            "time_step" => {
+                let time_steps: Vec<i32> = rows.iter().map(|row| row.time_step).collect();
+                column
+                    .typed::<Int32Type>()
+                    .write_batch(&time_steps, None, None)?
+            }
+            "entity" => column.typed::<ByteArrayType>().write_batch(
+                &strings(|row| row.entity),
+                None,
+                None,
+            )?,
+            "entity_id" => {
+                let (ids, levels) =
+                    optional(rows.iter().map(|row| row.entity_id.map(|id| id as i64)));
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&ids, Some(&levels), None)?
+            }
+            "component" => column.typed::<ByteArrayType>().write_batch(
+                &strings(|row| &row.component),
+                None,
+                None,
+            )?,
+            "field" => column.typed::<ByteArrayType>().write_batch(
+                &strings(|row| &row.field),
+                None,
+                None,
+            )?,
+            "number" => {
+                let (numbers, levels) = optional(rows.iter().map(|row| row.number));
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(&numbers, Some(&levels), None)?
+            }
+            _ => {
+                let (texts, levels) = optional(
+                    rows.iter()
+                        .map(|row| row.text.as_deref().map(ByteArray::from)),
+                );
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&texts, Some(&levels), None)?
+            }
+        };
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+    Ok(())
+}
+
+/// The values and definition levels of an optional column.
+fn optional<T>(values: impl Iterator<Item = Option<T>>) -> (Vec<T>, Vec<i16>) {
+    let mut present = Vec::new();
+    let levels = values
+        .map(|value| match value {
+            Some(value) => {
+                present.push(value);
+                1
+            }
+            None => 0,
+        })
+        .collect();
+    (present, levels)
+}
+
+/// Export frames of a simulation. The frames are read from the database in chunks of
+/// [`EXPORT_CHUNK_SIZE`] frames, every item of the stream holds the next bytes of the file.
+pub fn export(
+    db: Arc<Mutex<SimulationsDB>>,
+    simulation_id: i32,
+    time_steps: Vec<i32>,
+    components: Option<Vec<String>>,
+    format: Format,
+) -> impl Stream<Item = Result<Vec<u8>>> {
+    async_stream::try_stream! {
+        let mut writer = Writer::new(format)?;
+        for chunk in time_steps.chunks(EXPORT_CHUNK_SIZE) {
+            let frames = db.lock().await.get_frames(simulation_id, chunk, components.as_deref()).await?;
+            let rows: Vec<Row> = frames
+                .iter()
+                .flat_map(|(time_step, state)| flatten(*time_step, state, components.as_deref()))
+                .collect();
+            let bytes = writer.write(&rows)?;
+            if !bytes.is_empty() {
+                yield bytes;
+            }
+        }
+        yield writer.finish()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{ListValue, Struct};
+    use proto::simulation::{Edge, Graph, Node};
+
+    fn number(n: f64) -> Value {
+        Value {
+            kind: Some(Kind::NumberValue(n)),
+        }
+    }
+
+    fn state() -> State {
+        let generator = Value {
+            kind: Some(Kind::StructValue(Struct {
+                fields: [
+                    (
+                        "active".to_string(),
+                        Value {
+                            kind: Some(Kind::BoolValue(true)),
+                        },
+                    ),
+                    (
+                        "output".to_string(),
+                        Value {
+                            kind: Some(Kind::ListValue(ListValue {
+                                values: vec![number(1.5), number(2.5)],
+                            })),
+                        },
+                    ),
+                ]
+                .into(),
+            })),
+        };
+        State {
+            graph: Some(Graph {
+                nodes: vec![Node {
+                    id: 3,
+                    components: [("Generator".to_string(), generator)].into(),
+                    ..Default::default()
+                }],
+                edge: vec![Edge {
+                    id: 4,
+                    component_type: "Cable".to_string(),
+                    component_data: Some(number(0.5)),
+                    ..Default::default()
+                }],
+            }),
+            global_components: [(
+                "Weather".to_string(),
+                Value {
+                    kind: Some(Kind::StringValue("sunny".to_string())),
+                },
+            )]
+            .into(),
+        }
+    }
+
+    #[test]
+    fn tidy_rows() {
+        let rows = flatten(2, &state(), None);
+        let fields: Vec<_> = rows
+            .iter()
+            .map(|row| (row.entity, row.entity_id, row.field.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("node", Some(3), "$.active"),
+                ("node", Some(3), "$.output[0]"),
+                ("node", Some(3), "$.output[1]"),
+                ("edge", Some(4), "$"),
+                ("global", None, "$"),
+            ]
+        );
+        assert!(rows.iter().all(|row| row.time_step == 2));
+        assert_eq!(rows[0].text.as_deref(), Some("true"));
+        assert_eq!(rows[2].number, Some(2.5));
+        assert_eq!(rows[4].text.as_deref(), Some("sunny"));
+
+        let filtered = flatten(2, &state(), Some(&["Cable".to_string()]));
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].component, "Cable");
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(time_steps(0, None, 3), vec![0, 1, 2, 3]);
+        assert_eq!(time_steps(1, Some(3), 5), vec![1, 2]);
+        assert_eq!(time_steps(2, Some(10), 3), vec![2, 3]);
+        assert!(time_steps(4, None, 3).is_empty());
+    }
+
+    #[test]
+    fn csv_and_json_lines() {
+        let rows = flatten(
+            0,
+            &state(),
+            Some(&["Cable".to_string(), "Weather".to_string()]),
+        );
+
+        let mut writer = Writer::new(Format::Csv).unwrap();
+        let mut csv = writer.write(&rows).unwrap();
+        csv.extend(writer.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "time_step,entity,entity_id,component,field,number,text\n\
+             0,edge,4,Cable,$,0.5,\n\
+             0,global,,Weather,$,,sunny\n"
+        );
+
+        let mut writer = Writer::new(Format::JsonLines).unwrap();
+        let json = writer.write(&rows).unwrap();
+        assert!(writer.finish().unwrap().is_empty());
+        let lines: Vec<serde_json::Value> = String::from_utf8(json)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines[1],
+            serde_json::json!({
+                "time_step": 0,
+                "entity": "global",
+                "entity_id": null,
+                "component": "Weather",
+                "field": "$",
+                "number": null,
+                "text": "sunny",
+            })
+        );
+    }
+
+    #[test]
+    fn parquet() {
+        let mut writer = Writer::new(Format::Parquet).unwrap();
+        let mut file = writer.write(&flatten(0, &state(), None)).unwrap();
+        file.extend(writer.write(&[]).unwrap());
+        file.extend(writer.write(&flatten(1, &state(), None)).unwrap());
+        file.extend(writer.finish().unwrap());
+        assert!(file.starts_with(b"PAR1"));
+        assert!(file.ends_with(b"PAR1"));
+    }
+}
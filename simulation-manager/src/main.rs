@@ -1,28 +1,110 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
 use std::env;
+use std::io::Write;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 // imports
 use crate::connector::SimulatorsInfo;
+use clap::{Parser, Subcommand};
 use connector::SimulatorConnector;
-use database_buffer::{DatabaseBuffer, Transport};
+use database::{SimulationsDB, StatusEnum};
+use database_buffer::DatabaseBuffer;
+use events::{Event, EventListener};
 use proto::simulation::{
     simulation_manager::SimulationManagerServer, simulator_connection::SimulatorConnectionServer,
 };
-use runner::Runner;
-use sqlx::postgres::PgPool;
-use tokio::sync::{mpsc, Mutex};
+use retention::{Maintenance, RetentionAction, RetentionPolicy};
+use runner::{Runner, TimestepPolicy};
+use sqlx::postgres::PgPoolOptions;
+use telemetry::SimulationGauges;
+use tokio::sync::{broadcast, Mutex};
+use tokio_stream::StreamExt;
 use tonic::transport::Server;
 use tracing::{error, info};
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 
 // modules
+mod archive;
+mod compare;
 pub mod connector;
 pub mod database;
 mod database_buffer;
+mod events;
+mod export;
 pub mod manager;
+mod queue;
+mod retention;
 pub mod runner;
+mod schedule;
+mod stop;
+mod sweep;
+mod telemetry;
+mod topology;
+mod upgrade;
+mod validation;
+
+/// The simulation manager. Runs the manager when no command is given.
+#[derive(Debug, Parser)]
+#[command(name = "simulation-manager")]
+#[command(about = "Computes simulations and stores their frames.", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Export the frames of a simulation as a file with one row per value in a component.
+    Export(Export),
+    /// Archive a finished simulation, so it can be imported elsewhere.
+    Archive(Archive),
+    /// Import an archived simulation as a finished simulation.
+    Import(Import),
+}
+
+#[derive(Debug, Parser)]
+struct Export {
+    /// The name of the simulation.
+    simulation: String,
+    /// The format of the file.
+    #[arg(short, long, value_enum, default_value_t = export::Format::Csv)]
+    format: export::Format,
+    /// Only export these components, separated by commas. All components are exported when not
+    /// given.
+    #[arg(short, long, value_delimiter = ',')]
+    components: Vec<String>,
+    /// The first frame to export.
+    #[arg(long, default_value_t = 0)]
+    start: u32,
+    /// The frame after the last frame to export. All frames that have been computed are exported
+    /// when not given.
+    #[arg(long)]
+    end: Option<u32>,
+    /// The file to write to, the export is written to stdout when not given.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+struct Archive {
+    /// The name of the simulation.
+    simulation: String,
+    /// The file to write to, the archive is written to stdout when not given.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+struct Import {
+    /// The archive to import.
+    file: PathBuf,
+    /// Import the simulation under this name instead of the name in the archive.
+    #[arg(long)]
+    name: Option<String>,
+}
 
 /// Main function that spawns runner and manager to handle requests for new simulations and
 /// manage currently running simulations
@@ -33,13 +115,39 @@ pub mod runner;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        // the output of a command may be written to stdout, so the logs go to stderr
+        tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::from_default_env())
+            .with_writer(std::io::stderr)
+            .init();
+        return Ok(match command {
+            Commands::Export(args) => export_simulation(args).await,
+            Commands::Archive(args) => archive_simulation(args).await,
+            Commands::Import(args) => import_simulation(args).await,
+        }?);
+    }
+
+    // Closing a span logs how long it took, so slow simulators and database writes show up
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE)
         .init();
 
+    // Amount of simulations that can be run at the same time, default 1
+    let workers = env::var("SIMULATION_RUNNER_WORKERS")
+        .unwrap_or("1".to_string())
+        .parse::<usize>()?;
+
     // Create database connection with provided environment variables
+    // Every worker uses up to three connections: one for its transaction, one outside of it and
+    // one that holds the lock on its simulation.
     let database_url = database_config::database_url("simulation_manager");
-    let pool = PgPool::connect(&database_url).await?;
+    let pool = PgPoolOptions::new()
+        .max_connections(10.max(3 * workers as u32 + 5))
+        .connect(&database_url)
+        .await?;
     let pool_clone1 = pool.clone();
     let pool_clone2 = pool.clone();
 
@@ -53,12 +161,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or("127.0.0.1:8099".to_string())
         .parse::<SocketAddr>()?;
 
+    // Interval and timeout of the simulator health checks in seconds, default 30 and 5
+    let health_check_interval = env::var("SIMULATOR_HEALTH_CHECK_INTERVAL")
+        .unwrap_or("30".to_string())
+        .parse::<u64>()?;
+    let health_check_timeout = env::var("SIMULATOR_HEALTH_CHECK_TIMEOUT")
+        .unwrap_or("5".to_string())
+        .parse::<u64>()?;
+
     let connector = SimulatorConnector::new(simulators.clone());
     let connector_server = SimulatorConnectionServer::new(connector);
 
-    // mpsc channel
-    let (notif_sender, notif_receiver) = mpsc::channel(1);
-    let (state_sender, state_receiver) = mpsc::unbounded_channel::<Transport>();
+    // channel to the database buffer
+    let (state_sender, state_receiver) = database_buffer::channel();
 
     // Set up GRPC server listening on provided address or default localhost:8100
     let listen_addr = env::var("SIMULATOR_MANAGER_ADDR")
@@ -66,13 +181,94 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse::<SocketAddr>()?;
     info!("Listening on {listen_addr}");
 
-    let manager = manager::Manager::new(pool.clone(), simulators.clone(), notif_sender).await;
+    // Serve the Prometheus metrics on provided address or default localhost:9100
+    let metrics_addr = env::var("SIMULATOR_MANAGER_METRICS_ADDR")
+        .unwrap_or("127.0.0.1:9100".to_string())
+        .parse::<SocketAddr>()?;
+    telemetry::install(metrics_addr)?;
+    info!("Serving metrics on {metrics_addr}");
+    // How often the metrics read from the database are updated in seconds, default 15
+    let metrics_interval = env::var("SIMULATOR_MANAGER_METRICS_INTERVAL")
+        .unwrap_or("15".to_string())
+        .parse::<u64>()?;
+
+    // Events about stored frames and status changes, used to follow the progress of simulations.
+    // The events are published by the database, so they include the simulations of other managers.
+    let (events, _) = broadcast::channel::<Event>(1024);
+    let event_listener = EventListener::new(&pool, events.clone())
+        .await
+        .context("Failed to set up the event listener")?;
+
+    let manager = manager::Manager::new(pool.clone(), simulators.clone(), events)
+        .await
+        .with_workers(workers);
     let server = SimulationManagerServer::new(manager);
 
+    // How long a timestep may take in seconds, default 60
+    let timestep_timeout = env::var("SIMULATOR_TIMESTEP_TIMEOUT")
+        .unwrap_or("60".to_string())
+        .parse::<u64>()?;
+    // How many times a failed timestep is retried, default 3
+    let timestep_retries = env::var("SIMULATOR_TIMESTEP_RETRIES")
+        .unwrap_or("3".to_string())
+        .parse::<u32>()?;
+    // How long to wait before the first retry in milliseconds, default 500
+    let timestep_backoff = env::var("SIMULATOR_TIMESTEP_BACKOFF_MS")
+        .unwrap_or("500".to_string())
+        .parse::<u64>()?;
+
     // Set up simulation runner
-    let mut runner = Runner::new(pool_clone1, simulators_clone, notif_receiver, state_sender)
+    let mut runner = Runner::new(pool_clone1, simulators_clone, state_sender, workers)
         .await
-        .context("Failed to set up the runner")?;
+        .context("Failed to set up the runner")?
+        .with_timestep_policy(TimestepPolicy {
+            timeout: Duration::from_secs(timestep_timeout),
+            retries: timestep_retries,
+            backoff: Duration::from_millis(timestep_backoff),
+        });
+
+    // Simulations created more than this amount of days ago expire, never by default
+    let retention_max_age_days = env::var("SIMULATION_RETENTION_MAX_AGE_DAYS")
+        .ok()
+        .map(|days| days.parse::<u32>())
+        .transpose()?;
+    // Simulations of a twin expire when it has more than this amount of newer simulations, never
+    // by default
+    let retention_max_per_twin = env::var("SIMULATION_RETENTION_MAX_PER_TWIN")
+        .ok()
+        .map(|count| count.parse::<u32>())
+        .transpose()?;
+    // Whether pinned simulations are kept when they expire, default true
+    let retention_keep_pinned = env::var("SIMULATION_RETENTION_KEEP_PINNED")
+        .unwrap_or("true".to_string())
+        .parse::<bool>()?;
+    // Whether expired simulations are deleted or downsampled, default delete
+    let retention_action = env::var("SIMULATION_RETENTION_ACTION").unwrap_or("delete".to_string());
+    // Every how many frames a frame is kept when downsampling, default 10
+    let retention_stride = env::var("SIMULATION_RETENTION_DOWNSAMPLE_STRIDE")
+        .unwrap_or("10".to_string())
+        .parse::<u32>()?;
+    let retention_action = match retention_action.as_str() {
+        "delete" => RetentionAction::Delete,
+        "downsample" if retention_stride > 1 => RetentionAction::Downsample(retention_stride),
+        "downsample" => return Err("the downsample stride must be at least 2".into()),
+        action => {
+            return Err(format!(
+                "unknown retention action `{action}`, expected delete or downsample"
+            )
+            .into())
+        }
+    };
+    // How often expired simulations are deleted or downsampled in seconds, default 3600
+    let maintenance_interval = env::var("SIMULATION_MAINTENANCE_INTERVAL")
+        .unwrap_or("3600".to_string())
+        .parse::<u64>()?;
+    let retention_policy = RetentionPolicy {
+        max_age_days: retention_max_age_days,
+        max_per_twin: retention_max_per_twin,
+        keep_pinned: retention_keep_pinned,
+        action: retention_action,
+    };
 
     // Database thread
     let task1 = tokio::spawn(async move {
@@ -80,6 +276,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         database_buffer.start().await
     });
 
+    // Event thread
+    let task5 = tokio::spawn(event_listener.start());
+
     // Connector thread
     let task2 = tokio::spawn(
         Server::builder()
@@ -87,6 +286,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .serve(connector_listen_addr),
     );
 
+    // Health check thread
+    tokio::spawn(SimulatorConnector::health_check(
+        simulators.clone(),
+        Duration::from_secs(health_check_interval),
+        Duration::from_secs(health_check_timeout),
+    ));
+
+    // Metrics thread
+    let gauges = SimulationGauges::new(pool.clone()).await?;
+    tokio::spawn(gauges.start(Duration::from_secs(metrics_interval)));
+
+    // Maintenance thread, only when simulations can expire
+    if retention_policy.is_enabled() {
+        let maintenance = Maintenance::new(pool.clone(), retention_policy).await?;
+        tokio::spawn(maintenance.start(Duration::from_secs(maintenance_interval)));
+    }
+
     // Runner thread
     let task3 = tokio::spawn(async move {
         // Infinitely loop in order to retry if the runner encounters an error.
@@ -106,7 +322,89 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         err = task2 => { err??; },
         err = task3 => { err?; },
         err = task4 => { err?; },
+        err = task5 => { err??; },
     }
 
     Ok(())
 }
+
+/// Export a simulation straight from the database, without running the manager.
+async fn export_simulation(args: Export) -> anyhow::Result<()> {
+    let mut db = SimulationsDB::connect().await?;
+    let simulation = db
+        .get_simulation_via_name(&args.simulation)
+        .await
+        .with_context(|| format!("simulation `{}` not found", args.simulation))?;
+    let last = db.get_max_timestep(simulation.id).await?;
+    let time_steps = export::time_steps(args.start, args.end, last);
+    let components = (!args.components.is_empty()).then_some(args.components);
+
+    let mut output = output(args.output.as_deref())?;
+    let chunks = export::export(
+        Arc::new(Mutex::new(db)),
+        simulation.id,
+        time_steps,
+        components,
+        args.format,
+    );
+    tokio::pin!(chunks);
+    while let Some(chunk) = chunks.next().await {
+        output.write_all(&chunk?)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Archive a finished simulation straight from the database, without running the manager.
+async fn archive_simulation(args: Archive) -> anyhow::Result<()> {
+    let mut db = SimulationsDB::connect().await?;
+    let simulation = db
+        .get_simulation_via_name(&args.simulation)
+        .await
+        .with_context(|| format!("simulation `{}` not found", args.simulation))?;
+    if StatusEnum::from_string(&simulation.status) != StatusEnum::Finished {
+        bail!("simulation `{}` has not finished", args.simulation);
+    }
+    if db.get_retention(simulation.id).await?.downsampled.is_some() {
+        bail!("simulation `{}` has been downsampled", args.simulation);
+    }
+
+    let mut output = output(args.output.as_deref())?;
+    let parts = archive::archive(Arc::new(Mutex::new(db)), simulation.id);
+    tokio::pin!(parts);
+    while let Some(part) = parts.next().await {
+        output.write_all(&archive::encode(&part?))?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Import an archived simulation straight into the database, without running the manager.
+async fn import_simulation(args: Import) -> anyhow::Result<()> {
+    let bytes = std::fs::read(&args.file)
+        .with_context(|| format!("could not read {}", args.file.display()))?;
+    let mut archive =
+        archive::Archive::from_parts(archive::decode(&bytes)?).map_err(anyhow::Error::msg)?;
+    if let Some(name) = args.name {
+        archive.rename(name);
+    }
+
+    let mut db = SimulationsDB::connect().await?;
+    if db.get_simulation_via_name(archive.name()).await.is_ok() {
+        bail!("simulation `{}` already exists", archive.name());
+    }
+    archive.import(&mut db).await?;
+    info!("Imported simulation `{}`", archive.name());
+    Ok(())
+}
+
+/// Open the file a command writes to, or stdout when no file is given.
+fn output(path: Option<&Path>) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("could not create {}", path.display()))?,
+        ),
+        None => Box::new(std::io::stdout().lock()),
+    })
+}
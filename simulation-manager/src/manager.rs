@@ -1,38 +1,130 @@
-use prost_types::value::Kind;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use prost_types::{value::Kind, Value};
+use sqlx::types::chrono::{DateTime, Utc};
 use sqlx::PgPool;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, Mutex};
 use tokio_stream::StreamExt;
 use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
+use tracing::error;
 
+use crate::archive::{self, Archive};
+use crate::compare::Comparison;
 use crate::connector::SimulatorsInfo;
 use crate::database::StatusEnum::Failed;
-use crate::database::{SimulationsDB, StatusEnum};
-use proto::simulation::component_structure::ComponentStructure;
+use crate::database::{ControlEnum, ErrorPolicyEnum, SimulationsDB, StatusEnum};
+use crate::events::Event;
+use crate::export::{self, Format};
+use crate::queue::{estimate_starts, expected_duration, Waiting};
+use crate::retention;
+use crate::stop::StopConditions;
+use crate::sweep::{combinations, get_in_state, set_in_state};
+use crate::upgrade::{self, ComponentVersions};
+use crate::validation::{describe, missing_simulator, validate_state};
 use proto::simulation::simulation_manager::DeleteSimulationRequest as DeleteSimulationRequestManager;
 use proto::simulation::simulator::{IoConfigRequest, SimulatorClient};
 use proto::simulation::{
     simulation_manager::{
-        ComponentsInfo, PushSimulationRequest, SimulationData, SimulationFrame,
-        SimulationFrameRequest, SimulationId, SimulationManager, SimulatorInfo, Simulators,
+        simulation_comparison, Branch, BranchSimulationRequest, CompareSimulationsRequest,
+        ComparisonSummary, ComponentsInfo, ErrorPolicy, ExportChunk, ExportFormat,
+        ExportSimulationRequest, PinSimulationRequest, PushSimulationRequest,
+        PushSimulationSweepRequest, Queue, QueuedSimulation, SimulationArchivePart,
+        SimulationComparison, SimulationData, SimulationEvent, SimulationFrame,
+        SimulationFrameRangeRequest, SimulationFrameRequest, SimulationId, SimulationManager,
+        SimulationStatus, SimulatorInfo, Simulators, Stop, StopCondition, StorageStats,
+        StructureViolation, StructureViolations, Sweep, SweepResult, SweepSimulation, SweepSummary,
+        SweepSummaryRequest, ValidateInitialStateRequest,
     },
-    ComponentPrimitive, ComponentSpecification, Graph, State,
+    ComponentSpecification, Graph, State,
 };
 
+/// The maximum amount of frames that are read from the database at once by
+/// `get_simulation_frame_range`.
+const FRAME_RANGE_CHUNK_SIZE: usize = 64;
+
+/// The amount of recently computed frames over which the duration of a time step is averaged, to
+/// estimate when queued simulations are started.
+const STEP_DURATION_FRAMES: i64 = 1000;
+
+/// The maximum amount of simulations a sweep can consist of.
+const MAX_SWEEP_SIZE: usize = 1000;
+
+/// A simulation that is about to be queued, see `Manager::queue_simulation`.
+#[derive(Clone)]
+struct NewSimulation {
+    name: String,
+    initial_state: State,
+    timesteps: i32,
+    step_size_ms: i32,
+    simulators: Vec<String>,
+    error_policy: ErrorPolicy,
+    priority: i32,
+    not_before: Option<DateTime<Utc>>,
+    twin: Option<String>,
+    stop_conditions: Vec<StopCondition>,
+    /// The versions of the components in the initial state, components that are not included
+    /// have the version the simulators use.
+    component_versions: ComponentVersions,
+}
+
+impl NewSimulation {
+    /// Check the arguments of a pushed simulation.
+    fn from_request(simulation: PushSimulationRequest) -> Result<Self, Status> {
+        let name = simulation
+            .id
+            .ok_or(Status::invalid_argument("Invalid grpc, no id present"))?
+            .uuid;
+        let initial_state = simulation
+            .initial_state
+            .filter(|state| state.graph.is_some())
+            .ok_or(Status::invalid_argument(
+                "Invalid grpc, no initial state with a graph present",
+            ))?;
+        let selection = simulation.selection.ok_or(Status::invalid_argument(
+            "Invalid grpc, no selection present",
+        ))?;
+        let error_policy = ErrorPolicy::try_from(simulation.error_policy)
+            .map_err(|_| Status::invalid_argument("Invalid grpc, unknown error policy"))?;
+        let not_before = simulation
+            .not_before
+            .map(|not_before| SystemTime::try_from(not_before).map(DateTime::<Utc>::from))
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid grpc, invalid not_before"))?;
+        StopConditions::new(&simulation.stop_conditions)
+            .map_err(|err| Status::invalid_argument(format!("Invalid grpc, {err}")))?;
+        Ok(Self {
+            name,
+            initial_state,
+            timesteps: simulation.timesteps as i32,
+            step_size_ms: (simulation.timestep_delta * 1000.0) as i32,
+            simulators: selection.name,
+            error_policy,
+            priority: simulation.priority,
+            not_before,
+            twin: simulation.twin,
+            stop_conditions: simulation.stop_conditions,
+            component_versions: simulation.component_versions,
+        })
+    }
+}
+
 /// The Manager handles incoming requests from the frontend. It can return all known component types
 /// at a certain time, queue new simulations, return info about a simulation and return the state of
 /// a simulation at a requested timestep.
 ///
-/// The manager holds a database connection, a vector of all known simulators and a sender for the
-/// asynchronous channel created in main.rs. The sender is used to notify the runner that a new
-/// simulation has been queued.
+/// The manager holds a database connection and a vector of all known simulators. The events are
+/// used to follow the progress of simulations.
 pub struct Manager {
     simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
     db: Arc<Mutex<SimulationsDB>>,
-    notif_sender: mpsc::Sender<()>,
+    events: broadcast::Sender<Event>,
+    /// The amount of simulations the runner computes at the same time, used to estimate when
+    /// queued simulations are started.
+    workers: usize,
 }
 
 impl Manager {
@@ -40,135 +132,288 @@ impl Manager {
     pub async fn new(
         pool: PgPool,
         simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
-        notif_sender: mpsc::Sender<()>,
+        events: broadcast::Sender<Event>,
     ) -> Self {
         let db: SimulationsDB = SimulationsDB::from_pg_pool(pool).await.unwrap();
         let db: Arc<Mutex<SimulationsDB>> = Arc::new(Mutex::new(db));
         Self {
             simulators,
             db,
-            notif_sender,
+            events,
+            workers: 1,
         }
     }
 
-    async fn get_selected_simulators(
+    /// Set the amount of simulations the runner computes at the same time.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Get the id of a simulation using its name.
+    async fn get_simulation_id(&self, name: &str) -> Result<i32, Status> {
+        Ok(self
+            .db
+            .lock()
+            .await
+            .get_simulation_via_name(name)
+            .await
+            .map_err(|err| Status::not_found(format!("simulation `{name}` not found: {err}")))?
+            .id)
+    }
+
+    /// Change the status of a simulation from `from` to `to`. Returns whether the simulation had
+    /// status `from`.
+    async fn change_status(
+        &self,
+        simulation_id: i32,
+        from: StatusEnum,
+        to: StatusEnum,
+    ) -> Result<bool, Status> {
+        self.db
+            .lock()
+            .await
+            .update_status_from(simulation_id, from, to)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+
+    /// Get the id and the current progress of a simulation using its name.
+    async fn get_progress(
+        db: &Mutex<SimulationsDB>,
+        name: &str,
+    ) -> Result<(i32, SimulationEvent), Status> {
+        let mut db = db.lock().await;
+        let simulation = db
+            .get_simulation_via_name(name)
+            .await
+            .map_err(|err| Status::not_found(format!("simulation `{name}` not found: {err}")))?;
+        let timestep = db
+            .get_max_timestep(simulation.id)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        let progress = SimulationEvent {
+            id: Some(SimulationId {
+                uuid: name.to_string(),
+            }),
+            frame_nr: timestep as u64,
+            status: StatusEnum::to_simulation_status(StatusEnum::from_string(&simulation.status))
+                .into(),
+            status_info: simulation.status_info,
+            step_duration: None,
+        };
+        Ok((simulation.id, progress))
+    }
+
+    /// Ask the worker computing a simulation to stop. Only works for simulations that are being
+    /// computed. The request is stored in the database, so the worker can be on any manager.
+    async fn control_running(
         &self,
         simulation_id: i32,
-    ) -> anyhow::Result<Vec<SimulatorClient<Channel>>> {
-        let simulators = self.simulators.lock().await;
+        control: ControlEnum,
+    ) -> Result<(), Status> {
         let mut db = self.db.lock().await;
+        if db
+            .set_control(simulation_id, control)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+        {
+            return Ok(());
+        }
+        let status = db
+            .get_status(simulation_id)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        Err(Status::failed_precondition(format!(
+            "simulation has status `{}`",
+            StatusEnum::to_string(status)
+        )))
+    }
 
-        // get only selected simulators
-        let selection = db
-            .get_selected_simulators(simulation_id)
-            .await?
-            .unwrap_or(Vec::new());
-        let selected = simulators
+    /// Get the connected simulators with the given names.
+    async fn get_simulators_by_name(&self, names: &[String]) -> Vec<SimulatorClient<Channel>> {
+        self.simulators
+            .lock()
+            .await
             .iter()
-            .filter(|sim| selection.contains(&sim.name))
+            .filter(|sim| names.contains(&sim.name))
             .map(|sim| sim.simulator.clone())
-            .collect::<Vec<_>>();
-        drop(simulators);
-        drop(db);
-        Ok(selected)
+            .collect()
     }
 
-    async fn get_selected_components(&self, id: i32) -> Result<ComponentsInfo, Status> {
-        let mut components: ComponentsInfo = ComponentsInfo::default();
-
-        // clone simulator vec and drop mutex
-        let mut simulators = self
-            .get_selected_simulators(id)
+    /// Leave out the time steps that were dropped when the simulation was downsampled. `last` is
+    /// the last time step of the simulation, which is always kept.
+    async fn stored_time_steps(
+        db: &Mutex<SimulationsDB>,
+        simulation_id: i32,
+        last: i32,
+        time_steps: Vec<i32>,
+    ) -> Result<Vec<i32>, Status> {
+        let retention = db
+            .lock()
+            .await
+            .get_retention(simulation_id)
             .await
             .map_err(|err| Status::internal(err.to_string()))?;
+        Ok(match retention.downsampled {
+            Some(stride) => time_steps
+                .into_iter()
+                .filter(|time_step| retention::is_kept(*time_step, last, stride as u32))
+                .collect(),
+            None => time_steps,
+        })
+    }
+
+    /// Commit the transaction of `db` when `result` is `Ok`, and roll it back otherwise. The
+    /// connection is shared by all requests, so a transaction is never left open.
+    async fn end_transaction<T>(
+        db: &mut SimulationsDB,
+        result: Result<T, Status>,
+    ) -> Result<T, Status> {
+        match result {
+            Err(status) => {
+                if let Err(err) = db.rollback().await {
+                    error!("Could not roll back the transaction: {err:?}");
+                }
+                Err(status)
+            }
+            result => {
+                db.commit().await.map_err(|err| {
+                    Status::internal(format!(
+                        "could not commit the transaction with message: {:?}",
+                        err.to_string()
+                    ))
+                })?;
+                result
+            }
+        }
+    }
+
+    /// The status info of a simulation that failed because its initial state has components that
+    /// do not match the structures expected by the simulators.
+    fn violations_info(violations: &[StructureViolation]) -> String {
+        format!(
+            "Provided component structures do not match structures expected by simulators: {}",
+            violations
+                .iter()
+                .map(describe)
+                .collect::<Vec<_>>()
+                .join("; ")
+        )
+    }
 
+    /// Get the components of the given simulators. The simulators have to use the same version of
+    /// every component.
+    async fn get_components_of(
+        mut simulators: Vec<SimulatorClient<Channel>>,
+    ) -> Result<ComponentsInfo, Status> {
+        let mut components: ComponentsInfo = ComponentsInfo::default();
         for server in simulators.iter_mut() {
             let request = tonic::Request::new(IoConfigRequest {});
             let response = server.get_io_config(request).await?.into_inner();
-            let response_components = response.components;
-            for (key, value) in response_components {
-                components.components.insert(key, value);
-            }
+            upgrade::merge_components(&mut components.components, response.components)
+                .map_err(Status::failed_precondition)?;
         }
         Ok(components)
     }
 
-    /// Unpacks ComponentStructure from ComponentSpecification given name and map of specifications for all components
-    fn get_component_structure(
+    /// Upgrade the components of the initial state of `simulation` that are older than the
+    /// versions in `components`, using the selected simulators.
+    async fn upgrade_initial_state(
+        &self,
+        simulation: &mut NewSimulation,
         components: &HashMap<String, ComponentSpecification>,
-        component_name: &String,
-    ) -> Option<ComponentStructure> {
-        let component_spec = match components.get(component_name) {
-            None => return None,
-            Some(component) => component,
-        };
-        let component_structure = component_spec.clone().structure?.component_structure?;
-        Some(component_structure)
+    ) -> Result<(), Status> {
+        let mut stored = upgrade::versions_of(components);
+        stored.extend(simulation.component_versions.clone());
+        let outdated =
+            upgrade::outdated(&stored, components).map_err(Status::failed_precondition)?;
+        upgrade::upgrade_state(
+            &mut simulation.initial_state,
+            &outdated,
+            self.get_simulators_by_name(&simulation.simulators).await,
+        )
+        .await
+        .map_err(|err| {
+            Status::failed_precondition(format!("could not upgrade the initial state: {err}"))
+        })
     }
 
-    /// Compares simulation.proto ComponentStructure to protobuf Value Kind
-    fn compare_component_structure(expected: ComponentStructure, actual: Kind) -> bool {
-        match (actual, expected) {
-            (Kind::ListValue(a), ComponentStructure::List(e)) => {
-                for value in a.values {
-                    let Some(actual_structure) = value.kind else {
-                        return false;
-                    };
-                    let Some(expected_structure) = e.component_structure.clone() else {
-                        return false;
-                    };
-                    if !Manager::compare_component_structure(expected_structure, actual_structure) {
-                        return false;
-                    }
-                }
-                true
-            }
-            (Kind::StructValue(a), ComponentStructure::Struct(e)) => {
-                for (item_name, actual_item_value) in a.fields {
-                    let Some(actual_item_kind) = actual_item_value.kind else {
-                        return false;
-                    };
-                    let Some(expected_item_value) = e.data.get(&item_name) else {
-                        return false;
-                    };
-                    let Some(expected_item_structure) =
-                        expected_item_value.clone().component_structure
-                    else {
-                        return false;
-                    };
-                    if !Manager::compare_component_structure(
-                        expected_item_structure,
-                        actual_item_kind,
-                    ) {
-                        return false;
-                    }
-                }
-                true
-            }
-            (a, ComponentStructure::Primitive(e)) => {
-                let Ok(e): Result<ComponentPrimitive, _> = (e).try_into() else {
-                    return false;
-                };
-                matches!(
-                    (a, e),
-                    (Kind::NumberValue(_), ComponentPrimitive::F32)
-                        | (Kind::NumberValue(_), ComponentPrimitive::F64)
-                        | (Kind::NumberValue(_), ComponentPrimitive::U8)
-                        | (Kind::NumberValue(_), ComponentPrimitive::U16)
-                        | (Kind::NumberValue(_), ComponentPrimitive::U32)
-                        | (Kind::NumberValue(_), ComponentPrimitive::U64)
-                        | (Kind::NumberValue(_), ComponentPrimitive::U128)
-                        | (Kind::NumberValue(_), ComponentPrimitive::I8)
-                        | (Kind::NumberValue(_), ComponentPrimitive::I16)
-                        | (Kind::NumberValue(_), ComponentPrimitive::I32)
-                        | (Kind::NumberValue(_), ComponentPrimitive::I64)
-                        | (Kind::NumberValue(_), ComponentPrimitive::I128)
-                        | (Kind::StringValue(_), ComponentPrimitive::String)
-                        | (Kind::BoolValue(_), ComponentPrimitive::Bool)
-                )
-            }
-            _ => false,
+    /// Add a simulation to the database, using the transaction of `db`, and return its id.
+    ///
+    /// The simulation is set to Failed when its initial state has components that do not match
+    /// the structures in `components`, otherwise the initial state is stored at timestep 0. The
+    /// initial state should already be upgraded to the versions in `components`.
+    async fn queue_simulation(
+        db: &mut SimulationsDB,
+        simulation: &NewSimulation,
+        components: &HashMap<String, ComponentSpecification>,
+    ) -> Result<i32, Status> {
+        let violations = validate_state(components, &simulation.initial_state);
+
+        let mut status = StatusEnum::Pending;
+        if simulation.timesteps == 0 {
+            status = StatusEnum::Finished;
+        }
+        //add a new simulation to the simulation table
+        let simulation_index = db
+            .add_simulation(
+                &simulation.name,
+                simulation.step_size_ms,
+                simulation.timesteps,
+                status,
+                simulation.simulators.clone(),
+            )
+            .await
+            .map_err(|err| {
+                Status::internal(format!(
+                    "queue_simulation could not insert the simulation with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
+        db.set_error_policy(
+            simulation_index,
+            ErrorPolicyEnum::from_error_policy(simulation.error_policy),
+        )
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?;
+        db.set_schedule(simulation_index, simulation.priority, simulation.not_before)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        if let Some(twin) = &simulation.twin {
+            db.set_twin(simulation_index, twin)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?;
+        }
+        db.set_stop_conditions(simulation_index, &simulation.stop_conditions)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        let mut versions = simulation.component_versions.clone();
+        versions.extend(upgrade::versions_of(components));
+        db.set_component_versions(simulation_index, &versions)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+
+        if violations.is_empty() {
+            // Store graph in database
+            db.add_frame(simulation_index, 0, &simulation.initial_state, None)
+                .await
+                .map_err(|err| {
+                    Status::internal(format!(
+                        "queue_simulation could not add the initial state with message: {:?}",
+                        err.to_string()
+                    ))
+                })?;
+        } else {
+            db.update_status(
+                simulation_index,
+                Failed,
+                Some(&Manager::violations_info(&violations)),
+            )
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
         }
+        Ok(simulation_index)
     }
 }
 
@@ -216,57 +461,104 @@ impl SimulationManager for Manager {
         })?;
 
         // Delete the simulation
-        db.delete_simulation_via_name(&simulation_id)
+        let result = db
+            .delete_simulation_via_name(&simulation_id)
             .await
             .map_err(|err| {
                 Status::internal(format!(
                     "delete_simulation could not delete the simulation with message: {:?}",
                     err.to_string()
                 ))
-            })?;
+            });
+        Manager::end_transaction(&mut db, result).await?;
+        Ok(Response::new(()))
+    }
 
-        // Commit transaction
-        db.commit().await.map_err(|err| {
-            Status::internal(format!(
-                "delete_simulation could not commit the deletion with message: {:?}",
-                err.to_string()
-            ))
-        })?;
+    /// Pin or unpin a simulation
+    ///
+    /// Pinned simulations are kept by the maintenance task when they expire, unless its retention
+    /// policy does not keep pinned simulations.
+    async fn pin_simulation(
+        &self,
+        request: Request<PinSimulationRequest>,
+    ) -> Result<Response<()>, Status> {
+        let request = request.into_inner();
+        let name = request
+            .id
+            .ok_or(Status::invalid_argument("Invalid grpc, no id present"))?
+            .uuid;
+        let simulation_id = self.get_simulation_id(&name).await?;
+        self.db
+            .lock()
+            .await
+            .set_pinned(simulation_id, request.pinned)
+            .await
+            .map_err(|err| {
+                Status::internal(format!(
+                    "pin_simulation could not pin the simulation with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
         Ok(Response::new(()))
     }
 
+    /// Return how much data is stored and how much the maintenance task has reclaimed
+    ///
+    /// The totals of the maintenance task are stored in the database, so they include what was
+    /// reclaimed by every manager since the database was created.
+    async fn get_storage_stats(&self, _: Request<()>) -> Result<Response<StorageStats>, Status> {
+        let stats = self
+            .db
+            .lock()
+            .await
+            .get_storage_stats()
+            .await
+            .map_err(|err| {
+                Status::internal(format!(
+                    "get_storage_stats could not count the rows with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
+        Ok(Response::new(StorageStats {
+            simulations: stats.simulations as u64,
+            frames: stats.frames as u64,
+            rows: stats.rows as u64,
+            deleted_simulations: stats.deleted_simulations as u64,
+            downsampled_simulations: stats.downsampled_simulations as u64,
+            reclaimed_rows: stats.reclaimed_rows as u64,
+            last_maintenance: stats
+                .last_run
+                .map(|last_run| SystemTime::from(last_run).into()),
+        }))
+    }
+
     /// Queue a new simulation
     ///
-    /// The manager starts by decomposing the request into the needed components to populate the database
-    /// with the simulations initial state.
-    /// It then proceeds by adding the simulation to the database. Then the manager checks if the
-    /// needed components are present in the database and if their values have the correct structure.
-    /// If this is not the case the simulation will automatically be set to Failed.
-    /// If all the required components are present and valid the manager will queue the simulation.
-    /// It then places every needed component into the database at timestep 0.
-    /// This is all done using a transaction so that it can be committed in one go.
-    /// After this the manager will use the asynchronous channel to notify the runner that a new
-    /// simulation has been queued.
+    /// The manager starts by upgrading the components of the initial state that have an older
+    /// version than the selected simulators use, and checking whether they have the structure
+    /// expected by the selected simulators.
+    /// It then proceeds by adding the simulation to the database, along with its priority and the
+    /// time before which it should not be started.
+    /// If the initial state has a component with the wrong structure the simulation will
+    /// automatically be set to Failed, with every violation in the status info.
+    /// Otherwise the manager places every component of the initial state into the database at
+    /// timestep 0.
+    /// This is all done using a transaction so that it can be committed in one go. Once it is
+    /// committed, the database notifies the runners that a new simulation has been queued.
     async fn push_simulation(
         &self,
         request: Request<PushSimulationRequest>,
     ) -> Result<Response<()>, Status> {
-        let simulation = request.into_inner();
-        let simulation_id = simulation.id.expect("Request should provide an ID.").uuid;
-        let initial_state = simulation
-            .initial_state
-            .expect("Request should have an initial state.");
-        let graph = initial_state
-            .graph
-            .expect("Initial state should have a graph.");
-        let nodes = graph.nodes;
-        let edges = graph.edge;
-
-        let global = initial_state.global_components;
-        let selection = simulation.selection.ok_or(Status::invalid_argument(
-            "Invalid grpc, no selection present",
-        ))?;
-        let simulators = selection.name;
+        let mut simulation = NewSimulation::from_request(request.into_inner())?;
+
+        // check if all components have valid structure
+        let components =
+            Manager::get_components_of(self.get_simulators_by_name(&simulation.simulators).await)
+                .await?
+                .components;
+        self.upgrade_initial_state(&mut simulation, &components)
+            .await?;
+
         // Start transaction
         let mut db = self.db.lock().await;
         db.begin_transaction().await.map_err(|err| {
@@ -276,218 +568,570 @@ impl SimulationManager for Manager {
             ))
         })?;
 
-        let mut status = StatusEnum::Pending;
-        if simulation.timesteps == 0 {
-            status = StatusEnum::Finished;
-        }
-        //add a new simulation to the simulation table
-        let simulation_index = db
-            .add_simulation(
-                simulation_id.as_str(),
-                (simulation.timestep_delta * 1000.0) as i32,
-                simulation.timesteps as i32,
-                status,
-                simulators,
-            )
+        let result = Manager::queue_simulation(&mut db, &simulation, &components).await;
+        Manager::end_transaction(&mut db, result).await?;
+        Ok(Response::new(()))
+    }
+
+    /// Queue a new simulation that starts from a frame of an existing simulation
+    ///
+    /// The frame of the parent is copied inside the database as the initial state of the new
+    /// simulation, after which the overrides are applied to it. Components of the frame that have
+    /// an older version than the selected simulators use are upgraded before the overrides. The
+    /// new simulation uses the same timestep delta as its parent, and the simulators of its parent
+    /// unless others are selected.
+    /// The initial state is checked in the same way as in `push_simulation`, and the simulation
+    /// is set to Failed if it does not match the structures expected by the simulators.
+    async fn branch_simulation(
+        &self,
+        request: Request<BranchSimulationRequest>,
+    ) -> Result<Response<()>, Status> {
+        let request = request.into_inner();
+        let name = request
+            .id
+            .ok_or(Status::invalid_argument("Invalid grpc, no id present"))?
+            .uuid;
+        let parent_name = request
+            .parent
+            .ok_or(Status::invalid_argument("Invalid grpc, no parent present"))?
+            .uuid;
+        let frame_nr = request.frame_nr as i32;
+        let overrides = request.overrides.unwrap_or_default();
+        let error_policy = ErrorPolicy::try_from(request.error_policy)
+            .map_err(|_| Status::invalid_argument("Invalid grpc, unknown error policy"))?;
+        let not_before = request
+            .not_before
+            .map(|not_before| SystemTime::try_from(not_before).map(DateTime::<Utc>::from))
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid grpc, invalid not_before"))?;
+        StopConditions::new(&request.stop_conditions)
+            .map_err(|err| Status::invalid_argument(format!("Invalid grpc, {err}")))?;
+
+        let mut db = self.db.lock().await;
+        let parent = db
+            .get_simulation_via_name(&parent_name)
             .await
             .map_err(|err| {
-                Status::internal(format!(
-                    "push_simulation could not insert the simulation with message: {:?}",
-                    err.to_string()
-                ))
+                Status::not_found(format!("simulation `{parent_name}` not found: {err}"))
             })?;
-        db.commit()
+        let computed = db.get_max_timestep(parent.id).await.map_err(|err| {
+            Status::internal(format!(
+                "branch_simulation could not fetch the max timestep with message: {:?}",
+                err.to_string()
+            ))
+        })?;
+        if frame_nr > computed {
+            return Err(Status::out_of_range(format!(
+                "frame {frame_nr} of simulation `{parent_name}` has not been computed"
+            )));
+        }
+        let stored = db
+            .has_frame(parent.id, frame_nr)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        if !stored {
+            return Err(Status::not_found(format!(
+                "frame {frame_nr} of simulation `{parent_name}` was dropped when the simulation was downsampled"
+            )));
+        }
+        let simulators = match request.selection {
+            Some(selection) => selection.name,
+            None => db
+                .get_selected_simulators(parent.id)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?
+                .unwrap_or_default(),
+        };
+        let mut versions = db
+            .get_component_versions(parent.id)
             .await
             .map_err(|err| Status::internal(err.to_string()))?;
         drop(db);
 
-        // check if all components have valid structure
-        let components = self
-            .get_selected_components(simulation_index)
+        let components = Manager::get_components_of(self.get_simulators_by_name(&simulators).await)
             .await?
             .components;
-        db = self.db.lock().await;
-        db.begin_transaction()
-            .await
-            .map_err(|err| Status::internal(err.to_string()))?;
-
-        // check if all required node components are valid
-        for node in &nodes {
-            for (component_name, component_value) in &node.components {
-                // get expected component structure from io_config
-                let expected_structure =
-                    match Manager::get_component_structure(&components, component_name) {
-                        None => {
-                            break;
-                        }
-                        Some(s) => s,
-                    };
-
-                // get actual structure
-                let actual_structure = match component_value.clone().kind {
-                    None => {
-                        db.update_status(
-                            simulation_index,
-                            Failed,
-                            "Component value has no Kind".into(),
-                        )
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()))?;
-                        db.commit()
-                            .await
-                            .map_err(|err| Status::internal(err.to_string()))?;
-                        return Ok(Response::new(()));
-                    }
-                    Some(s) => s,
-                };
 
-                // compare
-                if !Manager::compare_component_structure(expected_structure, actual_structure) {
-                    db.update_status(simulation_index, Failed, "Provided node component structures do not match structures expected by simulators".into()).await.map_err(|err| Status::internal(err.to_string()))?;
-                    db.commit()
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()))?;
-                    return Ok(Response::new(()));
-                }
-            }
+        // upgrade the components of the frame that are older than the simulators expect
+        let outdated =
+            upgrade::outdated(&versions, &components).map_err(Status::failed_precondition)?;
+        let mut upgraded = None;
+        if !outdated.is_empty() {
+            let mut state = self
+                .db
+                .lock()
+                .await
+                .get_frames(parent.id, &[frame_nr], None)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?
+                .remove(&frame_nr)
+                .unwrap_or_default();
+            upgrade::upgrade_state(
+                &mut state,
+                &outdated,
+                self.get_simulators_by_name(&simulators).await,
+            )
+            .await
+            .map_err(|err| {
+                Status::failed_precondition(format!(
+                    "could not upgrade frame {frame_nr} of simulation `{parent_name}`: {err}"
+                ))
+            })?;
+            upgraded = Some(state);
         }
+        versions.extend(upgrade::versions_of(&components));
 
-        // check if all required edge components are valid
-        for edge in &edges {
-            // get edge component name and value
-            let component_name = &edge.component_type;
-            let component_value = &edge.component_data;
-
-            // get expected structure
-            let expected_structure =
-                match Manager::get_component_structure(&components, component_name) {
-                    None => {
-                        continue;
-                    }
-                    Some(s) => s,
-                };
-
-            // get actual structure
-            let actual_value = match component_value.clone() {
-                None => {
-                    db.update_status(simulation_index, Failed, "Component has no value".into())
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()))?;
-                    db.commit()
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()))?;
-                    return Ok(Response::new(()));
-                }
-                Some(s) => s,
-            };
-
-            let actual_structure = match actual_value.clone().kind {
-                None => {
-                    db.update_status(
-                        simulation_index,
-                        Failed,
-                        "Component value has no Kind".into(),
-                    )
+        // Start transaction
+        let mut db = self.db.lock().await;
+        db.begin_transaction().await.map_err(|err| {
+            Status::internal(format!(
+                "branch_simulation could not begin transaction with message: {:?}",
+                err.to_string()
+            ))
+        })?;
+        // The transaction is rolled back on every error, so the shared connection can be used again
+        let result = async {
+            let mut status = StatusEnum::Pending;
+            if request.timesteps == 0 {
+                status = StatusEnum::Finished;
+            }
+            let simulation_index = db
+                .add_simulation(
+                    &name,
+                    parent.step_size_ms,
+                    request.timesteps as i32,
+                    status,
+                    simulators,
+                )
+                .await
+                .map_err(|err| {
+                    Status::internal(format!(
+                        "branch_simulation could not insert the simulation with message: {:?}",
+                        err.to_string()
+                    ))
+                })?;
+            db.set_error_policy(
+                simulation_index,
+                ErrorPolicyEnum::from_error_policy(error_policy),
+            )
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+            db.set_schedule(simulation_index, request.priority, not_before)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?;
+            db.set_parent(simulation_index, parent.id, frame_nr)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?;
+            if let Some(twin) = &request.twin {
+                db.set_twin(simulation_index, twin)
                     .await
                     .map_err(|err| Status::internal(err.to_string()))?;
-                    db.commit()
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()))?;
-                    return Ok(Response::new(()));
-                }
-                Some(s) => s,
-            };
+            }
+            db.set_stop_conditions(simulation_index, &request.stop_conditions)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?;
+            db.set_component_versions(simulation_index, &versions)
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?;
 
-            // compare
-            if !Manager::compare_component_structure(expected_structure, actual_structure) {
-                db.update_status(simulation_index, Failed, "Provided edge component structures do not match structures expected by simulators".into()).await.map_err(|err| Status::internal(err.to_string()))?;
-                db.commit()
+            // Copy the frame and apply the overrides
+            db.copy_frame(parent.id, frame_nr, simulation_index)
+                .await
+                .map_err(|err| {
+                    Status::internal(format!(
+                        "branch_simulation could not copy the frame with message: {:?}",
+                        err.to_string()
+                    ))
+                })?;
+            if let Some(state) = &upgraded {
+                upgrade::store_upgraded(&mut db, simulation_index, state, &outdated)
                     .await
                     .map_err(|err| Status::internal(err.to_string()))?;
-                return Ok(Response::new(()));
             }
-        }
-
-        // check if all global components are valid
-        for (component_name, component_value) in &global.clone() {
-            // get expected structure
-            let expected_structure =
-                match Manager::get_component_structure(&components, component_name) {
-                    None => {
-                        continue;
-                    }
-                    Some(s) => s,
-                };
-
-            // get actual structure
-            let actual_structure = match component_value.clone().kind {
-                None => {
-                    db.update_status(
-                        simulation_index,
-                        Failed,
-                        "Component value has no Kind".into(),
-                    )
+            let mut missing = Vec::new();
+            for (node_id, node) in overrides.nodes {
+                let found = db
+                    .override_node_components(simulation_index, 0, node_id as i32, node.components)
                     .await
                     .map_err(|err| Status::internal(err.to_string()))?;
-                    db.commit()
-                        .await
-                        .map_err(|err| Status::internal(err.to_string()))?;
-                    return Ok(Response::new(()));
+                if !found {
+                    missing.push(format!("node {node_id}"));
                 }
-                Some(s) => s,
-            };
-
-            // compare
-            if !Manager::compare_component_structure(expected_structure, actual_structure) {
-                db.update_status(simulation_index, Failed, "Provided global component structures do not match structures expected by simulators".into()).await.map_err(|err| Status::internal(err.to_string()))?;
-                db.commit()
+            }
+            for (edge_id, value) in overrides.edges {
+                let found = db
+                    .override_edge_component(simulation_index, 0, edge_id as i32, value)
                     .await
                     .map_err(|err| Status::internal(err.to_string()))?;
-                return Ok(Response::new(()));
+                if !found {
+                    missing.push(format!("edge {edge_id}"));
+                }
+            }
+            for (name, value) in overrides.global_components {
+                db.override_global_component(simulation_index, 0, &name, value)
+                    .await
+                    .map_err(|err| Status::internal(err.to_string()))?;
+            }
+            if !missing.is_empty() {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid grpc, overrides for {} that are not part of frame {frame_nr}",
+                    missing.join(", ")
+                )));
             }
-        }
 
-        // Store graph in database
-        for node in nodes {
-            db.add_node(node, simulation_index, 0)
+            // check if all components have valid structure
+            let initial_state = db
+                .get_frames(simulation_index, &[0], None)
                 .await
-                .map_err(|err| {
-                    Status::internal(format!(
-                        "push_simulation could not add the nodes with message: {:?}",
-                        err.to_string()
-                    ))
-                })?;
-        }
-        for edge in edges {
-            db.add_edge(edge, simulation_index, 0)
+                .map_err(|err| Status::internal(err.to_string()))?
+                .remove(&0)
+                .unwrap_or_default();
+            let violations = validate_state(&components, &initial_state);
+            if !violations.is_empty() {
+                db.update_status(
+                    simulation_index,
+                    Failed,
+                    Some(&Manager::violations_info(&violations)),
+                )
                 .await
-                .map_err(|err| {
-                    Status::internal(format!(
-                        "push_simulation could not add the edges with message: {:?}",
-                        err.to_string()
-                    ))
-                })?;
+                .map_err(|err| Status::internal(err.to_string()))?;
+            }
+            Ok::<(), Status>(())
+        }
+        .await;
+        Manager::end_transaction(&mut db, result).await?;
+        Ok(Response::new(()))
+    }
+
+    /// Queue a simulation for every combination of a set of parameter values
+    ///
+    /// Every simulation of the sweep starts from the base initial state, with the value of every
+    /// axis replaced by one of its values. The simulations are queued in the same way as in
+    /// `push_simulation`, and are all added in one transaction along with the sweep. A simulation
+    /// whose initial state does not match the structures expected by the simulators is set to
+    /// Failed, but stays part of the sweep.
+    async fn push_simulation_sweep(
+        &self,
+        request: Request<PushSimulationSweepRequest>,
+    ) -> Result<Response<Sweep>, Status> {
+        let request = request.into_inner();
+        let sweep_name = request
+            .id
+            .ok_or(Status::invalid_argument("Invalid grpc, no id present"))?
+            .uuid;
+        let base = request.base.ok_or(Status::invalid_argument(
+            "Invalid grpc, no base simulation present",
+        ))?;
+        let mut base = NewSimulation::from_request(PushSimulationRequest {
+            id: Some(SimulationId {
+                uuid: sweep_name.clone(),
+            }),
+            ..base
+        })?;
+        let paths = request
+            .axes
+            .iter()
+            .map(|axis| {
+                axis.path.clone().ok_or(Status::invalid_argument(
+                    "Invalid grpc, axis without a path",
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let sizes: Vec<usize> = request.axes.iter().map(|axis| axis.values.len()).collect();
+        if sizes.contains(&0) {
+            return Err(Status::invalid_argument(
+                "Invalid grpc, axis without values",
+            ));
+        }
+        let size = sizes
+            .iter()
+            .try_fold(1usize, |size, &len| size.checked_mul(len))
+            .filter(|size| *size <= MAX_SWEEP_SIZE)
+            .ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "Invalid grpc, the sweep consists of more than {MAX_SWEEP_SIZE} simulations, which is not allowed"
+                ))
+            })?;
+
+        // check if all components have valid structure
+        let components =
+            Manager::get_components_of(self.get_simulators_by_name(&base.simulators).await)
+                .await?
+                .components;
+        self.upgrade_initial_state(&mut base, &components).await?;
+
+        // The names are checked before the transaction, so a sweep that can not be stored is
+        // rejected with a clear error.
+        let mut db = self.db.lock().await;
+        let exists = db
+            .has_sweep(&sweep_name)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        if exists {
+            return Err(Status::already_exists(format!(
+                "sweep `{sweep_name}` already exists"
+            )));
+        }
+        let names: Vec<String> = (0..size)
+            .map(|index| format!("{sweep_name}-{index}"))
+            .collect();
+        let existing = db
+            .existing_simulations(&names)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        if !existing.is_empty() {
+            return Err(Status::already_exists(format!(
+                "simulations of the sweep already exist: {}",
+                existing.join(", ")
+            )));
+        }
+
+        // Start transaction
+        db.begin_transaction().await.map_err(|err| {
+            Status::internal(format!(
+                "push_simulation_sweep could not begin transaction with message: {:?}",
+                err.to_string()
+            ))
+        })?;
+        // The transaction is rolled back on every error, so the shared connection can be used again
+        let result = async {
+            let sweep_index = db.add_sweep(&sweep_name).await.map_err(|err| {
+                Status::internal(format!(
+                    "push_simulation_sweep could not insert the sweep with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
+
+            let mut simulations = Vec::with_capacity(size);
+            for (index, combination) in combinations(&sizes).into_iter().enumerate() {
+                let mut simulation = base.clone();
+                simulation.name = format!("{sweep_name}-{index}");
+                let parameters: Vec<Value> = request
+                    .axes
+                    .iter()
+                    .zip(combination)
+                    .map(|(axis, value)| axis.values[value].clone())
+                    .collect();
+                for (axis, (path, value)) in paths.iter().zip(&parameters).enumerate() {
+                    if let Err(err) =
+                        set_in_state(&mut simulation.initial_state, path, value.clone())
+                    {
+                        return Err(Status::invalid_argument(format!(
+                            "Invalid grpc, axis {axis} can not be set in `{}`: {err}",
+                            simulation.name
+                        )));
+                    }
+                }
+
+                let simulation_index =
+                    Manager::queue_simulation(&mut db, &simulation, &components).await?;
+                db.add_to_sweep(simulation_index, sweep_index, parameters.clone())
+                    .await
+                    .map_err(|err| Status::internal(err.to_string()))?;
+                simulations.push(SweepSimulation {
+                    id: Some(SimulationId {
+                        uuid: simulation.name,
+                    }),
+                    parameters,
+                });
+            }
+            Ok(simulations)
         }
-        for (key, value) in global {
-            db.add_global_component(&key, value, simulation_index, 0)
+        .await;
+        let simulations = Manager::end_transaction(&mut db, result).await?;
+        Ok(Response::new(Sweep {
+            id: Some(SimulationId { uuid: sweep_name }),
+            simulations,
+        }))
+    }
+
+    /// Collect values from the last frame of every simulation of a sweep
+    ///
+    /// Fails when a simulation of the sweep is still pending, computing or paused. The last frame
+    /// of a simulation that failed or was cancelled is used as well, a simulation that failed
+    /// before computing any frame has null values for all metrics.
+    async fn get_sweep_summary(
+        &self,
+        request: Request<SweepSummaryRequest>,
+    ) -> Result<Response<SweepSummary>, Status> {
+        let request = request.into_inner();
+        let sweep_name = request
+            .id
+            .ok_or(Status::invalid_argument("Invalid grpc, no id present"))?
+            .uuid;
+        let components: Vec<String> = request
+            .metrics
+            .iter()
+            .map(|metric| metric.component.clone())
+            .collect();
+
+        let mut db = self.db.lock().await;
+        let sweep_index = db
+            .get_sweep_via_name(&sweep_name)
+            .await
+            .map_err(|err| Status::not_found(format!("sweep `{sweep_name}` not found: {err}")))?;
+        let simulations = db.get_sweep_simulations(sweep_index).await.map_err(|err| {
+            Status::internal(format!(
+                "get_sweep_summary could not fetch the simulations with message: {:?}",
+                err.to_string()
+            ))
+        })?;
+        let unfinished = simulations
+            .iter()
+            .filter(|simulation| {
+                matches!(
+                    simulation.status,
+                    StatusEnum::Pending | StatusEnum::Computing | StatusEnum::Paused
+                )
+            })
+            .count();
+        if unfinished > 0 {
+            return Err(Status::failed_precondition(format!(
+                "{unfinished} of the {} simulations of sweep `{sweep_name}` have not finished",
+                simulations.len()
+            )));
+        }
+
+        let mut results = Vec::with_capacity(simulations.len());
+        for simulation in simulations {
+            let frame_nr = db.get_max_timestep(simulation.id).await.map_err(|err| {
+                Status::internal(format!(
+                    "get_sweep_summary could not fetch the max timestep with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
+            let frame = db
+                .get_frames(simulation.id, &[frame_nr], Some(&components))
                 .await
                 .map_err(|err| {
                     Status::internal(format!(
-                        "push_simulation could not add global variable {} with message: {:?}",
-                        key,
+                        "get_sweep_summary could not fetch the last frame with message: {:?}",
                         err.to_string()
                     ))
-                })?;
+                })?
+                .remove(&frame_nr)
+                .unwrap_or_default();
+            results.push(SweepResult {
+                id: Some(SimulationId {
+                    uuid: simulation.name,
+                }),
+                parameters: simulation.parameters,
+                status: StatusEnum::to_simulation_status(simulation.status).into(),
+                frame_nr: frame_nr as u64,
+                metrics: request
+                    .metrics
+                    .iter()
+                    .map(|metric| {
+                        get_in_state(&frame, metric).cloned().unwrap_or(Value {
+                            kind: Some(Kind::NullValue(0)),
+                        })
+                    })
+                    .collect(),
+            });
         }
-        // Commit transaction
-        db.commit().await.map_err(|err| {
+        Ok(Response::new(SweepSummary {
+            id: Some(SimulationId { uuid: sweep_name }),
+            results,
+        }))
+    }
+
+    /// Check an initial state without queueing a simulation
+    ///
+    /// The state is checked against the components of the selected simulators in the same way as
+    /// in `push_simulation`. Every value that does not match its expected structure is returned,
+    /// after the selected simulators that are not connected.
+    async fn validate_initial_state(
+        &self,
+        request: Request<ValidateInitialStateRequest>,
+    ) -> Result<Response<StructureViolations>, Status> {
+        let request = request.into_inner();
+        let initial_state = request.initial_state.ok_or(Status::invalid_argument(
+            "Invalid grpc, no initial state present",
+        ))?;
+        let selection = request.selection.ok_or(Status::invalid_argument(
+            "Invalid grpc, no selection present",
+        ))?;
+
+        let connected: Vec<String> = self
+            .simulators
+            .lock()
+            .await
+            .iter()
+            .map(|sim| sim.name.clone())
+            .collect();
+        let mut violations: Vec<StructureViolation> = selection
+            .name
+            .iter()
+            .filter(|name| !connected.contains(name))
+            .map(|name| missing_simulator(name))
+            .collect();
+
+        let simulators = self.get_simulators_by_name(&selection.name).await;
+        let components = Manager::get_components_of(simulators).await?.components;
+        violations.extend(validate_state(&components, &initial_state));
+        Ok(Response::new(StructureViolations { violations }))
+    }
+
+    /// List the simulations in the queue
+    ///
+    /// The pending simulations are returned in the order in which they are expected to be started,
+    /// along with an estimate of when they are started. The estimate assumes that the duration of
+    /// a time step of a queued simulation is the average over the recently computed frames, and
+    /// that the simulations are computed by as many workers as this manager has, or by as many
+    /// as are computing simulations right now if that is more.
+    async fn list_queue(&self, _request: Request<()>) -> Result<Response<Queue>, Status> {
+        let mut db = self.db.lock().await;
+        let queue = db.get_queue().await.map_err(|err| {
             Status::internal(format!(
-                "push_simulation could not commit the new simulation with message: {:?}",
+                "list_queue could not get the queue with message: {:?}",
                 err.to_string()
             ))
         })?;
-        self.notif_sender.try_send(()).ok();
-        Ok(Response::new(()))
+        let computing = db.get_computing_simulations().await.map_err(|err| {
+            Status::internal(format!(
+                "list_queue could not get the computing simulations with message: {:?}",
+                err.to_string()
+            ))
+        })?;
+        let step_duration = db
+            .get_average_step_duration(STEP_DURATION_FRAMES)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        drop(db);
+
+        let now = Utc::now();
+        let busy: Vec<f64> = computing
+            .iter()
+            .map(|s| expected_duration(s.remaining_steps, s.step_duration.or(step_duration)))
+            .collect();
+        let waiting: Vec<Waiting> = queue
+            .iter()
+            .map(|s| Waiting {
+                not_before: s.not_before.map_or(0.0, |not_before| {
+                    ((not_before - now).num_milliseconds() as f64 / 1000.0).max(0.0)
+                }),
+                duration: expected_duration(s.remaining_steps, step_duration),
+            })
+            .collect();
+
+        let simulations = estimate_starts(self.workers, &busy, &waiting)
+            .into_iter()
+            .enumerate()
+            .map(|(position, (i, start))| QueuedSimulation {
+                id: Some(SimulationId {
+                    uuid: queue[i].name.clone(),
+                }),
+                position: position as u32,
+                priority: queue[i].priority,
+                not_before: queue[i]
+                    .not_before
+                    .map(|not_before| SystemTime::from(not_before).into()),
+                estimated_start: start
+                    .is_finite()
+                    .then(|| (SystemTime::from(now) + Duration::from_secs_f64(start)).into()),
+            })
+            .collect();
+        Ok(Response::new(Queue { simulations }))
     }
 
     /// Return all relevant info about a simulation. This includes the simulations status
@@ -507,56 +1151,78 @@ impl SimulationManager for Manager {
                 err.to_string()
             ))
         })?;
+        let result = async {
+            let simulation = db
+                .get_simulation_via_name(&simulation_id)
+                .await
+                .map_err(|err| {
+                    Status::internal(format!(
+                        "get_simulation could not fetch the simulation with message: {:?}",
+                        err.to_string()
+                    ))
+                })?;
 
-        let simulation = db
-            .get_simulation_via_name(&simulation_id)
-            .await
-            .map_err(|err| {
+            // Get current timestep
+            let timestep = db.get_max_timestep(simulation.id).await.map_err(|err| {
                 Status::internal(format!(
-                    "get_simulation could not fetch the simulation with message: {:?}",
+                    "get_simulation could not fetch the max timestep with message: {:?}",
                     err.to_string()
                 ))
             })?;
 
-        // Get current timestep
-        let node_timestep = db
-            .get_node_max_timestep(simulation.id)
-            .await
-            .map_err(|err| {
+            let sim_status = db.get_status(simulation.id).await.map_err(|err| {
                 Status::internal(format!(
-                    "get_simulation could not fetch the max node timestep with message: {:?}",
+                    "get_simulation could not fetch the simulation status with message: {:?}",
                     err.to_string()
                 ))
             })?;
 
-        let component_timestep = db
-            .get_global_components_max_timestep(simulation.id)
-            .await
-            .map_err(|err| Status::internal(format!("get_simulation could not fetch the max global variable timestep with message: {:?}", err.to_string())))?;
-
-        let timestep = node_timestep.max(component_timestep);
+            let parent = db.get_parent(simulation.id).await.map_err(|err| {
+                Status::internal(format!(
+                    "get_simulation could not fetch the parent simulation with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
 
-        let sim_status = db.get_status(simulation.id).await.map_err(|err| {
-            Status::internal(format!(
-                "get_simulation could not fetch the simulation status with message: {:?}",
-                err.to_string()
-            ))
-        })?;
+            let retention = db.get_retention(simulation.id).await.map_err(|err| {
+                Status::internal(format!(
+                    "get_simulation could not fetch the retention with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
 
-        // Create response
-        let simulation_data = SimulationData {
-            id: Some(SimulationId {
-                uuid: simulation_id,
-            }),
-            status: StatusEnum::to_simulation_status(sim_status).into(),
-            timestep_count: timestep as u64,
-            max_timestep_count: simulation.max_steps as u64,
-            timestep_delta: simulation.step_size_ms as f64 / 1000.0,
-            status_info: simulation.status_info,
-        };
+            let stopped = db.get_stopped(simulation.id).await.map_err(|err| {
+                Status::internal(format!(
+                    "get_simulation could not fetch the stop condition with message: {:?}",
+                    err.to_string()
+                ))
+            })?;
 
-        // Commit transaction
-        db.commit().await.expect("Commit transaction has failed");
+            // Create response
+            let simulation_data = SimulationData {
+                id: Some(SimulationId {
+                    uuid: simulation_id,
+                }),
+                status: StatusEnum::to_simulation_status(sim_status).into(),
+                timestep_count: timestep as u64,
+                max_timestep_count: simulation.max_steps as u64,
+                timestep_delta: simulation.step_size_ms as f64 / 1000.0,
+                status_info: simulation.status_info,
+                branched_from: parent.map(|(name, frame_nr)| Branch {
+                    parent: Some(SimulationId { uuid: name }),
+                    frame_nr: frame_nr as u32,
+                }),
+                pinned: retention.pinned,
+                downsampled: retention.downsampled.map(|stride| stride as u32),
+                stopped: stopped.map(|(condition, frame_nr)| Stop {
+                    condition: condition as u32,
+                    frame_nr: frame_nr as u32,
+                }),
+            };
+            Ok(simulation_data)
+        }
+        .await;
+        let simulation_data = Manager::end_transaction(&mut db, result).await?;
 
         Ok(Response::new(simulation_data))
     }
@@ -620,6 +1286,8 @@ impl SimulationManager for Manager {
 
                 let globals = db.get_global_components(simulation_id, frame_index).await.map_err(|err| Status::internal(format!("get_simulation_frames could not get the global variables with message: {:?}", err.to_string())))?;
 
+                let skipped_simulators = db.get_skipped_simulators(simulation_id, frame_index).await.map_err(|err| Status::internal(format!("get_simulation_frames could not get the skipped simulators with message: {:?}", err.to_string())))?;
+
                 // Commit transaction
                 db.commit().await.map_err(|err| Status::internal(format!("get_simulation_frames could not commit the changes with message: {:?}", err.to_string())))?;
 
@@ -629,13 +1297,270 @@ impl SimulationManager for Manager {
                     state: Some(State {
                         graph: Some(graph),
                         global_components: globals
-                    })
+                    }),
+                    skipped_simulators,
                 });
             }
         };
         Ok(Response::new(Box::pin(output)))
     }
 
+    type GetSimulationFrameRangeStream = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<SimulationFrame, Status>> + Send + 'static>,
+    >;
+
+    /// Get a range of frames of a simulation
+    ///
+    /// The frames are read from the database in chunks of [`FRAME_RANGE_CHUNK_SIZE`] frames. Every
+    /// chunk only takes a few queries, no matter how many nodes or edges a frame has. Frames that
+    /// were dropped when the simulation was downsampled are left out.
+    async fn get_simulation_frame_range(
+        &self,
+        request: Request<SimulationFrameRangeRequest>,
+    ) -> Result<Response<Self::GetSimulationFrameRangeStream>, Status> {
+        let request = request.into_inner();
+        let id = request
+            .simulation_id
+            .ok_or(Status::invalid_argument("No simulation id was provided"))?;
+        let (simulation_id, progress) = Manager::get_progress(&self.db, &id.uuid).await?;
+
+        // frames after the last computed frame do not exist yet
+        let end = request.end.min(progress.frame_nr as u32 + 1);
+        let time_steps: Vec<i32> = (request.start..end)
+            .step_by(request.stride.max(1) as usize)
+            .map(|t| t as i32)
+            .collect();
+        let time_steps = Manager::stored_time_steps(
+            &self.db,
+            simulation_id,
+            progress.frame_nr as i32,
+            time_steps,
+        )
+        .await?;
+        let components = (!request.components.is_empty()).then_some(request.components);
+
+        let db = self.db.clone();
+        let output = async_stream::stream! {
+            for chunk in time_steps.chunks(FRAME_RANGE_CHUNK_SIZE) {
+                let mut db = db.lock().await;
+                let frames = db.get_frames(simulation_id, chunk, components.as_deref()).await.map_err(|err| Status::internal(format!("get_simulation_frame_range could not get the frames with message: {:?}", err.to_string())))?;
+                let mut skipped = db.get_skipped_simulators_in(simulation_id, chunk).await.map_err(|err| Status::internal(format!("get_simulation_frame_range could not get the skipped simulators with message: {:?}", err.to_string())))?;
+                drop(db);
+
+                for (frame_nr, state) in frames {
+                    yield Ok(SimulationFrame {
+                        request: Some(SimulationFrameRequest {
+                            simulation_id: Some(id.clone()),
+                            frame_nr: frame_nr as u32,
+                        }),
+                        state: Some(state),
+                        skipped_simulators: skipped.remove(&frame_nr).unwrap_or_default(),
+                    });
+                }
+            }
+        };
+        Ok(Response::new(Box::pin(output)))
+    }
+
+    type CompareSimulationsStream = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<SimulationComparison, Status>> + Send + 'static>,
+    >;
+
+    /// Compare the frames of two simulations
+    ///
+    /// The frames that have been computed and are still stored for both simulations are read in
+    /// chunks of [`FRAME_RANGE_CHUNK_SIZE`] frames, like in `get_simulation_frame_range`. Every frame that
+    /// differs is sent as soon as it is compared, the summary is sent once all frames have been
+    /// compared.
+    async fn compare_simulations(
+        &self,
+        request: Request<CompareSimulationsRequest>,
+    ) -> Result<Response<Self::CompareSimulationsStream>, Status> {
+        let request = request.into_inner();
+        let a = request.a.ok_or(Status::invalid_argument(
+            "Invalid grpc, no simulation a present",
+        ))?;
+        let b = request.b.ok_or(Status::invalid_argument(
+            "Invalid grpc, no simulation b present",
+        ))?;
+        if request.tolerance.is_nan() || request.tolerance < 0.0 {
+            return Err(Status::invalid_argument(
+                "Invalid grpc, tolerance must not be negative",
+            ));
+        }
+        let (a, progress_a) = Manager::get_progress(&self.db, &a.uuid).await?;
+        let (b, progress_b) = Manager::get_progress(&self.db, &b.uuid).await?;
+
+        // only the frames that are stored for both simulations are compared
+        let time_steps: Vec<i32> =
+            (0..=progress_a.frame_nr.min(progress_b.frame_nr) as i32).collect();
+        let time_steps =
+            Manager::stored_time_steps(&self.db, a, progress_a.frame_nr as i32, time_steps).await?;
+        let time_steps =
+            Manager::stored_time_steps(&self.db, b, progress_b.frame_nr as i32, time_steps).await?;
+        let components = (!request.components.is_empty()).then_some(request.components);
+        let mut comparison = Comparison::new(request.tolerance, components.clone());
+
+        let db = self.db.clone();
+        let output = async_stream::stream! {
+            for chunk in time_steps.chunks(FRAME_RANGE_CHUNK_SIZE) {
+                let mut db = db.lock().await;
+                let frames_a = db.get_frames(a, chunk, components.as_deref()).await.map_err(|err| Status::internal(format!("compare_simulations could not get the frames with message: {:?}", err.to_string())))?;
+                let frames_b = db.get_frames(b, chunk, components.as_deref()).await.map_err(|err| Status::internal(format!("compare_simulations could not get the frames with message: {:?}", err.to_string())))?;
+                drop(db);
+
+                for ((frame_nr, frame_a), frame_b) in frames_a.iter().zip(frames_b.values()) {
+                    if let Some(difference) = comparison.compare(*frame_nr as u32, frame_a, frame_b) {
+                        yield Ok(SimulationComparison {
+                            comparison: Some(simulation_comparison::Comparison::Frame(difference)),
+                        });
+                    }
+                }
+            }
+            yield Ok(SimulationComparison {
+                comparison: Some(simulation_comparison::Comparison::Summary(ComparisonSummary {
+                    frames: time_steps.len() as u32,
+                    fields: comparison.summary(),
+                })),
+            });
+        };
+        Ok(Response::new(Box::pin(output)))
+    }
+
+    type ExportSimulationStream = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<ExportChunk, Status>> + Send + 'static>,
+    >;
+
+    /// Export the frames of a simulation as a file
+    ///
+    /// The frames are read and written in chunks, every chunk of the file is sent as soon as it
+    /// has been written. Concatenating the chunks gives the whole file. Frames that were dropped
+    /// when the simulation was downsampled are left out.
+    async fn export_simulation(
+        &self,
+        request: Request<ExportSimulationRequest>,
+    ) -> Result<Response<Self::ExportSimulationStream>, Status> {
+        let request = request.into_inner();
+        let id = request.id.ok_or(Status::invalid_argument(
+            "Invalid grpc, no simulation id present",
+        ))?;
+        let format = match ExportFormat::try_from(request.format) {
+            Ok(ExportFormat::Csv) => Format::Csv,
+            Ok(ExportFormat::JsonLines) => Format::JsonLines,
+            Ok(ExportFormat::Parquet) => Format::Parquet,
+            Err(_) => {
+                return Err(Status::invalid_argument(
+                    "Invalid grpc, unknown export format",
+                ))
+            }
+        };
+        let (simulation_id, progress) = Manager::get_progress(&self.db, &id.uuid).await?;
+
+        let time_steps = export::time_steps(request.start, request.end, progress.frame_nr as i32);
+        let time_steps = Manager::stored_time_steps(
+            &self.db,
+            simulation_id,
+            progress.frame_nr as i32,
+            time_steps,
+        )
+        .await?;
+        let components = (!request.components.is_empty()).then_some(request.components);
+
+        let output = export::export(
+            self.db.clone(),
+            simulation_id,
+            time_steps,
+            components,
+            format,
+        )
+        .map(|chunk| {
+            chunk.map(|data| ExportChunk { data }).map_err(|err| {
+                Status::internal(format!(
+                    "export_simulation could not export the frames with message: {:?}",
+                    err.to_string()
+                ))
+            })
+        });
+        Ok(Response::new(Box::pin(output)))
+    }
+
+    type ArchiveSimulationStream = std::pin::Pin<
+        Box<
+            dyn tokio_stream::Stream<Item = Result<SimulationArchivePart, Status>> + Send + 'static,
+        >,
+    >;
+
+    /// Archive a finished simulation
+    ///
+    /// The header is sent first, followed by every frame. The frames are read from the database in
+    /// chunks, like in `get_simulation_frame_range`.
+    async fn archive_simulation(
+        &self,
+        request: Request<SimulationId>,
+    ) -> Result<Response<Self::ArchiveSimulationStream>, Status> {
+        let name = request.into_inner().uuid;
+        let (simulation_id, progress) = Manager::get_progress(&self.db, &name).await?;
+        if progress.status() != SimulationStatus::Finished {
+            return Err(Status::failed_precondition(format!(
+                "simulation `{name}` has not finished"
+            )));
+        }
+        let retention = self
+            .db
+            .lock()
+            .await
+            .get_retention(simulation_id)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        if retention.downsampled.is_some() {
+            return Err(Status::failed_precondition(format!(
+                "simulation `{name}` has been downsampled"
+            )));
+        }
+
+        let output = archive::archive(self.db.clone(), simulation_id).map(|part| {
+            part.map_err(|err| {
+                Status::internal(format!(
+                    "archive_simulation could not archive the simulation with message: {:?}",
+                    err.to_string()
+                ))
+            })
+        });
+        Ok(Response::new(Box::pin(output)))
+    }
+
+    /// Import an archived simulation as a finished simulation
+    ///
+    /// The whole archive is received before the simulation is stored in a single transaction, so
+    /// an archive that is cut off is not imported.
+    async fn import_simulation(
+        &self,
+        request: Request<tonic::Streaming<SimulationArchivePart>>,
+    ) -> Result<Response<()>, Status> {
+        let mut stream = request.into_inner();
+        let mut parts = Vec::new();
+        while let Some(part) = stream.message().await? {
+            parts.push(part);
+        }
+        let archive = Archive::from_parts(parts)
+            .map_err(|err| Status::invalid_argument(format!("Invalid grpc, {err}")))?;
+
+        let name = archive.name();
+        let mut db = self.db.lock().await;
+        if db.get_simulation_via_name(name).await.is_ok() {
+            return Err(Status::already_exists(format!(
+                "simulation `{name}` already exists"
+            )));
+        }
+        archive.import(&mut db).await.map_err(|err| {
+            Status::internal(format!(
+                "import_simulation could not store the simulation with message: {:?}",
+                err.to_string()
+            ))
+        })?;
+        Ok(Response::new(()))
+    }
+
     /// Get information about the simulators
     ///
     /// Gives the name and the output components of each simulator
@@ -661,12 +1586,147 @@ impl SimulationManager for Manager {
         drop(simulators);
         Ok(Response::new(components))
     }
-}
 
-// Uses ports 8005-8008 in localhost
-#[cfg(test)]
-mod manager_grpc_test {
-    use std::net::SocketAddr;
+    /// Pause a simulation
+    ///
+    /// A pending simulation is paused immediately. A simulation that is being computed is paused
+    /// by the runner once the current timestep is done.
+    async fn pause_simulation(
+        &self,
+        request: Request<SimulationId>,
+    ) -> Result<Response<()>, Status> {
+        let simulation_id = self.get_simulation_id(&request.into_inner().uuid).await?;
+        if !self
+            .change_status(simulation_id, StatusEnum::Pending, StatusEnum::Paused)
+            .await?
+        {
+            self.control_running(simulation_id, ControlEnum::Pause)
+                .await?;
+        }
+        Ok(Response::new(()))
+    }
+
+    /// Resume a paused simulation
+    ///
+    /// The simulation is put back in the queue, the runner will continue it from the last stored
+    /// frame. Resuming a simulation that is being computed, but has not been paused yet, undoes
+    /// the pause.
+    async fn resume_simulation(
+        &self,
+        request: Request<SimulationId>,
+    ) -> Result<Response<()>, Status> {
+        let simulation_id = self.get_simulation_id(&request.into_inner().uuid).await?;
+        if self
+            .db
+            .lock()
+            .await
+            .clear_control(simulation_id, ControlEnum::Pause)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+        {
+            return Ok(Response::new(()));
+        }
+
+        if !self
+            .change_status(simulation_id, StatusEnum::Paused, StatusEnum::Pending)
+            .await?
+        {
+            return Err(Status::failed_precondition("simulation is not paused"));
+        }
+        Ok(Response::new(()))
+    }
+
+    /// Cancel a simulation
+    ///
+    /// A pending or paused simulation is cancelled immediately. A simulation that is being
+    /// computed is cancelled by the runner once the current timestep is done.
+    async fn cancel_simulation(
+        &self,
+        request: Request<SimulationId>,
+    ) -> Result<Response<()>, Status> {
+        let simulation_id = self.get_simulation_id(&request.into_inner().uuid).await?;
+        for from in [StatusEnum::Pending, StatusEnum::Paused] {
+            if self
+                .change_status(simulation_id, from, StatusEnum::Cancelled)
+                .await?
+            {
+                return Ok(Response::new(()));
+            }
+        }
+        self.control_running(simulation_id, ControlEnum::Cancel)
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    type WatchSimulationStream = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<SimulationEvent, Status>> + Send + 'static>,
+    >;
+
+    /// Follow the progress of a simulation
+    ///
+    /// The current progress is read from the database and sent first. After that, the events
+    /// published when a frame is stored or the status changes are used to update the progress,
+    /// so the database does not need to be polled. The stream ends once the simulation is done.
+    async fn watch_simulation(
+        &self,
+        request: Request<SimulationId>,
+    ) -> Result<Response<Self::WatchSimulationStream>, Status> {
+        let name = request.into_inner().uuid;
+        // Subscribe before reading the progress, so no events are missed in between.
+        let mut events = self.events.subscribe();
+        let db = self.db.clone();
+        let (simulation_id, mut progress) = Manager::get_progress(&db, &name).await?;
+
+        let output = async_stream::stream! {
+            yield Ok(progress.clone());
+            while !matches!(
+                progress.status(),
+                SimulationStatus::Finished | SimulationStatus::Failed | SimulationStatus::Cancelled
+            ) {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    // Some events were missed, read the progress from the database again.
+                    Err(RecvError::Lagged(_)) => {
+                        match Manager::get_progress(&db, &name).await {
+                            Ok((_, new_progress)) => progress = new_progress,
+                            Err(err) => {
+                                yield Err(err);
+                                break;
+                            }
+                        }
+                        yield Ok(progress.clone());
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if event.simulation_id() != simulation_id {
+                    continue;
+                }
+
+                progress.step_duration = None;
+                match event {
+                    Event::Frame { frame_nr, step_duration, .. } => {
+                        progress.frame_nr = frame_nr as u64;
+                        progress.step_duration = Some(step_duration.as_secs_f64());
+                    }
+                    Event::Status { status, status_info, .. } => {
+                        progress.status = StatusEnum::to_simulation_status(status).into();
+                        if !status_info.is_empty() {
+                            progress.status_info = Some(status_info);
+                        }
+                    }
+                }
+                yield Ok(progress.clone());
+            }
+        };
+        Ok(Response::new(Box::pin(output)))
+    }
+}
+
+// Uses ports 8005-8008 in localhost
+#[cfg(test)]
+mod manager_grpc_test {
+    use std::net::SocketAddr;
     use tokio::time::{sleep, Duration};
     use tonic::transport::Server;
 
@@ -685,8 +1745,7 @@ mod manager_grpc_test {
         use proto::simulation::{Edge, Node};
         //set up
         let simulators: Arc<Mutex<Vec<SimulatorsInfo>>> = Arc::new(Mutex::new(Vec::default()));
-        let (send, _recv) = mpsc::channel(1);
-        let manager = Manager::new(pool.clone(), simulators, send).await;
+        let manager = Manager::new(pool.clone(), simulators, broadcast::channel(1).0).await;
 
         let node0 = Node {
             longitude: 11.11,
@@ -728,6 +1787,12 @@ mod manager_grpc_test {
             timestep_delta: 30.0,
             timesteps: 3,
             selection: Some(SimulatorSelection { name: vec![] }),
+            error_policy: ErrorPolicy::FailFast.into(),
+            priority: 0,
+            not_before: None,
+            twin: None,
+            stop_conditions: vec![],
+            component_versions: HashMap::new(),
             initial_state: Some(State {
                 graph: Some(Graph {
                     nodes: vec![node0.clone(), node1.clone()],
@@ -800,6 +1865,635 @@ mod manager_grpc_test {
         );
     }
 
+    /// Tests pushing a sweep and summarizing it once its simulations have finished. This test
+    /// only runs if the db_test feature is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_push_simulation_sweep(pool: PgPool) {
+        use proto::simulation::simulation_manager::{
+            component_path::Location, ComponentPath, SimulatorSelection, SweepAxis,
+        };
+        use proto::simulation::Node;
+        let simulators: Arc<Mutex<Vec<SimulatorsInfo>>> = Arc::new(Mutex::new(Vec::default()));
+        let manager = Manager::new(pool.clone(), simulators, broadcast::channel(1).0).await;
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+
+        let temperature = ComponentPath {
+            location: Some(Location::NodeId(0)),
+            component: "TemperatureComponent".to_string(),
+            path: "$".to_string(),
+        };
+        let tolerance = ComponentPath {
+            location: None,
+            component: "LoadFlowAnalytics".to_string(),
+            path: "$".to_string(),
+        };
+        let base = PushSimulationRequest {
+            id: None,
+            timestep_delta: 1.0,
+            timesteps: 3,
+            selection: Some(SimulatorSelection { name: vec![] }),
+            error_policy: ErrorPolicy::FailFast.into(),
+            priority: 0,
+            not_before: None,
+            twin: None,
+            stop_conditions: vec![],
+            component_versions: HashMap::new(),
+            initial_state: Some(State {
+                graph: Some(Graph {
+                    nodes: vec![Node {
+                        id: 0,
+                        ..Default::default()
+                    }],
+                    edge: vec![],
+                }),
+                global_components: Default::default(),
+            }),
+        };
+        let request = PushSimulationSweepRequest {
+            id: Some(SimulationId {
+                uuid: "sweep".to_string(),
+            }),
+            base: Some(base.clone()),
+            axes: vec![
+                SweepAxis {
+                    path: Some(temperature.clone()),
+                    values: vec![number(10.0), number(20.0)],
+                },
+                SweepAxis {
+                    path: Some(tolerance.clone()),
+                    values: vec![number(0.1), number(0.01), number(0.001)],
+                },
+            ],
+        };
+        let sweep = manager
+            .push_simulation_sweep(Request::new(request.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(sweep.simulations.len(), 6);
+        assert_eq!(sweep.simulations[4].id.as_ref().unwrap().uuid, "sweep-4");
+        assert_eq!(
+            sweep.simulations[4].parameters,
+            vec![number(20.0), number(0.01)]
+        );
+
+        // the initial states have the parameters of the simulation
+        let mut db = manager.db.lock().await;
+        let simulation = db.get_simulation_via_name("sweep-4").await.unwrap();
+        let state = db.get_frames(simulation.id, &[0], None).await.unwrap()[&0].clone();
+        assert_eq!(get_in_state(&state, &temperature), Some(&number(20.0)));
+        assert_eq!(get_in_state(&state, &tolerance), Some(&number(0.01)));
+        drop(db);
+
+        // the summary is only available once every simulation has finished
+        let summary_request = SweepSummaryRequest {
+            id: Some(SimulationId {
+                uuid: "sweep".to_string(),
+            }),
+            metrics: vec![temperature.clone(), tolerance.clone()],
+        };
+        let err = manager
+            .get_sweep_summary(Request::new(summary_request.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        let mut db = manager.db.lock().await;
+        for simulation in &sweep.simulations {
+            let id = db
+                .get_simulation_via_name(&simulation.id.as_ref().unwrap().uuid)
+                .await
+                .unwrap()
+                .id;
+            db.update_status(id, StatusEnum::Finished, None)
+                .await
+                .unwrap();
+        }
+        drop(db);
+        let summary = manager
+            .get_sweep_summary(Request::new(summary_request))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(summary.results.len(), 6);
+        assert_eq!(summary.results[4].status(), SimulationStatus::Finished);
+        assert_eq!(summary.results[4].metrics, vec![number(20.0), number(0.01)]);
+
+        // sweeps with names that are already used are rejected before anything is stored
+        let err = manager
+            .push_simulation_sweep(Request::new(request.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+        manager
+            .db
+            .lock()
+            .await
+            .add_simulation("taken-1", 1000, 1, StatusEnum::Finished, vec![])
+            .await
+            .unwrap();
+        let taken = PushSimulationSweepRequest {
+            id: Some(SimulationId {
+                uuid: "taken".to_string(),
+            }),
+            ..request.clone()
+        };
+        let err = manager
+            .push_simulation_sweep(Request::new(taken))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+        assert!(err.message().ends_with("taken-1"));
+        assert!(!manager.db.lock().await.has_sweep("taken").await.unwrap());
+
+        // sweeps that are too large are rejected, also when their size overflows
+        let too_large = PushSimulationSweepRequest {
+            axes: vec![
+                SweepAxis {
+                    path: Some(temperature.clone()),
+                    values: vec![number(10.0), number(20.0)],
+                };
+                65
+            ],
+            ..request
+        };
+        let err = manager
+            .push_simulation_sweep(Request::new(too_large))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        // axes that can not be set do not queue anything
+        let request = PushSimulationSweepRequest {
+            id: Some(SimulationId {
+                uuid: "invalid".to_string(),
+            }),
+            base: Some(base),
+            axes: vec![SweepAxis {
+                path: Some(ComponentPath {
+                    location: Some(Location::NodeId(7)),
+                    ..temperature
+                }),
+                values: vec![number(10.0)],
+            }],
+        };
+        let err = manager
+            .push_simulation_sweep(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(manager
+            .db
+            .lock()
+            .await
+            .get_sweep_via_name("invalid")
+            .await
+            .is_err());
+    }
+
+    /// Tests comparing the frames of two simulations. This test only runs if the db_test feature
+    /// is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_compare_simulations(pool: PgPool) {
+        use proto::simulation::simulation_manager::simulation_comparison::Comparison;
+        let manager = Manager::new(pool.clone(), Default::default(), broadcast::channel(1).0).await;
+        let state = |load: f64| State {
+            graph: Some(Graph::default()),
+            global_components: [(
+                "Load".to_string(),
+                Value {
+                    kind: Some(Kind::NumberValue(load)),
+                },
+            )]
+            .into(),
+        };
+
+        let mut db = manager.db.lock().await;
+        for (name, loads) in [("a", [1.0, 2.0, 3.0]), ("b", [1.0, 2.5, 5.0])] {
+            let id = db
+                .add_simulation(name, 1000, 2, StatusEnum::Finished, vec![])
+                .await
+                .unwrap();
+            for (time_step, load) in loads.into_iter().enumerate() {
+                db.add_frame(id, time_step as i32, &state(load), None)
+                    .await
+                    .unwrap();
+            }
+        }
+        drop(db);
+
+        let request = CompareSimulationsRequest {
+            a: Some(SimulationId {
+                uuid: "a".to_string(),
+            }),
+            b: Some(SimulationId {
+                uuid: "b".to_string(),
+            }),
+            components: vec![],
+            tolerance: 1.0,
+        };
+        let comparison: Vec<_> = manager
+            .compare_simulations(Request::new(request.clone()))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|comparison| comparison.unwrap().comparison.unwrap())
+            .collect()
+            .await;
+        let [Comparison::Frame(frame), Comparison::Summary(summary)] = &comparison[..] else {
+            panic!("unexpected comparison {comparison:?}");
+        };
+        assert_eq!(frame.frame_nr, 2);
+        assert_eq!(summary.frames, 3);
+        assert_eq!(summary.fields.len(), 1);
+        assert_eq!(summary.fields[0].max, 2.0);
+        assert_eq!(summary.fields[0].count, 3);
+
+        // frames that were dropped by downsampling are left out instead of read as empty frames
+        let mut db = manager.db.lock().await;
+        let b = db.get_simulation_via_name("b").await.unwrap().id;
+        db.keep_frames(b, &[0, 2]).await.unwrap();
+        db.set_downsampled(b, 2).await.unwrap();
+        drop(db);
+        let comparison: Vec<_> = manager
+            .compare_simulations(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|comparison| comparison.unwrap().comparison.unwrap())
+            .collect()
+            .await;
+        let [Comparison::Frame(frame), Comparison::Summary(summary)] = &comparison[..] else {
+            panic!("unexpected comparison {comparison:?}");
+        };
+        assert_eq!(frame.frame_nr, 2);
+        assert_eq!(summary.frames, 2);
+
+        let frames: Vec<u32> = manager
+            .get_simulation_frame_range(Request::new(SimulationFrameRangeRequest {
+                simulation_id: Some(SimulationId {
+                    uuid: "b".to_string(),
+                }),
+                start: 0,
+                end: 3,
+                stride: 1,
+                components: vec![],
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|frame| frame.unwrap().request.unwrap().frame_nr)
+            .collect()
+            .await;
+        assert_eq!(frames, vec![0, 2]);
+    }
+
+    /// Tests that a branch that can not be stored does not leave its transaction open on the
+    /// shared connection. This test only runs if the db_test feature is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_branch_simulation_error(pool: PgPool) {
+        use proto::simulation::simulation_manager::SimulatorSelection;
+        let manager = Manager::new(pool.clone(), Default::default(), broadcast::channel(1).0).await;
+
+        let mut db = manager.db.lock().await;
+        for name in ["a", "b"] {
+            let id = db
+                .add_simulation(name, 1000, 1, StatusEnum::Finished, vec![])
+                .await
+                .unwrap();
+            db.add_frame(id, 0, &State::default(), None).await.unwrap();
+        }
+        drop(db);
+
+        // `b` already exists, so the branch can not be inserted
+        let request = BranchSimulationRequest {
+            id: Some(SimulationId {
+                uuid: "b".to_string(),
+            }),
+            parent: Some(SimulationId {
+                uuid: "a".to_string(),
+            }),
+            frame_nr: 0,
+            timesteps: 1,
+            selection: Some(SimulatorSelection { name: vec![] }),
+            ..Default::default()
+        };
+        let err = manager
+            .branch_simulation(Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Internal);
+
+        // the aborted transaction was rolled back, so the connection can be used again
+        let simulation = manager
+            .db
+            .lock()
+            .await
+            .get_simulation_via_name("a")
+            .await
+            .unwrap();
+        assert_eq!(simulation.max_steps, 1);
+    }
+
+    /// Tests archiving a simulation and importing the archive under another name. This test only
+    /// runs if the db_test feature is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_archive_simulation(pool: PgPool) {
+        use proto::simulation::simulation_manager::{stop_condition::Operand, ComparisonOperator};
+        use proto::simulation::simulator::SimulatorIoConfig;
+        let manager = Manager::new(pool.clone(), Default::default(), broadcast::channel(1).0).await;
+        let state = |load: f64| State {
+            graph: Some(Graph::default()),
+            global_components: [(
+                "Load".to_string(),
+                Value {
+                    kind: Some(Kind::NumberValue(load)),
+                },
+            )]
+            .into(),
+        };
+        let io_config = SimulatorIoConfig {
+            output_components: vec!["Load".to_string()],
+            ..Default::default()
+        };
+
+        let mut db = manager.db.lock().await;
+        let id = db
+            .add_simulation(
+                "original",
+                1000,
+                2,
+                StatusEnum::Pending,
+                vec!["a".to_string()],
+            )
+            .await
+            .unwrap();
+        db.set_simulator_config(id, "a", &io_config).await.unwrap();
+        db.set_component_versions(id, &HashMap::from([("Load".to_string(), 2)]))
+            .await
+            .unwrap();
+        for (time_step, load) in [1.0, 2.0, 3.0].into_iter().enumerate() {
+            db.add_frame(id, time_step as i32, &state(load), None)
+                .await
+                .unwrap();
+        }
+        db.add_skipped_simulators(id, 2, &["a".to_string()])
+            .await
+            .unwrap();
+        let stop_conditions = vec![StopCondition {
+            component: "Load".to_string(),
+            path: "$".to_string(),
+            comparison: ComparisonOperator::GreaterOrEqual.into(),
+            operand: Some(Operand::Value(Value {
+                kind: Some(Kind::NumberValue(3.0)),
+            })),
+            consecutive: 0,
+        }];
+        db.set_stop_conditions(id, &stop_conditions).await.unwrap();
+        db.set_stopped(id, 0, 2).await.unwrap();
+        drop(db);
+
+        let archive_simulation = || {
+            manager.archive_simulation(Request::new(SimulationId {
+                uuid: "original".to_string(),
+            }))
+        };
+        // only finished simulations are archived
+        assert_eq!(
+            archive_simulation().await.err().unwrap().code(),
+            tonic::Code::FailedPrecondition
+        );
+        manager
+            .change_status(id, StatusEnum::Pending, StatusEnum::Finished)
+            .await
+            .unwrap();
+
+        let parts: Vec<_> = archive_simulation()
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|part| part.unwrap())
+            .collect()
+            .await;
+        let mut archive = Archive::from_parts(parts).unwrap();
+        assert_eq!(archive.header.simulators, vec!["a"]);
+        assert_eq!(archive.header.simulator_configs["a"], io_config);
+        assert_eq!(archive.header.component_versions["Load"], 2);
+        assert_eq!(archive.header.stop_conditions, stop_conditions);
+        assert_eq!(
+            archive.header.stopped,
+            Some(Stop {
+                condition: 0,
+                frame_nr: 2
+            })
+        );
+        assert_eq!(archive.frames.len(), 3);
+
+        archive.rename("imported".to_string());
+        let mut db = manager.db.lock().await;
+        let imported = archive.import(&mut db).await.unwrap();
+        assert_eq!(db.get_status(imported).await.unwrap(), StatusEnum::Finished);
+        assert_eq!(
+            db.get_simulator_configs(imported).await.unwrap()["a"],
+            io_config
+        );
+        assert_eq!(
+            db.get_component_versions(imported).await.unwrap(),
+            HashMap::from([("Load".to_string(), 2)])
+        );
+        assert_eq!(
+            db.get_frames(imported, &[0, 1, 2], None).await.unwrap(),
+            db.get_frames(id, &[0, 1, 2], None).await.unwrap()
+        );
+        assert_eq!(
+            db.get_skipped_simulators(imported, 2).await.unwrap(),
+            vec!["a"]
+        );
+        assert_eq!(
+            db.get_stop_conditions(imported).await.unwrap(),
+            stop_conditions
+        );
+        assert_eq!(db.get_stopped(imported).await.unwrap(), Some((0, 2)));
+    }
+
+    /// Tests the status changes made by pausing, resuming and cancelling simulations. This test
+    /// only runs if the db_test feature is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_pause_resume_cancel(pool: PgPool) {
+        use proto::simulation::simulation_manager::SimulatorSelection;
+
+        let simulators: Arc<Mutex<Vec<SimulatorsInfo>>> = Arc::new(Mutex::new(Vec::default()));
+        let manager = Manager::new(pool.clone(), simulators, broadcast::channel(1).0).await;
+
+        let id = |uuid: &str| SimulationId {
+            uuid: uuid.to_string(),
+        };
+        let status = |uuid: &'static str| {
+            let pool = pool.clone();
+            async move {
+                let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+                let simulation = db.get_simulation_via_name(uuid).await.unwrap();
+                db.get_status(simulation.id).await.unwrap()
+            }
+        };
+        // reads the request to stop a simulation without handling it
+        let control = |simulation_id: i32| {
+            let pool = pool.clone();
+            async move {
+                let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+                let control = db.take_control(simulation_id).await.unwrap();
+                if let Some(control) = control {
+                    db.set_control(simulation_id, control).await.unwrap();
+                }
+                control
+            }
+        };
+
+        for uuid in ["sim1", "sim2"] {
+            let request = PushSimulationRequest {
+                id: Some(id(uuid)),
+                timestep_delta: 30.0,
+                timesteps: 3,
+                selection: Some(SimulatorSelection { name: vec![] }),
+                error_policy: ErrorPolicy::FailFast.into(),
+                priority: 0,
+                not_before: None,
+                twin: None,
+                stop_conditions: vec![],
+                component_versions: HashMap::new(),
+                initial_state: Some(State {
+                    graph: Some(Graph {
+                        nodes: vec![],
+                        edge: vec![],
+                    }),
+                    global_components: Default::default(),
+                }),
+            };
+            manager
+                .push_simulation(Request::new(request))
+                .await
+                .unwrap();
+        }
+
+        // pending simulations are paused, resumed and cancelled directly
+        manager
+            .pause_simulation(Request::new(id("sim1")))
+            .await
+            .unwrap();
+        assert_eq!(status("sim1").await, StatusEnum::Paused);
+        manager
+            .resume_simulation(Request::new(id("sim1")))
+            .await
+            .unwrap();
+        assert_eq!(status("sim1").await, StatusEnum::Pending);
+        assert!(manager
+            .resume_simulation(Request::new(id("sim1")))
+            .await
+            .is_err());
+        manager
+            .cancel_simulation(Request::new(id("sim1")))
+            .await
+            .unwrap();
+        assert_eq!(status("sim1").await, StatusEnum::Cancelled);
+        assert!(manager
+            .pause_simulation(Request::new(id("sim1")))
+            .await
+            .is_err());
+
+        // computing simulations are signalled
+        let sim2 = manager.get_simulation_id("sim2").await.unwrap();
+        manager
+            .change_status(sim2, StatusEnum::Pending, StatusEnum::Computing)
+            .await
+            .unwrap();
+        manager
+            .pause_simulation(Request::new(id("sim2")))
+            .await
+            .unwrap();
+        assert_eq!(control(sim2).await, Some(ControlEnum::Pause));
+        manager
+            .resume_simulation(Request::new(id("sim2")))
+            .await
+            .unwrap();
+        assert_eq!(control(sim2).await, None);
+        manager
+            .cancel_simulation(Request::new(id("sim2")))
+            .await
+            .unwrap();
+        assert_eq!(control(sim2).await, Some(ControlEnum::Cancel));
+        assert_eq!(status("sim2").await, StatusEnum::Computing);
+    }
+
+    /// Tests that watching a simulation sends the current progress followed by all events of the
+    /// simulation, which are published by the database. This test only runs if the db_test
+    /// feature is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_watch_simulation(pool: PgPool) {
+        use crate::events::EventListener;
+
+        let simulators: Arc<Mutex<Vec<SimulatorsInfo>>> = Arc::new(Mutex::new(Vec::default()));
+        let (events, _) = broadcast::channel(16);
+        let listener = EventListener::new(&pool, events.clone()).await.unwrap();
+        tokio::spawn(listener.start());
+        let manager = Manager::new(pool.clone(), simulators, events).await;
+
+        // the changes are made on another connection, like another manager would
+        let mut db = SimulationsDB::from_pg_pool(pool).await.unwrap();
+        let simulation_id = db
+            .add_simulation("sim1", 1000, 2, StatusEnum::Computing, vec![])
+            .await
+            .unwrap();
+        let other_id = db
+            .add_simulation("sim2", 1000, 2, StatusEnum::Computing, vec![])
+            .await
+            .unwrap();
+
+        let mut stream = manager
+            .watch_simulation(Request::new(SimulationId {
+                uuid: "sim1".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.status(), SimulationStatus::Computing);
+        assert_eq!(first.frame_nr, 0);
+
+        // events of other simulations are ignored
+        let state = State {
+            graph: Some(Graph {
+                nodes: vec![],
+                edge: vec![],
+            }),
+            global_components: Default::default(),
+        };
+        db.add_frame(other_id, 1, &state, None).await.unwrap();
+        db.set_step_duration(other_id, 1, 1.0).await.unwrap();
+        db.add_frame(simulation_id, 1, &state, None).await.unwrap();
+        db.set_step_duration(simulation_id, 1, 0.5).await.unwrap();
+        db.update_status(simulation_id, StatusEnum::Finished, None)
+            .await
+            .unwrap();
+
+        let frame = stream.next().await.unwrap().unwrap();
+        assert_eq!(frame.frame_nr, 1);
+        assert_eq!(frame.step_duration, Some(0.5));
+        let finished = stream.next().await.unwrap().unwrap();
+        assert_eq!(finished.status(), SimulationStatus::Finished);
+        assert_eq!(finished.frame_nr, 1);
+        assert_eq!(finished.step_duration, None);
+        assert!(stream.next().await.is_none());
+    }
+
     pub struct ManagerTest {}
 
     impl ManagerTest {
@@ -821,6 +2515,86 @@ mod manager_grpc_test {
             unreachable!()
         }
 
+        async fn validate_initial_state(
+            &self,
+            _request: Request<ValidateInitialStateRequest>,
+        ) -> Result<Response<StructureViolations>, Status> {
+            unreachable!()
+        }
+
+        async fn list_queue(&self, _request: Request<()>) -> Result<Response<Queue>, Status> {
+            unreachable!()
+        }
+
+        async fn branch_simulation(
+            &self,
+            _request: Request<BranchSimulationRequest>,
+        ) -> Result<Response<()>, Status> {
+            unreachable!()
+        }
+
+        type CompareSimulationsStream = std::pin::Pin<
+            Box<
+                dyn tokio_stream::Stream<Item = Result<SimulationComparison, Status>>
+                    + Send
+                    + 'static,
+            >,
+        >;
+
+        async fn compare_simulations(
+            &self,
+            _request: Request<CompareSimulationsRequest>,
+        ) -> Result<Response<Self::CompareSimulationsStream>, Status> {
+            unreachable!()
+        }
+
+        type ExportSimulationStream = std::pin::Pin<
+            Box<dyn tokio_stream::Stream<Item = Result<ExportChunk, Status>> + Send + 'static>,
+        >;
+
+        async fn export_simulation(
+            &self,
+            _request: Request<ExportSimulationRequest>,
+        ) -> Result<Response<Self::ExportSimulationStream>, Status> {
+            unreachable!()
+        }
+
+        type ArchiveSimulationStream = std::pin::Pin<
+            Box<
+                dyn tokio_stream::Stream<Item = Result<SimulationArchivePart, Status>>
+                    + Send
+                    + 'static,
+            >,
+        >;
+
+        async fn archive_simulation(
+            &self,
+            _request: Request<SimulationId>,
+        ) -> Result<Response<Self::ArchiveSimulationStream>, Status> {
+            unreachable!()
+        }
+
+        async fn import_simulation(
+            &self,
+            _request: Request<tonic::Streaming<SimulationArchivePart>>,
+        ) -> Result<Response<()>, Status> {
+            unreachable!()
+        }
+
+        async fn push_simulation_sweep(
+            &self,
+            _request: Request<PushSimulationSweepRequest>,
+        ) -> Result<Response<Sweep>, Status> {
+            unreachable!()
+        }
+
+        async fn get_sweep_summary(
+            &self,
+            _request: Request<SweepSummaryRequest>,
+        ) -> Result<Response<SweepSummary>, Status> {
+            unreachable!()
+        }
+
         async fn delete_simulation(
             &self,
             _request: Request<DeleteSimulationRequestManager>,
@@ -828,6 +2602,20 @@ mod manager_grpc_test {
             unreachable!()
         }
 
+        async fn pin_simulation(
+            &self,
+            _request: Request<PinSimulationRequest>,
+        ) -> Result<Response<()>, Status> {
+            unreachable!()
+        }
+
+        async fn get_storage_stats(
+            &self,
+            _: Request<()>,
+        ) -> Result<Response<StorageStats>, Status> {
+            unreachable!()
+        }
+
         // requests an ID and returns the same ID
         async fn get_simulation(
             &self,
@@ -849,6 +2637,10 @@ mod manager_grpc_test {
                 max_timestep_count: Default::default(),
                 timestep_delta: Default::default(),
                 status_info: Default::default(),
+                branched_from: Default::default(),
+                pinned: Default::default(),
+                downsampled: Default::default(),
+                stopped: Default::default(),
             };
             Ok(Response::new(simulation_data))
         }
@@ -874,7 +2666,8 @@ mod manager_grpc_test {
 
                     yield Ok(SimulationFrame {
                         request: Some(frame_request),
-                        state: Default::default()
+                        state: Default::default(),
+                        skipped_simulators: Default::default()
                     });
                 }
             };
@@ -887,6 +2680,47 @@ mod manager_grpc_test {
         ) -> Result<Response<Simulators>, Status> {
             Ok(Response::new(Default::default()))
         }
+
+        async fn pause_simulation(
+            &self,
+            _request: Request<SimulationId>,
+        ) -> Result<Response<()>, Status> {
+            unreachable!()
+        }
+
+        async fn resume_simulation(
+            &self,
+            _request: Request<SimulationId>,
+        ) -> Result<Response<()>, Status> {
+            unreachable!()
+        }
+
+        async fn cancel_simulation(
+            &self,
+            _request: Request<SimulationId>,
+        ) -> Result<Response<()>, Status> {
+            unreachable!()
+        }
+
+        type GetSimulationFrameRangeStream = std::pin::Pin<
+            Box<dyn tokio_stream::Stream<Item = Result<SimulationFrame, Status>> + Send + 'static>,
+        >;
+        async fn get_simulation_frame_range(
+            &self,
+            _request: Request<SimulationFrameRangeRequest>,
+        ) -> Result<Response<Self::GetSimulationFrameRangeStream>, Status> {
+            unreachable!()
+        }
+
+        type WatchSimulationStream = std::pin::Pin<
+            Box<dyn tokio_stream::Stream<Item = Result<SimulationEvent, Status>> + Send + 'static>,
+        >;
+        async fn watch_simulation(
+            &self,
+            _request: Request<SimulationId>,
+        ) -> Result<Response<Self::WatchSimulationStream>, Status> {
+            unreachable!()
+        }
     }
 
     // tests a simple connection between server and client.
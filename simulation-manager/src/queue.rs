@@ -0,0 +1,120 @@
+/// A simulation that is waiting in the queue, as seen by [`estimate_starts`].
+#[derive(Debug, Clone, Copy)]
+pub struct Waiting {
+    /// Seconds from now before which the simulation may not be started, zero if it may be
+    /// started right away.
+    pub not_before: f64,
+    /// The expected time it takes to compute the simulation in seconds, infinite if unknown.
+    pub duration: f64,
+}
+
+/// The expected time it takes to compute `steps` time steps in seconds, infinite if the duration
+/// of a time step is unknown.
+pub fn expected_duration(steps: i32, step_duration: Option<f64>) -> f64 {
+    if steps <= 0 {
+        return 0.0;
+    }
+    step_duration.map_or(f64::INFINITY, |step_duration| steps as f64 * step_duration)
+}
+
+/// Estimate in which order and when the simulations in the queue are started.
+///
+/// `busy` contains the seconds until each worker that is computing a simulation is expected to be
+/// free, out of `workers` workers in total. `queue` is ordered in the same way as the runner takes
+/// simulations from it: a worker that is free takes the first simulation that may be started.
+///
+/// Returns the index in `queue` of every simulation in the order they are expected to be started,
+/// along with the seconds from now at which they are expected to be started. The start is
+/// infinite if it depends on a duration that is unknown.
+pub fn estimate_starts(workers: usize, busy: &[f64], queue: &[Waiting]) -> Vec<(usize, f64)> {
+    let mut free = busy.to_vec();
+    free.resize(workers.max(busy.len()).max(1), 0.0);
+
+    let mut remaining: Vec<usize> = (0..queue.len()).collect();
+    let mut starts = Vec::with_capacity(queue.len());
+    while !remaining.is_empty() {
+        let (worker, time) = free
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("there is at least one worker");
+        // If nothing may be started yet, the worker waits for the first simulation that may.
+        let position = remaining
+            .iter()
+            .position(|&i| queue[i].not_before <= time)
+            .unwrap_or_else(|| {
+                let (position, _) = remaining
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, &a), (_, &b)| queue[a].not_before.total_cmp(&queue[b].not_before))
+                    .expect("remaining is not empty");
+                position
+            });
+        let i = remaining.remove(position);
+        let start = time.max(queue[i].not_before);
+        free[worker] = start + queue[i].duration;
+        starts.push((i, start));
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiting(not_before: f64, duration: f64) -> Waiting {
+        Waiting {
+            not_before,
+            duration,
+        }
+    }
+
+    #[test]
+    fn free_workers() {
+        let queue = [waiting(0.0, 10.0), waiting(0.0, 5.0), waiting(0.0, 1.0)];
+        assert_eq!(
+            estimate_starts(2, &[], &queue),
+            vec![(0, 0.0), (1, 0.0), (2, 5.0)]
+        );
+    }
+
+    #[test]
+    fn busy_workers() {
+        let queue = [waiting(0.0, 10.0), waiting(0.0, 5.0)];
+        assert_eq!(
+            estimate_starts(2, &[3.0, 8.0], &queue),
+            vec![(0, 3.0), (1, 8.0)]
+        );
+        // Other managers can compute more simulations than this manager has workers.
+        assert_eq!(
+            estimate_starts(1, &[3.0, 8.0], &queue),
+            vec![(0, 3.0), (1, 8.0)]
+        );
+    }
+
+    #[test]
+    fn not_before() {
+        let queue = [waiting(20.0, 10.0), waiting(0.0, 5.0), waiting(15.0, 1.0)];
+        assert_eq!(
+            estimate_starts(1, &[], &queue),
+            vec![(1, 0.0), (2, 15.0), (0, 20.0)]
+        );
+    }
+
+    #[test]
+    fn unknown_duration() {
+        assert_eq!(expected_duration(0, None), 0.0);
+        assert_eq!(expected_duration(4, Some(0.5)), 2.0);
+
+        let queue = [
+            waiting(0.0, expected_duration(10, None)),
+            waiting(0.0, 5.0),
+            waiting(30.0, 5.0),
+        ];
+        assert_eq!(
+            estimate_starts(1, &[], &queue),
+            vec![(0, 0.0), (1, f64::INFINITY), (2, f64::INFINITY)]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+
+use crate::database::SimulationsDB;
+
+/// What happens to a simulation when it expires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetentionAction {
+    /// Delete the simulation.
+    Delete,
+    /// Keep only every nth frame of the simulation and its last frame.
+    Downsample(u32),
+}
+
+/// When simulations expire and what happens to them, see
+/// [`SimulationsDB::get_expired_simulations`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<u32>,
+    pub max_per_twin: Option<u32>,
+    pub keep_pinned: bool,
+    pub action: RetentionAction,
+}
+
+impl RetentionPolicy {
+    /// Whether simulations expire at all.
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_per_twin.is_some()
+    }
+}
+
+/// What a run of the maintenance task reclaimed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Reclaimed {
+    pub deleted_simulations: i64,
+    pub downsampled_simulations: i64,
+    pub rows: i64,
+}
+
+/// The background task that deletes or downsamples expired simulations.
+pub struct Maintenance {
+    db: SimulationsDB,
+    policy: RetentionPolicy,
+}
+
+impl Maintenance {
+    pub async fn new(pool: PgPool, policy: RetentionPolicy) -> Result<Self> {
+        Ok(Self {
+            db: SimulationsDB::from_pg_pool(pool).await?,
+            policy,
+        })
+    }
+
+    /// Apply the retention policy every `interval`, starting right away.
+    pub async fn start(mut self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match self.run().await {
+                Ok(reclaimed) if reclaimed != Reclaimed::default() => info!(
+                    "Deleted {} and downsampled {} expired simulations, reclaiming {} rows",
+                    reclaimed.deleted_simulations,
+                    reclaimed.downsampled_simulations,
+                    reclaimed.rows
+                ),
+                Ok(_) => {}
+                Err(err) => error!("Error encountered in maintenance: {err:?}"),
+            }
+        }
+    }
+
+    /// Delete or downsample every expired simulation, each in its own transaction.
+    ///
+    /// A simulation that can not be expired is skipped, it is tried again in the next run. What
+    /// was reclaimed is added to the totals in the database.
+    pub async fn run(&mut self) -> Result<Reclaimed> {
+        let expired = self
+            .db
+            .get_expired_simulations(
+                self.policy.max_age_days.map(|days| days as i32),
+                self.policy.max_per_twin.map(i64::from),
+                self.policy.keep_pinned,
+                matches!(self.policy.action, RetentionAction::Downsample(_)),
+            )
+            .await?;
+
+        let mut reclaimed = Reclaimed::default();
+        for simulation_id in expired {
+            self.db.begin_transaction().await?;
+            match self.expire(simulation_id).await {
+                Ok(Some(rows)) => {
+                    self.db.commit().await?;
+                    match self.policy.action {
+                        RetentionAction::Delete => reclaimed.deleted_simulations += 1,
+                        RetentionAction::Downsample(_) => reclaimed.downsampled_simulations += 1,
+                    }
+                    reclaimed.rows += rows;
+                }
+                Ok(None) => self.db.rollback().await?,
+                Err(err) => {
+                    self.db.rollback().await?;
+                    warn!("Could not expire simulation {simulation_id}: {err:?}");
+                }
+            }
+        }
+
+        self.db
+            .add_maintenance_run(
+                reclaimed.deleted_simulations,
+                reclaimed.downsampled_simulations,
+                reclaimed.rows,
+            )
+            .await?;
+        Ok(reclaimed)
+    }
+
+    /// Delete or downsample an expired simulation, using the transaction of the database. Returns
+    /// the amount of rows that were reclaimed, or `None` when the simulation was left alone.
+    async fn expire(&mut self, simulation_id: i32) -> Result<Option<i64>> {
+        // Another manager might be expiring the same simulation, or the simulation might have
+        // been pinned or deleted since it was found to be expired.
+        let Some(retention) = self.db.lock_retention(simulation_id).await? else {
+            return Ok(None);
+        };
+        if retention.pinned && self.policy.keep_pinned {
+            return Ok(None);
+        }
+
+        let before = self.db.count_rows(simulation_id).await?;
+        match self.policy.action {
+            RetentionAction::Delete => self.db.delete_simulation_via_id(simulation_id).await?,
+            RetentionAction::Downsample(_) if retention.downsampled.is_some() => return Ok(None),
+            RetentionAction::Downsample(stride) => {
+                if before > 0 {
+                    let last = self.db.get_max_timestep(simulation_id).await?;
+                    self.db
+                        .keep_frames(simulation_id, &downsampled_time_steps(last, stride))
+                        .await?;
+                }
+                self.db
+                    .set_downsampled(simulation_id, stride as i32)
+                    .await?;
+            }
+        }
+        Ok(Some(before - self.db.count_rows(simulation_id).await?))
+    }
+}
+
+/// The time steps that are kept when a simulation whose last frame is `last` is downsampled:
+/// every `stride`th time step and the last one.
+pub fn downsampled_time_steps(last: i32, stride: u32) -> Vec<i32> {
+    (0..=last)
+        .filter(|time_step| is_kept(*time_step, last, stride))
+        .collect()
+}
+
+/// Whether `time_step` is kept when a simulation whose last frame is `last` is downsampled, see
+/// [`downsampled_time_steps`].
+pub fn is_kept(time_step: i32, last: i32, stride: u32) -> bool {
+    time_step % stride as i32 == 0 || time_step == last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsampling() {
+        assert_eq!(downsampled_time_steps(10, 5), vec![0, 5, 10]);
+        assert_eq!(downsampled_time_steps(11, 5), vec![0, 5, 10, 11]);
+        assert_eq!(downsampled_time_steps(3, 10), vec![0, 3]);
+        assert_eq!(downsampled_time_steps(0, 10), vec![0]);
+    }
+
+    #[test]
+    fn enabled() {
+        let mut policy = RetentionPolicy {
+            max_age_days: None,
+            max_per_twin: None,
+            keep_pinned: true,
+            action: RetentionAction::Delete,
+        };
+        assert!(!policy.is_enabled());
+        policy.max_per_twin = Some(5);
+        assert!(policy.is_enabled());
+    }
+
+    /// Tests downsampling and then deleting expired simulations. This test only runs if the
+    /// db_test feature is enabled.
+    #[cfg(feature = "db_test")]
+    #[sqlx::test(migrations = "../migrations/simulator/")]
+    async fn test_maintenance(pool: PgPool) {
+        use crate::database::StatusEnum;
+        use proto::simulation::State;
+
+        let mut db = SimulationsDB::from_pg_pool(pool.clone()).await.unwrap();
+        let mut ids = vec![];
+        for (name, pinned) in [("expired", false), ("newest", false), ("kept", true)] {
+            let id = db
+                .add_simulation(name, 1000, 10, StatusEnum::Finished, vec![])
+                .await
+                .unwrap();
+            db.set_twin(id, "twin").await.unwrap();
+            db.set_pinned(id, pinned).await.unwrap();
+            for time_step in 0..10 {
+                let state = State {
+                    graph: None,
+                    global_components: [(
+                        "time".to_string(),
+                        prost_types::Value {
+                            kind: Some(prost_types::value::Kind::NumberValue(time_step as f64)),
+                        },
+                    )]
+                    .into(),
+                };
+                db.add_frame(id, time_step, &state, None).await.unwrap();
+            }
+            ids.push(id);
+        }
+        // "kept" is pinned, so it does not count towards the maximum of the twin
+        let mut policy = RetentionPolicy {
+            max_age_days: None,
+            max_per_twin: Some(1),
+            keep_pinned: true,
+            action: RetentionAction::Downsample(4),
+        };
+
+        let mut maintenance = Maintenance::new(pool.clone(), policy.clone())
+            .await
+            .unwrap();
+        let reclaimed = maintenance.run().await.unwrap();
+        assert_eq!(reclaimed.downsampled_simulations, 1);
+        // frames 0, 4, 8 and 9 are kept, with one global component each
+        assert_eq!(reclaimed.rows, 2 * 10 - 2 * 4);
+        assert_eq!(db.get_retention(ids[0]).await.unwrap().downsampled, Some(4));
+        assert_eq!(maintenance.run().await.unwrap(), Reclaimed::default());
+
+        policy.action = RetentionAction::Delete;
+        let mut maintenance = Maintenance::new(pool, policy).await.unwrap();
+        let reclaimed = maintenance.run().await.unwrap();
+        assert_eq!(reclaimed.deleted_simulations, 1);
+        assert_eq!(reclaimed.rows, 2 * 4);
+
+        let stats = db.get_storage_stats().await.unwrap();
+        assert_eq!(stats.simulations, 2);
+        assert_eq!(stats.frames, 2 * 10);
+        assert_eq!(stats.downsampled_simulations, 1);
+        assert_eq!(stats.deleted_simulations, 1);
+        assert_eq!(stats.reclaimed_rows, 2 * 10 - 2 * 4 + 2 * 4);
+        assert!(stats.last_run.is_some());
+    }
+}
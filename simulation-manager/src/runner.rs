@@ -1,26 +1,38 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
 use futures::future;
-use std::collections::HashSet;
+use metrics::histogram;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::error;
+use std::time::{Duration, Instant};
+use tracing::{debug_span, error, info, info_span, warn, Instrument};
 
 // sqlx
+use sqlx::postgres::PgListener;
+use sqlx::types::chrono::Utc;
 use sqlx::PgPool;
 // tokio
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 use tokio::time::sleep;
 // tonic
 use tonic::transport::Channel;
+use tonic::Code;
 
 // proto
 use crate::connector::SimulatorsInfo;
-use crate::database::{SimulationsDB, StatusEnum};
-use crate::database_buffer::{StateTransport, StatusTransport, Transport};
+use crate::database::{ControlEnum, ErrorPolicyEnum, SimulationsDB, StatusEnum};
+use crate::database_buffer::{
+    BufferSender, StateTransport, StatusTransport, StoppedTransport, Transport,
+};
+use crate::schedule::{self, SimulatorIo};
+use crate::stop::StopConditions;
+use crate::telemetry;
+use crate::topology::Topology;
+use crate::upgrade;
 use proto::simulation::simulator::{
-    simulator_client::SimulatorClient, InitialState, IoConfigRequest,
+    simulator_client::SimulatorClient, InitialState, IoConfigRequest, TeardownRequest,
+    TimestepRequest, TimestepResult as SimulatorTimestepResult, TopologyChanges,
 };
-use proto::simulation::{ComponentType, Graph, State};
+use proto::simulation::{ComponentSpecification, ComponentType, Graph, State};
 
 /// Enum that represents the set-up status of the simulation
 #[derive(PartialEq)]
@@ -29,151 +41,650 @@ pub enum SetupStatus {
     Success,
 }
 
+/// How the runner handles simulators that return an error or do not respond during a timestep.
+#[derive(Debug, Clone, Copy)]
+pub struct TimestepPolicy {
+    /// How long a timestep may take, for simulators that did not ask for their own deadline.
+    pub timeout: Duration,
+    /// How many times a timestep is retried when a simulator is unavailable or too slow.
+    pub retries: u32,
+    /// How long to wait before the first retry. The wait doubles with every retry.
+    pub backoff: Duration,
+}
+
+impl Default for TimestepPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Do a timestep on a simulator.
+///
+/// The timestep is retried with exponential backoff when the simulator returns `Unavailable` or
+/// does not respond within `timeout`. Other errors are returned immediately.
+async fn do_timestep(
+    simulator: &mut SimulatorClient<Channel>,
+    request: TimestepRequest,
+    timeout: Duration,
+    policy: TimestepPolicy,
+) -> Result<SimulatorTimestepResult, tonic::Status> {
+    let mut backoff = policy.backoff;
+    let mut attempt = 0;
+    loop {
+        let result =
+            match tokio::time::timeout(timeout, simulator.do_timestep(request.clone())).await {
+                Ok(result) => result.map(|response| response.into_inner()),
+                Err(_) => Err(tonic::Status::deadline_exceeded(format!(
+                    "simulator did not respond within {timeout:?}"
+                ))),
+            };
+        match result {
+            Err(err)
+                if attempt < policy.retries
+                    && matches!(err.code(), Code::Unavailable | Code::DeadlineExceeded) =>
+            {
+                attempt += 1;
+                warn!(
+                    "Timestep failed: {} ({}), retrying in {backoff:?} (attempt {attempt}/{})",
+                    err.message(),
+                    err.code(),
+                    policy.retries
+                );
+                sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The channel on which the database notifies that a simulation has been queued.
+const QUEUE_CHANNEL: &str = "simulation_queue";
+
+/// How often the runner looks for interrupted simulations, see [`Runner::resume_interrupted`].
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Get the names of the simulators in `selection` that are not in `simulators`.
+fn missing_simulators(simulators: &[SimulatorsInfo], selection: &[String]) -> Vec<String> {
+    selection
+        .iter()
+        .filter(|name| !simulators.iter().any(|sim| &sim.name == *name))
+        .cloned()
+        .collect()
+}
+
+/// Get the names of the simulators in `selected` whose connection is not in `simulators`
+/// anymore. A simulator that reconnected has a new connection, so it is also disconnected.
+fn disconnected_simulators(
+    simulators: &[SimulatorsInfo],
+    selected: &[SimulatorsInfo],
+) -> Vec<String> {
+    selected
+        .iter()
+        .filter(|selected| !simulators.iter().any(|sim| sim.id == selected.id))
+        .map(|sim| sim.name.clone())
+        .collect()
+}
+
 /// The runner contains all the functionality to interface with all the different simulators.
 ///
-/// The runner holds a database connection, a vector of known simulators and a receiver for the
-/// asynchronous channel created in main.rs
+/// The runner holds a database connection, a vector of known simulators and a listener that is
+/// notified when a simulation is queued. It takes simulations from the queue and hands each of
+/// them to a [`Worker`]. At most `workers` simulations are run at the same time.
 pub struct Runner {
     db: SimulationsDB,
+    pool: PgPool,
     simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
-    notif_receiver: mpsc::Receiver<()>,
-    state_sender: mpsc::UnboundedSender<Transport>,
+    listener: PgListener,
+    state_sender: BufferSender,
+    workers: Arc<Semaphore>,
+    /// When the runner last looked for interrupted simulations.
+    last_recovery: Option<Instant>,
+    timestep_policy: TimestepPolicy,
+}
+
+/// A worker runs a single simulation from start to finish.
+///
+/// Every worker has its own database connection, its own set of selected simulators and reports
+/// the status of its simulation to the database buffer. This allows multiple workers to run next
+/// to each other without interfering.
+pub struct Worker {
+    db: SimulationsDB,
+    simulation_id: i32,
+    simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
+    state_sender: BufferSender,
+    timestep_policy: TimestepPolicy,
     input_components: HashSet<String>,
+    /// The connections to the selected simulators that the simulation was set up with. A
+    /// simulator that reconnects does not know the simulation, so its new connection is not used.
+    selected: Vec<SimulatorsInfo>,
+    /// Names of the selected simulators, grouped in the stages in which they are run during a
+    /// timestep. See [`schedule::stages`].
+    stages: Vec<Vec<String>>,
+    /// Status info if the simulation failed before it was started. This status is written
+    /// directly to the database.
+    failed: Option<String>,
 }
 
 impl Runner {
-    /// Create a new Runner
+    /// Create a new Runner that runs at most `workers` simulations at the same time.
     pub async fn new(
         pool: PgPool,
         simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
-        notif_receiver: mpsc::Receiver<()>,
-        state_sender: mpsc::UnboundedSender<Transport>,
+        state_sender: BufferSender,
+        workers: usize,
     ) -> anyhow::Result<Self> {
+        if workers == 0 {
+            bail!("the runner needs at least one worker");
+        }
+        let mut listener = PgListener::connect_with(&pool)
+            .await
+            .context("Failed to set up a listener for the queue")?;
+        listener
+            .listen(QUEUE_CHANNEL)
+            .await
+            .context("Failed to listen to the queue")?;
         Ok(Self {
-            db: SimulationsDB::from_pg_pool(pool)
+            db: SimulationsDB::from_pg_pool(pool.clone())
                 .await
                 .context("Failed to setup a pool to the database from the simulation manager")?,
+            pool,
             simulators,
-            notif_receiver,
+            listener,
             state_sender,
-            input_components: HashSet::new(),
+            workers: Arc::new(Semaphore::new(workers)),
+            last_recovery: None,
+            timestep_policy: TimestepPolicy::default(),
         })
     }
-    ///Get the selected simulators
+
+    /// Set how the workers handle simulators that fail during a timestep.
+    pub fn with_timestep_policy(mut self, policy: TimestepPolicy) -> Self {
+        self.timestep_policy = policy;
+        self
+    }
+
+    /// Start the runner.
+    ///
+    /// A simulation is only taken from the queue once a worker is available, so simulations are
+    /// still started in the order of the queue. Every simulation is then set up and run by its own
+    /// [`Worker`] on a separate task.
+    /// If no simulation can be started the runner waits until the database notifies it that a
+    /// simulation has been queued, until the first simulation that is scheduled for later may
+    /// be started, or until it looks for interrupted simulations again.
     ///
-    /// Get all the SimulatorClients for a simulation by looking at each entry in self.simulators and
-    /// for each one where the name is in the list of selected simulators add it to the selected list.
-    pub async fn get_selected_simulators(
+    /// Multiple managers can take simulations from the same queue. Every simulation that is being
+    /// computed is locked by the worker computing it, see [`SimulationsDB::lock_simulation`].
+    /// Simulations that were still being computed when their manager stopped are continued from
+    /// the last frame that was stored, see [`Runner::resume_interrupted`].
+    pub async fn start(&mut self) -> anyhow::Result<()> {
+        loop {
+            self.resume_interrupted().await?;
+
+            // Wait for a free worker before taking a simulation out of the queue.
+            let permit = self.acquire_worker().await?;
+
+            let mut db = SimulationsDB::from_pg_pool(self.pool.clone()).await?;
+            let top = db
+                .get_next_simulation()
+                .await
+                .context("could not get next simulation")?;
+            if let Some(simulation_id) = top {
+                self.spawn_worker(db, simulation_id, permit).await?;
+            } else {
+                drop(permit);
+                let next = self
+                    .db
+                    .get_next_not_before()
+                    .await
+                    .context("could not get the next scheduled simulation")?;
+                let scheduled = next.map(|next| (next - Utc::now()).to_std().unwrap_or_default());
+                let recovery = self.last_recovery.map_or(Duration::ZERO, |last| {
+                    RECOVERY_INTERVAL.saturating_sub(last.elapsed())
+                });
+                tokio::select! {
+                    notification = self.listener.recv() => {
+                        notification.context("could not listen to the queue")?;
+                    },
+                    _ = sleep(scheduled.unwrap_or_default()), if scheduled.is_some() => {},
+                    _ = sleep(recovery) => {},
+                }
+            }
+        }
+    }
+
+    /// Continue the simulations that are being computed, but that no manager is computing
+    /// anymore. These are the simulations of managers that stopped or lost their connection to the
+    /// database, including this manager before it was restarted.
+    ///
+    /// This is checked every [`RECOVERY_INTERVAL`], when a worker is available. Right after a
+    /// restart the simulators have not reconnected yet, so a simulation is only continued once all
+    /// of its selected simulators are connected. Until then it keeps its status.
+    async fn resume_interrupted(&mut self) -> anyhow::Result<()> {
+        if self
+            .last_recovery
+            .is_some_and(|last| last.elapsed() < RECOVERY_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_recovery = Some(Instant::now());
+
+        let computing = self
+            .db
+            .get_simulations_with_status(StatusEnum::Computing)
+            .await
+            .context("could not get interrupted simulations")?;
+        for simulation_id in computing {
+            let selection = self
+                .db
+                .get_selected_simulators(simulation_id)
+                .await?
+                .unwrap_or_default();
+            let missing = missing_simulators(&self.simulators.lock().await, &selection);
+            if !missing.is_empty() {
+                continue;
+            }
+
+            let Ok(permit) = self.workers.clone().try_acquire_owned() else {
+                // Try again once a worker is available.
+                return Ok(());
+            };
+            let mut db = SimulationsDB::from_pg_pool(self.pool.clone()).await?;
+            if !db
+                .lock_simulation(simulation_id)
+                .await
+                .context("could not lock interrupted simulation")?
+            {
+                // The simulation is being computed by a worker, or is no longer being computed.
+                continue;
+            }
+            info!("Resuming interrupted simulation `{simulation_id}`");
+            self.spawn_worker(db, simulation_id, permit).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait until a worker is available.
+    async fn acquire_worker(&self) -> anyhow::Result<OwnedSemaphorePermit> {
+        self.workers
+            .clone()
+            .acquire_owned()
+            .await
+            .context("worker pool was closed")
+    }
+
+    /// Run a simulation on a new worker, using the database connection that holds the lock on the
+    /// simulation. The worker is freed again and the lock is released when the simulation stops.
+    async fn spawn_worker(
         &mut self,
+        db: SimulationsDB,
         simulation_id: i32,
-    ) -> anyhow::Result<Vec<SimulatorClient<Channel>>> {
-        let simulators = self.simulators.lock().await;
+        permit: OwnedSemaphorePermit,
+    ) -> anyhow::Result<()> {
+        let mut worker = Worker::new(
+            db,
+            simulation_id,
+            self.simulators.clone(),
+            self.state_sender.clone(),
+            self.timestep_policy,
+        )
+        .await?;
+        tokio::spawn(
+            async move {
+                if let Err(err) = worker.run().await {
+                    error!("Error encountered in worker for simulation `{simulation_id}`: {err:?}");
+                }
+                if let Err(err) = worker.db.unlock_simulation(simulation_id).await {
+                    error!("Could not release the lock on simulation `{simulation_id}`: {err:?}");
+                }
+                // Free up the worker for the next simulation.
+                drop(permit);
+            }
+            .instrument(info_span!("simulation", simulation_id)),
+        );
+        Ok(())
+    }
+}
 
-        // get only selected simulators
+impl Worker {
+    /// Create a new worker for the simulation with id `simulation_id`.
+    pub async fn new(
+        db: SimulationsDB,
+        simulation_id: i32,
+        simulators: Arc<Mutex<Vec<SimulatorsInfo>>>,
+        state_sender: BufferSender,
+        timestep_policy: TimestepPolicy,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            db,
+            simulation_id,
+            simulators,
+            state_sender,
+            timestep_policy,
+            input_components: HashSet::new(),
+            selected: Vec::new(),
+            stages: Vec::new(),
+            failed: None,
+        })
+    }
+
+    /// Get the selected simulators along with their connection info.
+    async fn get_selected_simulators_info(
+        &mut self,
+        simulation_id: i32,
+    ) -> anyhow::Result<Vec<SimulatorsInfo>> {
         let selection = self
             .db
             .get_selected_simulators(simulation_id)
             .await?
-            .unwrap_or(Vec::new());
-        let selected = simulators
+            .unwrap_or_default();
+        let simulators = self.simulators.lock().await;
+        Ok(simulators
             .iter()
             .filter(|sim| selection.contains(&sim.name))
-            .map(|sim| sim.simulator.clone())
-            .collect::<Vec<_>>();
-        drop(simulators);
-        Ok(selected)
+            .cloned()
+            .collect())
     }
-    /// Start the runner.
+
+    /// Get the names of the simulators in `selection` that are not connected (anymore).
+    async fn get_missing_simulators(&self, selection: &[String]) -> Vec<String> {
+        missing_simulators(&self.simulators.lock().await, selection)
+    }
+
+    /// Drop the connections to the selected simulators that disconnected or reconnected since the
+    /// simulation was set up, and get their names.
+    async fn drop_disconnected_simulators(&mut self) -> Vec<String> {
+        let simulators = self.simulators.lock().await;
+        let disconnected = disconnected_simulators(&simulators, &self.selected);
+        self.selected
+            .retain(|selected| simulators.iter().any(|sim| sim.id == selected.id));
+        disconnected
+    }
+
+    /// Run the simulation of this worker.
     ///
-    /// The runner is currently implemented to use busy waiting to poll the database for new simulations.
-    /// It will then set up every simulator and start the simulation. Currently, simulations are handled
-    /// one by one.
-    /// If no new simulation is found the runner waits 30 sec before checking the database again except
-    /// if during this wait time a message is received over the asynchronous channel.
-    pub async fn start(&mut self) -> anyhow::Result<()> {
-        loop {
-            let top = self
-                .db
-                .get_next_simulation()
+    /// The worker checks the configuration of the selected simulators, sets them up and runs the
+    /// simulation. Status updates are sent to the database buffer.
+    pub async fn run(&mut self) -> anyhow::Result<()> {
+        let simulation_id = self.simulation_id;
+        let selection = self
+            .db
+            .get_selected_simulators(simulation_id)
+            .await?
+            .unwrap_or_default();
+        let missing = self.get_missing_simulators(&selection).await;
+        if !missing.is_empty() {
+            self.fail(
+                simulation_id,
+                format!(
+                    "Selected simulators are not connected: {}",
+                    missing.join(", ")
+                ),
+            )
+            .await
+            .context("could not update status")?;
+        } else {
+            // The configurations are committed before the simulation is run. Rows that refer to
+            // the simulation lock it, so it could not be deleted while the transaction is open.
+            self.db
+                .begin_transaction()
                 .await
-                .context("could not get next simulation")?;
-            if let Some(simulation_id) = top {
+                .context("could not begin transaction")?;
+            // check that the simulators do not change the same information
+            let mut output_components: HashSet<String> = HashSet::default();
+            let mut io = Vec::new();
+            let mut components = HashMap::new();
+            let mut clients = Vec::new();
+            // get only selected simulators
+            self.selected = self.get_selected_simulators_info(simulation_id).await?;
+            for mut sim in self.selected.clone() {
+                let request = tonic::Request::new(IoConfigRequest {});
+                let response = sim.simulator.get_io_config(request).await?.into_inner();
+                if let Err(err) =
+                    upgrade::merge_components(&mut components, response.components.clone())
+                {
+                    if self.failed.is_none() {
+                        self.fail(simulation_id, format!("Incompatible simulators: {err}"))
+                            .await
+                            .context("could not update status")?;
+                    }
+                }
+                clients.push(sim.simulator.clone());
+                // archives of the simulation include the configuration it was computed with
                 self.db
-                    .begin_transaction()
+                    .set_simulator_config(simulation_id, &sim.name, &response)
                     .await
-                    .context("could not begin transaction")?;
-
-                // check that the simulators do not change the same information
-                let mut output_components: HashSet<String> = HashSet::default();
-                // get only selected simulators
-                let mut selected = self.get_selected_simulators(simulation_id).await?;
-                for server in &mut selected {
-                    let request = tonic::Request::new(IoConfigRequest {});
-                    let response = server.get_io_config(request).await?.into_inner();
-                    for name in response.required_input_components {
-                        self.input_components.insert(name);
-                    }
-                    for name in response.optional_input_components {
-                        self.input_components.insert(name);
-                    }
-                    for name in response.output_components {
-                        if !output_components.insert(name) {
-                            self.db
-                                .update_status(
-                                    simulation_id,
-                                    StatusEnum::Failed,
-                                    Some("multiple simulators change the same components"),
-                                )
-                                .await
-                                .context("could not update status")?;
-                            break;
-                        }
+                    .context("could not store the simulator config")?;
+                let mut simulator_io = SimulatorIo {
+                    name: sim.name,
+                    ..Default::default()
+                };
+                for name in response
+                    .required_input_components
+                    .into_iter()
+                    .chain(response.optional_input_components)
+                {
+                    self.input_components.insert(name.clone());
+                    simulator_io.inputs.insert(name);
+                }
+                for name in response.output_components {
+                    simulator_io.outputs.insert(name.clone());
+                    if !output_components.insert(name) && self.failed.is_none() {
+                        self.fail(
+                            simulation_id,
+                            "multiple simulators change the same components".to_string(),
+                        )
+                        .await
+                        .context("could not update status")?;
                     }
                 }
-                let status = self
-                    .db
-                    .get_status(simulation_id)
-                    .await
-                    .context("could not get status")?;
-                if status != StatusEnum::Failed {
-                    // Make error handling easier by putting the two functions below into one async
-                    // block. This allows errors from both to be handled by the same code.
-                    let do_simulation = async {
-                        if self.set_up(simulation_id).await.context("in `set_up`")?
-                            == SetupStatus::Success
-                        {
-                            self.start_simulation(simulation_id)
-                                .await
-                                .context("in `start_simulation`")?;
-                        }
-                        anyhow::Ok(())
-                    };
-                    if let Err(err) = do_simulation.await {
-                        error!("Simulation `{simulation_id}` failed: {err:?}");
-                        // Send to buffer so it does not get overwritten by status updates in the buffer.
-                        let status = StatusTransport {
+                io.push(simulator_io);
+            }
+
+            // simulators that use the output of other simulators run after them
+            if self.failed.is_none() {
+                match schedule::stages(&io) {
+                    Ok(stages) => self.stages = stages,
+                    Err(cycle) => {
+                        self.fail(
                             simulation_id,
-                            status: StatusEnum::Failed,
-                            status_info: format!(
-                                "An internal error occurred when running the simulation: {err:#}"
+                            format!(
+                                "Selected simulators have a cyclic dependency: {}",
+                                cycle.join(", ")
                             ),
-                        };
-                        self.state_sender
-                            .send(Transport::Status(status))
-                            .context("could not update status after failed simulation")?;
+                        )
+                        .await
+                        .context("could not update status")?;
                     }
                 }
-                self.db
-                    .commit()
+            }
+            self.db
+                .commit()
+                .await
+                .context("could not commit transaction")?;
+
+            // the components are not upgraded once the simulation failed
+            if self.failed.is_none() {
+                self.upgrade_components(simulation_id, &components, clients)
                     .await
-                    .context("could not commit transaction")?;
-            } else {
-                tokio::select! {
-                    _ = sleep(Duration::from_secs(30)) => {},
-                    _ = self.notif_receiver.recv() => {},
+                    .context("could not upgrade the components")?;
+            }
+        }
+        let status = self
+            .db
+            .get_status(simulation_id)
+            .await
+            .context("could not get status")?;
+        if status != StatusEnum::Failed {
+            // Make error handling easier by putting the two functions below into one async
+            // block. This allows errors from both to be handled by the same code.
+            let do_simulation = async {
+                let start = self.get_start_timestep(simulation_id).await?;
+                if self
+                    .set_up(simulation_id, start)
+                    .instrument(debug_span!("set_up", start))
+                    .await
+                    .context("in `set_up`")?
+                    == SetupStatus::Success
+                {
+                    self.start_simulation(simulation_id, start)
+                        .await
+                        .context("in `start_simulation`")?;
                 }
+                anyhow::Ok(())
+            };
+            if let Err(err) = do_simulation.await {
+                error!("Simulation `{simulation_id}` failed: {err:?}");
+                // Send to buffer so it does not get overwritten by status updates in the buffer.
+                let status = StatusTransport {
+                    simulation_id,
+                    status: StatusEnum::Failed,
+                    status_info: format!(
+                        "An internal error occurred when running the simulation: {err:#}"
+                    ),
+                };
+                self.state_sender
+                    .send(Transport::Status(status))
+                    .context("could not update status after failed simulation")?;
             }
+            self.teardown(simulation_id).await?;
         }
+        // A request that arrived after the last check does not apply anymore.
+        self.db
+            .take_control(simulation_id)
+            .await
+            .context("could not clear the control request")?;
+        Ok(())
+    }
+
+    /// Upgrade the components of the initial state that have an older version than the
+    /// simulators use, which happens when the simulators were updated while the simulation was
+    /// queued.
+    ///
+    /// Only the initial state can be upgraded, so the simulation fails when it already computed
+    /// timesteps, as it does when a component would have to be downgraded.
+    ///
+    /// The upgraded state and the new versions are committed before the first timestep, so the
+    /// first frame is stored against the upgraded state, and a resumed simulation keeps them.
+    async fn upgrade_components(
+        &mut self,
+        simulation_id: i32,
+        components: &HashMap<String, ComponentSpecification>,
+        simulators: Vec<SimulatorClient<Channel>>,
+    ) -> anyhow::Result<()> {
+        let mut versions = self.db.get_component_versions(simulation_id).await?;
+        let outdated = match upgrade::outdated(&versions, components) {
+            Ok(outdated) if outdated.is_empty() => return Ok(()),
+            Ok(outdated) => outdated,
+            Err(err) => return self.fail(simulation_id, err).await,
+        };
+        if self.get_start_timestep(simulation_id).await? > 0 {
+            let names: Vec<&str> = outdated.keys().map(String::as_str).collect();
+            return self
+                .fail(
+                    simulation_id,
+                    format!(
+                        "The simulators use newer versions of components {}, branch the simulation to upgrade them",
+                        names.join(", ")
+                    ),
+                )
+                .await;
+        }
+
+        let mut state = self
+            .db
+            .get_frames(simulation_id, &[0], None)
+            .await?
+            .remove(&0)
+            .unwrap_or_default();
+        if let Err(err) = upgrade::upgrade_state(&mut state, &outdated, simulators).await {
+            return self
+                .fail(
+                    simulation_id,
+                    format!("Could not upgrade the initial state: {err}"),
+                )
+                .await;
+        }
+        self.db.begin_transaction().await?;
+        let stored = async {
+            upgrade::store_upgraded(&mut self.db, simulation_id, &state, &outdated).await?;
+            versions.extend(upgrade::versions_of(components));
+            self.db
+                .set_component_versions(simulation_id, &versions)
+                .await
+        }
+        .await;
+        self.db.end_transaction(stored).await
+    }
+
+    /// Mark the simulation as failed before it was started.
+    async fn fail(&mut self, simulation_id: i32, status_info: String) -> anyhow::Result<()> {
+        self.db
+            .update_status(simulation_id, StatusEnum::Failed, Some(&status_info))
+            .await?;
+        self.failed = Some(status_info);
+        Ok(())
+    }
+
+    /// Get the last timestep that was stored for the simulation. A simulation that is started for
+    /// the first time only has timestep 0, a simulation that is resumed continues from here.
+    async fn get_start_timestep(&mut self, simulation_id: i32) -> anyhow::Result<i32> {
+        self.db
+            .get_max_timestep(simulation_id)
+            .await
+            .context("error getting max timestep")
+    }
+
+    /// Check whether the simulation should stop before computing the next timestep. The request
+    /// is read from the database, so it can be made by any manager.
+    ///
+    /// If a request was made, the new status is sent to the database buffer and `true` is
+    /// returned.
+    async fn handle_control_request(&mut self, simulation_id: i32) -> anyhow::Result<bool> {
+        let control = self
+            .db
+            .take_control(simulation_id)
+            .await
+            .context("could not get the control request")?;
+        let status = match control {
+            Some(ControlEnum::Pause) => StatusEnum::Paused,
+            Some(ControlEnum::Cancel) => StatusEnum::Cancelled,
+            None => return Ok(false),
+        };
+        // Send to buffer so the status is only updated after all previous timesteps are stored.
+        let status = StatusTransport {
+            simulation_id,
+            status,
+            status_info: String::new(),
+        };
+        self.state_sender
+            .send(Transport::Status(status))
+            .context("status was not updated")?;
+        Ok(true)
+    }
+
+    /// End the session of a simulation on all selected simulators.
+    ///
+    /// Simulators that could not be reached are ignored, as they will remove the session
+    /// themselves once it has not been used for some time.
+    async fn teardown(&mut self, simulation_id: i32) -> anyhow::Result<()> {
+        self.drop_disconnected_simulators().await;
+        let selected = self.selected.iter().map(|sim| sim.simulator.clone());
+        let results = future::join_all(selected.map(|mut server| async move {
+            let request = tonic::Request::new(TeardownRequest {
+                session_id: simulation_id.to_string(),
+            });
+            server.teardown(request).await
+        }))
+        .await;
+        for err in results.into_iter().filter_map(Result::err) {
+            warn!("Could not tear down session of simulation `{simulation_id}`: {err}");
+        }
+        Ok(())
     }
 
     /// Set up a simulation based on simulation id.
@@ -181,15 +692,16 @@ impl Runner {
     /// The setup for a simulation consists of creating an initial state.
     /// The runner assumes all data needed to run a simulation is present in the database. This means
     /// every node and edge along with its components and the global components should be
-    /// present with time_step == 0.
+    /// present with time_step == start. For a new simulation this is timestep 0, a resumed
+    /// simulation starts from the last timestep that was stored.
     /// The runner will get all these nodes, edges and components, compose a proto::simulation::Graph
     /// and then put this graph along with the step size into a state. This state is then sent to the
     /// simulators, and it will return Success as an SetupStatus enum.
     /// However, the state will only be sent to the simulators if all necessary global components are
     /// available. If this is not the case, the simulation will directly get the status of "failed"
     /// and the function will return Failed as the SetupStatus.
-    async fn set_up(&mut self, simulation_id: i32) -> anyhow::Result<SetupStatus> {
-        let mut selected = self.get_selected_simulators(simulation_id).await?;
+    async fn set_up(&mut self, simulation_id: i32, start: i32) -> anyhow::Result<SetupStatus> {
+        let mut selected = self.selected.clone();
 
         // get tick delta
         let delta = self
@@ -202,22 +714,22 @@ impl Runner {
             edge: vec![],
         };
 
-        // get current simulation nodes at timestep 0 and add to graph
+        // get current simulation nodes at the start timestep and add to graph
         let mut nodes = self
             .db
             .get_nodes_filtered(
                 simulation_id,
-                0,
+                start,
                 Some(self.input_components.clone().into_iter().collect()),
             )
             .await
             .context("error getting nodes")?;
         graph.nodes.append(&mut nodes);
 
-        // get current simulation edges at timestep 0 and add to graph
+        // get current simulation edges at the start timestep and add to graph
         let mut edges = self
             .db
-            .get_edges(simulation_id, 0)
+            .get_edges(simulation_id, start)
             .await
             .context("error getting edges")?;
         graph.edge.append(&mut edges);
@@ -225,14 +737,14 @@ impl Runner {
         // add all global components to the graph
         let globals = self
             .db
-            .get_global_components(simulation_id, 0)
+            .get_global_components(simulation_id, start)
             .await
             .context("error getting global components")?;
 
         // check if the necessary components are present
-        for server in &mut selected {
+        for sim in &mut selected {
             let request = tonic::Request::new(IoConfigRequest {});
-            let config = server.get_io_config(request).await?.into_inner();
+            let config = sim.simulator.get_io_config(request).await?.into_inner();
             let required = &config.required_input_components.clone();
             let component_info = &config.components;
             for component in required {
@@ -247,20 +759,15 @@ impl Runner {
                     .try_into()
                     .context("type of component was not recognized")?;
                 if comp_type == ComponentType::Global && !(globals.contains_key(component)) {
-                    self.db
-                        .update_status(
-                            simulation_id,
-                            StatusEnum::Failed,
-                            Some(
-                                format!(
-                                    "You're missing at least the global variable: {:?}",
-                                    component
-                                )
-                                .as_str(),
-                            ),
-                        )
-                        .await
-                        .context("status was not updated")?;
+                    self.fail(
+                        simulation_id,
+                        format!(
+                            "You're missing at least the global variable: {:?}",
+                            component
+                        ),
+                    )
+                    .await
+                    .context("status was not updated")?;
                     break;
                 }
             }
@@ -280,6 +787,7 @@ impl Runner {
                     global_components: globals,
                 }),
                 timestep_delta: delta as u64,
+                session_id: simulation_id.to_string(),
             };
 
             // Setup of simulators. If any one of the simulators returns en error, set the status as
@@ -288,26 +796,28 @@ impl Runner {
                 selected
                     .clone()
                     .into_iter()
-                    .map(|server| (initial_state.clone(), server))
-                    .map(|(initial_state, mut server)| async move {
+                    .map(|sim| (initial_state.clone(), sim))
+                    .map(|(initial_state, mut sim)| async move {
                         let setup_request = tonic::Request::new(initial_state);
-                        server.setup(setup_request).await
+                        let setup_start = Instant::now();
+                        let response = sim.simulator.setup(setup_request).await;
+                        histogram!(telemetry::SETUP_DURATION, "simulator" => sim.name)
+                            .record(setup_start.elapsed());
+                        response
                     }),
             )
             .await
             {
-                self.db
-                    .update_status(
-                        simulation_id,
-                        StatusEnum::Failed,
-                        Some(&format!(
-                            "Simulator returned error during setup: {} ({})",
-                            err.message(),
-                            err.code()
-                        )),
-                    )
-                    .await
-                    .context("status was not updated")?;
+                self.fail(
+                    simulation_id,
+                    format!(
+                        "Simulator returned error during setup: {} ({})",
+                        err.message(),
+                        err.code()
+                    ),
+                )
+                .await
+                .context("status was not updated")?;
                 return Ok(SetupStatus::Failed);
             }
             return Ok(SetupStatus::Success);
@@ -317,13 +827,16 @@ impl Runner {
 
     /// Run a full simulation
     ///
-    /// For each tick in the simulation the runner will execute a timestep for each simulator.
+    /// For each tick in the simulation, starting at `start`, the runner will execute a timestep for
+    /// each simulator. Before every tick the runner checks whether the simulation was paused or
+    /// cancelled, in which case it stops.
     /// The runner will get all the needed components (nodes, edges and components) from the previous
-    /// timestep and send this state to the simulators using grpc and wait for a response. This now
-    /// happens in parallel to improve performance and decrease the time it takes to run a full simulation.
-    /// When the runner receives a response from a simulator, it will create a Transport object. Once
-    /// All simulators have responded. These different transport objects are returned as a vector.
-    /// The runner then combines all these responses into one big state and sends this to the
+    /// timestep and send this state to the simulators using grpc and wait for a response.
+    /// Simulators are run in the stages computed by [`schedule::stages`]: the simulators in a stage
+    /// run in parallel, and get the state with the output of the earlier stages of the same
+    /// timestep. When the runner receives a response from a simulator, it will create a Transport
+    /// object. Once all simulators in a stage have responded, their responses are merged into the
+    /// state. The runner then sends the combined state of the timestep to the
     /// database buffer using the async channel. The buffer will then write the timestep to the database.
     /// Writing to the database is done on a seperate thread so that the runner does not need to spend
     /// time waiting on the database. The async channel has an unbounded queue of messages so the
@@ -333,7 +846,9 @@ impl Runner {
     /// to be marked as complete while the buffer is still writing to the database.
     /// In the case that a simulator does not return all components the components that weren't sent
     /// back will be duplicated into the next timestep so that they are available in the next tick
-    async fn start_simulation(&mut self, simulation_id: i32) -> anyhow::Result<()> {
+    /// Simulators can also add and remove nodes and edges, these changes are applied after their
+    /// components are merged, see [`Topology`].
+    async fn start_simulation(&mut self, simulation_id: i32, start: i32) -> anyhow::Result<()> {
         // get amount of iterations to run the simulation for
         let iterations = self
             .db
@@ -348,21 +863,21 @@ impl Runner {
         };
         let mut nodes_send = self
             .db
-            .get_nodes(simulation_id, 0)
+            .get_nodes(simulation_id, start)
             .await
             .context("error getting nodes")?;
         graph.nodes.append(&mut nodes_send);
 
         let mut edges_send = self
             .db
-            .get_edges(simulation_id, 0)
+            .get_edges(simulation_id, start)
             .await
             .context("error getting edges")?;
         graph.edge.append(&mut edges_send);
 
         let globals = self
             .db
-            .get_global_components(simulation_id, 0)
+            .get_global_components(simulation_id, start)
             .await
             .context("error getting glboal components")?;
 
@@ -370,138 +885,298 @@ impl Runner {
             graph: Some(graph),
             global_components: globals,
         };
+        let (next_node, next_edge) = self
+            .db
+            .get_next_ids(simulation_id)
+            .await
+            .context("error getting the next node and edge ids")?;
+        let mut topology = Topology::new(next_node, next_edge);
 
-        let selected = self.get_selected_simulators(simulation_id).await?;
+        let selected: HashMap<String, SimulatorsInfo> = self
+            .selected
+            .clone()
+            .into_iter()
+            .map(|sim| (sim.name.clone(), sim))
+            .collect();
+        let error_policy = self
+            .db
+            .get_error_policy(simulation_id)
+            .await
+            .context("error getting error policy")?;
+        let policy = self.timestep_policy;
+        let stop_conditions = self
+            .db
+            .get_stop_conditions(simulation_id)
+            .await
+            .context("error getting stop conditions")?;
+        let mut stop_conditions = StopConditions::new(&stop_conditions)
+            .map_err(anyhow::Error::msg)
+            .context("invalid stop conditions")?;
+        // The frames that were computed before the simulation was paused count towards the
+        // consecutive frames in which a condition has to hold.
+        if !stop_conditions.is_empty() {
+            let first = 1.max(start + 2 - stop_conditions.max_consecutive() as i32);
+            let time_steps: Vec<i32> = (first..=start).collect();
+            let frames = self
+                .db
+                .get_frames(simulation_id, &time_steps, None)
+                .await
+                .context("error getting previous frames")?;
+            for frame in frames.values() {
+                stop_conditions.check(frame);
+            }
+        }
+
+        // All timesteps were already computed before the simulation was stopped.
+        if start >= iterations {
+            let status = StatusTransport {
+                simulation_id,
+                status: StatusEnum::Finished,
+                status_info: String::new(),
+            };
+            self.state_sender
+                .send(Transport::Status(status))
+                .context("error updating status")?;
+            return Ok(());
+        }
+
+        for i in start..iterations {
+            if self.handle_control_request(simulation_id).await? {
+                return Ok(());
+            }
+
+            // A simulator that was removed by the health check will not respond anymore, and a
+            // simulator that reconnected lost the session of the simulation.
+            let missing = self.drop_disconnected_simulators().await;
+            if !missing.is_empty() {
+                let status = StatusTransport {
+                    simulation_id,
+                    status: StatusEnum::Failed,
+                    status_info: format!(
+                        "Simulators disconnected during the simulation: {}",
+                        missing.join(", ")
+                    ),
+                };
+                self.state_sender
+                    .send(Transport::Status(status))
+                    .context("status was not updated")?;
+                return Ok(());
+            }
+
+            let step_start = Instant::now();
+            let timestep_span = debug_span!("timestep", frame = i + 1);
 
-        for i in 0..iterations {
             // Used to indicate whether a simulator experienced an error during simulation. A
             // separate enum is made for this as we want to handle this separately from other
             // error types.
             enum TimestepResult {
-                Ok(StateTransport),
-                Aborted(tonic::Status),
+                /// The name of the simulator, its output and the nodes and edges it added or
+                /// removed.
+                Ok(String, StateTransport, Option<TopologyChanges>),
+                /// The name of the simulator and the error it returned.
+                Aborted(String, tonic::Status),
             }
 
-            // parallel execution of the simulators in the simulation for the current time step
-            let results = future::try_join_all(
-                selected
-                    .clone()
-                    .into_iter()
-                    .map(|server| (prev.clone(), server))
-                    .map(|(prev, mut server)| async move {
-                        // get values from state
-                        let graph = prev
-                            .clone()
-                            .graph
-                            .context("Failed to get the graph of the previous timestep")?;
-                        let grpc_global = prev.clone().global_components;
-                        let nodes = graph.nodes.clone();
-                        let edges = graph.edge.clone();
-
-                        let mut edge_ids_sent = Vec::new();
-                        for edge in edges {
-                            edge_ids_sent.push(edge.id as i32);
-                        }
-                        let mut node_ids_sent = Vec::new();
-                        for node in nodes {
-                            node_ids_sent.push(node.id as i32);
-                        }
-                        let mut global_ids_sent = Vec::new();
-                        for (key, _value) in grpc_global.clone() {
-                            global_ids_sent.push(key);
-                        }
+            // make copy of previous state
+            let mut new_state = prev.clone();
+            let mut skipped_simulators = Vec::new();
+            topology.start_timestep();
+
+            for stage in &self.stages {
+                let stage = stage
+                    .iter()
+                    .map(|name| {
+                        selected
+                            .get(name)
+                            .cloned()
+                            .with_context(|| format!("simulator `{name}` is not connected"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
 
-                        // send to server and do time step
-                        let do_time_step_request = tonic::Request::new(State {
-                            graph: Some(graph.clone()),
-                            global_components: grpc_global.clone(),
-                        });
-                        let do_time_step_response =
-                            match server.do_timestep(do_time_step_request).await {
+                let timestep_span = &timestep_span;
+                // parallel execution of the simulators in this stage, on the state that includes the
+                // output of the previous stages
+                let results = future::try_join_all(
+                    stage.into_iter().map(|sim| (new_state.clone(), sim)).map(
+                        |(prev, mut sim)| async move {
+                            // get values from state
+                            let graph = prev
+                                .clone()
+                                .graph
+                                .context("Failed to get the graph of the previous timestep")?;
+                            let grpc_global = prev.clone().global_components;
+                            let nodes = graph.nodes.clone();
+                            let edges = graph.edge.clone();
+
+                            let mut edge_ids_sent = Vec::new();
+                            for edge in edges {
+                                edge_ids_sent.push(edge.id as i32);
+                            }
+                            let mut node_ids_sent = Vec::new();
+                            for node in nodes {
+                                node_ids_sent.push(node.id as i32);
+                            }
+                            let mut global_ids_sent = Vec::new();
+                            for (key, _value) in grpc_global.clone() {
+                                global_ids_sent.push(key);
+                            }
+
+                            // send to server and do time step
+                            let do_time_step_request = TimestepRequest {
+                                session_id: simulation_id.to_string(),
+                                state: Some(State {
+                                    graph: Some(graph.clone()),
+                                    global_components: grpc_global.clone(),
+                                }),
+                            };
+                            let timeout = sim.timestep_timeout.unwrap_or(policy.timeout);
+                            let timestep_start = Instant::now();
+                            let do_time_step_response = do_timestep(
+                                &mut sim.simulator,
+                                do_time_step_request,
+                                timeout,
+                                policy,
+                            )
+                            .instrument(debug_span!(
+                                parent: timestep_span,
+                                "do_timestep",
+                                simulator = %sim.name
+                            ))
+                            .await;
+                            let simulator = sim.name.clone();
+                            histogram!(telemetry::TIMESTEP_DURATION, "simulator" => simulator)
+                                .record(timestep_start.elapsed());
+                            let do_time_step_response = match do_time_step_response {
                                 Ok(val) => val,
-                                Err(err) => return Ok(TimestepResult::Aborted(err)),
+                                Err(err) => return Ok(TimestepResult::Aborted(sim.name, err)),
                             };
 
-                        // read out results of simulator
-                        let output_state = do_time_step_response
-                            .into_inner()
-                            .output_state
-                            .context("no output state found")?;
-
-                        // place results into Transport struct
-                        anyhow::Ok(TimestepResult::Ok(StateTransport {
-                            simulation_id,
-                            iteration: i + 1,
-                            state: output_state,
-                        }))
-                    }),
-            )
-            .await?;
+                            // read out results of simulator
+                            let output_state = do_time_step_response
+                                .output_state
+                                .context("no output state found")?;
 
-            // make copy of previous state
-            let mut new_state = prev.clone();
+                            // place results into Transport struct
+                            anyhow::Ok(TimestepResult::Ok(
+                                sim.name,
+                                StateTransport {
+                                    simulation_id,
+                                    iteration: i + 1,
+                                    state: output_state,
+                                    step_duration: step_start.elapsed(),
+                                    skipped_simulators: Vec::new(),
+                                },
+                                do_time_step_response.topology,
+                            ))
+                        },
+                    ),
+                )
+                .instrument(timestep_span.clone())
+                .await?;
 
-            // merge previous state with all output states
-            for result in results {
-                let result = match result {
-                    TimestepResult::Ok(v) => v,
-                    TimestepResult::Aborted(err) => {
-                        // Send to buffer to not get overwritten
-                        let status = StatusTransport {
-                            simulation_id,
-                            status: StatusEnum::Failed,
-                            status_info: format!(
-                                "Simulator returned error during timestep: {} ({})",
+                // merge the state with all output states of this stage
+                for result in results {
+                    let (name, result, changes) = match result {
+                        TimestepResult::Ok(name, result, changes) => (name, result, changes),
+                        TimestepResult::Aborted(name, err)
+                            if error_policy == ErrorPolicyEnum::SkipAndCarryForward =>
+                        {
+                            // The components of the simulator are not replaced, so the values of
+                            // the previous timestep are used.
+                            warn!(
+                                "Skipping simulator `{name}` in timestep {} of simulation `{simulation_id}`: {} ({})",
+                                i + 1,
                                 err.message(),
                                 err.code()
-                            ),
+                            );
+                            skipped_simulators.push(name);
+                            continue;
+                        }
+                        TimestepResult::Aborted(_, err) => {
+                            // Send to buffer to not get overwritten
+                            let status = StatusTransport {
+                                simulation_id,
+                                status: StatusEnum::Failed,
+                                status_info: format!(
+                                    "Simulator returned error during timestep: {} ({})",
+                                    err.message(),
+                                    err.code()
+                                ),
+                            };
+                            self.state_sender
+                                .send(Transport::Status(status))
+                                .context("status was not updated")?;
+                            // Return ok here as the error has already been handled. Returning an error
+                            // would override the status again.
+                            return Ok(());
+                        }
+                    };
+
+                    // Replace previous node with the version that has been returned by the simulator.
+                    // Since simulators can not edit the same nodes, this will always work.
+                    // Nodes that were not returned by any simulator will also still be present
+                    let result_graph = result
+                        .state
+                        .graph
+                        .context("Failed to get the graph in the result of the simulation")?;
+                    for result_node in result_graph.nodes {
+                        let Some(node) = new_state
+                            .graph
+                            .as_mut()
+                            .context("Failed to get the graph of the new state")?
+                            .nodes
+                            .iter_mut()
+                            .find(|n| n.id == result_node.id)
+                        else {
+                            // another simulator in this stage removed the node
+                            if topology.is_removed_node(result_node.id) {
+                                continue;
+                            }
+                            bail!(
+                                "Simulator `{name}` returned unknown node {}",
+                                result_node.id
+                            );
                         };
-                        self.state_sender
-                            .send(Transport::Status(status))
-                            .context("status was not updated")?;
-                        // Return ok here as the error has already been handled. Returning an error
-                        // would override the status again.
-                        return Ok(());
+                        for (name, c) in result_node.components.into_iter() {
+                            node.components.insert(name, c);
+                        }
                     }
-                };
 
-                // Replace previous node with the version that has been returned by the simulator.
-                // Since simulators can not edit the same nodes, this will always work.
-                // Nodes that were not returned by any simulator will also still be present
-                let result_graph = result
-                    .state
-                    .graph
-                    .context("Failed to get the graph in the result of the simulation")?;
-                for result_node in result_graph.nodes {
-                    let node = new_state
-                        .graph
-                        .as_mut()
-                        .context("Failed to get the graph of the new state")?
-                        .nodes
-                        .iter_mut()
-                        .find(|n| n.id == result_node.id)
-                        .context("Failed to get the nodes in the new state")?;
-                    for (name, c) in result_node.components.into_iter() {
-                        node.components.insert(name, c);
+                    // Idem for edges
+                    for result_edge in result_graph.edge {
+                        let Some(edge) = new_state
+                            .graph
+                            .as_mut()
+                            .context("Failed to get the graph of the new state")?
+                            .edge
+                            .iter_mut()
+                            .find(|e| e.id == result_edge.id)
+                        else {
+                            if topology.is_removed_edge(result_edge.id) {
+                                continue;
+                            }
+                            bail!(
+                                "Simulator `{name}` returned unknown edge {}",
+                                result_edge.id
+                            );
+                        };
+                        *edge = result_edge;
                     }
-                }
 
-                // Idem for edges
-                for result_edge in result_graph.edge {
-                    let edge = new_state
-                        .graph
-                        .as_mut()
-                        .context("Failed to get the graph of the new state")?
-                        .edge
-                        .iter_mut()
-                        .find(|e| e.id == result_edge.id)
-                        .context("Failed to get the edges in the new state")?;
-                    *edge = result_edge;
-                }
+                    // Idem for global components
+                    for (key, value) in result.state.global_components.clone() {
+                        new_state.global_components.insert(key, value);
+                    }
 
-                // Idem for global components
-                for (key, value) in result.state.global_components.clone() {
-                    new_state.global_components.insert(key, value);
+                    // Add and remove the nodes and edges the simulator asked for
+                    if let Some(changes) = changes {
+                        topology
+                            .apply(&mut new_state, changes)
+                            .map_err(anyhow::Error::msg)
+                            .with_context(|| {
+                                format!("Simulator `{name}` returned invalid topology changes")
+                            })?;
+                    }
                 }
             }
 
@@ -510,8 +1185,26 @@ impl Runner {
                 simulation_id,
                 iteration: i + 1,
                 state: new_state.clone(),
+                step_duration: step_start.elapsed(),
+                skipped_simulators,
             };
             self.state_sender.send(Transport::State(transport))?;
+            if let Some(index) = stop_conditions.check(&new_state) {
+                let stopped = StoppedTransport {
+                    simulation_id,
+                    condition: index as i32,
+                    iteration: i + 1,
+                    status_info: format!(
+                        "Stopped at frame {} by stop condition {index}: {}",
+                        i + 1,
+                        stop_conditions.description(index)
+                    ),
+                };
+                self.state_sender
+                    .send(Transport::Stopped(stopped))
+                    .context("error updating status")?;
+                return Ok(());
+            }
             let status = match i {
                 i if i == iterations - 1 => StatusEnum::Finished,
                 i if i < iterations - 1 => StatusEnum::Computing,
@@ -538,3 +1231,41 @@ impl Runner {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulator(name: &str, id: u64) -> SimulatorsInfo {
+        let channel = Channel::from_static("http://127.0.0.1:1").connect_lazy();
+        SimulatorsInfo {
+            simulator: SimulatorClient::new(channel),
+            name: name.to_string(),
+            id,
+            timestep_timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_simulator() {
+        let connected = vec![simulator("a", 0), simulator("b", 1)];
+        let selection = vec!["a".to_string(), "c".to_string()];
+        assert_eq!(missing_simulators(&connected, &selection), vec!["c"]);
+        assert!(missing_simulators(&connected, &selection[..1]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn reconnected_simulator() {
+        let selected = vec![simulator("a", 0), simulator("b", 1)];
+        assert!(disconnected_simulators(&selected, &selected).is_empty());
+        // `b` restarted and connected again, so it does not know the simulation anymore
+        let connected = vec![simulator("a", 0), simulator("b", 2)];
+        assert!(missing_simulators(&connected, &["a".to_string(), "b".to_string()]).is_empty());
+        assert_eq!(disconnected_simulators(&connected, &selected), vec!["b"]);
+        // `a` disconnected
+        assert_eq!(
+            disconnected_simulators(&connected[1..], &selected),
+            vec!["a", "b"]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::{BTreeSet, HashSet};
+
+/// The components a simulator reads and writes, taken from its `SimulatorIoConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatorIo {
+    pub name: String,
+    /// Required and optional input components.
+    pub inputs: HashSet<String>,
+    pub outputs: HashSet<String>,
+}
+
+/// Split simulators into stages that are run one after the other within a timestep.
+///
+/// A simulator depends on every other simulator that outputs one of its input components, and is
+/// placed in a later stage than all of them. Simulators in the same stage do not depend on each
+/// other and can be run in parallel. A simulator that reads its own output does not depend on
+/// itself, it sees the value of the previous timestep.
+///
+/// Returns the names of the simulators in each stage, or the names of the simulators that are
+/// part of a dependency cycle. Simulators that only wait on a cycle are not included.
+pub fn stages(simulators: &[SimulatorIo]) -> Result<Vec<Vec<String>>, Vec<String>> {
+    // dependencies[i] contains the simulators that simulator i waits on.
+    let mut dependencies: Vec<HashSet<usize>> = simulators
+        .iter()
+        .enumerate()
+        .map(|(i, consumer)| {
+            simulators
+                .iter()
+                .enumerate()
+                .filter(|(j, producer)| *j != i && !producer.outputs.is_disjoint(&consumer.inputs))
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect();
+
+    // Kahn's algorithm, taking all simulators without dependencies at once.
+    let mut remaining: BTreeSet<usize> = (0..simulators.len()).collect();
+    let mut stages = Vec::new();
+    while !remaining.is_empty() {
+        let stage: Vec<usize> = remaining
+            .iter()
+            .copied()
+            .filter(|i| dependencies[*i].is_empty())
+            .collect();
+        if stage.is_empty() {
+            return Err(remaining
+                .into_iter()
+                .filter(|i| in_cycle(&dependencies, *i))
+                .map(|i| simulators[i].name.clone())
+                .collect());
+        }
+        for i in &stage {
+            remaining.remove(i);
+        }
+        for deps in &mut dependencies {
+            for i in &stage {
+                deps.remove(i);
+            }
+        }
+        stages.push(
+            stage
+                .into_iter()
+                .map(|i| simulators[i].name.clone())
+                .collect(),
+        );
+    }
+    Ok(stages)
+}
+
+/// Whether simulator `start` (indirectly) waits on itself.
+fn in_cycle(dependencies: &[HashSet<usize>], start: usize) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack: Vec<usize> = dependencies[start].iter().copied().collect();
+    while let Some(i) = stack.pop() {
+        if i == start {
+            return true;
+        }
+        if visited.insert(i) {
+            stack.extend(&dependencies[i]);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn io(name: &str, inputs: &[&str], outputs: &[&str]) -> SimulatorIo {
+        SimulatorIo {
+            name: name.to_string(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn independent_simulators_share_a_stage() {
+        let sims = [io("a", &["x"], &["y"]), io("b", &["x"], &["z"])];
+        assert_eq!(stages(&sims).unwrap(), vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn consumers_run_after_producers() {
+        let sims = [
+            io("load-flow", &["sensor", "cable"], &["bus"]),
+            io("supply-demand", &["sensor"], &["sensor"]),
+            io("weather", &[], &["temperature"]),
+        ];
+        assert_eq!(
+            stages(&sims).unwrap(),
+            vec![vec!["supply-demand", "weather"], vec!["load-flow"]]
+        );
+    }
+
+    #[test]
+    fn chains_get_a_stage_per_simulator() {
+        let sims = [
+            io("c", &["y"], &["z"]),
+            io("b", &["x"], &["y"]),
+            io("a", &[], &["x"]),
+        ];
+        assert_eq!(
+            stages(&sims).unwrap(),
+            vec![vec!["a"], vec!["b"], vec!["c"]]
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let sims = [
+            io("a", &["y"], &["x"]),
+            io("b", &["x"], &["y"]),
+            io("c", &[], &["z"]),
+            io("d", &["x"], &["w"]),
+            io("e", &["w"], &[]),
+        ];
+        // `d` and `e` wait on the cycle, but are not part of it
+        assert_eq!(stages(&sims).unwrap_err(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn no_simulators() {
+        assert!(stages(&[]).unwrap().is_empty());
+    }
+}
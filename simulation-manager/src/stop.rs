@@ -0,0 +1,291 @@
+use std::cmp::Ordering;
+
+use prost_types::{value::Kind, Value};
+use proto::simulation::{
+    simulation_manager::{stop_condition::Operand, ComparisonOperator, StopCondition},
+    State,
+};
+
+use crate::sweep::{get_value, parse_path, Segment};
+
+/// A stop condition whose paths have been parsed.
+struct Condition {
+    component: String,
+    path: Vec<Segment>,
+    comparison: ComparisonOperator,
+    operand: Operand,
+    /// The path of the operand if it is a field of the component.
+    field: Vec<Segment>,
+    consecutive: u32,
+}
+
+/// Checks the stop conditions of a simulation after every computed frame, keeping track of how
+/// many consecutive frames every condition held in.
+pub struct StopConditions {
+    conditions: Vec<Condition>,
+    descriptions: Vec<String>,
+    held: Vec<u32>,
+}
+
+impl StopConditions {
+    /// Check that the conditions are valid: their paths can be parsed and they have an operand.
+    pub fn new(conditions: &[StopCondition]) -> Result<Self, String> {
+        let parsed = conditions
+            .iter()
+            .enumerate()
+            .map(|(index, condition)| {
+                let invalid = |err: String| format!("stop condition {index}: {err}");
+                let comparison = ComparisonOperator::try_from(condition.comparison)
+                    .map_err(|_| invalid("unknown comparison".to_string()))?;
+                let operand = condition
+                    .operand
+                    .clone()
+                    .ok_or_else(|| invalid("no operand".to_string()))?;
+                let field = match &operand {
+                    Operand::Field(path) => parse_path(path).map_err(invalid)?,
+                    Operand::Value(_) => vec![],
+                };
+                Ok(Condition {
+                    component: condition.component.clone(),
+                    path: parse_path(&condition.path).map_err(invalid)?,
+                    comparison,
+                    operand,
+                    field,
+                    consecutive: condition.consecutive.max(1),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            held: vec![0; parsed.len()],
+            descriptions: conditions.iter().map(describe).collect(),
+            conditions: parsed,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// The largest amount of consecutive frames a condition has to hold in.
+    pub fn max_consecutive(&self) -> u32 {
+        self.conditions
+            .iter()
+            .map(|condition| condition.consecutive)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Check the conditions for the next frame. Returns the index of the first condition that
+    /// fires.
+    pub fn check(&mut self, state: &State) -> Option<usize> {
+        for (condition, held) in self.conditions.iter().zip(self.held.iter_mut()) {
+            *held = if condition.holds(state) { *held + 1 } else { 0 };
+        }
+        self.conditions
+            .iter()
+            .zip(&self.held)
+            .position(|(condition, held)| *held >= condition.consecutive)
+    }
+
+    /// A description of condition `index`, see [`describe`].
+    pub fn description(&self, index: usize) -> &str {
+        &self.descriptions[index]
+    }
+}
+
+impl Condition {
+    /// Whether the condition holds for any node, edge or global component of `state`.
+    fn holds(&self, state: &State) -> bool {
+        let graph = state.graph.iter();
+        let nodes = graph
+            .clone()
+            .flat_map(|graph| &graph.nodes)
+            .filter_map(|node| node.components.get(&self.component));
+        let edges = graph
+            .flat_map(|graph| &graph.edge)
+            .filter(|edge| edge.component_type == self.component)
+            .filter_map(|edge| edge.component_data.as_ref());
+        let global = state.global_components.get(&self.component);
+        nodes
+            .chain(edges)
+            .chain(global)
+            .any(|component| self.holds_for(component))
+    }
+
+    fn holds_for(&self, component: &Value) -> bool {
+        let Some(value) = get_value(component, &self.path) else {
+            return false;
+        };
+        let operand = match &self.operand {
+            Operand::Value(value) => Some(value),
+            Operand::Field(_) => get_value(component, &self.field),
+        };
+        operand.is_some_and(|operand| compare(self.comparison, value, operand))
+    }
+}
+
+/// Compare two values. Numbers and strings can be ordered, other values are only equal when they
+/// are the same.
+fn compare(comparison: ComparisonOperator, a: &Value, b: &Value) -> bool {
+    let ordering = match (&a.kind, &b.kind) {
+        (Some(Kind::NumberValue(a)), Some(Kind::NumberValue(b))) => a.partial_cmp(b),
+        (Some(Kind::StringValue(a)), Some(Kind::StringValue(b))) => Some(a.cmp(b)),
+        _ if a == b => Some(Ordering::Equal),
+        _ => None,
+    };
+    match comparison {
+        ComparisonOperator::Equal => ordering == Some(Ordering::Equal),
+        ComparisonOperator::NotEqual => ordering != Some(Ordering::Equal),
+        ComparisonOperator::Less => ordering == Some(Ordering::Less),
+        ComparisonOperator::LessOrEqual => {
+            matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+        }
+        ComparisonOperator::Greater => ordering == Some(Ordering::Greater),
+        ComparisonOperator::GreaterOrEqual => {
+            matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+        }
+    }
+}
+
+/// A short description of a stop condition, to be used in the status info of a simulation that
+/// was stopped by it.
+pub fn describe(condition: &StopCondition) -> String {
+    let comparison = match condition.comparison() {
+        ComparisonOperator::Equal => "==",
+        ComparisonOperator::NotEqual => "!=",
+        ComparisonOperator::Less => "<",
+        ComparisonOperator::LessOrEqual => "<=",
+        ComparisonOperator::Greater => ">",
+        ComparisonOperator::GreaterOrEqual => ">=",
+    };
+    let operand = match &condition.operand {
+        Some(Operand::Value(value)) => prost_value::prost_to_serde_json(value.clone())
+            .map_or("null".to_string(), |value| value.to_string()),
+        Some(Operand::Field(path)) => format!("`{path}`"),
+        None => String::new(),
+    };
+    let mut description = format!(
+        "component `{}` at `{}` {comparison} {operand}",
+        condition.component, condition.path
+    );
+    if condition.consecutive > 1 {
+        description += &format!(" for {} consecutive frames", condition.consecutive);
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::simulation::{Edge, Graph};
+
+    fn number(n: f64) -> Value {
+        Value {
+            kind: Some(Kind::NumberValue(n)),
+        }
+    }
+
+    fn component(fields: &[(&str, Value)]) -> Value {
+        Value {
+            kind: Some(Kind::StructValue(prost_types::Struct {
+                fields: fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            })),
+        }
+    }
+
+    fn state(current: f64, converged: bool) -> State {
+        State {
+            graph: Some(Graph {
+                nodes: vec![],
+                edge: vec![Edge {
+                    id: 0,
+                    from: 0,
+                    to: 1,
+                    component_type: "TransmissionEdge".to_string(),
+                    component_data: Some(component(&[
+                        ("current", number(current)),
+                        ("max_current", number(10.0)),
+                    ])),
+                }],
+            }),
+            global_components: [(
+                "LoadFlowAnalytics".to_string(),
+                component(&[(
+                    "solver_converged",
+                    Value {
+                        kind: Some(Kind::BoolValue(converged)),
+                    },
+                )]),
+            )]
+            .into(),
+        }
+    }
+
+    #[test]
+    fn fields_and_values() {
+        let over_max = StopCondition {
+            component: "TransmissionEdge".to_string(),
+            path: "$.current".to_string(),
+            comparison: ComparisonOperator::Greater.into(),
+            operand: Some(Operand::Field("$.max_current".to_string())),
+            consecutive: 0,
+        };
+        let not_converged = StopCondition {
+            component: "LoadFlowAnalytics".to_string(),
+            path: "$.solver_converged".to_string(),
+            comparison: ComparisonOperator::Equal.into(),
+            operand: Some(Operand::Value(Value {
+                kind: Some(Kind::BoolValue(false)),
+            })),
+            consecutive: 3,
+        };
+        let mut conditions = StopConditions::new(&[over_max, not_converged]).unwrap();
+        assert_eq!(conditions.max_consecutive(), 3);
+
+        assert_eq!(conditions.check(&state(5.0, false)), None);
+        assert_eq!(conditions.check(&state(5.0, false)), None);
+        assert_eq!(conditions.check(&state(5.0, true)), None);
+        assert_eq!(conditions.check(&state(5.0, false)), None);
+        assert_eq!(conditions.check(&state(5.0, false)), None);
+        assert_eq!(conditions.check(&state(5.0, false)), Some(1));
+        assert_eq!(conditions.check(&state(12.0, false)), Some(0));
+        assert_eq!(
+            conditions.description(1),
+            "component `LoadFlowAnalytics` at `$.solver_converged` == false for 3 consecutive frames"
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        use ComparisonOperator::*;
+        let text = |s: &str| Value {
+            kind: Some(Kind::StringValue(s.to_string())),
+        };
+        assert!(compare(GreaterOrEqual, &number(2.0), &number(2.0)));
+        assert!(compare(Less, &text("a"), &text("b")));
+        assert!(compare(NotEqual, &number(1.0), &text("1")));
+        assert!(!compare(Less, &number(1.0), &text("2")));
+        assert!(!compare(Greater, &number(f64::NAN), &number(1.0)));
+    }
+
+    #[test]
+    fn invalid() {
+        let condition = StopCondition {
+            component: "global_time".to_string(),
+            path: "unix_timestamp".to_string(),
+            comparison: ComparisonOperator::GreaterOrEqual.into(),
+            operand: Some(Operand::Value(number(0.0))),
+            consecutive: 1,
+        };
+        assert!(StopConditions::new(&[condition.clone()]).is_err());
+        assert!(StopConditions::new(&[StopCondition {
+            path: "$".to_string(),
+            operand: None,
+            ..condition
+        }])
+        .is_err());
+    }
+}
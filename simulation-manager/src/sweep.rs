@@ -0,0 +1,317 @@
+use std::fmt;
+
+use prost_types::{value::Kind, Value};
+use proto::simulation::{
+    simulation_manager::{component_path::Location, ComponentPath},
+    State,
+};
+
+/// A step in a JSON path, see [`parse_path`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Field(name) => write!(f, ".{name}"),
+            Segment::Index(index) => write!(f, "[{index}]"),
+        }
+    }
+}
+
+/// Parse a JSON path in the same format as the paths of structure violations: `$` is the
+/// component itself, fields are selected with `.name` and list elements with `[index]`.
+pub fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let invalid = || format!("invalid path `{path}`");
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(field) = rest.strip_prefix('.') {
+            let end = field.find(['.', '[']).unwrap_or(field.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(Segment::Field(field[..end].to_string()));
+            rest = &field[end..];
+        } else if let Some(index) = rest.strip_prefix('[') {
+            let end = index.find(']').ok_or_else(invalid)?;
+            let index_value = index[..end].parse().map_err(|_| invalid())?;
+            segments.push(Segment::Index(index_value));
+            rest = &index[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+/// Get the value at `path` within `value`.
+pub fn get_value<'a>(value: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    let Some((segment, rest)) = path.split_first() else {
+        return Some(value);
+    };
+    let child = match (segment, value.kind.as_ref()?) {
+        (Segment::Field(name), Kind::StructValue(fields)) => fields.fields.get(name)?,
+        (Segment::Index(index), Kind::ListValue(list)) => list.values.get(*index)?,
+        _ => return None,
+    };
+    get_value(child, rest)
+}
+
+/// Replace the value at `path` within `value` by `new`.
+///
+/// A field that is missing is added, as long as the struct it belongs to exists.
+pub fn set_value(value: &mut Value, path: &[Segment], new: Value) -> Result<(), String> {
+    let Some((segment, rest)) = path.split_first() else {
+        *value = new;
+        return Ok(());
+    };
+    let child = match (segment, value.kind.as_mut()) {
+        (Segment::Field(name), Some(Kind::StructValue(fields))) if rest.is_empty() => {
+            fields.fields.insert(name.clone(), new);
+            return Ok(());
+        }
+        (Segment::Field(name), Some(Kind::StructValue(fields))) => fields
+            .fields
+            .get_mut(name)
+            .ok_or_else(|| format!("missing field `{name}`"))?,
+        (Segment::Index(index), Some(Kind::ListValue(list))) => {
+            let len = list.values.len();
+            list.values
+                .get_mut(*index)
+                .ok_or_else(|| format!("index {index} out of range for a list of {len}"))?
+        }
+        (Segment::Field(_), _) => return Err(format!("`{segment}` is not in a struct")),
+        (Segment::Index(_), _) => return Err(format!("`{segment}` is not in a list")),
+    };
+    set_value(child, rest, new)
+}
+
+/// Get the component `target` points to in a state.
+fn component<'a>(state: &'a State, target: &ComponentPath) -> Option<&'a Value> {
+    let graph = state.graph.as_ref();
+    match target.location {
+        Some(Location::NodeId(id)) => graph?
+            .nodes
+            .iter()
+            .find(|node| node.id == id)?
+            .components
+            .get(&target.component),
+        Some(Location::EdgeId(id)) => graph?
+            .edge
+            .iter()
+            .find(|edge| edge.id == id && edge.component_type == target.component)?
+            .component_data
+            .as_ref(),
+        None => state.global_components.get(&target.component),
+    }
+}
+
+/// Get the value `target` points to in a state.
+pub fn get_in_state<'a>(state: &'a State, target: &ComponentPath) -> Option<&'a Value> {
+    let path = parse_path(&target.path).ok()?;
+    get_value(component(state, target)?, &path)
+}
+
+/// Replace the value `target` points to in a state by `new`.
+///
+/// Node and global components that are missing are added when `target` points to the whole
+/// component.
+pub fn set_in_state(state: &mut State, target: &ComponentPath, new: Value) -> Result<(), String> {
+    let path = parse_path(&target.path)?;
+    let name = &target.component;
+    let component = match target.location {
+        Some(Location::NodeId(id)) => {
+            let node = state
+                .graph
+                .as_mut()
+                .and_then(|graph| graph.nodes.iter_mut().find(|node| node.id == id))
+                .ok_or_else(|| format!("node {id} does not exist"))?;
+            if path.is_empty() {
+                node.components.insert(name.clone(), new);
+                return Ok(());
+            }
+            node.components
+                .get_mut(name)
+                .ok_or_else(|| format!("node {id} has no component `{name}`"))?
+        }
+        Some(Location::EdgeId(id)) => {
+            let edge = state
+                .graph
+                .as_mut()
+                .and_then(|graph| graph.edge.iter_mut().find(|edge| edge.id == id))
+                .ok_or_else(|| format!("edge {id} does not exist"))?;
+            if edge.component_type != *name {
+                return Err(format!("edge {id} has no component `{name}`"));
+            }
+            edge.component_data.get_or_insert_with(Value::default)
+        }
+        None => {
+            if path.is_empty() {
+                state.global_components.insert(name.clone(), new);
+                return Ok(());
+            }
+            state
+                .global_components
+                .get_mut(name)
+                .ok_or_else(|| format!("there is no global component `{name}`"))?
+        }
+    };
+    set_value(component, &path, new)
+}
+
+/// Every combination of one value of each axis, given the amount of values of every axis.
+///
+/// A combination holds the index of the value of every axis. The combinations are ordered such
+/// that the last axis changes fastest.
+pub fn combinations(sizes: &[usize]) -> Vec<Vec<usize>> {
+    sizes.iter().fold(vec![vec![]], |combinations, &size| {
+        combinations
+            .into_iter()
+            .flat_map(|combination| {
+                (0..size).map(move |index| {
+                    let mut combination = combination.clone();
+                    combination.push(index);
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{ListValue, Struct};
+    use proto::simulation::{Graph, Node};
+
+    fn number(n: f64) -> Value {
+        Value {
+            kind: Some(Kind::NumberValue(n)),
+        }
+    }
+
+    fn fields(fields: Vec<(&str, Value)>) -> Value {
+        Value {
+            kind: Some(Kind::StructValue(Struct {
+                fields: fields
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value))
+                    .collect(),
+            })),
+        }
+    }
+
+    fn list(values: Vec<Value>) -> Value {
+        Value {
+            kind: Some(Kind::ListValue(ListValue { values })),
+        }
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(parse_path("$"), Ok(vec![]));
+        assert_eq!(
+            parse_path("$.generators[2].share"),
+            Ok(vec![
+                Segment::Field("generators".to_string()),
+                Segment::Index(2),
+                Segment::Field("share".to_string()),
+            ])
+        );
+        assert!(parse_path("generators").is_err());
+        assert!(parse_path("$.").is_err());
+        assert!(parse_path("$[x]").is_err());
+        assert!(parse_path("$[1").is_err());
+    }
+
+    #[test]
+    fn set_and_get() {
+        let mut value = fields(vec![(
+            "generators",
+            list(vec![fields(vec![("share", number(0.5))])]),
+        )]);
+        let share = parse_path("$.generators[0].share").unwrap();
+        set_value(&mut value, &share, number(0.8)).unwrap();
+        assert_eq!(get_value(&value, &share), Some(&number(0.8)));
+
+        // missing fields are added, but only at the end of the path
+        let added = parse_path("$.generators[0].kind").unwrap();
+        set_value(&mut value, &added, number(1.0)).unwrap();
+        assert_eq!(get_value(&value, &added), Some(&number(1.0)));
+        assert!(set_value(
+            &mut value,
+            &parse_path("$.missing.field").unwrap(),
+            number(1.0)
+        )
+        .is_err());
+        assert!(set_value(
+            &mut value,
+            &parse_path("$.generators[1]").unwrap(),
+            number(1.0)
+        )
+        .is_err());
+        assert!(set_value(&mut value, &parse_path("$[0]").unwrap(), number(1.0)).is_err());
+    }
+
+    #[test]
+    fn state_paths() {
+        let mut state = State {
+            graph: Some(Graph {
+                nodes: vec![Node {
+                    id: 3,
+                    components: [(
+                        "TemperatureComponent".to_string(),
+                        fields(vec![("current_temp", number(10.0))]),
+                    )]
+                    .into(),
+                    ..Default::default()
+                }],
+                edge: vec![],
+            }),
+            global_components: Default::default(),
+        };
+        let node = ComponentPath {
+            location: Some(Location::NodeId(3)),
+            component: "TemperatureComponent".to_string(),
+            path: "$.current_temp".to_string(),
+        };
+        set_in_state(&mut state, &node, number(20.0)).unwrap();
+        assert_eq!(get_in_state(&state, &node), Some(&number(20.0)));
+
+        let global = ComponentPath {
+            location: None,
+            component: "LoadFlowAnalytics".to_string(),
+            path: "$".to_string(),
+        };
+        assert_eq!(get_in_state(&state, &global), None);
+        set_in_state(&mut state, &global, number(0.01)).unwrap();
+        assert_eq!(get_in_state(&state, &global), Some(&number(0.01)));
+
+        let missing = ComponentPath {
+            location: Some(Location::NodeId(4)),
+            ..node
+        };
+        assert!(set_in_state(&mut state, &missing, number(1.0)).is_err());
+    }
+
+    #[test]
+    fn all_combinations() {
+        assert_eq!(combinations(&[]), vec![Vec::<usize>::new()]);
+        assert_eq!(
+            combinations(&[2, 3]),
+            vec![
+                vec![0, 0],
+                vec![0, 1],
+                vec![0, 2],
+                vec![1, 0],
+                vec![1, 1],
+                vec![1, 2],
+            ]
+        );
+        assert!(combinations(&[2, 0]).is_empty());
+    }
+}
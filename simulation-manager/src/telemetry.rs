@@ -0,0 +1,111 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::Result;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, Unit};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::database::{SimulationsDB, StatusEnum};
+
+/// Pending simulations that may be started right away.
+pub const QUEUE_DEPTH: &str = "simulation_manager_queue_depth";
+/// Simulations by status, labelled with `status`.
+pub const SIMULATIONS: &str = "simulation_manager_simulations";
+/// How long a simulator took to do a timestep, labelled with `simulator`.
+pub const TIMESTEP_DURATION: &str = "simulation_manager_timestep_duration_seconds";
+/// How long a simulator took to set up a simulation, labelled with `simulator`.
+pub const SETUP_DURATION: &str = "simulation_manager_setup_duration_seconds";
+/// Messages in the channel to the database buffer that have not been written yet.
+pub const DATABASE_BACKLOG: &str = "simulation_manager_database_backlog";
+/// Frames written by the database buffer.
+pub const FRAMES_WRITTEN: &str = "simulation_manager_frames_written_total";
+
+/// The buckets of the latency histograms, in seconds.
+const BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Every status a simulation can have, so statuses without simulations are reported as zero.
+const STATUSES: [StatusEnum; 6] = [
+    StatusEnum::Pending,
+    StatusEnum::Computing,
+    StatusEnum::Finished,
+    StatusEnum::Failed,
+    StatusEnum::Paused,
+    StatusEnum::Cancelled,
+];
+
+/// Serve the metrics of the manager in the Prometheus format on `addr`.
+pub fn install(addr: SocketAddr) -> Result<(), BuildError> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), BUCKETS)?
+        .install()?;
+
+    describe_gauge!(
+        QUEUE_DEPTH,
+        "Pending simulations that may be started right away."
+    );
+    describe_gauge!(SIMULATIONS, "Simulations by status.");
+    describe_histogram!(
+        TIMESTEP_DURATION,
+        Unit::Seconds,
+        "How long a simulator took to do a timestep, including retries."
+    );
+    describe_histogram!(
+        SETUP_DURATION,
+        Unit::Seconds,
+        "How long a simulator took to set up a simulation."
+    );
+    describe_gauge!(
+        DATABASE_BACKLOG,
+        "Frames and status updates waiting to be written by the database buffer."
+    );
+    describe_counter!(FRAMES_WRITTEN, "Frames written by the database buffer.");
+    // Report the counter before the first frame is written, so its rate is known right away.
+    counter!(FRAMES_WRITTEN).absolute(0);
+    Ok(())
+}
+
+/// The background task that reads the queue depth and the simulations by status from the
+/// database.
+///
+/// These are read from the database, so every manager that uses the same database reports the
+/// same values.
+pub struct SimulationGauges {
+    db: SimulationsDB,
+}
+
+impl SimulationGauges {
+    pub async fn new(pool: PgPool) -> Result<Self> {
+        Ok(Self {
+            db: SimulationsDB::from_pg_pool(pool).await?,
+        })
+    }
+
+    /// Update the gauges every `interval`, starting right away.
+    pub async fn start(mut self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.update().await {
+                error!("Could not update the simulation metrics: {err:?}");
+            }
+        }
+    }
+
+    async fn update(&mut self) -> Result<()> {
+        let counts = self.db.count_simulations_by_status().await?;
+        for status in STATUSES {
+            let count = counts
+                .iter()
+                .find(|(s, _)| *s == status)
+                .map_or(0, |(_, count)| *count);
+            gauge!(SIMULATIONS, "status" => StatusEnum::to_string(status)).set(count as f64);
+        }
+        gauge!(QUEUE_DEPTH).set(self.db.count_startable_simulations().await? as f64);
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+
+use proto::simulation::{simulator::TopologyChanges, State};
+
+/// Applies the nodes and edges simulators add and remove to the state of a simulation.
+///
+/// The ids of new nodes and edges are allocated here, starting after the largest id the
+/// simulation ever used, so the id of a removed node or edge is never used again.
+pub struct Topology {
+    next_node: u64,
+    next_edge: u64,
+    /// Nodes removed in the current timestep. Simulators that ran in the same stage as the
+    /// simulator that removed them can still return their components.
+    removed_nodes: HashSet<u64>,
+    /// Edges removed in the current timestep, see `removed_nodes`.
+    removed_edges: HashSet<u64>,
+}
+
+impl Topology {
+    pub fn new(next_node: u64, next_edge: u64) -> Self {
+        Self {
+            next_node,
+            next_edge,
+            removed_nodes: HashSet::new(),
+            removed_edges: HashSet::new(),
+        }
+    }
+
+    /// Forget the nodes and edges that were removed in the previous timestep.
+    pub fn start_timestep(&mut self) {
+        self.removed_nodes.clear();
+        self.removed_edges.clear();
+    }
+
+    /// Whether node `id` was removed in the current timestep.
+    pub fn is_removed_node(&self, id: u64) -> bool {
+        self.removed_nodes.contains(&id)
+    }
+
+    /// Whether edge `id` was removed in the current timestep.
+    pub fn is_removed_edge(&self, id: u64) -> bool {
+        self.removed_edges.contains(&id)
+    }
+
+    /// Apply the changes made by a simulator to `state`. Removals are applied first, then the new
+    /// nodes and edges get their ids and are added.
+    ///
+    /// Nodes and edges that were already removed in this timestep can be removed again, but
+    /// unknown ids are an error.
+    pub fn apply(&mut self, state: &mut State, changes: TopologyChanges) -> Result<(), String> {
+        let graph = state.graph.get_or_insert_with(Default::default);
+
+        for id in changes.removed_edges {
+            if self.removed_edges.contains(&id) {
+                continue;
+            }
+            let index = graph
+                .edge
+                .iter()
+                .position(|edge| edge.id == id)
+                .ok_or_else(|| format!("can not remove unknown edge {id}"))?;
+            graph.edge.remove(index);
+            self.removed_edges.insert(id);
+        }
+        for id in changes.removed_nodes {
+            if self.removed_nodes.contains(&id) {
+                continue;
+            }
+            let index = graph
+                .nodes
+                .iter()
+                .position(|node| node.id == id)
+                .ok_or_else(|| format!("can not remove unknown node {id}"))?;
+            graph.nodes.remove(index);
+            self.removed_nodes.insert(id);
+            graph.edge.retain(|edge| {
+                let connected = edge.from == id || edge.to == id;
+                if connected {
+                    self.removed_edges.insert(edge.id);
+                }
+                !connected
+            });
+        }
+
+        // provisional ids of the new nodes, to the allocated ids. A provisional id can be the id of
+        // a node another simulator in the same stage added, as the simulator did not know it.
+        let mut added: HashMap<u64, u64> = HashMap::new();
+        for mut node in changes.added_nodes {
+            if added.contains_key(&node.id) {
+                return Err(format!(
+                    "multiple new nodes have the provisional id {}",
+                    node.id
+                ));
+            }
+            added.insert(node.id, self.next_node);
+            node.id = self.next_node;
+            self.next_node += 1;
+            graph.nodes.push(node);
+        }
+        for mut edge in changes.added_edges {
+            for id in [&mut edge.from, &mut edge.to] {
+                if let Some(allocated) = added.get(id) {
+                    *id = *allocated;
+                } else if !graph.nodes.iter().any(|n| n.id == *id) {
+                    return Err(format!("can not add an edge to unknown node {id}"));
+                }
+            }
+            edge.id = self.next_edge;
+            self.next_edge += 1;
+            graph.edge.push(edge);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::simulation::{Edge, Graph, Node};
+
+    fn node(id: u64) -> Node {
+        Node {
+            id,
+            longitude: 0.0,
+            latitude: 0.0,
+            components: HashMap::new(),
+        }
+    }
+
+    fn edge(id: u64, from: u64, to: u64) -> Edge {
+        Edge {
+            id,
+            from,
+            to,
+            component_type: "cable".to_string(),
+            component_data: None,
+        }
+    }
+
+    fn state() -> State {
+        State {
+            graph: Some(Graph {
+                nodes: vec![node(0), node(1), node(2)],
+                edge: vec![edge(0, 0, 1), edge(1, 1, 2)],
+            }),
+            global_components: HashMap::new(),
+        }
+    }
+
+    fn ids(state: &State) -> (Vec<u64>, Vec<(u64, u64, u64)>) {
+        let graph = state.graph.as_ref().unwrap();
+        (
+            graph.nodes.iter().map(|n| n.id).collect(),
+            graph.edge.iter().map(|e| (e.id, e.from, e.to)).collect(),
+        )
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut topology = Topology::new(5, 2);
+        let mut state = state();
+        topology
+            .apply(
+                &mut state,
+                TopologyChanges {
+                    added_nodes: vec![node(10), node(11)],
+                    added_edges: vec![edge(7, 10, 11), edge(8, 0, 11)],
+                    removed_nodes: vec![2],
+                    removed_edges: vec![0],
+                },
+            )
+            .unwrap();
+        assert_eq!(ids(&state), (vec![0, 1, 5, 6], vec![(2, 5, 6), (3, 0, 6)]));
+        assert!(topology.is_removed_node(2));
+        assert!(topology.is_removed_edge(0));
+        assert!(topology.is_removed_edge(1));
+
+        // another simulator in the same stage adds a node with the id that was just allocated
+        topology
+            .apply(
+                &mut state,
+                TopologyChanges {
+                    added_nodes: vec![node(5)],
+                    added_edges: vec![edge(0, 5, 1)],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            ids(&state),
+            (vec![0, 1, 5, 6, 7], vec![(2, 5, 6), (3, 0, 6), (4, 7, 1)])
+        );
+
+        // another simulator in the same stage removes the same node
+        let removed_again = TopologyChanges {
+            removed_nodes: vec![2],
+            removed_edges: vec![1],
+            ..Default::default()
+        };
+        topology.apply(&mut state, removed_again.clone()).unwrap();
+
+        topology.start_timestep();
+        assert!(!topology.is_removed_node(2));
+        assert_eq!(
+            topology.apply(&mut state, removed_again),
+            Err("can not remove unknown edge 1".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_ids() {
+        let mut topology = Topology::new(3, 2);
+        let mut state = state();
+        assert_eq!(
+            topology.apply(
+                &mut state,
+                TopologyChanges {
+                    added_nodes: vec![node(4), node(4)],
+                    ..Default::default()
+                }
+            ),
+            Err("multiple new nodes have the provisional id 4".to_string())
+        );
+        assert_eq!(
+            topology.apply(
+                &mut state,
+                TopologyChanges {
+                    added_edges: vec![edge(2, 0, 9)],
+                    ..Default::default()
+                }
+            ),
+            Err("can not add an edge to unknown node 9".to_string())
+        );
+        assert_eq!(
+            topology.apply(
+                &mut state,
+                TopologyChanges {
+                    removed_nodes: vec![9],
+                    ..Default::default()
+                }
+            ),
+            Err("can not remove unknown node 9".to_string())
+        );
+    }
+}
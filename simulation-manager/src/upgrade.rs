@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use prost_types::Value;
+use proto::simulation::{
+    simulator::{simulator_client::SimulatorClient, IoConfigRequest, UpgradeRequest},
+    ComponentSpecification, State,
+};
+use tonic::transport::Channel;
+
+use crate::database::SimulationsDB;
+
+/// The version of the structure of every component, by name.
+pub type ComponentVersions = HashMap<String, u32>;
+
+/// The versions of the structures in `components`.
+pub fn versions_of(components: &HashMap<String, ComponentSpecification>) -> ComponentVersions {
+    components
+        .iter()
+        .map(|(name, spec)| (name.clone(), spec.version))
+        .collect()
+}
+
+/// Add the components of a simulator to the `components` of the other selected simulators.
+///
+/// The simulators of a simulation have to agree on the version of every component, so a component
+/// that is already in `components` with another version is an error.
+pub fn merge_components(
+    components: &mut HashMap<String, ComponentSpecification>,
+    other: HashMap<String, ComponentSpecification>,
+) -> Result<(), String> {
+    for (name, spec) in other {
+        if let Some(existing) = components.get(&name) {
+            if existing.version != spec.version {
+                return Err(format!(
+                    "the selected simulators use different versions of component `{name}`: {} and {}",
+                    existing.version, spec.version
+                ));
+            }
+        }
+        components.insert(name, spec);
+    }
+    Ok(())
+}
+
+/// The components that have an older version in `stored` than in `components`, with the version
+/// in `stored`. Components that are not in `stored` have version 0.
+///
+/// Components can not be downgraded, so a component with a newer version in `stored` is an error.
+pub fn outdated(
+    stored: &ComponentVersions,
+    components: &HashMap<String, ComponentSpecification>,
+) -> Result<BTreeMap<String, u32>, String> {
+    let mut outdated = BTreeMap::new();
+    for (name, spec) in components {
+        let version = stored.get(name).copied().unwrap_or(0);
+        if version > spec.version {
+            return Err(format!(
+                "component `{name}` has version {version}, but the simulators use the older version {}, components can not be downgraded",
+                spec.version
+            ));
+        }
+        if version < spec.version {
+            outdated.insert(name.clone(), version);
+        }
+    }
+    Ok(outdated)
+}
+
+/// Upgrade the `outdated` components in `state`, by sending their values to a simulator in
+/// `simulators` that uses the component.
+pub async fn upgrade_state(
+    state: &mut State,
+    outdated: &BTreeMap<String, u32>,
+    simulators: Vec<SimulatorClient<Channel>>,
+) -> Result<(), String> {
+    if outdated.is_empty() {
+        return Ok(());
+    }
+
+    let mut upgraders = HashMap::new();
+    for mut simulator in simulators {
+        let config = simulator
+            .get_io_config(IoConfigRequest {})
+            .await
+            .map_err(|err| err.message().to_string())?
+            .into_inner();
+        for name in config.components.into_keys() {
+            upgraders.entry(name).or_insert_with(|| simulator.clone());
+        }
+    }
+
+    for (name, version) in outdated {
+        let mut values = values_mut(state, name);
+        if values.is_empty() {
+            continue;
+        }
+        let Some(simulator) = upgraders.get_mut(name) else {
+            return Err(format!("no simulator uses component `{name}`"));
+        };
+        let upgraded = simulator
+            .upgrade_components(UpgradeRequest {
+                component: name.clone(),
+                version: *version,
+                values: values.iter().map(|value| (**value).clone()).collect(),
+            })
+            .await
+            .map_err(|err| err.message().to_string())?
+            .into_inner()
+            .values;
+        if upgraded.len() != values.len() {
+            return Err(format!(
+                "upgrading component `{name}` returned {} values instead of {}",
+                upgraded.len(),
+                values.len()
+            ));
+        }
+        for (value, upgraded) in values.iter_mut().zip(upgraded) {
+            **value = upgraded;
+        }
+    }
+    Ok(())
+}
+
+/// Every value of the component `name` in `state`: first those of the nodes, then the edges and
+/// then the global component.
+fn values_mut<'a>(state: &'a mut State, name: &str) -> Vec<&'a mut Value> {
+    let mut values = Vec::new();
+    if let Some(graph) = &mut state.graph {
+        for node in &mut graph.nodes {
+            values.extend(node.components.get_mut(name));
+        }
+        for edge in &mut graph.edge {
+            if edge.component_type == name {
+                values.extend(edge.component_data.as_mut());
+            }
+        }
+    }
+    values.extend(state.global_components.get_mut(name));
+    values
+}
+
+/// Replace the `outdated` components in frame 0 of a simulation with their upgraded values in
+/// `state`.
+pub async fn store_upgraded(
+    db: &mut SimulationsDB,
+    simulation_id: i32,
+    state: &State,
+    outdated: &BTreeMap<String, u32>,
+) -> Result<()> {
+    if let Some(graph) = &state.graph {
+        for node in &graph.nodes {
+            let components: HashMap<String, Value> = node
+                .components
+                .iter()
+                .filter(|(name, _)| outdated.contains_key(*name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            if !components.is_empty() {
+                db.override_node_components(simulation_id, 0, node.id as i32, components)
+                    .await?;
+            }
+        }
+        for edge in &graph.edge {
+            if let (true, Some(value)) = (
+                outdated.contains_key(&edge.component_type),
+                &edge.component_data,
+            ) {
+                db.override_edge_component(simulation_id, 0, edge.id as i32, value.clone())
+                    .await?;
+            }
+        }
+    }
+    for (name, value) in &state.global_components {
+        if outdated.contains_key(name) {
+            db.override_global_component(simulation_id, 0, name, value.clone())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::value::Kind;
+    use proto::simulation::{Edge, Graph, Node};
+
+    fn spec(version: u32) -> ComponentSpecification {
+        ComponentSpecification {
+            r#type: 0,
+            structure: None,
+            version,
+        }
+    }
+
+    #[test]
+    fn outdated_components() {
+        let components = HashMap::from([
+            ("a".to_string(), spec(2)),
+            ("b".to_string(), spec(1)),
+            ("c".to_string(), spec(0)),
+        ]);
+        let stored = HashMap::from([("a".to_string(), 1), ("b".to_string(), 1)]);
+        assert_eq!(
+            outdated(&stored, &components),
+            Ok(BTreeMap::from([("a".to_string(), 1)]))
+        );
+        // components that are not stored have version 0
+        assert_eq!(
+            outdated(&HashMap::new(), &components),
+            Ok(BTreeMap::from([("a".to_string(), 0), ("b".to_string(), 0)]))
+        );
+        assert_eq!(
+            outdated(&HashMap::from([("c".to_string(), 1)]), &components),
+            Err("component `c` has version 1, but the simulators use the older version 0, components can not be downgraded".to_string())
+        );
+    }
+
+    #[test]
+    fn merge_versions() {
+        let mut components = HashMap::from([("a".to_string(), spec(1))]);
+        assert_eq!(
+            merge_components(
+                &mut components,
+                HashMap::from([("a".to_string(), spec(1)), ("b".to_string(), spec(2))])
+            ),
+            Ok(())
+        );
+        assert_eq!(versions_of(&components).len(), 2);
+        assert_eq!(
+            merge_components(&mut components, HashMap::from([("b".to_string(), spec(3))])),
+            Err(
+                "the selected simulators use different versions of component `b`: 2 and 3"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn values_of_component() {
+        let number = |n: f64| Value {
+            kind: Some(Kind::NumberValue(n)),
+        };
+        let mut state = State {
+            graph: Some(Graph {
+                nodes: vec![Node {
+                    id: 0,
+                    longitude: 0.0,
+                    latitude: 0.0,
+                    components: HashMap::from([
+                        ("a".to_string(), number(1.0)),
+                        ("b".to_string(), number(2.0)),
+                    ]),
+                }],
+                edge: vec![Edge {
+                    id: 0,
+                    from: 0,
+                    to: 0,
+                    component_type: "a".to_string(),
+                    component_data: Some(number(3.0)),
+                }],
+            }),
+            global_components: HashMap::from([("a".to_string(), number(4.0))]),
+        };
+        let values: Vec<Value> = values_mut(&mut state, "a")
+            .into_iter()
+            .map(|value| value.clone())
+            .collect();
+        assert_eq!(values, vec![number(1.0), number(3.0), number(4.0)]);
+        assert_eq!(values_mut(&mut state, "c").len(), 0);
+    }
+}
@@ -0,0 +1,688 @@
+use std::collections::{BTreeSet, HashMap};
+
+use prost_types::{value::Kind, Value};
+use proto::simulation::{
+    component_structure::ComponentStructure as Structure,
+    simulation_manager::{structure_violation::Location, StructureViolation},
+    ComponentMetadata, ComponentPrimitive, ComponentSpecification, ComponentStructure, State,
+};
+
+/// Check every node, edge and global component of a state against the structure expected by the
+/// simulators.
+///
+/// Components that are not known by any of the simulators in `components` are not checked.
+pub fn validate_state(
+    components: &HashMap<String, ComponentSpecification>,
+    state: &State,
+) -> Vec<StructureViolation> {
+    let mut violations = Vec::new();
+    let mut check = |location: Option<Location>, name: &str, value: Option<&Value>| {
+        let Some(expected) = components
+            .get(name)
+            .and_then(|spec| spec.structure.as_ref())
+            .filter(|structure| structure.component_structure.is_some())
+        else {
+            return;
+        };
+        let start = violations.len();
+        match value {
+            Some(value) => check_value(expected, value, "$".to_string(), None, &mut violations),
+            None => violations.push(violation(expected, None, "$", "component has no value")),
+        }
+        for violation in &mut violations[start..] {
+            violation.location = location.clone();
+            violation.component = name.to_string();
+        }
+    };
+
+    if let Some(graph) = &state.graph {
+        for node in &graph.nodes {
+            for name in node.components.keys().collect::<BTreeSet<_>>() {
+                check(
+                    Some(Location::NodeId(node.id)),
+                    name,
+                    node.components.get(name),
+                );
+            }
+        }
+        for edge in &graph.edge {
+            check(
+                Some(Location::EdgeId(edge.id)),
+                &edge.component_type,
+                edge.component_data.as_ref(),
+            );
+        }
+    }
+    for name in state.global_components.keys().collect::<BTreeSet<_>>() {
+        check(None, name, state.global_components.get(name));
+    }
+    violations
+}
+
+/// A short description of a violation, to be used in the status info of a failed simulation.
+pub fn describe(violation: &StructureViolation) -> String {
+    let location = match &violation.location {
+        Some(Location::NodeId(id)) => format!("node {id}"),
+        Some(Location::EdgeId(id)) => format!("edge {id}"),
+        None => "global".to_string(),
+    };
+    format!(
+        "{location}, component `{}` at `{}`: {}",
+        violation.component, violation.path, violation.reason
+    )
+}
+
+/// A violation for a selected simulator that is not connected, so the structures of its
+/// components are unknown.
+pub fn missing_simulator(name: &str) -> StructureViolation {
+    StructureViolation {
+        location: None,
+        component: String::new(),
+        path: String::new(),
+        expected: None,
+        actual: None,
+        reason: format!("selected simulator `{name}` is not connected"),
+    }
+}
+
+fn violation(
+    expected: &ComponentStructure,
+    actual: Option<&Value>,
+    path: &str,
+    reason: impl Into<String>,
+) -> StructureViolation {
+    StructureViolation {
+        location: None,
+        component: String::new(),
+        path: path.to_string(),
+        expected: Some(expected.clone()),
+        actual: actual.cloned(),
+        reason: reason.into(),
+    }
+}
+
+/// Compare a value to the structure that is expected at `path`. Every mismatch is added to
+/// `violations`.
+///
+/// `outer` is the metadata of the option or list this value is in, which applies when the
+/// structure has no metadata of its own.
+fn check_value(
+    expected: &ComponentStructure,
+    actual: &Value,
+    path: String,
+    outer: Option<&ComponentMetadata>,
+    violations: &mut Vec<StructureViolation>,
+) {
+    let metadata = expected.metadata.as_ref().or(outer);
+    let Some(structure) = &expected.component_structure else {
+        violations.push(violation(
+            expected,
+            Some(actual),
+            &path,
+            "the expected structure is empty",
+        ));
+        return;
+    };
+    let Some(kind) = &actual.kind else {
+        violations.push(violation(
+            expected,
+            Some(actual),
+            &path,
+            "value has no kind",
+        ));
+        return;
+    };
+
+    match (structure, kind) {
+        (Structure::Option(_), Kind::NullValue(_)) => {}
+        (Structure::Option(inner), _) => check_value(inner, actual, path, metadata, violations),
+        (Structure::List(inner), Kind::ListValue(list)) => {
+            for (index, value) in list.values.iter().enumerate() {
+                check_value(
+                    inner,
+                    value,
+                    format!("{path}[{index}]"),
+                    metadata,
+                    violations,
+                );
+            }
+        }
+        (Structure::Struct(expected_struct), Kind::StructValue(actual_struct)) => {
+            let names: BTreeSet<_> = expected_struct
+                .data
+                .keys()
+                .chain(actual_struct.fields.keys())
+                .collect();
+            for name in names {
+                let field_path = format!("{path}.{name}");
+                match (
+                    expected_struct.data.get(name),
+                    actual_struct.fields.get(name),
+                ) {
+                    (Some(expected), Some(actual)) => {
+                        check_value(expected, actual, field_path, None, violations)
+                    }
+                    // a missing optional field is the same as a null value
+                    (Some(expected), None) => {
+                        if !matches!(expected.component_structure, Some(Structure::Option(_))) {
+                            violations.push(violation(
+                                expected,
+                                None,
+                                &field_path,
+                                "missing field",
+                            ));
+                        }
+                    }
+                    (None, Some(actual)) => violations.push(violation(
+                        expected,
+                        Some(actual),
+                        &field_path,
+                        "unknown field",
+                    )),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (Structure::Enum(expected_enum), Kind::StringValue(name)) => {
+            let is_variant = |name: &str| {
+                expected_enum
+                    .variants
+                    .iter()
+                    .any(|variant| variant.eq_ignore_ascii_case(name))
+            };
+            let is_alias = expected_enum
+                .aliases
+                .iter()
+                .any(|(alias, variant)| alias.eq_ignore_ascii_case(name) && is_variant(variant));
+            if !is_variant(name) && !is_alias {
+                violations.push(violation(
+                    expected,
+                    Some(actual),
+                    &path,
+                    format!("unknown variant `{name}`"),
+                ));
+            }
+        }
+        // a variant with data is a struct with a single field named after the variant
+        (Structure::Enum(expected_enum), Kind::StructValue(actual_struct)) => {
+            let mut fields = actual_struct.fields.iter();
+            let (Some((name, value)), None) = (fields.next(), fields.next()) else {
+                violations.push(violation(
+                    expected,
+                    Some(actual),
+                    &path,
+                    "a variant with data has to be a struct with a single field",
+                ));
+                return;
+            };
+            let variant = expected_enum.aliases.get(name).unwrap_or(name);
+            match expected_enum.data_variants.get(variant) {
+                Some(expected) => {
+                    check_value(expected, value, format!("{path}.{name}"), None, violations)
+                }
+                None => violations.push(violation(
+                    expected,
+                    Some(actual),
+                    &path,
+                    format!("unknown variant `{name}`"),
+                )),
+            }
+        }
+        (Structure::Primitive(primitive), kind) => {
+            let Ok(primitive) = ComponentPrimitive::try_from(*primitive) else {
+                violations.push(violation(
+                    expected,
+                    Some(actual),
+                    &path,
+                    format!("unknown primitive `{primitive}`"),
+                ));
+                return;
+            };
+            let checked = check_primitive(primitive, kind).and_then(|()| match kind {
+                Kind::NumberValue(number) => check_range(*number, metadata),
+                _ => Ok(()),
+            });
+            if let Err(reason) = checked {
+                violations.push(violation(expected, Some(actual), &path, reason));
+            }
+        }
+        (structure, kind) => violations.push(violation(
+            expected,
+            Some(actual),
+            &path,
+            format!(
+                "expected {}, found {}",
+                structure_name(structure),
+                kind_name(kind)
+            ),
+        )),
+    }
+}
+
+/// Check that a value fits in a primitive. Integers have to be whole numbers within the range of
+/// the integer type.
+fn check_primitive(primitive: ComponentPrimitive, kind: &Kind) -> Result<(), String> {
+    let range = match primitive {
+        ComponentPrimitive::U8 => Some((u8::MIN as f64, u8::MAX as f64)),
+        ComponentPrimitive::U16 => Some((u16::MIN as f64, u16::MAX as f64)),
+        ComponentPrimitive::U32 => Some((u32::MIN as f64, u32::MAX as f64)),
+        ComponentPrimitive::U64 => Some((u64::MIN as f64, u64::MAX as f64)),
+        ComponentPrimitive::U128 => Some((u128::MIN as f64, u128::MAX as f64)),
+        ComponentPrimitive::I8 => Some((i8::MIN as f64, i8::MAX as f64)),
+        ComponentPrimitive::I16 => Some((i16::MIN as f64, i16::MAX as f64)),
+        ComponentPrimitive::I32 => Some((i32::MIN as f64, i32::MAX as f64)),
+        ComponentPrimitive::I64 => Some((i64::MIN as f64, i64::MAX as f64)),
+        ComponentPrimitive::I128 => Some((i128::MIN as f64, i128::MAX as f64)),
+        _ => None,
+    };
+    match (primitive, kind, range) {
+        (ComponentPrimitive::Bool, Kind::BoolValue(_), _)
+        | (ComponentPrimitive::String, Kind::StringValue(_), _)
+        | (ComponentPrimitive::F32 | ComponentPrimitive::F64, Kind::NumberValue(_), _) => Ok(()),
+        (_, Kind::NumberValue(number), Some((min, max))) => {
+            if number.fract() != 0.0 {
+                Err(format!("{number} is not an integer"))
+            } else if !(min..=max).contains(number) {
+                Err(format!(
+                    "{number} is out of range for {}",
+                    primitive.as_str_name()
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        (primitive, kind, _) => Err(format!(
+            "expected {}, found {}",
+            primitive.as_str_name(),
+            kind_name(kind)
+        )),
+    }
+}
+
+/// Check that a number is within the `min` and `max` of its metadata.
+fn check_range(number: f64, metadata: Option<&ComponentMetadata>) -> Result<(), String> {
+    let Some(metadata) = metadata else {
+        return Ok(());
+    };
+    match (metadata.min, metadata.max) {
+        (Some(min), _) if number < min => Err(format!("{number} is less than the minimum {min}")),
+        (_, Some(max)) if number > max => {
+            Err(format!("{number} is greater than the maximum {max}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn structure_name(structure: &Structure) -> String {
+    match structure {
+        Structure::Primitive(primitive) => ComponentPrimitive::try_from(*primitive)
+            .map(|p| p.as_str_name().to_string())
+            .unwrap_or_else(|_| format!("unknown primitive `{primitive}`")),
+        Structure::Option(_) => "OPTION".to_string(),
+        Structure::List(_) => "LIST".to_string(),
+        Structure::Struct(_) => "STRUCT".to_string(),
+        Structure::Enum(_) => "ENUM".to_string(),
+    }
+}
+
+fn kind_name(kind: &Kind) -> &'static str {
+    match kind {
+        Kind::NullValue(_) => "null",
+        Kind::NumberValue(_) => "number",
+        Kind::StringValue(_) => "string",
+        Kind::BoolValue(_) => "bool",
+        Kind::StructValue(_) => "struct",
+        Kind::ListValue(_) => "list",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::{ListValue, Struct};
+    use proto::simulation::{ComponentEnum, ComponentStruct, Edge, Graph, Node};
+
+    fn structure(structure: Structure) -> ComponentStructure {
+        ComponentStructure {
+            component_structure: Some(structure),
+            metadata: None,
+        }
+    }
+
+    fn primitive(primitive: ComponentPrimitive) -> ComponentStructure {
+        structure(Structure::Primitive(primitive.into()))
+    }
+
+    fn value(kind: Kind) -> Value {
+        Value { kind: Some(kind) }
+    }
+
+    fn value_struct(fields: impl IntoIterator<Item = (String, Value)>) -> Value {
+        value(Kind::StructValue(Struct {
+            fields: fields.into_iter().collect(),
+        }))
+    }
+
+    fn specs(
+        specs: impl IntoIterator<Item = (&'static str, ComponentStructure)>,
+    ) -> HashMap<String, ComponentSpecification> {
+        specs
+            .into_iter()
+            .map(|(name, structure)| {
+                (
+                    name.to_string(),
+                    ComponentSpecification {
+                        r#type: 0,
+                        structure: Some(structure),
+                        version: 0,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn global_state(components: impl IntoIterator<Item = (&'static str, Value)>) -> State {
+        State {
+            graph: None,
+            global_components: components
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn valid_state() {
+        let specs = specs([
+            ("temperature", primitive(ComponentPrimitive::F64)),
+            (
+                "names",
+                structure(Structure::List(Box::new(primitive(
+                    ComponentPrimitive::String,
+                )))),
+            ),
+        ]);
+        let state = global_state([
+            ("temperature", value(Kind::NumberValue(21.5))),
+            (
+                "names",
+                value(Kind::ListValue(ListValue {
+                    values: vec![value(Kind::StringValue("a".to_string()))],
+                })),
+            ),
+            // unknown components are not checked
+            ("unknown", value(Kind::BoolValue(true))),
+        ]);
+        assert!(validate_state(&specs, &state).is_empty());
+    }
+
+    #[test]
+    fn wrong_kind_in_list() {
+        let specs = specs([(
+            "names",
+            structure(Structure::List(Box::new(primitive(
+                ComponentPrimitive::String,
+            )))),
+        )]);
+        let state = global_state([(
+            "names",
+            value(Kind::ListValue(ListValue {
+                values: vec![
+                    value(Kind::StringValue("a".to_string())),
+                    value(Kind::NumberValue(1.0)),
+                ],
+            })),
+        )]);
+        let violations = validate_state(&specs, &state);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].location, None);
+        assert_eq!(violations[0].component, "names");
+        assert_eq!(violations[0].path, "$[1]");
+        assert_eq!(
+            violations[0].expected,
+            Some(primitive(ComponentPrimitive::String))
+        );
+        assert_eq!(violations[0].actual, Some(value(Kind::NumberValue(1.0))));
+        assert_eq!(violations[0].reason, "expected STRING, found number");
+    }
+
+    #[test]
+    fn struct_fields() {
+        let specs = specs([(
+            "power",
+            structure(Structure::Struct(ComponentStruct {
+                data: [
+                    ("voltage".to_string(), primitive(ComponentPrimitive::F64)),
+                    ("current".to_string(), primitive(ComponentPrimitive::F64)),
+                    (
+                        "phase".to_string(),
+                        structure(Structure::Option(Box::new(primitive(
+                            ComponentPrimitive::U8,
+                        )))),
+                    ),
+                ]
+                .into(),
+            })),
+        )]);
+        let state = State {
+            graph: Some(Graph {
+                nodes: vec![Node {
+                    id: 3,
+                    longitude: 0.0,
+                    latitude: 0.0,
+                    components: [(
+                        "power".to_string(),
+                        value(Kind::StructValue(Struct {
+                            fields: [
+                                ("voltage".to_string(), value(Kind::NumberValue(230.0))),
+                                ("frequency".to_string(), value(Kind::NumberValue(50.0))),
+                            ]
+                            .into(),
+                        })),
+                    )]
+                    .into(),
+                }],
+                edge: vec![],
+            }),
+            global_components: HashMap::new(),
+        };
+        let violations = validate_state(&specs, &state);
+        let found: Vec<_> = violations
+            .iter()
+            .map(|v| (v.location.clone(), v.path.as_str(), v.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(Location::NodeId(3)), "$.current", "missing field"),
+                (Some(Location::NodeId(3)), "$.frequency", "unknown field"),
+            ]
+        );
+        assert_eq!(
+            describe(&violations[0]),
+            "node 3, component `power` at `$.current`: missing field"
+        );
+    }
+
+    #[test]
+    fn integer_ranges() {
+        let specs = specs([
+            ("u8", primitive(ComponentPrimitive::U8)),
+            ("i8", primitive(ComponentPrimitive::I8)),
+            ("i128", primitive(ComponentPrimitive::I128)),
+            ("u32", primitive(ComponentPrimitive::U32)),
+        ]);
+        let state = global_state([
+            ("u8", value(Kind::NumberValue(256.0))),
+            ("i8", value(Kind::NumberValue(-128.0))),
+            ("i128", value(Kind::NumberValue(-1e30))),
+            ("u32", value(Kind::NumberValue(1.5))),
+        ]);
+        let reasons: Vec<_> = validate_state(&specs, &state)
+            .into_iter()
+            .map(|v| (v.component, v.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("u32".to_string(), "1.5 is not an integer".to_string()),
+                ("u8".to_string(), "256 is out of range for U8".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn edge_without_value() {
+        let specs = specs([("cable", primitive(ComponentPrimitive::F64))]);
+        let state = State {
+            graph: Some(Graph {
+                nodes: vec![],
+                edge: vec![Edge {
+                    id: 7,
+                    from: 0,
+                    to: 1,
+                    component_type: "cable".to_string(),
+                    component_data: None,
+                }],
+            }),
+            global_components: HashMap::new(),
+        };
+        let violations = validate_state(&specs, &state);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].location, Some(Location::EdgeId(7)));
+        assert_eq!(violations[0].actual, None);
+        assert_eq!(violations[0].reason, "component has no value");
+    }
+
+    #[test]
+    fn enum_variants() {
+        let specs = specs([(
+            "cable",
+            structure(Structure::Enum(ComponentEnum {
+                variants: vec!["XLPE_Cable".to_string()],
+                data_variants: [("Custom".to_string(), primitive(ComponentPrimitive::U8))].into(),
+                aliases: [
+                    ("XLPE_Conductor".to_string(), "XLPE_Cable".to_string()),
+                    ("Conductor".to_string(), "Custom".to_string()),
+                ]
+                .into(),
+            })),
+        )]);
+        let tagged = |name: &str, value: Value| {
+            global_state([("cable", value_struct([(name.to_string(), value)]))])
+        };
+        let reasons = |state: State| -> Vec<_> {
+            validate_state(&specs, &state)
+                .into_iter()
+                .map(|v| (v.path, v.reason))
+                .collect()
+        };
+
+        let string = |s: &str| value(Kind::StringValue(s.to_string()));
+        assert!(reasons(global_state([("cable", string("xlpe_cable"))])).is_empty());
+        assert!(reasons(tagged("Custom", value(Kind::NumberValue(3.0)))).is_empty());
+        assert!(reasons(global_state([("cable", string("xlpe_conductor"))])).is_empty());
+        assert!(reasons(tagged("Conductor", value(Kind::NumberValue(3.0)))).is_empty());
+        assert_eq!(
+            reasons(global_state([("cable", string("Conductor"))])),
+            vec![("$".to_string(), "unknown variant `Conductor`".to_string())]
+        );
+        assert_eq!(
+            reasons(global_state([("cable", string("PILC_Cable"))])),
+            vec![("$".to_string(), "unknown variant `PILC_Cable`".to_string())]
+        );
+        assert_eq!(
+            reasons(tagged("Custom", value(Kind::NumberValue(300.0)))),
+            vec![(
+                "$.Custom".to_string(),
+                "300 is out of range for U8".to_string()
+            )]
+        );
+        assert_eq!(
+            reasons(tagged("XLPE_Cable", value(Kind::NumberValue(3.0)))),
+            vec![("$".to_string(), "unknown variant `XLPE_Cable`".to_string())]
+        );
+        assert_eq!(
+            reasons(global_state([("cable", value(Kind::BoolValue(true)))])),
+            vec![("$".to_string(), "expected ENUM, found bool".to_string())]
+        );
+    }
+
+    #[test]
+    fn metadata_ranges() {
+        let range = |structure: ComponentStructure, min, max| ComponentStructure {
+            metadata: Some(ComponentMetadata {
+                unit: "A".to_string(),
+                min,
+                max,
+                ..Default::default()
+            }),
+            ..structure
+        };
+        let specs = specs([
+            (
+                "current",
+                range(primitive(ComponentPrimitive::F64), Some(0.0), Some(10.0)),
+            ),
+            (
+                "angles",
+                range(
+                    structure(Structure::List(Box::new(primitive(
+                        ComponentPrimitive::I32,
+                    )))),
+                    Some(-180.0),
+                    None,
+                ),
+            ),
+            (
+                "length",
+                range(
+                    structure(Structure::Option(Box::new(primitive(
+                        ComponentPrimitive::F64,
+                    )))),
+                    None,
+                    Some(5.0),
+                ),
+            ),
+        ]);
+        let number = |n: f64| value(Kind::NumberValue(n));
+        let list = |values: Vec<Value>| value(Kind::ListValue(ListValue { values }));
+        let reasons = |state: State| -> Vec<_> {
+            validate_state(&specs, &state)
+                .into_iter()
+                .map(|v| (v.component, v.path, v.reason))
+                .collect()
+        };
+
+        assert!(reasons(global_state([
+            ("current", number(10.0)),
+            ("angles", list(vec![number(-180.0), number(90.0)])),
+            ("length", value(Kind::NullValue(0))),
+        ]))
+        .is_empty());
+        assert_eq!(
+            reasons(global_state([
+                ("current", number(-0.5)),
+                ("angles", list(vec![number(0.0), number(-181.0)])),
+                ("length", number(5.5)),
+            ])),
+            vec![
+                (
+                    "angles".to_string(),
+                    "$[1]".to_string(),
+                    "-181 is less than the minimum -180".to_string()
+                ),
+                (
+                    "current".to_string(),
+                    "$".to_string(),
+                    "-0.5 is less than the minimum 0".to_string()
+                ),
+                (
+                    "length".to_string(),
+                    "$".to_string(),
+                    "5.5 is greater than the maximum 5".to_string()
+                ),
+            ]
+        );
+    }
+}
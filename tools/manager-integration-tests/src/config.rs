@@ -69,6 +69,13 @@ pub(crate) enum ComponentStructure {
     Option(Box<ComponentStructure>),
     #[serde(rename = "List")]
     List(Box<ComponentStructure>),
+    #[serde(rename = "Enum")]
+    Enum {
+        #[serde(default)]
+        variants: Vec<String>,
+        #[serde(default)]
+        data_variants: HashMap<String, ComponentStructure>,
+    },
     #[serde(untagged)]
     Struct(HashMap<String, ComponentStructure>),
 }
@@ -86,6 +93,19 @@ impl ComponentStructure {
         match (self, other) {
             (CSS::Option(s), CSO::Option(o)) => s.eq_proto(o),
             (CSS::List(s), CSO::List(o)) => s.eq_proto(o),
+            (
+                CSS::Enum {
+                    variants,
+                    data_variants,
+                },
+                CSO::Enum(o),
+            ) => {
+                *variants == o.variants
+                    && data_variants.len() == o.data_variants.len()
+                    && data_variants
+                        .iter()
+                        .all(|(name, s)| o.data_variants.get(name).is_some_and(|o| s.eq_proto(o)))
+            }
             (CSS::Struct(s), CSO::Struct(o)) => {
                 let o = &o.data;
                 if s.len() != o.len() {
@@ -148,6 +168,17 @@ impl ComponentStructure {
             ComponentStructure::F64 => CS::Primitive(CP::F64.into()),
             ComponentStructure::Option(i) => CS::Option(Box::new(i.to_proto())),
             ComponentStructure::List(i) => CS::List(Box::new(i.to_proto())),
+            ComponentStructure::Enum {
+                variants,
+                data_variants,
+            } => CS::Enum(proto::simulation::ComponentEnum {
+                variants: variants.clone(),
+                data_variants: data_variants
+                    .iter()
+                    .map(|(name, comp)| (name.clone(), comp.to_proto()))
+                    .collect(),
+                aliases: HashMap::new(),
+            }),
             ComponentStructure::Struct(i) => CS::Struct(proto::simulation::ComponentStruct {
                 data: i
                     .iter()
@@ -158,6 +189,7 @@ impl ComponentStructure {
 
         proto::simulation::ComponentStructure {
             component_structure: Some(is),
+            metadata: None,
         }
     }
 }
@@ -167,6 +199,9 @@ impl ComponentStructure {
 pub(crate) struct ComponentInfo {
     ty: ComponentType,
     structure: ComponentStructure,
+    /// The version of the structure, 0 when not given.
+    #[serde(default)]
+    version: u32,
 }
 
 impl ComponentInfo {
@@ -183,7 +218,7 @@ impl ComponentInfo {
                 Some(structure) => self.structure.eq_proto(structure),
                 None => false,
             }
-        })
+        }) && self.version == other.version
     }
 
     /// Convert into the proto version of this type.
@@ -191,6 +226,7 @@ impl ComponentInfo {
         proto::simulation::ComponentSpecification {
             r#type: self.ty.to_proto().into(),
             structure: Some(self.structure.to_proto()),
+            version: self.version,
         }
     }
 }
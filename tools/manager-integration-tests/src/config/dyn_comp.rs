@@ -797,6 +797,7 @@ fn from_structure(
                 C::Option(_) => return Err(anyhow!("lists of options is not suported atm")),
                 C::List(_) => return Err(anyhow!("lists of lists is not suported atm")),
                 C::Struct(_) => return Err(anyhow!("lists of structs is not suported atm")),
+                C::Enum { .. } => return Err(anyhow!("lists of enums is not suported atm")),
             };
             Ok((ComponentFillChecker::Value(false), i))
         }
@@ -818,6 +819,7 @@ fn from_structure(
                 C::Option(_) => return Err(anyhow!("option of option is not suported atm")),
                 C::List(_) => return Err(anyhow!("option of list is not suported atm")),
                 C::Struct(_) => return Err(anyhow!("option of structs is not suported atm")),
+                C::Enum { .. } => return Err(anyhow!("option of enums is not suported atm")),
             };
             Ok((ComponentFillChecker::Value(preset_options), i))
         }
@@ -838,6 +840,29 @@ fn from_structure(
                 Component::Struct(comps),
             ))
         }
+        // An enum starts out as its first variant without data, or else as the variant with data
+        // that comes first by name.
+        C::Enum {
+            variants,
+            data_variants,
+        } => {
+            if let Some(variant) = variants.first() {
+                return Ok((
+                    ComponentFillChecker::Value(false),
+                    Component::String(variant.clone()),
+                ));
+            }
+            let (name, s) = data_variants
+                .iter()
+                .min_by_key(|(name, _)| *name)
+                .context("enums without variants are not suported")?;
+            let (filler, comp) =
+                from_structure(s, preset_options).context(format!("in variant `{name}`"))?;
+            Ok((
+                ComponentFillChecker::Struct(HashMap::from([(name.clone(), filler)])),
+                Component::Struct(HashMap::from([(name.clone(), comp)])),
+            ))
+        }
     }
 }
 
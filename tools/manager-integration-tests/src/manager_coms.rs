@@ -2,17 +2,19 @@ use anyhow::{bail, Context};
 use futures::{stream, Future};
 use proto::simulation::{
     simulation_manager::{
-        ComponentsInfo, PushSimulationRequest, SimulationFrameRequest, SimulationId,
+        ComponentsInfo, ErrorPolicy, PushSimulationRequest, SimulationFrameRequest, SimulationId,
         SimulationManagerClient, SimulationStatus, SimulatorSelection,
     },
     simulator::{
         simulator_server, InitialState, IoConfigRequest, SetupResponse, SimulatorIoConfig,
-        SimulatorServer, TimestepResult,
+        SimulatorServer, TeardownRequest, TeardownResponse, TimestepRequest, TimestepResult,
+        UpgradeRequest, UpgradeResult,
     },
     simulator_connection::{SimulatorConnectionClient, SimulatorInfo},
     State,
 };
 use std::{
+    collections::HashMap,
     net::{Ipv4Addr, SocketAddr},
     sync::atomic::{AtomicUsize, Ordering},
     time::{Duration, SystemTime},
@@ -74,6 +76,12 @@ impl Client {
                 selection: Some(SimulatorSelection {
                     name: simulator_selection,
                 }),
+                error_policy: ErrorPolicy::FailFast.into(),
+                priority: 0,
+                not_before: None,
+                twin: None,
+                stop_conditions: vec![],
+                component_versions: HashMap::new(),
             })
             .await
             .context("manager error while pushing simulation")?;
@@ -191,7 +199,7 @@ where
         connection = connection => {
             connection
                 .context("mockserver could not connect with manger")?
-                .connect_simulator(SimulatorInfo { port: 8843, name: "mock simulator".to_owned() }).await
+                .connect_simulator(SimulatorInfo { port: 8843, name: "mock simulator".to_owned(), timestep_timeout_ms: None }).await
                 .context("advertising to manager")?;
 
             // Keep simulator running
@@ -237,7 +245,7 @@ impl simulator_server::Simulator for MockSimulator {
     /// Takes a simulation state as input, executes a simulation step, and returns the resulting state.
     async fn do_timestep(
         &self,
-        _request: Request<State>,
+        _request: Request<TimestepRequest>,
     ) -> Result<Response<TimestepResult>, Status> {
         let frame = self
             .config
@@ -260,6 +268,26 @@ impl simulator_server::Simulator for MockSimulator {
 
         Ok(Response::new(TimestepResult {
             output_state: Some(state),
+            topology: None,
         }))
     }
+
+    /// Ends a session. The mock simulator does not keep any data per session.
+    async fn teardown(
+        &self,
+        _request: Request<TeardownRequest>,
+    ) -> Result<Response<TeardownResponse>, Status> {
+        Ok(Response::new(TeardownResponse {}))
+    }
+
+    /// The components of the mock simulator are never upgraded.
+    async fn upgrade_components(
+        &self,
+        request: Request<UpgradeRequest>,
+    ) -> Result<Response<UpgradeResult>, Status> {
+        Err(Status::unimplemented(format!(
+            "the mock simulator can not upgrade `{}`",
+            request.into_inner().component
+        )))
+    }
 }
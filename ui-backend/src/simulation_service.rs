@@ -1,9 +1,10 @@
+use std::collections::HashMap;
 use std::env;
-use std::ffi::c_double;
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use futures::StreamExt;
+use prost_types::Timestamp;
 use sqlx::PgPool;
 use tonic::transport::Channel;
 use tonic::{Request, Response, Status, Streaming};
@@ -14,14 +15,15 @@ use proto::frontend::{
 };
 use proto::simulation::simulation_manager::DeleteSimulationRequest as DeleteSimulationRequestManager;
 use proto::simulation::simulation_manager::{
-    ComponentsInfo, PushSimulationRequest, SimulationData, SimulationFrame, SimulationFrameRequest,
-    SimulationManagerClient, SimulatorSelection, Simulators,
+    BranchSimulationRequest, ComponentsInfo, PushSimulationRequest, Queue, SimulationData,
+    SimulationEvent, SimulationFrame, SimulationFrameRangeRequest, SimulationFrameRequest,
+    SimulationManagerClient, Simulators, StructureViolations, ValidateInitialStateRequest,
 };
 
 use proto::frontend::DeleteSimulationRequest as DeleteSimulationRequestFrontend;
 use proto::frontend::DeleteSimulationResponse as DeleteSimulationResponseFrontend;
 
-use proto::simulation::{simulation_manager, State};
+use proto::simulation::simulation_manager;
 
 #[derive(Debug)]
 pub struct SimulationDB {
@@ -59,24 +61,18 @@ impl SimulationService {
     }
     async fn create_simulation_manager(
         &self,
-        id: String,
-        initial_state: Option<State>,
-        timesteps: u64,
-        timestep_delta: c_double,
-        simulator_selection: SimulatorSelection,
+        request: PushSimulationRequest,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        self.client
-            .clone()
-            .push_simulation(PushSimulationRequest {
-                id: Option::from(simulation_manager::SimulationId {
-                    uuid: id.to_string(),
-                }),
-                initial_state,
-                timesteps,
-                timestep_delta,
-                selection: Some(simulator_selection),
-            })
-            .await?;
+        self.client.clone().push_simulation(request).await?;
+
+        Ok(())
+    }
+
+    async fn branch_simulation_manager(
+        &self,
+        request: BranchSimulationRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.client.clone().branch_simulation(request).await?;
 
         Ok(())
     }
@@ -118,6 +114,7 @@ impl SimulationInterfaceService for SimulationService {
         request: Request<CreateSimulationParams>,
     ) -> Result<Response<CreateSimulationResponse>, Status> {
         let req = request.into_inner();
+        let error_policy = req.error_policy;
 
         let creation_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -171,18 +168,53 @@ impl SimulationInterfaceService for SimulationService {
             .await
             .map_err(|err| Status::from_error(Box::new(err)))?;
 
-        let success = self
-            .create_simulation_manager(
-                simulation_id.clone().to_string(),
-                req.start_state,
-                time_steps,
-                req.time_step_delta,
-                req.simulators.ok_or(Status::invalid_argument(
-                    "CreateSimulationParams does not contain selected simulators names",
-                ))?,
-            )
-            .await
-            .is_ok();
+        let id = simulation_manager::SimulationId {
+            uuid: simulation_id.to_string(),
+        };
+        let selection = req.simulators.ok_or(Status::invalid_argument(
+            "CreateSimulationParams does not contain selected simulators names",
+        ))?;
+        let not_before = req.not_before.map(|not_before| Timestamp {
+            seconds: not_before.into(),
+            nanos: 0,
+        });
+        let success = match (parent_id, parent_frame, req.start_state) {
+            // let the manager copy the frame of the parent
+            (Some(parent_id), Some(parent_frame), None) => self
+                .branch_simulation_manager(BranchSimulationRequest {
+                    id: Some(id),
+                    parent: Some(simulation_manager::SimulationId {
+                        uuid: parent_id.to_string(),
+                    }),
+                    frame_nr: parent_frame as u32,
+                    overrides: req.overrides,
+                    timesteps: time_steps,
+                    selection: Some(selection),
+                    error_policy,
+                    priority: req.priority,
+                    not_before,
+                    twin: Some(twin_id.to_string()),
+                    stop_conditions: vec![],
+                })
+                .await
+                .is_ok(),
+            (_, _, initial_state) => self
+                .create_simulation_manager(PushSimulationRequest {
+                    id: Some(id),
+                    initial_state,
+                    timesteps: time_steps,
+                    timestep_delta: req.time_step_delta,
+                    selection: Some(selection),
+                    error_policy,
+                    priority: req.priority,
+                    not_before,
+                    twin: Some(twin_id.to_string()),
+                    stop_conditions: vec![],
+                    component_versions: HashMap::new(),
+                })
+                .await
+                .is_ok(),
+        };
         let response = CreateSimulationResponse {
             success,
             id: simulation_id,
@@ -356,6 +388,18 @@ impl SimulationInterfaceService for SimulationService {
             .await
     }
 
+    type GetSimulationFrameRangeStream = Streaming<SimulationFrame>;
+
+    async fn get_simulation_frame_range(
+        &self,
+        request: Request<SimulationFrameRangeRequest>,
+    ) -> Result<Response<Self::GetSimulationFrameRangeStream>, Status> {
+        self.client
+            .clone()
+            .get_simulation_frame_range(request.into_inner())
+            .await
+    }
+
     async fn get_components(
         &self,
         _request: Request<()>,
@@ -366,6 +410,20 @@ impl SimulationInterfaceService for SimulationService {
             .unwrap_or(Response::new(ComponentsInfo::default())));
     }
 
+    async fn list_queue(&self, request: Request<()>) -> Result<Response<Queue>, Status> {
+        self.client.clone().list_queue(request.into_inner()).await
+    }
+
+    async fn validate_initial_state(
+        &self,
+        request: Request<ValidateInitialStateRequest>,
+    ) -> Result<Response<StructureViolations>, Status> {
+        self.client
+            .clone()
+            .validate_initial_state(request.into_inner())
+            .await
+    }
+
     async fn delete_simulation(
         &self,
         request: Request<DeleteSimulationRequestFrontend>,
@@ -411,4 +469,16 @@ impl SimulationInterfaceService for SimulationService {
         };
         Ok(result)
     }
+
+    type WatchSimulationStream = Streaming<SimulationEvent>;
+
+    async fn watch_simulation(
+        &self,
+        request: Request<simulation_manager::SimulationId>,
+    ) -> Result<Response<Self::WatchSimulationStream>, Status> {
+        self.client
+            .clone()
+            .watch_simulation(request.into_inner())
+            .await
+    }
 }